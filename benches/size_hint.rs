@@ -0,0 +1,114 @@
+//! Compares the allocations made encoding a large node with and without a
+//! [`StaticStore::size_hint`]. Run with `cargo bench --bench size_hint`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use auto_ipld::{CidShape, Link, MemoryError, MemoryStore, StaticStore};
+use cid::Cid;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Counts allocations and reallocations.
+struct Counting;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+static REALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        REALLOCS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+struct Tag;
+type Blocks = MemoryStore<Tag>;
+
+/// The length of the last block encoded through [`Hinted`].
+static LAST_LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// A store that guesses each block will be as large as the last one, as a store re-saving the
+/// same large node might.
+struct Hinted;
+
+impl StaticStore for Hinted {
+    type Error = MemoryError;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, MemoryError> {
+        LAST_LEN.store(value.len(), Ordering::Relaxed);
+        Blocks::store_bytes(value, shape)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, MemoryError> {
+        Blocks::encode(value)
+    }
+
+    fn encode_into<T: Serialize, W: std::io::Write>(value: &T, w: W) -> Result<(), MemoryError> {
+        Blocks::encode_into(value, w)
+    }
+
+    fn size_hint<T: Serialize>(_value: &T) -> Option<usize> {
+        Some(LAST_LEN.load(Ordering::Relaxed)).filter(|&len| len > 0)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, MemoryError> {
+        Blocks::load_bytes(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, MemoryError> {
+        Blocks::decode(bytes)
+    }
+}
+
+/// Edit and re-save a large node `rounds` times, returning the allocations and reallocations.
+fn resave<S: StaticStore>(rounds: usize) -> (usize, usize) {
+    let node: Vec<String> = (0..10_000).map(|i| format!("entry {i}")).collect();
+    let mut link: Link<Vec<String>, S> = Link::from_value(node, None);
+    link.save().unwrap();
+
+    let (allocs, reallocs) = (
+        ALLOCS.load(Ordering::Relaxed),
+        REALLOCS.load(Ordering::Relaxed),
+    );
+    for i in 0..rounds {
+        link.edit().unwrap()[i] = format!("edited {i}");
+        black_box(link.save().unwrap());
+    }
+    (
+        ALLOCS.load(Ordering::Relaxed) - allocs,
+        REALLOCS.load(Ordering::Relaxed) - reallocs,
+    )
+}
+
+fn main() {
+    const ROUNDS: usize = 100;
+    for (name, run) in [
+        ("no hint", resave::<Blocks> as fn(usize) -> (usize, usize)),
+        ("last block's size", resave::<Hinted>),
+    ] {
+        let start = Instant::now();
+        let (allocs, reallocs) = run(ROUNDS);
+        println!(
+            "{name:>18}: {:>5} allocs, {:>5} reallocs per save, {:?} per save",
+            allocs / ROUNDS,
+            reallocs / ROUNDS,
+            start.elapsed() / ROUNDS as u32,
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! The `#[ipld_node]` attribute and `#[derive(ContentEq)]` for `auto-ipld` node types.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, ToTokens};
+use syn::{
+    parse::Parser, parse_macro_input, parse_quote, punctuated::Punctuated, Attribute, Data,
+    DeriveInput, Fields, GenericParam, Ident, Member, Token, Type,
+};
+
+/// Turn a struct or enum into a node generic over its store, without the boilerplate: the store
+/// parameter, its `StaticStore` bound, and the serde bounds are added for you.
+///
+/// ```
+/// use auto_ipld::{Link, MemoryStore, StaticStore};
+/// use auto_ipld_derive::ipld_node;
+///
+/// #[ipld_node(store = S)]
+/// struct Node {
+///     name: String,
+///     children: Vec<Link<Node<S>, S>>,
+/// }
+///
+/// let leaf: Node<MemoryStore> = Node { name: "leaf".into(), children: vec![] };
+/// let k = MemoryStore::<()>::store(&leaf, None).unwrap();
+/// assert_eq!(MemoryStore::<()>::load::<Node<MemoryStore>>(&k).unwrap().name, "leaf");
+/// ```
+///
+/// The attribute:
+///
+/// - adds the store parameter (`S` here) to the type's generics unless it's already declared, and
+///   bounds it by `auto_ipld::StaticStore`;
+/// - derives `serde::Serialize` and `serde::Deserialize` (unless they're already derived), with
+///   bounds only on the type's other type parameters, so serde doesn't require `S: Serialize`;
+/// - for structs whose fields never mention the store, adds a `#[serde(skip)]`
+///   `_marker: PhantomData<fn(S)>` field so the parameter is used.
+///
+/// The type must be in a crate that depends on `auto-ipld` and `serde`.
+#[proc_macro_attribute]
+pub fn ipld_node(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut store = None;
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("store") {
+            store = Some(meta.value()?.parse::<Ident>()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `store = <type parameter>`"))
+        }
+    });
+    parse_macro_input!(args with parser);
+    let store = store.unwrap_or_else(|| Ident::new("Store", Span::call_site()));
+
+    let mut input = parse_macro_input!(item as DeriveInput);
+    match expand(&mut input, &store) {
+        Ok(()) => input.into_token_stream().into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn expand(input: &mut DeriveInput, store: &Ident) -> syn::Result<()> {
+    // Add the store parameter, bounded by `StaticStore`.
+    let declared = input.generics.type_params_mut().find(|p| p.ident == *store);
+    match declared {
+        Some(param) => param.bounds.push(parse_quote!(::auto_ipld::StaticStore)),
+        None => input
+            .generics
+            .params
+            .push(parse_quote!(#store: ::auto_ipld::StaticStore)),
+    }
+
+    // Use the parameter, if no field does.
+    if let Data::Struct(data) = &mut input.data {
+        if !mentions(data.fields.to_token_stream(), store) {
+            let marker = quote! {
+                #[serde(skip)]
+                _marker: ::core::marker::PhantomData<fn(#store)>
+            };
+            match &mut data.fields {
+                Fields::Named(fields) => fields.named.push(syn::Field::parse_named.parse2(marker)?),
+                Fields::Unnamed(_) | Fields::Unit => {
+                    return Err(syn::Error::new_spanned(
+                        &input.ident,
+                        "`ipld_node` needs named fields to add a store marker; mention the store \
+                         in a field instead",
+                    ))
+                }
+            }
+        }
+    }
+
+    // Bound the other type parameters for serde, but not the store.
+    let others: Vec<&Ident> = input
+        .generics
+        .params
+        .iter()
+        .filter_map(|p| match p {
+            GenericParam::Type(t) if t.ident != *store => Some(&t.ident),
+            _ => None,
+        })
+        .collect();
+    let serialize = bounds(&others, quote!(::serde::Serialize));
+    let deserialize = bounds(&others, quote!(::serde::Deserialize<'de>));
+    let derived = derived(input)?;
+    let missing: Vec<TokenStream2> = ["Serialize", "Deserialize"]
+        .into_iter()
+        .filter(|name| !derived.iter().any(|d| d == name))
+        .map(|name| {
+            let name = Ident::new(name, Span::call_site());
+            quote!(::serde::#name)
+        })
+        .collect();
+    if !missing.is_empty() {
+        // Derives must come before the `serde` attributes they enable.
+        input
+            .attrs
+            .insert(0, parse_quote!(#[derive(#(#missing),*)]));
+    }
+    input.attrs.push(parse_quote! {
+        #[serde(bound(serialize = #serialize, deserialize = #deserialize))]
+    });
+    Ok(())
+}
+
+/// Derive `auto_ipld::ContentEq`, comparing values field by field across link boundaries:
+/// fields whose types mention `Link` or `AutoLink` (including `Option`s, `Vec`s, and `Box`es of
+/// them) are compared with `ContentEq`, saving modified links to compare their CIDs, and every
+/// other field with `PartialEq`.
+///
+/// ```
+/// use auto_ipld::{ContentEq, Link, MemoryStore};
+/// use auto_ipld_derive::ContentEq;
+/// use serde::Serialize;
+///
+/// type Store = MemoryStore<()>;
+///
+/// #[derive(Serialize, ContentEq)]
+/// struct Node {
+///     name: String,
+///     child: Option<Link<String, Store>>,
+/// }
+///
+/// let node = |child: &str| Node {
+///     name: "node".into(),
+///     child: Some(Link::from_value(child.into(), None)),
+/// };
+/// assert!(node("child").content_eq(&node("child")).unwrap());
+/// assert!(!node("child").content_eq(&node("other")).unwrap());
+/// ```
+///
+/// The store type is `Store` unless set with `#[content_eq(store = S)]` on the type. Mark fields
+/// of other `ContentEq` types (e.g., nested nodes that contain links) with `#[content_eq(nested)]`
+/// to compare them with `ContentEq` too. Enums are equal if both values are the same variant with
+/// equal fields.
+#[proc_macro_derive(ContentEq, attributes(content_eq))]
+pub fn derive_content_eq(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    match content_eq(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.into_compile_error().into(),
+    }
+}
+
+fn content_eq(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut store: Type = parse_quote!(Store);
+    for attr in content_eq_attrs(&input.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("store") {
+                store = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("expected `store = <type>`"))
+            }
+        })?;
+    }
+
+    let mut bounds = Vec::new();
+    let mut compare = |fields: &Fields, a: &[Ident], b: &[Ident]| -> syn::Result<TokenStream2> {
+        let mut cmps = Vec::new();
+        for ((field, a), b) in fields.iter().zip(a).zip(b) {
+            let ty = &field.ty;
+            if uses_content_eq(field)? {
+                bounds.push(quote!(#ty: ::auto_ipld::ContentEq<#store>));
+                cmps.push(quote!(::auto_ipld::ContentEq::<#store>::content_eq(#a, #b)?));
+            } else {
+                bounds.push(quote!(#ty: ::core::cmp::PartialEq));
+                cmps.push(quote!(#a == #b));
+            }
+        }
+        Ok(quote!(::core::result::Result::Ok(true #(&& #cmps)*)))
+    };
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let (a, b) = bindings(&data.fields);
+            let members = members(&data.fields);
+            let cmp = compare(&data.fields, &a, &b)?;
+            quote! {
+                #(let #a = &self.#members;)*
+                #(let #b = &other.#members;)*
+                #cmp
+            }
+        }
+        Data::Enum(data) => {
+            let mut arms = Vec::new();
+            for variant in &data.variants {
+                let name = &variant.ident;
+                let (a, b) = bindings(&variant.fields);
+                let members = members(&variant.fields);
+                let cmp = compare(&variant.fields, &a, &b)?;
+                arms.push(quote! {
+                    (Self::#name { #(#members: #a,)* }, Self::#name { #(#members: #b,)* }) => #cmp,
+                });
+            }
+            quote! {
+                #[allow(unreachable_patterns)]
+                match (self, other) {
+                    #(#arms)*
+                    _ => ::core::result::Result::Ok(false),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`ContentEq` can't be derived for unions",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut predicates: Vec<TokenStream2> = where_clause
+        .map(|w| w.predicates.iter().map(ToTokens::to_token_stream).collect())
+        .unwrap_or_default();
+    predicates.push(quote!(#store: ::auto_ipld::StaticStore));
+    predicates.extend(bounds);
+    Ok(quote! {
+        impl #impl_generics ::auto_ipld::ContentEq<#store> for #name #ty_generics
+        where
+            #(#predicates,)*
+        {
+            fn content_eq(
+                &self,
+                other: &Self,
+            ) -> ::core::result::Result<bool, <#store as ::auto_ipld::StaticStore>::Error> {
+                #body
+            }
+        }
+    })
+}
+
+/// The `#[content_eq(...)]` attributes in `attrs`.
+fn content_eq_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|a| a.path().is_ident("content_eq"))
+}
+
+/// Whether a field is compared with `ContentEq` rather than `PartialEq`.
+fn uses_content_eq(field: &syn::Field) -> syn::Result<bool> {
+    let mut nested = false;
+    for attr in content_eq_attrs(&field.attrs) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("nested") {
+                nested = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `nested`"))
+            }
+        })?;
+    }
+    let tokens = field.ty.to_token_stream();
+    Ok(nested
+        || mentions(tokens.clone(), &Ident::new("Link", Span::call_site()))
+        || mentions(tokens, &Ident::new("AutoLink", Span::call_site())))
+}
+
+/// How to access each of `fields`.
+fn members(fields: &Fields) -> Vec<Member> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, f)| match &f.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(i.into()),
+        })
+        .collect()
+}
+
+/// Names to bind each of `fields` to, in `self` and in `other`.
+fn bindings(fields: &Fields) -> (Vec<Ident>, Vec<Ident>) {
+    (0..fields.len())
+        .map(|i| {
+            (
+                Ident::new(&format!("__a{i}"), Span::call_site()),
+                Ident::new(&format!("__b{i}"), Span::call_site()),
+            )
+        })
+        .unzip()
+}
+
+/// The names of the traits the input already derives.
+fn derived(input: &DeriveInput) -> syn::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("derive")) {
+        let paths = attr.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)?;
+        names.extend(
+            paths
+                .iter()
+                .filter_map(|p| p.segments.last())
+                .map(|s| s.ident.to_string()),
+        );
+    }
+    Ok(names)
+}
+
+/// A serde bound string requiring `bound` of each of `params`.
+fn bounds(params: &[&Ident], bound: TokenStream2) -> String {
+    params
+        .iter()
+        .map(|p| format!("{p}: {bound}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Returns true if `tokens` contain the identifier `ident`.
+fn mentions(tokens: TokenStream2, ident: &Ident) -> bool {
+    tokens.into_iter().any(|t| match t {
+        TokenTree::Ident(i) => i == *ident,
+        TokenTree::Group(g) => mentions(g.stream(), ident),
+        _ => false,
+    })
+}
@@ -0,0 +1,62 @@
+use auto_ipld::{AutoLink, ContentEq, Link, MemoryStore, StaticStore};
+use auto_ipld_derive::ContentEq;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, ContentEq)]
+#[content_eq(store = S)]
+#[serde(bound = "")]
+struct Tree<S: StaticStore> {
+    name: String,
+    note: Option<AutoLink<String, S, 16>>,
+    children: Vec<Link<Tree<S>, S>>,
+}
+
+#[derive(ContentEq)]
+#[content_eq(store = S)]
+enum Entry<S: StaticStore> {
+    Tree(#[content_eq(nested)] Box<Tree<S>>),
+    Name { name: String },
+}
+
+fn tree<S: StaticStore>(name: &str, children: Vec<Tree<S>>) -> Tree<S> {
+    Tree {
+        name: name.into(),
+        note: Some(AutoLink::from_value(format!("{name} note"))),
+        children: children
+            .into_iter()
+            .map(|c| Link::from_value(c, None))
+            .collect(),
+    }
+}
+
+#[test]
+fn independent_trees_compare_by_content() {
+    struct Tag;
+    type Store = MemoryStore<Tag>;
+
+    let build = || {
+        tree::<Store>(
+            "root",
+            vec![tree("a", vec![tree("b", vec![])]), tree("c", vec![])],
+        )
+    };
+    let (a, b) = (build(), build());
+    assert!(a.content_eq(&b).unwrap());
+    // Comparing saved the links.
+    assert!(a.children.iter().all(|c| !c.is_dirty()));
+
+    let mut c = build();
+    c.children[0].edit().unwrap().children[0]
+        .edit()
+        .unwrap()
+        .name = "B".into();
+    assert!(!a.content_eq(&c).unwrap());
+    c.note = None;
+    assert!(!c.content_eq(&a).unwrap());
+
+    let entry = |t| Entry::Tree(Box::new(t));
+    assert!(entry(build()).content_eq(&entry(b)).unwrap());
+    let name = || Entry::<Store>::Name { name: "x".into() };
+    assert!(name().content_eq(&name()).unwrap());
+    assert!(!name().content_eq(&entry(a)).unwrap());
+}
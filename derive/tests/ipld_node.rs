@@ -0,0 +1,50 @@
+use std::marker::PhantomData;
+
+use auto_ipld::{Link, MemoryStore, StaticStore};
+use auto_ipld_derive::ipld_node;
+use serde::{Deserialize, Serialize};
+
+#[ipld_node(store = S)]
+struct Tree<T> {
+    value: T,
+    children: Vec<Link<Tree<T, S>, S>>,
+}
+
+/// No field mentions the store, so it gets a marker.
+#[ipld_node(store = S)]
+#[derive(Serialize, Deserialize)]
+struct Leaf {
+    name: String,
+}
+
+#[test]
+fn nodes_round_trip() {
+    struct Tag;
+    type Store = MemoryStore<Tag>;
+
+    let leaf: Link<Tree<u64, Store>, Store> = Link::from_value(
+        Tree {
+            value: 2u64,
+            children: vec![],
+        },
+        None,
+    );
+    let root = Store::store(
+        &Tree {
+            value: 1u64,
+            children: vec![leaf],
+        },
+        None,
+    )
+    .unwrap();
+    let root: Tree<u64, Store> = Store::load(&root).unwrap();
+    assert_eq!(root.value, 1);
+    assert_eq!(root.children[0].read().unwrap().value, 2);
+
+    let leaf: Leaf<Store> = Leaf {
+        name: "leaf".into(),
+        _marker: PhantomData,
+    };
+    let k = Store::store(&leaf, None).unwrap();
+    assert_eq!(Store::load::<Leaf<Store>>(&k).unwrap().name, leaf.name);
+}
@@ -0,0 +1,342 @@
+use core::{
+    future::{self, Future},
+    pin::Pin,
+    task::Poll,
+};
+
+use alloc::{boxed::Box, vec::Vec};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    store, CidShape, CodecError, Link, StoreError, DAG_CBOR, DEFAULT_MAX_BLOCK_SIZE,
+    DEFAULT_MAX_LINK_DEPTH,
+};
+
+/// An asynchronous counterpart of [`StaticStore`](crate::StaticStore), for backends that fetch
+/// blocks over the network (e.g., bitswap or an HTTP gateway).
+///
+/// Only block I/O is asynchronous; encoding and decoding are not. [`Link`](crate::Link) and
+/// [`AutoLink`](crate::AutoLink) use this through their `read_async`, `edit_async`, and
+/// `save_async` methods.
+pub trait AsyncStore {
+    type Error: core::error::Error + Send + From<StoreError> + 'static;
+
+    /// Whether [`AsyncStore::load`] checks that loaded blocks match their CIDs, as
+    /// [`StaticStore::VERIFY`](crate::StaticStore::VERIFY).
+    const VERIFY: bool = true;
+
+    /// The largest block [`AsyncStore::load`] will accept, as
+    /// [`StaticStore::MAX_BLOCK_SIZE`](crate::StaticStore::MAX_BLOCK_SIZE).
+    const MAX_BLOCK_SIZE: usize = DEFAULT_MAX_BLOCK_SIZE;
+
+    /// How deeply modified links can be nested when they're saved, as
+    /// [`StaticStore::MAX_LINK_DEPTH`](crate::StaticStore::MAX_LINK_DEPTH).
+    const MAX_LINK_DEPTH: usize = DEFAULT_MAX_LINK_DEPTH;
+
+    /// The largest block saving a link will write, as
+    /// [`StaticStore::MAX_WRITE_SIZE`](crate::StaticStore::MAX_WRITE_SIZE).
+    const MAX_WRITE_SIZE: Option<usize> = None;
+
+    fn store_bytes(
+        value: &[u8],
+        shape: Option<&CidShape>,
+    ) -> impl Future<Output = Result<Cid, Self::Error>> + Send;
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error>;
+
+    fn load_bytes(k: &Cid) -> impl Future<Output = Result<Vec<u8>, Self::Error>> + Send;
+
+    /// Decode an object.
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error>;
+
+    /// Returns true if the store can encode and decode blocks of `codec`. By default, that's
+    /// DAG-CBOR (what [`AsyncStore::encode`] produces) and the codecs
+    /// [`StaticStore::encode_with`](crate::StaticStore::encode_with) handles itself.
+    fn supports_codec(codec: u64) -> bool {
+        codec == DAG_CBOR || store::builtin_codec(codec)
+    }
+
+    /// Encode `value` in the given codec, as
+    /// [`StaticStore::encode_with`](crate::StaticStore::encode_with) does, falling back to
+    /// [`AsyncStore::encode`].
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        match store::encode_builtin(value, codec) {
+            Some(encoded) => Ok(encoded?),
+            None => Self::encode(value),
+        }
+    }
+
+    /// Decode a block of the given codec, the inverse of [`AsyncStore::encode_with`].
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        match store::decode_builtin(bytes, codec) {
+            Some(decoded) => Ok(decoded?),
+            None => Self::decode(bytes),
+        }
+    }
+
+    /// Check that `bytes` hash to the multihash in `k`, as
+    /// [`StaticStore::verify`](crate::StaticStore::verify) does.
+    fn verify(k: &Cid, bytes: &[u8]) -> Result<(), Self::Error> {
+        Ok(store::verify_hash(k, bytes)?)
+    }
+
+    /// Load an object, as [`StaticStore::load`](crate::StaticStore::load) does: blocks larger
+    /// than [`AsyncStore::MAX_BLOCK_SIZE`] are rejected, blocks are verified unless
+    /// [`AsyncStore::VERIFY`] is false, and they're decoded according to their codec (see
+    /// [`AsyncStore::decode_with`]). Identity CIDs are decoded from the CID itself, without
+    /// loading anything.
+    fn load<T: DeserializeOwned>(key: &Cid) -> impl Future<Output = Result<T, Self::Error>> + Send {
+        async move {
+            let loaded;
+            let bytes = match store::inline_block(key) {
+                Some(bytes) => bytes,
+                None => {
+                    loaded = Self::load_bytes(key).await?;
+                    store::check_block_size(key, loaded.len(), Self::MAX_BLOCK_SIZE)?;
+                    &loaded[..]
+                }
+            };
+            if Self::VERIFY {
+                Self::verify(key, bytes)?;
+            }
+            Self::decode_with(bytes, key.codec())
+        }
+    }
+
+    /// Store an object. The `shape` is a hint. The object is encoded (with
+    /// [`AsyncStore::encode`]) before the returned future is first polled.
+    fn store<T: Serialize>(
+        value: &T,
+        shape: Option<&CidShape>,
+    ) -> impl Future<Output = Result<Cid, Self::Error>> + Send {
+        let encoded = Self::encode(value);
+        async move { Self::store_bytes(&encoded?, shape).await }
+    }
+}
+
+/// Encode a modified link's value for `S` in the codec of `shape` if there is one (see
+/// [`AsyncStore::encode_with`]), as [`store::encode`] does for a
+/// [`StaticStore`](crate::StaticStore): nested links are guarded against cycles and excessive
+/// depth, and the typed error of a link that failed to save is recovered.
+pub(crate) fn encode_link<S: AsyncStore + ?Sized, T: Serialize>(
+    value: &T,
+    shape: Option<&CidShape>,
+) -> Result<Vec<u8>, S::Error> {
+    store::recover_stashed(|| {
+        let _guard = store::enter_limited(value, S::MAX_LINK_DEPTH)?;
+        match shape {
+            Some(shape) if !S::supports_codec(shape.codec) => {
+                Err(StoreError::Codec(CodecError::UnsupportedCodec(shape.codec)).into())
+            }
+            Some(shape) => S::encode_with(value, shape.codec),
+            None => S::encode(value),
+        }
+    })
+}
+
+/// Store a modified link's encoded value in `S`, as [`store::store_link_block`] does for a
+/// [`StaticStore`](crate::StaticStore): identity-shaped blocks are embedded in their CIDs rather
+/// than written, and blocks larger than [`AsyncStore::MAX_WRITE_SIZE`] are rejected.
+pub(crate) async fn store_link_block<S: AsyncStore + ?Sized>(
+    bytes: &[u8],
+    shape: Option<&CidShape>,
+) -> Result<Cid, S::Error> {
+    if let Some(k) = store::prepare_link_block(bytes, shape, S::MAX_WRITE_SIZE)? {
+        return Ok(k);
+    }
+    S::store_bytes(bytes, shape).await
+}
+
+/// Load every unloaded, unmodified link in `links` concurrently, so that later reads are cache
+/// hits. Links that are already loaded (or modified) aren't fetched.
+///
+/// Each value is cached as by [`Link::read_async`], so a concurrent read of the same link is safe:
+/// whichever load finishes first fills the cache. Every load runs to completion even if one fails,
+/// and the first error (in `links` order) is returned.
+pub async fn preload<'a, T, Store>(
+    links: impl IntoIterator<Item = &'a Link<T, Store>>,
+) -> Result<(), Store::Error>
+where
+    T: DeserializeOwned + 'a,
+    Store: AsyncStore + 'a,
+{
+    let reads = (links.into_iter())
+        .filter(|l| !l.is_loaded() && !l.is_dirty())
+        .map(Link::read_async)
+        .collect();
+    join_all(reads)
+        .await
+        .into_iter()
+        .try_for_each(|r| r.map(drop))
+}
+
+/// Poll `futures` concurrently until they've all finished, returning their outputs in order.
+async fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+    let mut pending: Vec<Option<Pin<Box<F>>>> =
+        futures.into_iter().map(|f| Some(Box::pin(f))).collect();
+    let mut outputs: Vec<Option<F::Output>> = pending.iter().map(|_| None).collect();
+    future::poll_fn(|cx| {
+        let mut done = true;
+        for (fut, out) in pending.iter_mut().zip(&mut outputs) {
+            if let Some(f) = fut {
+                match f.as_mut().poll(cx) {
+                    Poll::Ready(v) => {
+                        *out = Some(v);
+                        *fut = None;
+                    }
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done {
+            Poll::Ready(outputs.iter_mut().map(|o| o.take().unwrap()).collect())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::{
+        future::Future,
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+
+    use crate::{
+        preload,
+        testing::{block_on, AsyncMemoryStore},
+        AsyncStore, AutoLink, CidShape, Link, MemoryError, MemoryStore, RawBytes, StaticStore,
+        StoreError, DAG_JSON, RAW,
+    };
+
+    fn poll_once<F: Future>(fut: std::pin::Pin<&mut F>) -> Poll<F::Output> {
+        fut.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn link_roundtrip() {
+        struct Tag;
+        type Store = AsyncMemoryStore<Tag>;
+
+        let link: Link<String, Store> = Link::from_value("hello".into(), None);
+        let k = block_on(link.save_async()).unwrap();
+
+        let mut link: Link<String, Store> = Link::new(k);
+        assert_eq!(block_on(link.read_async()).unwrap(), "hello");
+        block_on(link.edit_async()).unwrap().push_str(" world");
+        let k2 = block_on(link.save_async()).unwrap();
+        assert_ne!(k, k2);
+        assert_eq!(block_on(Store::load::<String>(&k2)).unwrap(), "hello world");
+
+        let auto: AutoLink<String, Store, 8> = AutoLink::from_value("much too large".into());
+        assert!(matches!(
+            block_on(auto.save_async()).unwrap(),
+            crate::MaybeLink::Link(_)
+        ));
+    }
+
+    #[test]
+    fn links_are_saved_and_read_in_their_codec() {
+        struct Tag;
+        type Store = AsyncMemoryStore<Tag>;
+
+        let json = CidShape::DEFAULT.with_codec(DAG_JSON);
+        let link: Link<String, Store> = Link::from_value("hello".into(), Some(json));
+        let k = block_on(link.save_async()).unwrap();
+        assert_eq!(k.codec(), DAG_JSON);
+        assert_eq!(MemoryStore::<Tag>::load_bytes(&k).unwrap(), br#""hello""#);
+        let link: Link<String, Store> = Link::new(k);
+        assert_eq!(block_on(link.read_async()).unwrap(), "hello");
+
+        let auto = AutoLink::<String, Store, 0>::from_value("linked".into()).with_shape(json);
+        let crate::MaybeLink::Link(k) = block_on(auto.save_async()).unwrap() else {
+            panic!("expected a link");
+        };
+        assert_eq!(k.codec(), DAG_JSON);
+        assert_eq!(MemoryStore::<Tag>::load::<String>(&k).unwrap(), "linked");
+
+        let raw = CidShape::DEFAULT.with_codec(RAW);
+        let k = MemoryStore::<Tag>::store_bytes(b"raw bytes", Some(&raw)).unwrap();
+        let link: Link<RawBytes, Store> = Link::new(k);
+        assert_eq!(block_on(link.read_async()).unwrap().0, b"raw bytes");
+
+        // Blocks that don't match their CIDs are rejected.
+        <MemoryStore<Tag> as crate::BlockBackend>::put(&k, b"tampered".to_vec()).unwrap();
+        let link: Link<RawBytes, Store> = Link::new(k);
+        assert!(matches!(
+            block_on(link.read_async()),
+            Err(MemoryError::Store(StoreError::HashMismatch(c))) if c == k
+        ));
+    }
+
+    #[test]
+    fn cancelled_edit_leaves_link_unmodified() {
+        struct Tag;
+        type Store = AsyncMemoryStore<Tag>;
+
+        let k = block_on(Store::store(&"original", None)).unwrap();
+        let mut link: Link<String, Store> = Link::new(k);
+        {
+            let edit = pin!(link.edit_async());
+            assert!(poll_once(edit).is_pending());
+        }
+        // The dropped edit neither cached a value nor marked the link as modified.
+        assert_eq!(block_on(link.save_async()).unwrap(), k);
+        assert_eq!(MemoryStore::<Tag>::len(), 1);
+        assert_eq!(block_on(link.read_async()).unwrap(), "original");
+    }
+
+    #[test]
+    fn concurrent_reads_share_cache() {
+        struct Tag;
+        type Store = AsyncMemoryStore<Tag>;
+
+        let k = block_on(Store::store(&"shared", None)).unwrap();
+        let link: Link<String, Store> = Link::new(k);
+        let mut a = pin!(link.read_async());
+        let mut b = pin!(link.read_async());
+        assert!(poll_once(a.as_mut()).is_pending());
+        assert!(poll_once(b.as_mut()).is_pending());
+        let Poll::Ready(Ok(a)) = poll_once(a) else {
+            panic!("expected the first read to finish")
+        };
+        let Poll::Ready(Ok(b)) = poll_once(b) else {
+            panic!("expected the second read to finish")
+        };
+        // The second load lost the race and returned the first one's cached value.
+        assert!(std::ptr::eq(a, b));
+    }
+
+    #[test]
+    fn preload_fetches_concurrently() {
+        struct Tag;
+        type Store = AsyncMemoryStore<Tag>;
+
+        let keys: Vec<_> = (0..4)
+            .map(|i| block_on(Store::store(&format!("child {i}"), None)).unwrap())
+            .collect();
+        let mut links: Vec<Link<String, Store>> = keys.iter().copied().map(Link::new).collect();
+        block_on(links[0].read_async()).unwrap();
+        links.push(Link::from_value("modified".into(), None));
+
+        // Each load yields once, so loading them one at a time would take several polls.
+        let mut fut = pin!(preload(&links));
+        assert!(poll_once(fut.as_mut()).is_pending());
+        assert!(matches!(poll_once(fut), Poll::Ready(Ok(()))));
+        assert!(links.iter().all(Link::is_loaded));
+        assert!(links[4].is_dirty());
+        assert_eq!(block_on(links[3].read_async()).unwrap(), "child 3");
+
+        let missing = MemoryStore::<()>::store(&"missing", None).unwrap();
+        let links: Vec<Link<String, Store>> = vec![Link::new(keys[1]), Link::new(missing)];
+        assert!(matches!(
+            block_on(preload(&links)),
+            Err(MemoryError::NotFound(k)) if k == missing
+        ));
+        assert!(links[0].is_loaded());
+    }
+}
@@ -1,23 +1,87 @@
-use std::{
+use core::{
     cell::{Cell, OnceCell},
+    fmt,
+    hash::{Hash, Hasher},
     marker::PhantomData,
     ops::{Deref, DerefMut},
 };
 
-use cid::Cid;
+use alloc::string::String;
+
+use cid::{multibase::Base, Cid};
 use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::{MagicStore, MaybeLink, StaticStore};
+use crate::{
+    async_store, error, link, store, Ambient, AsCid, AsyncStore, CidShape, Flush, Link, LinkStatus,
+    MagicStore, MaybeLink, StaticStore, StoreError,
+};
 
 /// A type that will be inlined if small enough, but is a link otherwise.
 ///
-/// NOTE: The maximum inline size isn't enforced on decode.
-pub struct AutoLink<T, Store, const S: usize = 256> {
+/// The maximum inline size is enforced when saving, not on decode: an inlined value is accepted
+/// whatever its size (e.g., if it was written with a larger `S`), but is treated as modified so
+/// that the next save re-checks its size and moves it into its own block if it's too large.
+///
+/// The maximum inline size is `S` bytes unless overridden at runtime with
+/// [`AutoLink::with_threshold`] (e.g., from a config value, or to fit the parent's size budget).
+/// The runtime threshold belongs to this `AutoLink` value, not the data: it isn't serialized, so
+/// deserialized links use `S` again. The same goes for the CID shape set with
+/// [`AutoLink::with_shape`], which only applies when the value is too large to inline. To choose
+/// regardless of size, pin the value inline or to its own block with [`AutoLink::pin_inline`] or
+/// [`AutoLink::pin_link`].
+///
+/// Like a [`Link`], an `AutoLink` is `Send` whenever `T` is, but never `Sync`.
+pub struct AutoLink<T, Store = Ambient, const S: usize = 256> {
     value: OnceCell<T>,
     state: Cell<InlineState>,
+    decision: Cell<Option<InlineDecision>>,
+    threshold: Option<usize>,
+    mode: InlineMode,
+    shape: Option<CidShape>,
+    /// Whether this was constructed (or deserialized) from a CID rather than a value.
+    was_linked: bool,
     _marker: PhantomData<fn(Store)>,
 }
 
+/// Why an [`AutoLink`] was inlined or stored as a link, as recorded by [`AutoLink::last_decision`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InlineDecision {
+    /// The size of the encoded value.
+    pub encoded_len: usize,
+    /// The maximum inline size.
+    pub threshold: usize,
+    /// Whether the value was inlined.
+    pub inlined: bool,
+    pub reason: InlineReason,
+}
+
+/// The reason behind an [`InlineDecision`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InlineReason {
+    /// The encoded value fit within the threshold.
+    WithinThreshold,
+    /// The encoded value exceeded the threshold.
+    ExceededThreshold,
+    /// The value was pinned inline with [`AutoLink::pin_inline`] (and fit within the threshold).
+    PinnedInline,
+    /// The value was pinned to its own block with [`AutoLink::pin_link`].
+    PinnedLink,
+}
+
+/// Whether an [`AutoLink`] inlines its value by size or always in one place. Set with
+/// [`AutoLink::pin_inline`] and [`AutoLink::pin_link`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InlineMode {
+    /// Inline the value if it encodes to at most the threshold.
+    #[default]
+    Auto,
+    /// Always inline the value, failing to save it if it's larger than the threshold.
+    Inline,
+    /// Always store the value in its own block, however small.
+    Link,
+}
+
 #[derive(Copy, Clone)]
 enum InlineState {
     Modified,
@@ -34,6 +98,56 @@ impl InlineState {
     }
 }
 
+/// Shows whether the value is inlined, modified, or a link (with its CID), and whether it's cached,
+/// without loading anything. The value itself isn't shown, so `T` needn't implement `Debug`.
+impl<T, Store, const S: usize> fmt::Debug for AutoLink<T, Store, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("AutoLink");
+        match self.state.get() {
+            InlineState::Modified => s.field("modified", &true),
+            InlineState::Inlined => s.field("inlined", &true),
+            InlineState::Link(k) => s.field("cid", &k),
+        };
+        s.field("loaded", &self.value.get().is_some()).finish()
+    }
+}
+
+/// Clones the cached value (if any) and the state exactly, so a clone of an inlined value is still
+/// inlined and a clone of a modified value is saved independently.
+impl<T: Clone, Store, const S: usize> Clone for AutoLink<T, Store, S> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            state: self.state.clone(),
+            decision: self.decision.clone(),
+            threshold: self.threshold,
+            mode: self.mode,
+            shape: self.shape,
+            was_linked: self.was_linked,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Like [`Link`]'s: values stored in their own blocks are equal if they have the same CID, and
+/// inlined or modified values aren't equal to anything (including themselves). As with `Link`s,
+/// putting an inlined or modified value in a `HashSet` or `HashMap` is a logic error.
+impl<T, Store, const S: usize> PartialEq for AutoLink<T, Store, S> {
+    fn eq(&self, other: &Self) -> bool {
+        matches!((self.cid(), other.cid()), (Some(a), Some(b)) if a == b)
+    }
+}
+
+impl<T, Store, const S: usize> Eq for AutoLink<T, Store, S> {}
+
+/// Hashes the CID of a value stored in its own block. Inlined and modified values all hash the
+/// same.
+impl<T, Store, const S: usize> Hash for AutoLink<T, Store, S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cid().hash(state)
+    }
+}
+
 impl<T, Store, const S: usize> Serialize for AutoLink<T, Store, S>
 where
     T: Serialize,
@@ -43,9 +157,34 @@ where
     where
         SS: Serializer,
     {
-        self.save()
-            .map_err(<SS::Error as serde::ser::Error>::custom)?
-            .serialize(serializer)
+        self.serialize_saved(serializer, |saved, serializer| saved.serialize(serializer))
+    }
+}
+
+impl<T, Store, const S: usize> AutoLink<T, Store, S>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    /// Save the link and serialize the result with `f`.
+    fn serialize_saved<SS, F>(&self, serializer: SS, f: F) -> Result<SS::Ok, SS::Error>
+    where
+        SS: Serializer,
+        F: FnOnce(MaybeLink<&T>, SS) -> Result<SS::Ok, SS::Error>,
+    {
+        let fail = |e: Store::Error| {
+            let msg = <SS::Error as serde::ser::Error>::custom(&e);
+            error::stash_error(e);
+            msg
+        };
+        let saved = self.save().map_err(fail)?;
+        // An inlined value is serialized right here rather than through an encode, so it needs its
+        // own guard against cycles.
+        let _guard = match saved {
+            MaybeLink::Value(v) => Some(store::enter::<Store, _>(v).map_err(|e| fail(e.into()))?),
+            MaybeLink::Link(_) => None,
+        };
+        f(saved, serializer)
     }
 }
 
@@ -59,24 +198,50 @@ where
         D: Deserializer<'de>,
     {
         Ok(match MaybeLink::deserialize(deserializer)? {
+            // We can't measure the value without re-encoding it, so let `save` decide.
             MaybeLink::Value(value) => AutoLink::from_value(value),
             MaybeLink::Link(k) => AutoLink::from_cid(k),
         })
     }
 }
 
+/// Wraps a value as a modified `AutoLink`. As with [`Link`], there's no `From<Cid>`: use
+/// [`AutoLink::from_cid`].
 impl<T, Store, const S: usize> From<T> for AutoLink<T, Store, S> {
     fn from(v: T) -> Self {
         Self::from_value(v)
     }
 }
 
+/// A value becomes a modified `AutoLink` (so the next save decides whether to inline it), and a
+/// link points at its CID without loading anything.
+impl<T, Store, const S: usize> From<MaybeLink<T>> for AutoLink<T, Store, S> {
+    fn from(v: MaybeLink<T>) -> Self {
+        match v {
+            MaybeLink::Value(v) => Self::from_value(v),
+            MaybeLink::Link(k) => Self::from_cid(k),
+        }
+    }
+}
+
+/// See [`AutoLink::cid`].
+impl<T, Store, const S: usize> AsCid for AutoLink<T, Store, S> {
+    fn as_cid(&self) -> Option<Cid> {
+        self.cid()
+    }
+}
+
 impl<T, Store, const S: usize> AutoLink<T, Store, S> {
     #[must_use]
     pub const fn from_cid(k: Cid) -> Self {
         Self {
             state: Cell::new(InlineState::Link(k)),
             value: OnceCell::new(),
+            decision: Cell::new(None),
+            threshold: None,
+            mode: InlineMode::Auto,
+            shape: None,
+            was_linked: true,
             _marker: PhantomData,
         }
     }
@@ -87,10 +252,157 @@ impl<T, Store, const S: usize> AutoLink<T, Store, S> {
         Self {
             state: Cell::new(InlineState::Modified),
             value: OnceCell::from(v),
+            decision: Cell::new(None),
+            threshold: None,
+            mode: InlineMode::Auto,
+            shape: None,
+            was_linked: false,
             _marker: PhantomData,
         }
     }
-    /// Read the object.
+
+    /// Construct a new `AutoLink` from a value, inlining it if it encodes to at most `threshold`
+    /// bytes instead of `S` (see [`AutoLink::with_threshold`]).
+    #[must_use]
+    pub fn from_value_with_threshold(v: T, threshold: usize) -> Self {
+        Self::from_value(v).with_threshold(threshold)
+    }
+
+    /// Set the maximum inline size used by future saves, overriding `S`. This doesn't change how
+    /// an already-saved value is stored until it's modified.
+    #[must_use]
+    pub fn with_threshold(mut self, threshold: usize) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Construct a new `AutoLink` from a value, with a link-shape hint used if the value is too
+    /// large to inline (see [`AutoLink::with_shape`]).
+    #[must_use]
+    pub fn from_value_with_shape(v: T, shape: Option<CidShape>) -> Self {
+        Self {
+            shape,
+            ..Self::from_value(v)
+        }
+    }
+
+    /// Set the shape of the CID future saves use when the value is too large to inline and is
+    /// stored in its own block. Inlined values have no CID, so the shape doesn't affect them.
+    #[must_use]
+    pub fn with_shape(mut self, shape: CidShape) -> Self {
+        self.shape = Some(shape);
+        self
+    }
+
+    /// Always inline the value when it's saved, whatever the threshold says, e.g., to keep it next
+    /// to its parent for locality. Saving fails with [`StoreError::InlineTooLarge`] if the value
+    /// encodes to more than the threshold, which still bounds the parent's size. Like the
+    /// threshold, this isn't serialized, and applies from the next save of a modified value.
+    #[must_use]
+    pub fn pin_inline(mut self) -> Self {
+        self.mode = InlineMode::Inline;
+        self
+    }
+
+    /// Always store the value in its own block when it's saved, however small, e.g., so identical
+    /// values are shared between parents. Like the threshold, this isn't serialized, and applies
+    /// from the next save of a modified value.
+    #[must_use]
+    pub fn pin_link(mut self) -> Self {
+        self.mode = InlineMode::Link;
+        self
+    }
+
+    /// How future saves decide whether to inline the value.
+    pub fn mode(&self) -> InlineMode {
+        self.mode
+    }
+
+    /// The maximum inline size: the runtime threshold, if set, and `S` otherwise.
+    pub fn threshold(&self) -> usize {
+        self.threshold.unwrap_or(S)
+    }
+
+    /// The CID of the block this value is stored in, or `None` if it's inlined or has been
+    /// modified and not yet saved. Unlike [`AutoLink::save`], this never writes anything.
+    pub fn cid(&self) -> Option<Cid> {
+        match self.state.get() {
+            InlineState::Link(k) => Some(k),
+            InlineState::Modified | InlineState::Inlined => None,
+        }
+    }
+
+    /// The CID in the given multibase (see [`Link::cid_string`]), or `None` if the value is
+    /// inlined or modified. This never saves the value.
+    pub fn cid_string(&self, base: Base) -> Option<String> {
+        self.cid().map(|k| link::cid_string(k, base))
+    }
+
+    /// Whether the value is stored in its own block, inlined, or has unsaved changes, without
+    /// loading or saving anything. [`AutoLink::cid`], [`AutoLink::is_modified`], and
+    /// [`AutoLink::is_inlined`] are shorthands for this.
+    pub fn status(&self) -> LinkStatus {
+        match self.state.get() {
+            InlineState::Link(k) => LinkStatus::Stored(k),
+            InlineState::Modified => LinkStatus::Modified,
+            InlineState::Inlined => LinkStatus::Inlined,
+        }
+    }
+
+    /// Returns true if the value is stored in its own block with CID `k` (see
+    /// [`Link::points_to`]). Inlined and modified values never match.
+    pub fn points_to(&self, k: &Cid) -> bool {
+        self.cid().as_ref() == Some(k)
+    }
+
+    /// Returns true if the value has been modified and not yet saved.
+    pub fn is_modified(&self) -> bool {
+        matches!(self.state.get(), InlineState::Modified)
+    }
+
+    /// Returns true if the value was small enough to be inlined when it was last saved.
+    pub fn is_inlined(&self) -> bool {
+        matches!(self.state.get(), InlineState::Inlined)
+    }
+
+    /// Returns true if this was constructed from a CID (with [`AutoLink::from_cid`], or by
+    /// deserializing a link) rather than from a value (including an inlined one). Unlike
+    /// [`AutoLink::is_inlined`], this describes where the value came from, not how it was last
+    /// saved: it never changes, even if an edit moves the value inline or into its own block, so
+    /// tools can re-emit data in the shape it was read in.
+    pub fn was_linked(&self) -> bool {
+        self.was_linked
+    }
+
+    /// The value, if it's cached.
+    pub(crate) fn cached(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    /// Convert this into a [`Link`], which always stores the value in its own block.
+    ///
+    /// - A value stored in its own block keeps its CID (and its cached value, if loaded, so the
+    ///   `Link` reads it without loading it again).
+    /// - An inlined or modified value becomes a modified `Link`, stored on its next save.
+    ///
+    /// Use [`AutoLink::cid`] first to only convert values that are already stored.
+    #[must_use]
+    pub fn into_link(self) -> Link<T, Store> {
+        match (self.state.get(), self.value.into_inner()) {
+            (InlineState::Link(k), value) => Link::from_cached(k, value),
+            (_, Some(v)) => Link::from_value(v, self.shape),
+            (_, None) => unreachable!("inlined link has no value"),
+        }
+    }
+
+    /// The inlining decision made the last time a modified value was saved, or `None` if this
+    /// link hasn't saved a value.
+    pub fn last_decision(&self) -> Option<InlineDecision> {
+        self.decision.get()
+    }
+
+    /// Read the object, loading it if it's stored in its own block. As with [`Link::read`], blocks
+    /// larger than [`StaticStore::MAX_BLOCK_SIZE`] are rejected.
     pub fn read(&self) -> Result<&T, Store::Error>
     where
         T: DeserializeOwned,
@@ -104,23 +416,122 @@ impl<T, Store, const S: usize> AutoLink<T, Store, S> {
         Ok(self.value.get_or_init(|| val))
     }
 
-    /// Edit the object.
+    /// Edit the object. If it was stored in its own block, the block's CID shape is kept for the
+    /// next save (unless a shape was set with [`AutoLink::with_shape`]), so a value that's still
+    /// too large to inline keeps its codec and hash.
+    ///
+    /// Like [`Link::edit`], this needs `T: Serialize`, so only values that can be saved again can
+    /// be modified:
+    ///
+    /// ```compile_fail
+    /// # use auto_ipld::{AutoLink, MemoryStore};
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize)]
+    /// struct ReadOnly(String);
+    ///
+    /// let mut link: AutoLink<ReadOnly, MemoryStore<()>> = ReadOnly("value".into()).into();
+    /// link.edit();
+    /// ```
     pub fn edit(&mut self) -> Result<&mut T, Store::Error>
     where
-        T: DeserializeOwned,
+        T: DeserializeOwned + Serialize,
         Store: StaticStore,
     {
         if let InlineState::Link(k) = self.state.get() {
             if self.value.get().is_none() {
                 self.value = OnceCell::from(Store::load::<T>(&k)?);
             }
-            self.state = Cell::new(InlineState::Modified);
+            // Like `Link::edit`, keep the block's shape if it's externalized again.
+            self.shape.get_or_insert_with(|| CidShape::from(&k));
         }
+        // Edited inline values are saved (and measured) again, too.
+        self.state = Cell::new(InlineState::Modified);
         Ok(self.value.get_mut().expect("expected value"))
     }
 
+    /// Replace the value with `value`, marking it as modified, and return the previously cached
+    /// value (if any). Unlike assigning through [`AutoLink::edit`], this never loads the old value.
+    pub fn set(&mut self, value: T) -> Option<T> {
+        let old = self.value.take();
+        self.value = OnceCell::from(value);
+        self.state.set(InlineState::Modified);
+        old
+    }
+
+    /// Consume the link and return its value: the cached value if there is one (always the case
+    /// for inlined and modified values) without touching the store, and otherwise the value loaded
+    /// from its block.
+    pub fn take(self) -> Result<T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        match (self.state.get(), self.value.into_inner()) {
+            (_, Some(v)) => Ok(v),
+            (InlineState::Link(k), None) => Store::load(&k),
+            (_, None) => unreachable!("inlined link has no value"),
+        }
+    }
+
+    /// Consume the link and return its cached value, or `None` if it isn't loaded, without
+    /// touching the store (see [`Link::into_inner`]). Inlined and modified values are always
+    /// cached.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+
+    /// Transform the cached value with `f` without touching the store. As with [`Link::map`], the
+    /// result is always modified, so the next save re-encodes it and decides afresh whether to
+    /// inline it. The runtime threshold and shape hint, if any, are kept.
+    ///
+    /// Panics if the value is stored in its own block and isn't loaded; use
+    /// [`AutoLink::try_map`] to load it first.
+    #[must_use]
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> AutoLink<U, Store, S> {
+        let value = (self.value.into_inner()).expect("expected link to be loaded");
+        AutoLink {
+            threshold: self.threshold,
+            mode: self.mode,
+            shape: self.shape,
+            was_linked: self.was_linked,
+            ..AutoLink::from_value(f(value))
+        }
+    }
+
+    /// Like [`AutoLink::map`], but loads the value first if it isn't cached.
+    pub fn try_map<U>(self, f: impl FnOnce(T) -> U) -> Result<AutoLink<U, Store, S>, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        self.read()?;
+        Ok(self.map(f))
+    }
+
+    /// Like [`AutoLink::read`]; the fallible counterpart of dereferencing the link.
+    pub fn get(&self) -> Result<&T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        self.read()
+    }
+
+    /// Like [`AutoLink::edit`]; the fallible counterpart of mutably dereferencing the link.
+    pub fn get_mut(&mut self) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+    {
+        self.edit()
+    }
+
     /// Write-back the value if modified, and return a [`MaybeLink`] that's either the object (if
     /// small enough) or a link to it (if too large).
+    ///
+    /// Modified links inside the value are saved either way, as the value is measured (or
+    /// encoded), so an inlined value needs nothing more from the store until it's edited again
+    /// (which marks it modified).
     pub fn save(&self) -> Result<MaybeLink<&T>, Store::Error>
     where
         T: Serialize,
@@ -136,42 +547,825 @@ impl<T, Store, const S: usize> AutoLink<T, Store, S> {
             }
         }
 
-        let encoded = Store::encode(self.value.get().expect("modified link has no value"))?;
-        if encoded.len() <= S {
-            // We're going to throw away the value here: serde doesn't give us a way to handle
-            // pre-serialized values generically.
-            //
-            // However, we only throw away _small_ values, so it's not terrible.
+        let value = self.value.get().expect("modified link has no value");
+        // Measure first so that inlined values (which the parent re-encodes anyway, as serde can't
+        // splice in pre-encoded bytes) aren't buffered. With a store that counts without buffering
+        // (like `MemoryStore`), saving an inlined value doesn't allocate; values that end up in
+        // their own block are encoded twice, but any links inside them are saved by the first pass.
+        // Values pinned to their own block don't need measuring.
+        let pinned_link = self.mode == InlineMode::Link;
+        if !pinned_link && self.decide(self.encoded_size()?)? {
             self.state.set(InlineState::Inlined);
-            Ok(MaybeLink::Value(
+            Ok(MaybeLink::Value(value))
+        } else {
+            let encoded = store::encode::<Store, _>(value, self.shape.as_ref())?;
+            if pinned_link {
+                self.decide(encoded.len())?;
+            }
+            let (k, saved) = store::store_link_block::<Store>(&encoded, self.shape.as_ref())?;
+            if saved {
+                self.state.set(InlineState::Link(k));
+            }
+            Ok(MaybeLink::Link(k))
+        }
+    }
+
+    /// The encoded length of the value: of its block if it's stored in its own block (see
+    /// [`StaticStore::block_size`]), and otherwise of the value as it would be inlined, which is
+    /// what [`AutoLink::save`] compares to the inline threshold. Like saving, measuring a value
+    /// saves any modified links inside it.
+    pub fn encoded_size(&self) -> Result<usize, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        match self.state.get() {
+            InlineState::Link(k) => Store::describe(&k).map(|(_, len)| len),
+            InlineState::Modified | InlineState::Inlined => store::encoded_len::<Store, _>(
                 self.value.get().expect("modified link has no value"),
-            ))
+                None,
+            ),
+        }
+    }
+
+    /// Save the value if modified (see [`AutoLink::save`]) and return it if it's inlined, or its CID
+    /// if it's stored in its own block. Unlike `save`, this yields the owned value, e.g., to embed
+    /// it elsewhere.
+    pub fn into_maybe_link(self) -> Result<MaybeLink<T>, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        Ok(match self.save()?.link() {
+            Some(k) => MaybeLink::Link(k),
+            None => MaybeLink::Value(self.value.into_inner().expect("inlined link has no value")),
+        })
+    }
+
+    /// Like [`AutoLink::save`], but saves every modified link inside the value first (see
+    /// [`Link::flush`]), whether the value is inlined or stored in its own block.
+    pub fn flush(&self) -> Result<MaybeLink<&T>, Store::Error>
+    where
+        T: Serialize + Flush<Store>,
+        Store: StaticStore,
+    {
+        let cached = !matches!(self.state.get(), InlineState::Link(_));
+        if let (true, Some(v)) = (cached, self.value.get()) {
+            let _guard = store::enter::<Store, _>(v)?;
+            v.flush_links()?;
+        }
+        self.save()
+    }
+
+    /// Decide whether to inline a value of the given encoded size, recording the decision.
+    fn decide(&self, encoded_len: usize) -> Result<bool, StoreError> {
+        let threshold = self.threshold();
+        let fits = encoded_len <= threshold;
+        let (inlined, reason) = match self.mode {
+            InlineMode::Auto if fits => (true, InlineReason::WithinThreshold),
+            InlineMode::Auto => (false, InlineReason::ExceededThreshold),
+            InlineMode::Inline if fits => (true, InlineReason::PinnedInline),
+            InlineMode::Inline => {
+                return Err(StoreError::InlineTooLarge {
+                    len: encoded_len,
+                    max: threshold,
+                })
+            }
+            InlineMode::Link => (false, InlineReason::PinnedLink),
+        };
+        self.decision.set(Some(InlineDecision {
+            encoded_len,
+            threshold,
+            inlined,
+            reason,
+        }));
+        Ok(inlined)
+    }
+
+    /// Like [`AutoLink::read`], but loads through an [`AsyncStore`]. See [`Link::read_async`].
+    ///
+    /// [`Link::read_async`]: crate::Link::read_async
+    pub async fn read_async(&self) -> Result<&T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: AsyncStore,
+    {
+        if let Some(val) = self.value.get() {
+            return Ok(val);
+        }
+        let val = Store::load(&self.state.get().unwrap_ref()).await?;
+        Ok(self.value.get_or_init(|| val))
+    }
+
+    /// Like [`AutoLink::edit`], but loads through an [`AsyncStore`]. See [`Link::edit_async`].
+    ///
+    /// [`Link::edit_async`]: crate::Link::edit_async
+    pub async fn edit_async(&mut self) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: AsyncStore,
+    {
+        if let InlineState::Link(k) = self.state.get() {
+            if self.value.get().is_none() {
+                self.value = OnceCell::from(Store::load::<T>(&k).await?);
+            }
+            // Like `Link::edit`, keep the block's shape if it's externalized again.
+            self.shape.get_or_insert_with(|| CidShape::from(&k));
+        }
+        // Edited inline values are saved (and measured) again, too.
+        self.state = Cell::new(InlineState::Modified);
+        Ok(self.value.get_mut().expect("expected value"))
+    }
+
+    /// Like [`AutoLink::save`], but writes through an [`AsyncStore`]. See [`Link::save_async`].
+    ///
+    /// [`Link::save_async`]: crate::Link::save_async
+    pub async fn save_async(&self) -> Result<MaybeLink<&T>, Store::Error>
+    where
+        T: Serialize,
+        Store: AsyncStore,
+    {
+        let value = self.value.get();
+        match self.state.get() {
+            InlineState::Modified => (),
+            InlineState::Link(k) => return Ok(MaybeLink::Link(k)),
+            InlineState::Inlined => {
+                return Ok(MaybeLink::Value(value.expect("modified link has no value")))
+            }
+        }
+
+        let value = value.expect("modified link has no value");
+        // Inlined values are measured as the parent encodes them; linked ones are re-encoded in
+        // their shape's codec.
+        let encoded = async_store::encode_link::<Store, _>(value, None)?;
+        if self.decide(encoded.len())? {
+            self.state.set(InlineState::Inlined);
+            Ok(MaybeLink::Value(value))
         } else {
-            let k = Store::store_bytes(&encoded, None)?;
+            let encoded = match self.shape {
+                Some(shape) => async_store::encode_link::<Store, _>(value, Some(&shape))?,
+                None => encoded,
+            };
+            let k = async_store::store_link_block::<Store>(&encoded, self.shape.as_ref()).await?;
             self.state.set(InlineState::Link(k));
             Ok(MaybeLink::Link(k))
         }
     }
 }
 
-impl<T, Store> Deref for AutoLink<T, Store>
+/// Dereferencing loads the value, panicking (via [`MagicStore::unwrap`]) if that fails. Use
+/// [`AutoLink::get`] to handle errors instead.
+impl<T, Store, const S: usize> Deref for AutoLink<T, Store, S>
 where
     T: DeserializeOwned,
     Store: MagicStore,
 {
     type Target = T;
 
+    #[track_caller]
     fn deref(&self) -> &Self::Target {
         Store::unwrap(self.read())
     }
 }
 
-impl<T, Store> DerefMut for AutoLink<T, Store>
+/// Like `Deref`, and marks the value as modified. Use [`AutoLink::get_mut`] to handle errors
+/// instead.
+impl<T, Store, const S: usize> DerefMut for AutoLink<T, Store, S>
 where
     T: DeserializeOwned + Serialize,
     Store: MagicStore,
 {
+    #[track_caller]
     fn deref_mut(&mut self) -> &mut Self::Target {
         Store::unwrap(self.edit())
     }
 }
+
+/// Serialize an [`AutoLink`] field in [`TaggedMaybeLink`]'s tagged representation
+/// (`{"value": <value>}` when inlined, `{"link": <cid>}` otherwise) rather than as a bare value or
+/// CID, for use with `#[serde(with = "...")]`:
+///
+/// ```
+/// use auto_ipld::{AutoLink, StaticStore};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(bound = "")]
+/// struct Entry<Store: StaticStore> {
+///     #[serde(with = "auto_ipld::tagged_auto_link")]
+///     body: AutoLink<String, Store>,
+/// }
+/// ```
+///
+/// The link is saved (and inlined or not) exactly as an untagged field would be.
+///
+/// [`TaggedMaybeLink`]: crate::TaggedMaybeLink
+pub mod tagged_auto_link {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{AutoLink, MaybeLink, StaticStore, TaggedMaybeLink};
+
+    pub fn serialize<T, Store, S, const N: usize>(
+        link: &AutoLink<T, Store, N>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+        S: Serializer,
+    {
+        link.serialize_saved(serializer, |saved, serializer| {
+            TaggedMaybeLink::from(saved).serialize(serializer)
+        })
+    }
+
+    pub fn deserialize<'de, T, Store, D, const N: usize>(
+        deserializer: D,
+    ) -> Result<AutoLink<T, Store, N>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        Ok(MaybeLink::from(TaggedMaybeLink::deserialize(deserializer)?).into())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use cid::multibase::Base;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        impl_flush, testing::allocations, AutoLink, CidShape, FaultyStore, InlineDecision,
+        InlineMode, InlineReason, Link, LinkStatus, Magic, MaybeLink, MemoryError, MemoryStore,
+        StaticStore, StatsStore, StoreError, DAG_JSON,
+    };
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(bound = "")]
+    struct Node<Store: StaticStore> {
+        small: AutoLink<String, Store, 16>,
+        large: AutoLink<String, Store, 16>,
+    }
+
+    #[test]
+    fn serialize_independent_of_cache() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let node = Node::<Store> {
+            small: "tiny".to_owned().into(),
+            large: "much too large to be inlined".to_owned().into(),
+        };
+        let k = Store::store(&node, None).unwrap();
+        let fresh = Store::encode(&Store::load::<Node<Store>>(&k).unwrap()).unwrap();
+
+        let loaded: Node<Store> = Store::load(&k).unwrap();
+        loaded.small.read().unwrap();
+        loaded.large.read().unwrap();
+        assert_eq!(Store::encode(&loaded).unwrap(), fresh);
+
+        let mut reverted: Node<Store> = Store::load(&k).unwrap();
+        *reverted.small.edit().unwrap() = "changed".into();
+        *reverted.small.edit().unwrap() = "tiny".into();
+        *reverted.large.edit().unwrap() = "changed".into();
+        *reverted.large.edit().unwrap() = "much too large to be inlined".into();
+        assert_eq!(Store::encode(&reverted).unwrap(), fresh);
+    }
+
+    #[test]
+    fn decision_at_threshold() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        // A short CBOR string encodes as one header byte plus its contents.
+        let fits = AutoLink::<String, Store, 16>::from_value("x".repeat(15));
+        assert_eq!(fits.last_decision(), None);
+        fits.save().unwrap();
+        assert_eq!(
+            fits.last_decision(),
+            Some(InlineDecision {
+                encoded_len: 16,
+                threshold: 16,
+                inlined: true,
+                reason: InlineReason::WithinThreshold,
+            })
+        );
+
+        let over = AutoLink::<String, Store, 16>::from_value("x".repeat(16));
+        over.save().unwrap();
+        assert_eq!(
+            over.last_decision(),
+            Some(InlineDecision {
+                encoded_len: 17,
+                threshold: 16,
+                inlined: false,
+                reason: InlineReason::ExceededThreshold,
+            })
+        );
+    }
+
+    #[test]
+    fn cached_inline_read_is_free() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let link = AutoLink::<String, Store, 16>::from_value("tiny".into());
+        assert!(matches!(link.save().unwrap(), MaybeLink::Value(_)));
+        Store::fail_when(|_| true);
+
+        let before = allocations();
+        drop(std::hint::black_box(vec![0u8; 1]));
+        assert_eq!(
+            allocations(),
+            before + 1,
+            "allocations aren't being counted"
+        );
+
+        let before = allocations();
+        for _ in 0..1000 {
+            assert_eq!(link.read().unwrap().len(), 4);
+        }
+        assert_eq!(allocations(), before);
+        assert_eq!(Store::operations(), 0);
+        Store::reset();
+    }
+
+    #[test]
+    fn inline_save_does_not_allocate() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let link = AutoLink::<String, Store, 16>::from_value("tiny".into());
+        let before = allocations();
+        assert!(matches!(link.save().unwrap(), MaybeLink::Value(_)));
+        assert_eq!(allocations(), before);
+        assert!(link.is_inlined());
+
+        let link = AutoLink::<String, Store, 16>::from_value("much too large to be inlined".into());
+        let MaybeLink::Link(k) = link.save().unwrap() else {
+            panic!("expected a link")
+        };
+        assert_eq!(
+            Store::load::<String>(&k).unwrap(),
+            "much too large to be inlined"
+        );
+    }
+
+    #[test]
+    fn set_without_loading() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let k = MemoryStore::<Tag>::store(&"much too large to be inlined", None).unwrap();
+        let mut link = AutoLink::<String, Store, 16>::from_cid(k);
+        // Every operation fails, so loading would too.
+        Store::fail_when(|_| true);
+        assert_eq!(link.set("tiny".into()), None);
+        assert!(link.is_modified());
+        assert_eq!(link.set("small".into()).as_deref(), Some("tiny"));
+        assert_eq!(Store::operations(), 0);
+        assert!(matches!(link.save().unwrap(), MaybeLink::Value(v) if v == "small"));
+        Store::reset();
+    }
+
+    #[test]
+    fn clone_preserves_state() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let large =
+            AutoLink::<String, Store, 16>::from_value("much too large to be inlined".into());
+        let copy = large.clone();
+        assert!(copy.is_modified());
+        let (MaybeLink::Link(a), MaybeLink::Link(b)) =
+            (large.save().unwrap(), copy.save().unwrap())
+        else {
+            panic!("expected links")
+        };
+        assert_eq!(a, b);
+
+        let small = AutoLink::<String, Store, 16>::from_value("tiny".into());
+        small.save().unwrap();
+        let copy = small.clone();
+        assert!(copy.is_inlined());
+        assert_eq!(
+            Store::encode(&copy).unwrap(),
+            Store::encode(&"tiny").unwrap()
+        );
+    }
+
+    #[test]
+    fn cid_without_saving() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let small = AutoLink::<String, Store, 16>::from_value("tiny".into());
+        let large =
+            AutoLink::<String, Store, 16>::from_value("much too large to be inlined".into());
+        assert_eq!((small.cid(), large.cid()), (None, None));
+        assert!(small.is_modified() && large.is_modified());
+
+        small.save().unwrap();
+        let MaybeLink::Link(k) = large.save().unwrap() else {
+            panic!("expected a link")
+        };
+        assert_eq!((small.cid(), large.cid()), (None, Some(k)));
+        assert!(small.is_inlined() && !small.is_modified());
+        assert!(!large.is_inlined() && !large.is_modified());
+    }
+
+    #[test]
+    fn oversized_inline_value_is_externalized() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let wide = AutoLink::<String, Store, 16>::from_value("twelve chars".into());
+        let bytes = Store::encode(&wide).unwrap();
+        assert!(wide.is_inlined());
+
+        let narrow: AutoLink<String, Store, 8> = Store::decode(&bytes).unwrap();
+        assert_eq!(narrow.read().unwrap(), "twelve chars");
+        let MaybeLink::Link(k) = narrow.save().unwrap() else {
+            panic!("expected the value to be moved into a block")
+        };
+        assert_eq!(Store::load::<String>(&k).unwrap(), "twelve chars");
+    }
+
+    #[test]
+    fn runtime_threshold() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let value = "twelve chars".to_string();
+        let large = AutoLink::<_, Store, 8>::from_value_with_threshold(value.clone(), 64);
+        assert!(matches!(large.save().unwrap(), MaybeLink::Value(_)));
+        assert_eq!(large.last_decision().unwrap().threshold, 64);
+
+        let small = AutoLink::<_, Store, 64>::from_value_with_threshold(value.clone(), 8);
+        assert!(matches!(small.save().unwrap(), MaybeLink::Link(_)));
+        assert_eq!(small.threshold(), 8);
+
+        // Without a runtime threshold, `S` applies.
+        let default = AutoLink::<_, Store, 8>::from_value(value);
+        assert!(matches!(default.save().unwrap(), MaybeLink::Link(_)));
+        assert_eq!(default.last_decision().unwrap().threshold, 8);
+    }
+
+    #[test]
+    fn tagged_fields() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize)]
+        struct Entry {
+            #[serde(with = "crate::tagged_auto_link")]
+            tagged: AutoLink<String, Store, 16>,
+            untagged: AutoLink<String, Store, 16>,
+        }
+
+        let entry = |value: &str| Entry {
+            tagged: AutoLink::from_value(value.into()),
+            untagged: AutoLink::from_value(value.into()),
+        };
+        let small = Store::encode(&entry("tiny")).unwrap();
+        // {"tagged": {"value": "tiny"}, "untagged": "tiny"}
+        assert_eq!(
+            small,
+            b"\xa2\x66tagged\xa1\x65value\x64tiny\x68untagged\x64tiny"
+        );
+        let decoded: Entry = Store::decode(&small).unwrap();
+        assert_eq!(decoded.tagged.read().unwrap(), "tiny");
+        assert!(!decoded.tagged.was_linked());
+
+        let large = entry("much too large to be inlined");
+        let k = large.tagged.save().unwrap().link().unwrap();
+        let decoded: Entry = Store::decode(&Store::encode(&large).unwrap()).unwrap();
+        assert_eq!(decoded.tagged.cid(), Some(k));
+        assert_eq!(decoded.untagged.cid(), Some(k));
+        assert_eq!(
+            decoded.tagged.read().unwrap(),
+            "much too large to be inlined"
+        );
+
+        let json = serde_ipld_dagjson::to_vec(&large).unwrap();
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            format!(r#"{{"tagged":{{"link":{{"/":"{k}"}}}},"untagged":{{"/":"{k}"}}}}"#)
+        );
+    }
+
+    #[test]
+    fn maybe_link_conversions() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+        type Auto = AutoLink<String, Store, 16>;
+
+        let small = Auto::from(MaybeLink::Value("tiny".to_owned()));
+        assert!(small.is_modified());
+        assert!(matches!(small.into_maybe_link().unwrap(), MaybeLink::Value(v) if v == "tiny"));
+
+        let large = Auto::from(MaybeLink::Value("much too large to be inlined".to_owned()));
+        let MaybeLink::Link(k) = large.into_maybe_link().unwrap() else {
+            panic!("expected a link")
+        };
+        assert_eq!(
+            Store::load::<String>(&k).unwrap(),
+            "much too large to be inlined"
+        );
+
+        let linked = Auto::from(MaybeLink::Link(k));
+        assert_eq!(linked.cid(), Some(k));
+        assert!(matches!(linked.into_maybe_link().unwrap(), MaybeLink::Link(l) if l == k));
+    }
+
+    #[test]
+    fn take_in_every_state() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+        type Auto = AutoLink<String, Store, 16>;
+
+        let modified = Auto::from_value("modified".into());
+        assert_eq!(modified.take().unwrap(), "modified");
+
+        let inlined: Auto = Store::decode(&Store::encode(&"inlined").unwrap()).unwrap();
+        assert_eq!(inlined.take().unwrap(), "inlined");
+        assert_eq!(Store::snapshot().loads, 0);
+
+        let k = Store::store(&"much too large to be inlined", None).unwrap();
+        assert_eq!(
+            Auto::from_cid(k).take().unwrap(),
+            "much too large to be inlined"
+        );
+        assert_eq!(Store::snapshot().loads, 1);
+    }
+
+    #[test]
+    fn map_keeps_threshold() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k = Store::store(&"much too large to be inlined", None).unwrap();
+        let link = AutoLink::<String, Store, 16>::from_cid(k).with_threshold(64);
+        let mapped = link.try_map(|s| s.len()).unwrap();
+        assert!(mapped.is_modified());
+        assert_eq!(mapped.threshold(), 64);
+        assert!(matches!(mapped.save().unwrap(), MaybeLink::Value(28)));
+
+        let inlined = AutoLink::<String, Store, 16>::from_value("small".into());
+        inlined.save().unwrap();
+        assert_eq!(*inlined.map(|s| s + "er").read().unwrap(), "smaller");
+    }
+
+    #[test]
+    fn shape_applies_to_large_values() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let shape = CidShape::DEFAULT.with_codec(DAG_JSON);
+        let large = AutoLink::<String, Store, 16>::from_value_with_shape(
+            "much too large to be inlined".into(),
+            Some(shape),
+        );
+        let MaybeLink::Link(k) = large.save().unwrap() else {
+            panic!("expected a link")
+        };
+        assert_eq!(k.codec(), DAG_JSON);
+        assert_eq!(CidShape::from(&k), shape);
+
+        let small = AutoLink::<String, Store, 16>::from_value("tiny".into()).with_shape(shape);
+        assert!(matches!(small.save().unwrap(), MaybeLink::Value(_)));
+        assert_eq!(Store::len(), 1);
+    }
+
+    #[test]
+    fn edits_keep_the_stored_shape() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let shape = CidShape::DEFAULT.with_codec(DAG_JSON);
+        let k = Store::store(&"much too large to be inlined", Some(&shape)).unwrap();
+        let mut link = AutoLink::<String, Store, 16>::from_cid(k);
+        link.edit().unwrap().push('!');
+        let MaybeLink::Link(edited) = link.save().unwrap() else {
+            panic!("expected a link")
+        };
+        assert_ne!(edited, k);
+        assert_eq!(CidShape::from(&edited), shape);
+        assert_eq!(
+            Store::load::<String>(&edited).unwrap(),
+            "much too large to be inlined!"
+        );
+    }
+
+    #[test]
+    fn inlined_values_save_their_links() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize)]
+        struct Holder {
+            inner: Link<String, Store>,
+        }
+        impl_flush!(Flush<Store> for Holder { inner });
+
+        let inner = Link::from_value("first".into(), None);
+        let mut holder = AutoLink::<Holder, Store, 64>::from_value(Holder { inner });
+        assert!(holder.flush().unwrap().link().is_none());
+        let first = holder.read().unwrap().inner.cid().unwrap();
+        assert!(Store::contains(&first));
+
+        // Editing the inlined value dirties it again, so its new link is saved too.
+        *holder.edit().unwrap().inner.edit().unwrap() = "second".into();
+        assert!(holder.flush().unwrap().link().is_none());
+        let second = holder.read().unwrap().inner.cid().unwrap();
+        assert_ne!(first, second);
+        assert_eq!(Store::load::<String>(&second).unwrap(), "second");
+    }
+
+    #[test]
+    fn was_linked_reports_the_decoded_form() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+        type Auto = AutoLink<String, Store, 16>;
+
+        let k = Store::store(&"stored", None).unwrap();
+        let mut linked: Auto =
+            Store::decode(&Store::encode(&MaybeLink::<()>::Link(k)).unwrap()).unwrap();
+        let mut inlined: Auto = Store::decode(&Store::encode(&"inlined").unwrap()).unwrap();
+        assert_eq!(linked.read().unwrap(), "stored");
+        assert_eq!(inlined.read().unwrap(), "inlined");
+        assert!(linked.was_linked());
+        assert!(!inlined.was_linked());
+
+        // Edits that flip the saved form don't change where the value came from.
+        *linked.edit().unwrap() = "tiny".into();
+        *inlined.edit().unwrap() = "much too large to be inlined".into();
+        assert!(matches!(linked.save().unwrap(), MaybeLink::Value(_)));
+        assert!(matches!(inlined.save().unwrap(), MaybeLink::Link(_)));
+        assert!(linked.was_linked());
+        assert!(!inlined.was_linked());
+    }
+
+    #[test]
+    fn encoded_size_measures_the_value_or_block() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let small = AutoLink::<String, Store, 16>::from_value("tiny".into());
+        let len = Store::encode(&"tiny").unwrap().len();
+        assert_eq!(small.encoded_size().unwrap(), len);
+        small.save().unwrap();
+        assert_eq!(small.encoded_size().unwrap(), len);
+
+        let large =
+            AutoLink::<String, Store, 16>::from_value("much too large to be inlined".into());
+        let MaybeLink::Link(k) = large.save().unwrap() else {
+            panic!("expected a link")
+        };
+        let loads = Store::snapshot().loads;
+        assert_eq!(
+            large.encoded_size().unwrap(),
+            MemoryStore::<Tag>::load_bytes(&k).unwrap().len()
+        );
+        assert_eq!(Store::snapshot().loads, loads);
+    }
+
+    #[test]
+    fn pinned_values_ignore_the_threshold() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let small = AutoLink::<String, Store, 64>::from_value("tiny".into()).pin_link();
+        assert_eq!(small.mode(), InlineMode::Link);
+        let MaybeLink::Link(k) = small.save().unwrap() else {
+            panic!("expected a link")
+        };
+        assert_eq!(Store::load::<String>(&k).unwrap(), "tiny");
+        let decision = small.last_decision().unwrap();
+        assert_eq!(
+            (decision.inlined, decision.reason),
+            (false, InlineReason::PinnedLink)
+        );
+
+        let fits = AutoLink::<String, Store, 8>::from_value("fits".into()).pin_inline();
+        assert!(matches!(fits.save().unwrap(), MaybeLink::Value(_)));
+        assert_eq!(
+            fits.last_decision().unwrap().reason,
+            InlineReason::PinnedInline
+        );
+
+        let large = AutoLink::<String, Store, 8>::from_value("far too large".into()).pin_inline();
+        assert!(matches!(
+            large.save(),
+            Err(MemoryError::Store(StoreError::InlineTooLarge {
+                len: 14,
+                max: 8
+            }))
+        ));
+        assert!(large.is_modified());
+        assert_eq!(Store::len(), 1);
+    }
+
+    #[test]
+    fn cid_strings_skip_inlined_values() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let small = AutoLink::<String, Store, 16>::from_value("tiny".into());
+        small.save().unwrap();
+        assert_eq!(small.cid_string(Base::Base32Lower), None);
+        let large =
+            AutoLink::<String, Store, 16>::from_value("much too large to be inlined".into());
+        assert_eq!(large.cid_string(Base::Base32Lower), None);
+        let MaybeLink::Link(k) = large.save().unwrap() else {
+            panic!("expected a link")
+        };
+        assert_eq!(large.cid_string(Base::Base32Lower), Some(k.to_string()));
+    }
+
+    #[test]
+    fn status_tracks_saves() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = Store::store(&"loaded later", None).unwrap();
+        let mut link = AutoLink::<String, Store, 8>::from_cid(k);
+        assert_eq!(link.status(), LinkStatus::Stored(k));
+        assert_eq!(Store::snapshot().loads, 0);
+
+        link.edit().unwrap().truncate(6);
+        assert_eq!(link.status(), LinkStatus::Modified);
+        link.save().unwrap();
+        assert_eq!(link.status(), LinkStatus::Inlined);
+
+        let large = AutoLink::<String, Store, 8>::from_value("too large to inline".into());
+        assert_eq!(large.status(), LinkStatus::Modified);
+        let MaybeLink::Link(saved) = large.save().unwrap() else {
+            panic!("expected a link");
+        };
+        assert_eq!(large.status(), LinkStatus::Stored(saved));
+        assert_eq!(Store::snapshot().stores, 2);
+    }
+
+    #[test]
+    fn into_inner_never_loads() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = Store::store(&"stored", None).unwrap();
+        assert_eq!(AutoLink::<String, Store>::from_cid(k).into_inner(), None);
+        let small = AutoLink::<String, Store>::from_value("fresh".into());
+        small.save().unwrap();
+        assert!(small.is_inlined());
+        assert_eq!(small.into_inner().as_deref(), Some("fresh"));
+        assert_eq!(Store::snapshot().loads, 0);
+    }
+
+    #[test]
+    fn externalized_values_convert_without_reloading() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let large = AutoLink::<String, Store, 8>::from_value("too large to inline".into());
+        let MaybeLink::Link(k) = large.save().unwrap() else {
+            panic!("expected a link");
+        };
+        let link = large.into_link();
+        assert_eq!(link.status(), LinkStatus::Stored(k));
+        assert_eq!(link.read().unwrap(), "too large to inline");
+
+        let loaded = AutoLink::<String, Store, 8>::from_cid(k);
+        loaded.read().unwrap();
+        assert_eq!(loaded.into_link().read().unwrap(), "too large to inline");
+        assert_eq!(Store::snapshot().loads, 1);
+    }
+
+    #[test]
+    fn points_to_only_stored_values() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k = Store::store(&"tiny", None).unwrap();
+        let mut link = AutoLink::<String, Store, 8>::from_cid(k);
+        assert!(link.points_to(&k));
+        link.edit().unwrap();
+        assert!(!link.points_to(&k));
+        // Saved again, the small value is inlined rather than stored at `k`.
+        link.save().unwrap();
+        assert!(link.is_inlined() && !link.points_to(&k));
+    }
+
+    #[test]
+    fn deref_with_any_threshold() {
+        struct Tag;
+        type Store = Magic<MemoryStore<Tag>>;
+
+        let k = Store::store(&"loaded on deref", None).unwrap();
+        let mut link = AutoLink::<String, Store, 32>::from_cid(k);
+        assert_eq!(link.len(), 15);
+        link.push('!');
+        assert!(link.is_modified());
+        assert!(matches!(link.save().unwrap(), MaybeLink::Value(s) if s == "loaded on deref!"));
+    }
+}
@@ -0,0 +1,328 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    io::{Read, Write},
+    marker::PhantomData,
+    sync::{LazyLock, Mutex},
+    time::Instant,
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{store, CidShape, StaticStore};
+
+#[derive(Default)]
+struct Pending {
+    /// The buffered blocks, in the order they were stored.
+    blocks: Vec<(Vec<u8>, Option<CidShape>)>,
+    /// The index of each buffered block in `blocks`.
+    index: HashMap<Cid, usize>,
+}
+
+impl Pending {
+    fn get(&self, k: &Cid) -> Option<&[u8]> {
+        self.index.get(k).map(|&i| &self.blocks[i].0[..])
+    }
+
+    /// Buffer a block, returning true if it wasn't already buffered.
+    fn insert(&mut self, k: Cid, block: &[u8], shape: Option<&CidShape>) -> bool {
+        if self.index.contains_key(&k) {
+            return false;
+        }
+        self.index.insert(k, self.blocks.len());
+        self.blocks.push((block.to_vec(), shape.copied()));
+        true
+    }
+
+    /// Drop a buffered block, returning true if it was buffered.
+    fn remove(&mut self, k: &Cid) -> bool {
+        let Some(i) = self.index.remove(k) else {
+            return false;
+        };
+        self.blocks.remove(i);
+        for j in self.index.values_mut() {
+            if *j > i {
+                *j -= 1;
+            }
+        }
+        true
+    }
+}
+
+static BATCHES: LazyLock<Mutex<HashMap<TypeId, Pending>>> = LazyLock::new(Default::default);
+
+/// A store wrapper that buffers every block written through it in memory until
+/// [`Batch::commit`] writes them all to `S` with one [`StaticStore::store_many`] call, or
+/// [`Batch::abort`] discards them. A subtree built in a batch either lands in `S` as a whole or (if
+/// it's aborted) leaves `S` untouched, as far as `S::store_many` is atomic.
+///
+/// CIDs are computed as blocks are buffered (in the requested shape, see [`CidShape::cid`]), so
+/// links saved into the batch get their final CIDs right away, and loads see buffered blocks before
+/// falling through to `S`. This assumes `S` derives CIDs from shapes in the usual way.
+///
+/// Every buffered block is held in memory, in full, until the batch is committed or aborted, so
+/// batches should be bounded (e.g., by committing every few thousand blocks). The buffer is
+/// process-global and keyed by the wrapper type, like [`CachingStore`](crate::CachingStore)'s
+/// cache, so it's shared by every thread writing through `Batch<S>`.
+///
+/// Aborting doesn't roll back links: a link saved into an aborted batch still points at a block
+/// that was never written.
+pub struct Batch<S>(PhantomData<S>);
+
+impl<S: 'static> Batch<S> {
+    fn with_pending<R>(f: impl FnOnce(&mut Pending) -> R) -> R {
+        f(BATCHES
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<Self>())
+            .or_default())
+    }
+
+    /// The number of buffered blocks.
+    pub fn len() -> usize {
+        Self::with_pending(|p| p.blocks.len())
+    }
+
+    /// Returns true if no blocks are buffered.
+    pub fn is_empty() -> bool {
+        Self::len() == 0
+    }
+
+    /// Discard every buffered block without writing anything.
+    pub fn abort() {
+        Self::with_pending(|p| *p = Pending::default())
+    }
+
+    /// Write every buffered block to `S` and empty the batch. If `S` fails, the blocks stay
+    /// buffered, so the commit can be retried (or the batch aborted).
+    pub fn commit() -> Result<(), S::Error>
+    where
+        S: StaticStore,
+    {
+        let pending = Self::with_pending(std::mem::take);
+        if pending.blocks.is_empty() {
+            return Ok(());
+        }
+        if let Err(e) = S::store_many(&pending.blocks) {
+            // Put the blocks back ahead of any buffered since.
+            Self::with_pending(|p| {
+                let newer = std::mem::replace(p, pending);
+                for (k, i) in newer.index {
+                    let (block, shape) = &newer.blocks[i];
+                    p.insert(k, block, shape.as_ref());
+                }
+            });
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn buffered(k: &Cid) -> Option<Vec<u8>> {
+        Self::with_pending(|p| p.get(k).map(<[u8]>::to_vec))
+    }
+}
+
+impl<S> StaticStore for Batch<S>
+where
+    S: StaticStore + 'static,
+{
+    type Error = S::Error;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        Self::store_if_absent(value, shape).map(|(k, _)| k)
+    }
+
+    /// Reports whether the block is new to the batch; `S` isn't consulted.
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        let k = shape.copied().unwrap_or_default().cid(value)?;
+        let new = Self::with_pending(|p| p.insert(k, value, shape));
+        Ok((k, new))
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        match Self::buffered(k) {
+            Some(block) => Ok(block),
+            None => S::load_bytes(k),
+        }
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        match Self::buffered(k) {
+            Some(block) => {
+                store::check_block_size(k, block.len(), max)?;
+                Ok(block)
+            }
+            None => S::load_bytes_limited(k, max),
+        }
+    }
+
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        match Self::buffered(k) {
+            Some(block) => Ok(block),
+            None => S::load_bytes_deadline(k, deadline),
+        }
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(Self::with_pending(|p| p.index.contains_key(k)) || S::has(k)?)
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        match Self::with_pending(|p| p.get(k).map(<[u8]>::len)) {
+            Some(len) => Ok(len),
+            None => S::block_size(k),
+        }
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    /// Lists the buffered blocks along with `S`'s.
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        let mut keys = S::keys()?;
+        let stored: std::collections::HashSet<Cid> = keys.iter().copied().collect();
+        let buffered = Self::with_pending(|p| p.index.keys().copied().collect::<Vec<_>>());
+        keys.extend(buffered.into_iter().filter(|k| !stored.contains(k)));
+        Ok(keys)
+    }
+
+    /// Drops the block from the batch and removes it from `S`.
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        let buffered = Self::with_pending(|p| p.remove(k));
+        Ok(S::remove(k)? || buffered)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Batch, FaultyStore, Link, MemoryStore, StaticStore};
+
+    #[derive(Serialize, Deserialize)]
+    struct Node {
+        name: String,
+        children: Vec<Link<Node, Batch<MemoryStore<Tag>>>>,
+    }
+
+    struct Tag;
+    type Inner = MemoryStore<Tag>;
+    type Store = Batch<Inner>;
+
+    #[test]
+    fn commits_or_aborts_as_a_whole() {
+        let node = |name: &str, children| Node {
+            name: name.into(),
+            children,
+        };
+        let leaf = Link::from_value(node("leaf", vec![]), None);
+        let root = Store::store(&node("root", vec![leaf]), None).unwrap();
+        assert_eq!(Store::len(), 2);
+        assert!(Inner::is_empty());
+
+        // Buffered blocks can be read back before they're committed.
+        let loaded: Node = Store::load(&root).unwrap();
+        assert_eq!(loaded.children[0].read().unwrap().name, "leaf");
+
+        Store::abort();
+        assert!(Store::is_empty() && Inner::is_empty());
+        assert!(!Store::has(&root).unwrap());
+
+        let root = Store::store(&node("root", vec![]), None).unwrap();
+        Store::commit().unwrap();
+        assert!(Store::is_empty());
+        assert_eq!(Inner::len(), 1);
+        assert_eq!(Inner::load::<Node>(&root).unwrap().name, "root");
+    }
+
+    #[test]
+    fn failed_commits_keep_the_blocks() {
+        struct Tag;
+        type Inner = FaultyStore<MemoryStore<Tag>>;
+        type Store = Batch<Inner>;
+
+        let k = Store::store(&"kept", None).unwrap();
+        Inner::fail_when(|_| true);
+        assert!(Store::commit().is_err());
+        assert_eq!(Store::len(), 1);
+        Inner::reset();
+        Store::commit().unwrap();
+        assert_eq!(MemoryStore::<Tag>::load::<String>(&k).unwrap(), "kept");
+    }
+}
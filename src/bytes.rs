@@ -0,0 +1,165 @@
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+use alloc::vec::Vec;
+
+use serde::{
+    de::{SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+/// A byte string, for struct fields (and link targets) that hold bytes.
+///
+/// Serde serializes a `Vec<u8>` as a sequence, so a `Vec<u8>` field becomes a DAG-CBOR array of
+/// integers (one or two bytes per byte, and not IPLD bytes). A `RawBytes` serializes with
+/// `serialize_bytes` instead, so it's a CBOR byte string, DAG-JSON's `{"/": {"bytes": ...}}`, and a
+/// [`Value::Bytes`](crate::Value::Bytes). It deserializes from a byte string or, for formats
+/// without one (e.g., plain JSON), a sequence of bytes. A `Link<RawBytes, _>` can be saved as a
+/// [`RAW`](crate::RAW) block, whose contents are the bytes themselves (see also
+/// [`RawLink`](crate::RawLink), which never encodes them).
+///
+/// A byte string is never a link: DAG-CBOR presents links as a tag 42, which [`MaybeLink`] sees as
+/// a newtype, so a `MaybeLink<RawBytes>` decodes bytes as a value, even if they happen to parse as
+/// a CID.
+///
+/// [`MaybeLink`]: crate::MaybeLink
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RawBytes(pub Vec<u8>);
+
+impl RawBytes {
+    /// The bytes.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// Shows the bytes in hex.
+impl fmt::Debug for RawBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RawBytes(")?;
+        for b in &self.0 {
+            write!(f, "{b:02x}")?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl Deref for RawBytes {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for RawBytes {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for RawBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for RawBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        RawBytes(bytes)
+    }
+}
+
+impl From<&[u8]> for RawBytes {
+    fn from(bytes: &[u8]) -> Self {
+        RawBytes(bytes.into())
+    }
+}
+
+impl From<RawBytes> for Vec<u8> {
+    fn from(bytes: RawBytes) -> Self {
+        bytes.0
+    }
+}
+
+impl Serialize for RawBytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawBytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_byte_buf(RawBytesVisitor)
+    }
+}
+
+struct RawBytesVisitor;
+
+impl<'de> Visitor<'de> for RawBytesVisitor {
+    type Value = RawBytes;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a byte string")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(RawBytes(v.into()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(RawBytes(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(b) = seq.next_element()? {
+            bytes.push(b);
+        }
+        Ok(RawBytes(bytes))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{CidShape, Link, MaybeLink, MemoryStore, RawBytes, StaticStore, RAW};
+
+    #[test]
+    fn raw_bytes_are_byte_strings() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Node {
+            data: RawBytes,
+        }
+
+        let node = Node {
+            data: RawBytes(vec![1, 2, 3]),
+        };
+        // {"data": h'010203'}, where a `Vec<u8>` would be [1, 2, 3].
+        let bytes = Store::encode(&node).unwrap();
+        assert_eq!(bytes, b"\xa1\x64data\x43\x01\x02\x03");
+        assert_eq!(Store::decode::<Node>(&bytes).unwrap(), node);
+        let json = serde_json::to_string(&[1u8, 2, 3]).unwrap();
+        assert_eq!(*serde_json::from_str::<RawBytes>(&json).unwrap(), [1, 2, 3]);
+
+        // Bytes that parse as a CID are still a value.
+        let k = Store::store(&"linked", None).unwrap();
+        let cid_bytes = RawBytes(k.to_bytes());
+        let decoded: MaybeLink<RawBytes> =
+            Store::decode(&Store::encode(&cid_bytes).unwrap()).unwrap();
+        assert_eq!(decoded.into_value(), Some(cid_bytes));
+
+        // And they can be raw blocks.
+        let shape = CidShape::DEFAULT.with_codec(RAW);
+        let link: Link<RawBytes, Store> = Link::from_value(RawBytes(b"raw".to_vec()), Some(shape));
+        let k = link.save().unwrap();
+        assert_eq!(Store::load_bytes(&k).unwrap(), b"raw");
+        assert_eq!(Link::<RawBytes, Store>::new(k).read().unwrap().0, b"raw");
+    }
+}
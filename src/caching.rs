@@ -0,0 +1,299 @@
+use std::{
+    any::TypeId,
+    collections::{BTreeMap, HashMap},
+    io::{Read, Write},
+    marker::PhantomData,
+    sync::{LazyLock, Mutex},
+    time::Instant,
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{store, CidShape, StaticStore};
+
+#[derive(Default)]
+struct Cache {
+    /// Each cached block and when it was last used.
+    blocks: HashMap<Cid, (Vec<u8>, u64)>,
+    /// The cached blocks, least recently used first.
+    by_use: BTreeMap<u64, Cid>,
+    bytes: usize,
+    clock: u64,
+}
+
+impl Cache {
+    fn get(&mut self, k: &Cid) -> Option<Vec<u8>> {
+        let (block, used) = self.blocks.get_mut(k)?;
+        self.by_use.remove(used);
+        self.clock += 1;
+        *used = self.clock;
+        self.by_use.insert(self.clock, *k);
+        Some(block.clone())
+    }
+
+    fn remove(&mut self, k: &Cid) {
+        if let Some((block, used)) = self.blocks.remove(k) {
+            self.by_use.remove(&used);
+            self.bytes -= block.len();
+        }
+    }
+
+    fn insert(&mut self, k: Cid, block: &[u8], cap: usize) {
+        if self.get(&k).is_some() || block.len() > cap {
+            return;
+        }
+        while self.bytes + block.len() > cap {
+            let (_, oldest) = self.by_use.pop_first().expect("cache is over budget");
+            let (evicted, _) = self.blocks.remove(&oldest).expect("cache index is stale");
+            self.bytes -= evicted.len();
+        }
+        self.clock += 1;
+        self.blocks.insert(k, (block.to_vec(), self.clock));
+        self.by_use.insert(self.clock, k);
+        self.bytes += block.len();
+    }
+}
+
+static CACHES: LazyLock<Mutex<HashMap<TypeId, Cache>>> = LazyLock::new(Default::default);
+
+/// A store wrapper that keeps the most recently used blocks of `S` in memory, up to `CAP` bytes of
+/// block data. When a new block would exceed the budget, the least recently used blocks are
+/// evicted; blocks larger than `CAP` are never cached.
+///
+/// Loads are answered from the cache when possible, and blocks stored through the wrapper are
+/// cached as they're written. Blocks removed from `S` directly stay cached until evicted (or
+/// [`CachingStore::clear`]ed).
+///
+/// The cache is process-global and keyed by the wrapper type (so `CachingStore<S, 1024>` and
+/// `CachingStore<S, 4096>` have separate caches), like
+/// [`NegativeCacheStore`](crate::NegativeCacheStore)'s. It's shared by all threads behind a mutex,
+/// which is never held while calling into `S`; two threads missing the same block concurrently
+/// may both load it from `S`.
+pub struct CachingStore<S, const CAP: usize>(PhantomData<S>);
+
+impl<S: 'static, const CAP: usize> CachingStore<S, CAP> {
+    fn with_cache<R>(f: impl FnOnce(&mut Cache) -> R) -> R {
+        f(CACHES
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<Self>())
+            .or_default())
+    }
+
+    /// Returns true if `k` is currently cached.
+    pub fn is_cached(k: &Cid) -> bool {
+        Self::with_cache(|c| c.blocks.contains_key(k))
+    }
+
+    /// The total size of the cached blocks, in bytes.
+    pub fn cached_bytes() -> usize {
+        Self::with_cache(|c| c.bytes)
+    }
+
+    /// Evict every cached block.
+    pub fn clear() {
+        Self::with_cache(|c| *c = Cache::default())
+    }
+
+    fn cache(k: Cid, block: &[u8]) {
+        Self::with_cache(|c| c.insert(k, block, CAP))
+    }
+}
+
+impl<S, const CAP: usize> StaticStore for CachingStore<S, CAP>
+where
+    S: StaticStore + 'static,
+{
+    type Error = S::Error;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        let k = S::store_bytes(value, shape)?;
+        Self::cache(k, value);
+        Ok(k)
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        let (k, new) = S::store_if_absent(value, shape)?;
+        Self::cache(k, value);
+        Ok((k, new))
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        if let Some(block) = Self::with_cache(|c| c.get(k)) {
+            return Ok(block);
+        }
+        let block = S::load_bytes(k)?;
+        Self::cache(*k, &block);
+        Ok(block)
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        if let Some(block) = Self::with_cache(|c| c.get(k)) {
+            store::check_block_size(k, block.len(), max)?;
+            return Ok(block);
+        }
+        let block = S::load_bytes_limited(k, max)?;
+        Self::cache(*k, &block);
+        Ok(block)
+    }
+
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        if let Some(block) = Self::with_cache(|c| c.get(k)) {
+            return Ok(block);
+        }
+        let block = S::load_bytes_deadline(k, deadline)?;
+        Self::cache(*k, &block);
+        Ok(block)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        if Self::is_cached(k) {
+            return Ok(true);
+        }
+        S::has(k)
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        S::block_size(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        S::keys_in_range(start, end)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        S::keys()
+    }
+
+    /// Evicts the block from the cache too.
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        Self::with_cache(|c| c.remove(k));
+        S::remove(k)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{CachingStore, FaultyStore, MemoryStore, StaticStore};
+
+    #[test]
+    fn evicts_least_recently_used() {
+        struct Tag;
+        type Inner = FaultyStore<MemoryStore<Tag>>;
+        // Room for three of the (equally sized) blocks below.
+        type Store = CachingStore<Inner, 15>;
+
+        Inner::fail_when(|_| false);
+        let keys: Vec<_> = ["aaaa", "bbbb", "cccc", "dddd"]
+            .iter()
+            .map(|s| Store::store(s, None).unwrap())
+            .collect();
+        assert_eq!(Store::cached_bytes(), 15);
+        assert_eq!(Inner::operations(), 4);
+
+        // The newest blocks are served from the cache.
+        for k in &keys[1..] {
+            Store::load_bytes(k).unwrap();
+        }
+        assert_eq!(Inner::operations(), 4);
+
+        // The oldest was evicted, so it's re-fetched from the inner store (evicting "bbbb").
+        assert!(!Store::is_cached(&keys[0]));
+        assert_eq!(Store::load::<String>(&keys[0]).unwrap(), "aaaa");
+        assert_eq!(Inner::operations(), 5);
+        assert!(Store::is_cached(&keys[0]) && !Store::is_cached(&keys[1]));
+        Inner::reset();
+    }
+
+    #[test]
+    fn remove_evicts() {
+        struct Tag;
+        type Store = CachingStore<MemoryStore<Tag>, 1024>;
+
+        let k = Store::store(&"removed", None).unwrap();
+        assert!(Store::is_cached(&k));
+        assert!(Store::remove(&k).unwrap());
+        assert!(!Store::is_cached(&k) && Store::cached_bytes() == 0);
+        assert!(Store::is_not_found(&Store::load_bytes(&k).unwrap_err()));
+        assert!(!Store::remove(&k).unwrap());
+    }
+}
@@ -0,0 +1,528 @@
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{self, Read, Seek, SeekFrom, Write},
+};
+
+use cid::Cid;
+
+use crate::{
+    codec::{self, cbor},
+    CidShape, CodecError, StaticStore, StoreError, DEFAULT_MAX_BLOCK_SIZE,
+};
+
+/// An error encountered while reading or writing a CAR file.
+#[derive(Debug)]
+pub enum CarError<E> {
+    Io(io::Error),
+    /// The CAR file or one of its blocks is malformed.
+    Codec(CodecError),
+    Store(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CarError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CarError::Io(e) => write!(f, "car i/o error: {e}"),
+            CarError::Codec(e) => write!(f, "car decode error: {e}"),
+            CarError::Store(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for CarError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CarError::Io(e) => Some(e),
+            CarError::Codec(e) => Some(e),
+            CarError::Store(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<io::Error> for CarError<E> {
+    fn from(e: io::Error) -> Self {
+        CarError::Io(e)
+    }
+}
+
+impl<E> From<CodecError> for CarError<E> {
+    fn from(e: CodecError) -> Self {
+        CarError::Codec(e)
+    }
+}
+
+pub(crate) fn write_varint(w: &mut impl Write, mut n: u64) -> io::Result<()> {
+    let mut buf = [0u8; 10];
+    let mut i = 0;
+    loop {
+        buf[i] = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            break;
+        }
+        buf[i] |= 0x80;
+        i += 1;
+    }
+    w.write_all(&buf[..=i])
+}
+
+/// Read a varint, returning `None` on a clean end-of-file.
+pub(crate) fn read_varint(r: &mut impl Read) -> io::Result<Option<u64>> {
+    let mut n = 0u64;
+    for i in 0..10 {
+        let mut b = [0u8];
+        if let Err(e) = r.read_exact(&mut b) {
+            return match e.kind() {
+                io::ErrorKind::UnexpectedEof if i == 0 => Ok(None),
+                _ => Err(e),
+            };
+        }
+        n |= ((b[0] & 0x7f) as u64) << (7 * i);
+        if b[0] & 0x80 == 0 {
+            return Ok(Some(n));
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint overflow",
+    ))
+}
+
+/// Encode a CARv1 header.
+pub(crate) fn car_header(roots: &[Cid]) -> Vec<u8> {
+    let mut out = Vec::new();
+    cbor::write_head(&mut out, 5, 2);
+    cbor::write_str(&mut out, "roots");
+    cbor::write_head(&mut out, 4, roots.len() as u64);
+    for root in roots {
+        cbor::write_cid(&mut out, root);
+    }
+    cbor::write_str(&mut out, "version");
+    cbor::write_head(&mut out, 0, 1);
+    out
+}
+
+fn invalid(e: CodecError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Write a CARv1 header (with its length prefix).
+fn write_header(w: &mut impl Write, roots: &[Cid]) -> io::Result<()> {
+    let header = car_header(roots);
+    write_varint(w, header.len() as u64)?;
+    w.write_all(&header)
+}
+
+/// Read a CARv1 header (with its length prefix), returning its roots.
+fn read_header(r: &mut impl Read) -> io::Result<Vec<Cid>> {
+    let header_len = read_varint(r)?.ok_or(invalid(CodecError::Truncated))?;
+    if header_len > MAX_SECTION_LEN {
+        return Err(invalid(CodecError::Malformed("header too large")));
+    }
+    let mut header = vec![0; header_len as usize];
+    r.read_exact(&mut header)?;
+    let mut roots = Vec::new();
+    cbor::links(&header, &mut roots).map_err(invalid)?;
+    Ok(roots)
+}
+
+/// Write a length-prefixed `(cid, block)` section.
+fn write_section(w: &mut impl Write, k: &Cid, block: &[u8]) -> io::Result<()> {
+    let k_bytes = k.to_bytes();
+    write_varint(w, (k_bytes.len() + block.len()) as u64)?;
+    w.write_all(&k_bytes)?;
+    w.write_all(block)
+}
+
+/// The largest encoded CID a section may start with: a 64-byte digest plus four varints.
+const MAX_CID_LEN: usize = 64 + 4 * 10;
+
+/// The largest section [`read_section`] accepts: a [`DEFAULT_MAX_BLOCK_SIZE`] block plus its CID.
+const MAX_SECTION_LEN: u64 = (DEFAULT_MAX_BLOCK_SIZE + MAX_CID_LEN) as u64;
+
+/// Read a length-prefixed section, returning its CID and block, or `None` at the end of the file.
+///
+/// Sections longer than [`MAX_SECTION_LEN`] are rejected before anything is allocated, and a
+/// section cut short by the end of the file fails with [`io::ErrorKind::UnexpectedEof`].
+fn read_section(r: &mut impl Read) -> io::Result<Option<(Cid, Vec<u8>)>> {
+    let Some(len) = read_varint(r)? else {
+        return Ok(None);
+    };
+    if len > MAX_SECTION_LEN {
+        return Err(invalid(CodecError::Malformed("section too large")));
+    }
+    let mut section = vec![0; len as usize];
+    r.read_exact(&mut section)?;
+    let mut block = &section[..];
+    let k = Cid::read_bytes(&mut block).map_err(|e| invalid(CodecError::InvalidCid(e)))?;
+    Ok(Some((k, block.to_vec())))
+}
+
+/// Write every block reachable from `root` (loaded from `S`) to `w` as a CARv1 file with `root` as
+/// its only root.
+///
+/// Blocks are written parents-first, starting with the root, and each block is written once, even
+/// if it's linked from several places. Links are found with [`codec::child_links`], so every
+/// reachable block must be DAG-CBOR, DAG-PB, raw, or DAG-JSON.
+pub fn export_car<S: StaticStore, W: Write>(
+    root: &Cid,
+    mut w: W,
+) -> Result<(), CarError<S::Error>> {
+    write_header(&mut w, &[*root])?;
+    let mut stack = vec![*root];
+    let mut visited = HashSet::from([*root]);
+    while let Some(k) = stack.pop() {
+        let block = S::load_bytes(&k).map_err(CarError::Store)?;
+        let children = codec::child_links(&block, k.codec())?;
+        write_section(&mut w, &k, &block)?;
+        // Push in reverse so children are written in the order they're linked.
+        stack.extend(children.into_iter().rev().filter(|c| visited.insert(*c)));
+    }
+    w.flush()?;
+    Ok(())
+}
+
+/// Read a CARv1 file from `r`, storing every block in `S`, and return the file's roots.
+///
+/// Each block is stored in the shape of its CID, and the CID `S` returns must match the one in the
+/// file, so a corrupt block fails with [`StoreError::HashMismatch`]. Blocks read before an error
+/// are left in the store.
+pub fn import_car<S: StaticStore, R: Read>(mut r: R) -> Result<Vec<Cid>, CarError<S::Error>> {
+    let roots = read_header(&mut r)?;
+    while let Some((k, block)) = read_section(&mut r)? {
+        let stored = S::store_bytes(&block, Some(&CidShape::from(&k))).map_err(CarError::Store)?;
+        // Compare the codec and hash so CIDv0 blocks match the CIDv1 stores return.
+        if (stored.codec(), stored.hash()) != (k.codec(), k.hash()) {
+            return Err(CarError::Store(StoreError::HashMismatch(k).into()));
+        }
+    }
+    Ok(roots)
+}
+
+/// A file that can be cut short, so [`CarAppender::open`] can drop a torn final section.
+pub trait Truncate {
+    /// Truncate the file to `len` bytes.
+    fn truncate(&mut self, len: u64) -> io::Result<()>;
+}
+
+impl Truncate for std::fs::File {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        self.set_len(len)
+    }
+}
+
+impl Truncate for io::Cursor<Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        self.get_mut().truncate(len as usize);
+        Ok(())
+    }
+}
+
+impl Truncate for io::Cursor<&mut Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        self.get_mut().truncate(len as usize);
+        Ok(())
+    }
+}
+
+impl<T: Truncate + ?Sized> Truncate for &mut T {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        (**self).truncate(len)
+    }
+}
+
+/// A block whose children are still being visited.
+struct Pending {
+    cid: Cid,
+    block: Vec<u8>,
+    children: Vec<Cid>,
+}
+
+/// Appends blocks to a CARv1 file, skipping blocks the file already contains.
+///
+/// CARv1 stores its roots in a header at the start of the file, so the roots can't change without
+/// rewriting it. The header roots are fixed when the file is created; roots added later (see
+/// [`CarAppender::add_subtree`]) are tracked in [`CarAppender::roots`] but aren't persisted in the
+/// file itself. Callers that need them should record new roots separately (e.g., in a sidecar
+/// index) or rewrite the header when compacting the file.
+///
+/// Subtrees are written leaves-first and a block is only skipped when it is already in the file,
+/// in which case its whole subtree is assumed to be present too. This holds for any file written
+/// exclusively by `CarAppender`, even one interrupted mid-write: [`CarAppender::open`] truncates a
+/// section torn by the interruption and appends after the last complete one.
+pub struct CarAppender<F> {
+    file: F,
+    known: HashSet<Cid>,
+    roots: Vec<Cid>,
+}
+
+impl<F: Read + Write + Seek> CarAppender<F> {
+    /// Start a new CAR file with the given header roots.
+    pub fn create(mut file: F, roots: &[Cid]) -> io::Result<Self> {
+        write_header(&mut file, roots)?;
+        Ok(CarAppender {
+            file,
+            known: HashSet::new(),
+            roots: roots.to_vec(),
+        })
+    }
+
+    /// Open an existing CAR file for appending, indexing the blocks it already contains.
+    ///
+    /// If the file ends partway through a section (e.g., because a previous append was
+    /// interrupted), it's truncated to the end of the last complete section.
+    pub fn open(mut file: F) -> io::Result<Self>
+    where
+        F: Truncate,
+    {
+        file.seek(SeekFrom::Start(0))?;
+        let roots = read_header(&mut file)?;
+
+        let mut known = HashSet::new();
+        let mut end = file.stream_position()?;
+        loop {
+            match read_section(&mut file) {
+                Ok(Some((k, _))) => {
+                    known.insert(k);
+                    end = file.stream_position()?;
+                }
+                Ok(None) => break,
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    file.truncate(end)?;
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(CarAppender { file, known, roots })
+    }
+
+    /// The file's header roots followed by any roots added since it was opened.
+    pub fn roots(&self) -> &[Cid] {
+        &self.roots
+    }
+
+    /// Returns true if the file contains the block.
+    pub fn contains(&self, k: &Cid) -> bool {
+        self.known.contains(k)
+    }
+
+    /// The number of blocks in the file.
+    pub fn len(&self) -> usize {
+        self.known.len()
+    }
+
+    /// Returns true if the file contains no blocks.
+    pub fn is_empty(&self) -> bool {
+        self.known.is_empty()
+    }
+
+    /// Append every block reachable from `root` (loaded from `S`) that isn't already in the file,
+    /// and record `root` as a root. Returns the number of blocks written.
+    pub fn add_subtree<S: StaticStore>(&mut self, root: &Cid) -> Result<usize, CarError<S::Error>> {
+        let mut written = 0;
+        // Post-order walk so a block is only written after all of its children.
+        let mut stack: Vec<Pending> = Vec::new();
+        let mut queued = HashSet::new();
+        if !self.known.contains(root) {
+            stack.push(Self::visit::<S>(root)?);
+            queued.insert(*root);
+        }
+        while let Some(top) = stack.last_mut() {
+            if let Some(child) = top.children.pop() {
+                if !self.known.contains(&child) && queued.insert(child) {
+                    stack.push(Self::visit::<S>(&child)?);
+                }
+                continue;
+            }
+            let done = stack.pop().unwrap();
+            self.write_block(&done.cid, &done.block)?;
+            written += 1;
+        }
+        if !self.roots.contains(root) {
+            self.roots.push(*root);
+        }
+        Ok(written)
+    }
+
+    fn visit<S: StaticStore>(k: &Cid) -> Result<Pending, CarError<S::Error>> {
+        let block = S::load_bytes(k).map_err(CarError::Store)?;
+        let children = codec::child_links(&block, k.codec())?;
+        Ok(Pending {
+            cid: *k,
+            block,
+            children,
+        })
+    }
+
+    fn write_block(&mut self, k: &Cid, block: &[u8]) -> io::Result<()> {
+        write_section(&mut self.file, k, block)?;
+        self.known.insert(*k);
+        Ok(())
+    }
+
+    /// Flush and return the underlying file.
+    pub fn into_inner(mut self) -> io::Result<F> {
+        self.file.flush()?;
+        Ok(self.file)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Cursor;
+
+    use cid::Cid;
+    use serde::Serialize;
+
+    use super::car_header;
+    use crate::{
+        export_car, import_car, CarAppender, CarError, MemoryError, MemoryStore, StaticStore,
+        StoreError,
+    };
+
+    #[derive(Serialize)]
+    struct Node {
+        name: &'static str,
+        children: Vec<Cid>,
+    }
+
+    fn node<S: StaticStore>(name: &'static str, children: &[Cid]) -> Cid {
+        let node = Node {
+            name,
+            children: children.to_vec(),
+        };
+        S::store(&node, None).unwrap()
+    }
+
+    #[test]
+    fn shared_blocks_written_once() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let leaf = node::<Store>("leaf", &[]);
+        let shared = node::<Store>("shared", &[leaf]);
+        let a = node::<Store>("a", &[]);
+        let b = node::<Store>("b", &[]);
+        let root1 = node::<Store>("root1", &[a, shared]);
+        let root2 = node::<Store>("root2", &[shared, b, b]);
+
+        let mut car = CarAppender::create(Cursor::new(Vec::new()), &[root1]).unwrap();
+        assert_eq!(car.add_subtree::<Store>(&root1).unwrap(), 4);
+
+        // Reopen to make sure the index is rebuilt from the file.
+        let mut car = CarAppender::open(car.into_inner().unwrap()).unwrap();
+        assert_eq!(car.len(), 4);
+        assert_eq!(car.roots(), [root1]);
+        assert_eq!(car.add_subtree::<Store>(&root2).unwrap(), 2);
+        assert_eq!(car.add_subtree::<Store>(&root2).unwrap(), 0);
+        assert_eq!(car.roots(), [root1, root2]);
+
+        let car = CarAppender::open(car.into_inner().unwrap()).unwrap();
+        assert_eq!(car.len(), 6);
+        for k in [leaf, shared, a, b, root1, root2] {
+            assert!(car.contains(&k));
+        }
+    }
+
+    #[test]
+    fn torn_sections_are_truncated() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let a = node::<Store>("a", &[]);
+        let b = node::<Store>("b", &[]);
+        let root = node::<Store>("root", &[a, b]);
+
+        let mut car = CarAppender::create(Cursor::new(Vec::new()), &[root]).unwrap();
+        car.add_subtree::<Store>(&root).unwrap();
+        let full = car.into_inner().unwrap().into_inner();
+        // Find where the first section ends.
+        let mut r = &full[..];
+        super::read_header(&mut r).unwrap();
+        let (first, _) = super::read_section(&mut r).unwrap().unwrap();
+        let intact = full[..full.len() - r.len()].to_vec();
+
+        // Cut the file off partway through the second section and reopen it.
+        let mut torn = full[..intact.len() + 5].to_vec();
+        let car = CarAppender::open(Cursor::new(&mut torn)).unwrap();
+        assert_eq!(car.len(), 1);
+        assert!(car.contains(&first));
+        drop(car);
+        assert_eq!(torn, intact);
+
+        // Appending after recovery yields a well-formed file.
+        let mut car = CarAppender::open(Cursor::new(&mut torn)).unwrap();
+        assert_eq!(car.add_subtree::<Store>(&root).unwrap(), 2);
+        drop(car);
+        let car = CarAppender::open(Cursor::new(torn)).unwrap();
+        assert_eq!(car.len(), 3);
+    }
+
+    #[test]
+    fn oversized_sections_are_rejected() {
+        let mut car = CarAppender::create(Cursor::new(Vec::new()), &[])
+            .unwrap()
+            .into_inner()
+            .unwrap()
+            .into_inner();
+        // A section claiming to be 1 TiB long.
+        super::write_varint(&mut car, 1 << 40).unwrap();
+        car.extend_from_slice(&[0; 16]);
+        let err = CarAppender::open(Cursor::new(car)).err().unwrap();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn export_import_roundtrip() {
+        struct Source;
+        struct Dest;
+
+        let leaf = node::<MemoryStore<Source>>("leaf", &[]);
+        let shared = node::<MemoryStore<Source>>("shared", &[leaf]);
+        let root = node::<MemoryStore<Source>>("root", &[shared, leaf, shared]);
+        node::<MemoryStore<Source>>("unreachable", &[]);
+
+        let mut car = Vec::new();
+        export_car::<MemoryStore<Source>, _>(&root, &mut car).unwrap();
+        assert_eq!(
+            import_car::<MemoryStore<Dest>, _>(&car[..]).unwrap(),
+            [root]
+        );
+        assert_eq!(MemoryStore::<Dest>::len(), 3);
+        assert_eq!(
+            MemoryStore::<Dest>::load_bytes(&root).unwrap(),
+            MemoryStore::<Source>::load_bytes(&root).unwrap()
+        );
+
+        // Corrupt the last byte of the last block.
+        *car.last_mut().unwrap() ^= 1;
+        struct Corrupt;
+        assert!(matches!(
+            import_car::<MemoryStore<Corrupt>, _>(&car[..]),
+            Err(CarError::Store(MemoryError::Store(
+                StoreError::HashMismatch(_)
+            )))
+        ));
+    }
+
+    #[test]
+    fn header_is_dag_cbor() {
+        #[derive(Serialize)]
+        struct Header {
+            roots: Vec<Cid>,
+            version: u64,
+        }
+
+        let roots = vec![node::<MemoryStore>("root", &[])];
+        let expected = serde_ipld_dagcbor::to_vec(&Header {
+            roots: roots.clone(),
+            version: 1,
+        })
+        .unwrap();
+        assert_eq!(car_header(&roots), expected);
+    }
+}
@@ -0,0 +1,160 @@
+use core::marker::PhantomData;
+
+use alloc::string::String;
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{link_as_cid, CidShape, Link, StaticStore};
+
+/// A type-level invariant on values of type `T`, checked by [`CheckedLink`] whenever it loads a
+/// value.
+pub trait Validator<T> {
+    /// Returns an error describing the violation if `value` breaks the invariant.
+    fn validate(value: &T) -> Result<(), String>;
+}
+
+/// A [`Link`] that validates the linked value when it's loaded, rejecting blocks that decode
+/// successfully but violate the invariant `V` (e.g., a balance that must be non-negative).
+/// Content addressing only guarantees that a block hasn't changed, not that it was valid to begin
+/// with.
+///
+/// Loads that fail validation return [`StoreError::ValidationFailed`](crate::StoreError) and
+/// leave the link unloaded. Values constructed locally (with [`CheckedLink::from_value`] or by
+/// editing) aren't validated.
+pub struct CheckedLink<T, Store, V> {
+    link: Link<T, Store>,
+    _marker: PhantomData<fn(V)>,
+}
+
+impl<T, Store, V> Serialize for CheckedLink<T, Store, V>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.link.serialize(serializer)
+    }
+}
+
+impl<'de, T, Store, V> Deserialize<'de> for CheckedLink<T, Store, V>
+where
+    T: Deserialize<'de>,
+    Store: StaticStore,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Self::new(link_as_cid::deserialize(deserializer)?))
+    }
+}
+
+impl<T, Store, V> CheckedLink<T, Store, V> {
+    /// Construct a new `CheckedLink` from a `Cid`.
+    #[must_use]
+    pub const fn new(k: Cid) -> Self {
+        Self {
+            link: Link::new(k),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Construct a new `CheckedLink` from a value (with an optional link-shape hint).
+    #[must_use]
+    pub fn from_value(v: T, shape: Option<CidShape>) -> Self {
+        Self {
+            link: Link::from_value(v, shape),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Read the linked object, loading and validating it if it isn't cached.
+    pub fn read(&self) -> Result<&T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+        V: Validator<T>,
+    {
+        self.link.read_checked(V::validate)
+    }
+
+    /// Edit the linked object, loading and validating it if it isn't cached. See [`Link::edit`].
+    pub fn edit(&mut self) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+        V: Validator<T>,
+    {
+        self.link.edit_checked(V::validate)
+    }
+
+    /// Write-back the value if modified, and return the CID. See [`Link::save`].
+    pub fn save(&self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        self.link.save()
+    }
+
+    /// Write-back the value if modified, return the CID, and drop any cached values.
+    pub fn free(&mut self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        self.link.free()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{CheckedLink, MemoryError, MemoryStore, StaticStore, StoreError, Validator};
+
+    #[derive(Serialize, Deserialize)]
+    struct Account {
+        balance: i64,
+    }
+
+    struct NonNegative;
+
+    impl Validator<Account> for NonNegative {
+        fn validate(account: &Account) -> Result<(), String> {
+            if account.balance < 0 {
+                return Err(format!("negative balance {}", account.balance));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn reject_invalid_block() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+        type Checked = CheckedLink<Account, Store, NonNegative>;
+
+        let good = Store::store(&Account { balance: 5 }, None).unwrap();
+        assert_eq!(Checked::new(good).read().unwrap().balance, 5);
+
+        let bad = Store::store(&Account { balance: -5 }, None).unwrap();
+        let mut link = Checked::new(bad);
+        let err = link.read().err().unwrap();
+        assert!(
+            matches!(
+                &err,
+                MemoryError::Store(StoreError::ValidationFailed { cid, reason })
+                    if *cid == bad && reason == "negative balance -5"
+            ),
+            "{err}"
+        );
+        assert!(link.edit().is_err());
+        // The rejected value isn't cached.
+        assert!(link.read().is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use core::ops::{Deref, DerefMut};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{store, AutoLink, Link, MaybeLink, StaticStore};
+
+/// A child node that may be linked or inlined into its parent, so generic code can read, edit, and
+/// save children the same way whether they're a [`Link`], an [`AutoLink`], or an [`Inline`].
+pub trait Child<Store: StaticStore> {
+    /// The child's value.
+    type Value;
+
+    /// Read the value, loading it if needed.
+    fn read(&self) -> Result<&Self::Value, Store::Error>;
+
+    /// Edit the value, loading it if needed and marking the child as modified.
+    fn edit(&mut self) -> Result<&mut Self::Value, Store::Error>;
+
+    /// Save the value (and any modified links inside it) if it's modified, returning its CID or,
+    /// if it's inlined into the parent, the value itself.
+    fn save(&self) -> Result<MaybeLink<&Self::Value>, Store::Error>;
+}
+
+impl<T, Store> Child<Store> for Link<T, Store>
+where
+    T: DeserializeOwned + Serialize,
+    Store: StaticStore,
+{
+    type Value = T;
+
+    fn read(&self) -> Result<&T, Store::Error> {
+        Link::read(self)
+    }
+
+    fn edit(&mut self) -> Result<&mut T, Store::Error> {
+        Link::edit(self)
+    }
+
+    fn save(&self) -> Result<MaybeLink<&T>, Store::Error> {
+        Link::save(self).map(MaybeLink::Link)
+    }
+}
+
+impl<T, Store, const S: usize> Child<Store> for AutoLink<T, Store, S>
+where
+    T: DeserializeOwned + Serialize,
+    Store: StaticStore,
+{
+    type Value = T;
+
+    fn read(&self) -> Result<&T, Store::Error> {
+        AutoLink::read(self)
+    }
+
+    fn edit(&mut self) -> Result<&mut T, Store::Error> {
+        AutoLink::edit(self)
+    }
+
+    fn save(&self) -> Result<MaybeLink<&T>, Store::Error> {
+        AutoLink::save(self)
+    }
+}
+
+/// A value that's always embedded in its parent, never stored in its own block: it serializes
+/// exactly like a bare `T`. Use it where a [`Child`] is expected to inline a node that would
+/// otherwise be linked (like an [`AutoLink`] with an unlimited threshold, without the CID
+/// bookkeeping).
+///
+/// Reading and editing never fail, and saving only saves the modified links inside the value.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Inline<T>(T);
+
+impl<T> Inline<T> {
+    /// Wrap `value`.
+    pub const fn new(value: T) -> Self {
+        Inline(value)
+    }
+
+    /// Unwrap the value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Inline<T> {
+    fn from(value: T) -> Self {
+        Inline(value)
+    }
+}
+
+impl<T> Deref for Inline<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Inline<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: Serialize, Store: StaticStore> Child<Store> for Inline<T> {
+    type Value = T;
+
+    fn read(&self) -> Result<&T, Store::Error> {
+        Ok(&self.0)
+    }
+
+    fn edit(&mut self) -> Result<&mut T, Store::Error> {
+        Ok(&mut self.0)
+    }
+
+    /// Saves the modified links inside the value by encoding it, as serializing the parent would.
+    fn save(&self) -> Result<MaybeLink<&T>, Store::Error> {
+        store::encoded_len::<Store, _>(&self.0, None)?;
+        Ok(MaybeLink::Value(&self.0))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use crate::{AutoLink, Child, Inline, Link, MaybeLink, MemoryStore, StaticStore};
+
+    struct Tag;
+    type Store = MemoryStore<Tag>;
+
+    fn increment(child: &mut impl Child<Store, Value = u64>) -> MaybeLink<u64> {
+        *child.edit().unwrap() += 1;
+        child.save().unwrap().map(|v| *v)
+    }
+
+    #[test]
+    fn inline_children_serialize_as_their_values() {
+        let inline = Inline::new(("name", [1, 2, 3]));
+        assert_eq!(
+            Store::encode(&inline).unwrap(),
+            Store::encode(&("name", [1, 2, 3])).unwrap()
+        );
+        let k = Store::store(&inline, None).unwrap();
+        assert_eq!(
+            Store::load::<Inline<(String, Vec<u8>)>>(&k).unwrap().1,
+            [1, 2, 3]
+        );
+
+        let mut link = Link::<u64, Store>::from_value(1, None);
+        let MaybeLink::Link(k) = increment(&mut link) else {
+            panic!("expected a link");
+        };
+        assert_eq!(Store::load::<u64>(&k).unwrap(), 2);
+        let mut auto = AutoLink::<u64, Store>::from_value(1);
+        assert!(matches!(increment(&mut auto), MaybeLink::Value(2)));
+        let mut inline = Inline::new(1);
+        assert!(matches!(increment(&mut inline), MaybeLink::Value(2)));
+    }
+
+    #[test]
+    fn saving_inline_values_saves_their_links() {
+        let inline = Inline::new(Link::<String, Store>::from_value("nested".into(), None));
+        Child::<Store>::save(&inline).unwrap();
+        let k = inline.cid().unwrap();
+        assert_eq!(Store::load::<String>(&k).unwrap(), "nested");
+    }
+}
@@ -0,0 +1,590 @@
+use core::fmt;
+
+use alloc::{string::ToString, vec::Vec};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{PbNode, StaticStore, StoreError};
+
+/// The DAG-CBOR multicodec.
+pub const DAG_CBOR: u64 = 0x71;
+/// The raw multicodec.
+pub const RAW: u64 = 0x55;
+/// The DAG-PB multicodec.
+pub const DAG_PB: u64 = 0x70;
+/// The DAG-JSON multicodec.
+pub const DAG_JSON: u64 = 0x0129;
+
+/// A content codec, for building [`CidShape`](crate::CidShape)s without mixing up codecs and
+/// multihash codes. Other codecs can still be used through the shape's raw `codec` field.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Codec {
+    DagCbor,
+    DagJson,
+    DagPb,
+    Raw,
+}
+
+impl Codec {
+    /// The codec's multicodec code.
+    pub const fn as_u64(self) -> u64 {
+        match self {
+            Codec::DagCbor => DAG_CBOR,
+            Codec::DagJson => DAG_JSON,
+            Codec::DagPb => DAG_PB,
+            Codec::Raw => RAW,
+        }
+    }
+}
+
+impl From<Codec> for u64 {
+    fn from(codec: Codec) -> Self {
+        codec.as_u64()
+    }
+}
+
+/// Fails with [`CodecError::UnsupportedCodec`] for codes without a variant.
+impl TryFrom<u64> for Codec {
+    type Error = CodecError;
+
+    fn try_from(code: u64) -> Result<Self, Self::Error> {
+        Ok(match code {
+            DAG_CBOR => Codec::DagCbor,
+            DAG_JSON => Codec::DagJson,
+            DAG_PB => Codec::DagPb,
+            RAW => Codec::Raw,
+            other => return Err(CodecError::UnsupportedCodec(other)),
+        })
+    }
+}
+
+/// A multihash function, the counterpart of [`Codec`] for a shape's hash. Whether a store can
+/// hash with it depends on the enabled `multihash` features; [`Blake3`](MultihashCode::Blake3)
+/// isn't enabled by default.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MultihashCode {
+    /// The identity "hash" (see [`IDENTITY`](crate::IDENTITY)).
+    Identity,
+    Sha2_256,
+    Sha2_512,
+    Blake3,
+}
+
+impl MultihashCode {
+    /// The function's multicodec code.
+    pub const fn as_u64(self) -> u64 {
+        match self {
+            MultihashCode::Identity => crate::IDENTITY,
+            MultihashCode::Sha2_256 => 0x12,
+            MultihashCode::Sha2_512 => 0x13,
+            MultihashCode::Blake3 => 0x1e,
+        }
+    }
+}
+
+impl From<MultihashCode> for u64 {
+    fn from(code: MultihashCode) -> Self {
+        code.as_u64()
+    }
+}
+
+/// Fails with [`StoreError::UnsupportedMultihash`] for codes without a variant.
+impl TryFrom<u64> for MultihashCode {
+    type Error = StoreError;
+
+    fn try_from(code: u64) -> Result<Self, Self::Error> {
+        Ok(match code {
+            crate::IDENTITY => MultihashCode::Identity,
+            0x12 => MultihashCode::Sha2_256,
+            0x13 => MultihashCode::Sha2_512,
+            0x1e => MultihashCode::Blake3,
+            other => return Err(StoreError::UnsupportedMultihash(other)),
+        })
+    }
+}
+
+/// An error encountered while scanning an encoded block.
+#[derive(Debug)]
+pub enum CodecError {
+    /// The block ended in the middle of an item.
+    Truncated,
+    /// The block isn't valid in its codec.
+    Malformed(&'static str),
+    /// The block contains an invalid CID.
+    InvalidCid(cid::Error),
+    /// The codec isn't supported by this operation.
+    UnsupportedCodec(u64),
+    /// A [`BlockCursor`] seek went past the end of the array.
+    OutOfBounds { index: usize, len: usize },
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Truncated => write!(f, "unexpected end of block"),
+            CodecError::Malformed(why) => write!(f, "malformed block: {why}"),
+            CodecError::InvalidCid(e) => write!(f, "invalid cid: {e}"),
+            CodecError::UnsupportedCodec(c) => write!(f, "unsupported codec 0x{c:x}"),
+            CodecError::OutOfBounds { index, len } => {
+                write!(f, "index {index} out of bounds for array of length {len}")
+            }
+        }
+    }
+}
+
+impl core::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            // `cid::Error` only implements `Error` with `std`.
+            #[cfg(feature = "std")]
+            CodecError::InvalidCid(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// The links directly referenced by a block of the given codec (DAG-CBOR, DAG-PB, raw, or
+/// DAG-JSON with `std`), in the order they appear. DAG-CBOR blocks are scanned without being
+/// decoded; DAG-JSON blocks are decoded as a [`Value`](crate::Value). Linked blocks aren't loaded.
+pub fn child_links(bytes: &[u8], codec: u64) -> Result<Vec<Cid>, CodecError> {
+    let mut out = Vec::new();
+    match codec {
+        DAG_CBOR => cbor::links(bytes, &mut out)?,
+        DAG_PB => out.extend(PbNode::decode(bytes)?.links.into_iter().map(|l| l.hash)),
+        RAW => (),
+        #[cfg(feature = "std")]
+        DAG_JSON => {
+            let value: crate::Value = serde_ipld_dagjson::from_slice(bytes)
+                .map_err(|_| CodecError::Malformed("invalid dag-json"))?;
+            json_links(&value, &mut out);
+        }
+        other => return Err(CodecError::UnsupportedCodec(other)),
+    }
+    Ok(out)
+}
+
+/// Append the links in a decoded DAG-JSON block to `out`, in the order they appear.
+#[cfg(feature = "std")]
+fn json_links(value: &crate::Value, out: &mut Vec<Cid>) {
+    use crate::Value;
+    match value {
+        Value::Link(k) => out.push(*k),
+        Value::List(items) => items.iter().for_each(|v| json_links(v, out)),
+        Value::Map(entries) => entries.values().for_each(|v| json_links(v, out)),
+        _ => (),
+    }
+}
+
+/// Encode `value` as a raw block: it must serialize as a single byte string (e.g., a
+/// [`Value::Bytes`](crate::Value::Bytes)), whose contents become the block.
+pub(crate) fn encode_raw<T: Serialize>(value: &T) -> Result<Vec<u8>, StoreError> {
+    let encoded =
+        serde_ipld_dagcbor::to_vec(value).map_err(|e| StoreError::Encoding(e.to_string()))?;
+    let mut reader = cbor::Reader::new(&encoded);
+    match reader.head() {
+        Ok((2, len)) if (encoded.len() - reader.position()) as u64 == len => {
+            Ok(encoded[reader.position()..].to_vec())
+        }
+        _ => Err(StoreError::Encoding(
+            "raw blocks must be byte strings".into(),
+        )),
+    }
+}
+
+/// Decode a raw block as a byte string.
+pub(crate) fn decode_raw<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, StoreError> {
+    let mut encoded = Vec::with_capacity(bytes.len() + 9);
+    cbor::write_head(&mut encoded, 2, bytes.len() as u64);
+    encoded.extend_from_slice(bytes);
+    serde_ipld_dagcbor::from_slice(&encoded).map_err(|e| StoreError::Encoding(e.to_string()))
+}
+
+/// A cursor over the elements of a DAG-CBOR block whose top-level item is an array, for random
+/// access into large arrays (e.g., AMT leaves).
+///
+/// Seeking skips elements using their CBOR length headers without decoding them, and only the
+/// element under the cursor is ever decoded. Seeking forward from the current position costs time
+/// proportional to the distance travelled; seeking backward rescans from the start.
+pub struct BlockCursor<'a> {
+    /// A reader positioned at the first element.
+    start: cbor::Reader<'a>,
+    reader: cbor::Reader<'a>,
+    len: usize,
+    index: usize,
+}
+
+impl<'a> BlockCursor<'a> {
+    /// Open a cursor at the first element of the array in `bytes`.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, CodecError> {
+        let mut reader = cbor::Reader::new(bytes);
+        let (major, len) = reader.head()?;
+        if major != 4 {
+            return Err(CodecError::Malformed("block isn't an array"));
+        }
+        let len = usize::try_from(len).map_err(|_| CodecError::Truncated)?;
+        Ok(BlockCursor {
+            start: reader.clone(),
+            reader,
+            len,
+            index: 0,
+        })
+    }
+
+    /// The number of elements in the array.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The index of the element under the cursor.
+    pub fn position(&self) -> usize {
+        self.index
+    }
+
+    /// Move the cursor to the element at `index`. Seeking to `len()` (just past the last element)
+    /// is allowed.
+    pub fn seek(&mut self, index: usize) -> Result<(), CodecError> {
+        if index > self.len {
+            return Err(CodecError::OutOfBounds {
+                index,
+                len: self.len,
+            });
+        }
+        if index < self.index {
+            self.reader = self.start.clone();
+            self.index = 0;
+        }
+        while self.index < index {
+            self.reader.skip(|_| ())?;
+            self.index += 1;
+        }
+        Ok(())
+    }
+
+    /// The encoded element under the cursor, advancing past it. Returns `None` at the end of the
+    /// array.
+    pub fn next_bytes(&mut self) -> Result<Option<&'a [u8]>, CodecError> {
+        if self.index == self.len {
+            return Ok(None);
+        }
+        let start = self.reader.position();
+        self.reader.skip(|_| ())?;
+        self.index += 1;
+        Ok(Some(self.reader.slice(start)))
+    }
+
+    /// Decode the element under the cursor with `Store`'s codec, advancing past it. Returns `None`
+    /// at the end of the array.
+    pub fn decode_next<Store, T>(&mut self) -> Result<Option<T>, Store::Error>
+    where
+        Store: StaticStore,
+        T: DeserializeOwned,
+    {
+        match self.next_bytes().map_err(StoreError::Codec)? {
+            Some(bytes) => Store::decode(bytes).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+pub(crate) mod cbor {
+    //! Just enough DAG-CBOR to find links without decoding.
+
+    use alloc::vec::Vec;
+
+    use cid::Cid;
+
+    use super::CodecError;
+
+    const CID_TAG: u64 = 42;
+
+    #[derive(Clone)]
+    pub(crate) struct Reader<'a> {
+        bytes: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        pub(crate) fn new(bytes: &'a [u8]) -> Self {
+            Reader { bytes, pos: 0 }
+        }
+
+        fn take(&mut self, n: u64) -> Result<&'a [u8], CodecError> {
+            let n = usize::try_from(n).map_err(|_| CodecError::Truncated)?;
+            let end = self.pos.checked_add(n).ok_or(CodecError::Truncated)?;
+            let out = self.bytes.get(self.pos..end).ok_or(CodecError::Truncated)?;
+            self.pos = end;
+            Ok(out)
+        }
+
+        /// Read an item header, returning the major type and argument.
+        pub(crate) fn head(&mut self) -> Result<(u8, u64), CodecError> {
+            let first = self.take(1)?[0];
+            let (major, info) = (first >> 5, first & 0x1f);
+            let arg = match info {
+                0..=23 => info as u64,
+                24 => self.take(1)?[0] as u64,
+                25 => u16::from_be_bytes(self.take(2)?.try_into().unwrap()) as u64,
+                26 => u32::from_be_bytes(self.take(4)?.try_into().unwrap()) as u64,
+                27 => u64::from_be_bytes(self.take(8)?.try_into().unwrap()),
+                31 => return Err(CodecError::Malformed("indefinite length item")),
+                _ => return Err(CodecError::Malformed("reserved additional info")),
+            };
+            Ok((major, arg))
+        }
+
+        /// Skip one complete item, calling `on_link` for every link inside it.
+        pub(crate) fn skip(&mut self, mut on_link: impl FnMut(Cid)) -> Result<(), CodecError> {
+            let mut remaining: u64 = 1;
+            while remaining > 0 {
+                remaining -= 1;
+                let (major, arg) = self.head()?;
+                match major {
+                    0 | 1 | 7 => (),
+                    2 | 3 => {
+                        self.take(arg)?;
+                    }
+                    4 => remaining = remaining.saturating_add(arg),
+                    5 => remaining = remaining.saturating_add(arg.saturating_mul(2)),
+                    6 if arg == CID_TAG => on_link(self.cid()?),
+                    6 => return Err(CodecError::Malformed("unsupported tag")),
+                    _ => unreachable!(),
+                }
+            }
+            Ok(())
+        }
+
+        /// Read the byte string following a CID tag.
+        fn cid(&mut self) -> Result<Cid, CodecError> {
+            let (major, len) = self.head()?;
+            if major != 2 {
+                return Err(CodecError::Malformed("cid tag on a non-bytes item"));
+            }
+            match self.take(len)? {
+                [0, cid @ ..] => Cid::try_from(cid).map_err(CodecError::InvalidCid),
+                _ => Err(CodecError::Malformed("cid missing multibase prefix")),
+            }
+        }
+
+        pub(crate) fn is_empty(&self) -> bool {
+            self.pos >= self.bytes.len()
+        }
+
+        /// The offset of the next item.
+        pub(crate) fn position(&self) -> usize {
+            self.pos
+        }
+
+        /// The bytes from `start` up to the current position.
+        pub(crate) fn slice(&self, start: usize) -> &'a [u8] {
+            &self.bytes[start..self.pos]
+        }
+    }
+
+    /// Collect every link in a DAG-CBOR block.
+    pub(crate) fn links(bytes: &[u8], out: &mut Vec<Cid>) -> Result<(), CodecError> {
+        let mut reader = Reader::new(bytes);
+        reader.skip(|k| out.push(k))?;
+        if !reader.is_empty() {
+            return Err(CodecError::Malformed("trailing bytes"));
+        }
+        Ok(())
+    }
+
+    /// Write an item header.
+    pub(crate) fn write_head(out: &mut Vec<u8>, major: u8, arg: u64) {
+        let major = major << 5;
+        match arg {
+            0..=23 => out.push(major | arg as u8),
+            24..=0xff => out.extend([major | 24, arg as u8]),
+            0x100..=0xffff => {
+                out.push(major | 25);
+                out.extend((arg as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                out.push(major | 26);
+                out.extend((arg as u32).to_be_bytes());
+            }
+            _ => {
+                out.push(major | 27);
+                out.extend(arg.to_be_bytes());
+            }
+        }
+    }
+
+    /// Write a link.
+    #[cfg(feature = "std")]
+    pub(crate) fn write_cid(out: &mut Vec<u8>, k: &Cid) {
+        let bytes = k.to_bytes();
+        write_head(out, 6, CID_TAG);
+        write_head(out, 2, bytes.len() as u64 + 1);
+        out.push(0);
+        out.extend(bytes);
+    }
+
+    /// Write a text string.
+    #[cfg(feature = "std")]
+    pub(crate) fn write_str(out: &mut Vec<u8>, s: &str) {
+        write_head(out, 3, s.len() as u64);
+        out.extend(s.as_bytes());
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::cell::Cell;
+
+    use serde::{Deserialize, Deserializer};
+
+    use crate::{
+        child_links, BlockCursor, CidShape, Codec, CodecError, MemoryStore, MultihashCode,
+        StaticStore, StoreError, Value, DAG_CBOR,
+    };
+
+    thread_local! {
+        static DECODED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// A number that counts how many times it's been decoded.
+    #[derive(Debug, PartialEq)]
+    struct Counted(u64);
+
+    impl<'de> Deserialize<'de> for Counted {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            DECODED.with(|c| c.set(c.get() + 1));
+            u64::deserialize(d).map(Counted)
+        }
+    }
+
+    #[test]
+    fn links_of_block() {
+        type Store = MemoryStore;
+
+        let a = Store::store(&"a", None).unwrap();
+        let b = Store::store(&"b", None).unwrap();
+        let c = Store::store(&"c", None).unwrap();
+        let block = Value::Map(
+            [
+                ("a".to_string(), Value::Link(a)),
+                (
+                    "nested".to_string(),
+                    Value::List(vec![
+                        Value::Map([("b".to_string(), Value::Link(b))].into()),
+                        // Looks like a CID, but it's just bytes.
+                        Value::Bytes(c.to_bytes()),
+                        Value::Integer(42),
+                    ]),
+                ),
+                ("trailing".to_string(), Value::List(vec![Value::Link(c)])),
+            ]
+            .into(),
+        );
+        let bytes = Store::encode(&block).unwrap();
+        assert_eq!(child_links(&bytes, DAG_CBOR).unwrap(), [a, b, c]);
+        assert!(child_links(&Store::encode(&42).unwrap(), DAG_CBOR)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn seek_into_large_array() {
+        type Store = MemoryStore;
+
+        let elements: Vec<(u64, String)> =
+            (0..10_000).map(|i| (i, format!("element {i}"))).collect();
+        let bytes = Store::encode(&elements.iter().map(|e| e.0).collect::<Vec<_>>()).unwrap();
+
+        let mut cursor = BlockCursor::new(&bytes).unwrap();
+        assert_eq!(cursor.len(), 10_000);
+        cursor.seek(9000).unwrap();
+        assert_eq!(
+            cursor.decode_next::<Store, Counted>().unwrap(),
+            Some(Counted(9000))
+        );
+        // Only the target element was decoded.
+        assert_eq!(DECODED.with(Cell::get), 1);
+        assert_eq!(cursor.position(), 9001);
+
+        cursor.seek(3).unwrap();
+        assert_eq!(cursor.decode_next::<Store, u64>().unwrap(), Some(3));
+        cursor.seek(10_000).unwrap();
+        assert_eq!(cursor.decode_next::<Store, u64>().unwrap(), None);
+        assert!(matches!(
+            cursor.seek(10_001),
+            Err(CodecError::OutOfBounds {
+                index: 10_001,
+                len: 10_000
+            })
+        ));
+
+        // Elements can be arbitrary items, not just integers.
+        let bytes = Store::encode(&elements).unwrap();
+        let mut cursor = BlockCursor::new(&bytes).unwrap();
+        cursor.seek(42).unwrap();
+        assert_eq!(
+            cursor.decode_next::<Store, (u64, String)>().unwrap(),
+            Some((42, "element 42".into()))
+        );
+    }
+
+    #[test]
+    fn typed_codes_round_trip() {
+        assert_eq!(
+            CidShape::new(Codec::DagCbor, MultihashCode::Sha2_256),
+            CidShape::DEFAULT
+        );
+        for codec in [Codec::DagCbor, Codec::DagJson, Codec::DagPb, Codec::Raw] {
+            assert_eq!(Codec::try_from(codec.as_u64()).unwrap(), codec);
+        }
+        assert!(matches!(
+            Codec::try_from(0x12),
+            Err(CodecError::UnsupportedCodec(0x12))
+        ));
+
+        let k = MemoryStore::<()>::compute_cid(&"typed", None).unwrap();
+        let shape = CidShape::from(&k);
+        assert_eq!(Codec::try_from(shape.codec).unwrap(), Codec::DagCbor);
+        assert_eq!(
+            MultihashCode::try_from(shape.mh_code).unwrap(),
+            MultihashCode::Sha2_256
+        );
+        assert!(matches!(
+            MultihashCode::try_from(DAG_CBOR),
+            Err(StoreError::UnsupportedMultihash(DAG_CBOR))
+        ));
+    }
+}
+
+/// What's left without `std`: the codec tables and link scanning, with only `alloc`.
+#[cfg(all(test, not(feature = "std")))]
+mod no_std_test {
+    use alloc::vec;
+
+    use cid::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
+
+    use crate::{child_links, Codec, CodecError, DAG_CBOR, DAG_JSON, RAW};
+
+    #[test]
+    fn links_without_std() {
+        let leaf = Cid::new_v1(RAW, Code::Sha2_256.digest(b"leaf"));
+        let block = serde_ipld_dagcbor::to_vec(&vec![leaf, leaf]).unwrap();
+        assert_eq!(child_links(&block, DAG_CBOR).unwrap(), vec![leaf, leaf]);
+        assert!(child_links(b"leaf", RAW).unwrap().is_empty());
+
+        assert_eq!(Codec::try_from(DAG_JSON).unwrap(), Codec::DagJson);
+        assert!(matches!(
+            child_links(b"[]", DAG_JSON),
+            Err(CodecError::UnsupportedCodec(DAG_JSON))
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+use std::{io::Write, marker::PhantomData};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{store, BlockBackend, CidShape, StaticStore, StoreError};
+
+/// A compression algorithm for [`Compress`].
+pub trait Compressor {
+    /// Compress a block.
+    fn compress(bytes: &[u8]) -> Result<Vec<u8>, StoreError>;
+
+    /// Decompress a block compressed with [`Compressor::compress`]. It should stop once it has
+    /// produced more than `max` bytes (returning what it has, or an error), so a small stored
+    /// value can't expand into an unbounded block.
+    fn decompress(bytes: &[u8], max: usize) -> Result<Vec<u8>, StoreError>;
+}
+
+/// A store that compresses every block with `C` before handing it to the backend `B`, and
+/// decompresses it on load, e.g., for large, repetitive text or JSON-like blocks.
+///
+/// Only the backend's copy is compressed: CIDs are computed over the uncompressed block (from the
+/// requested [`CidShape`], defaulting to DAG-CBOR with SHA2-256), and loads return (and verify)
+/// the uncompressed block, so the CIDs match those of any other store and links can be moved
+/// between stores or served to other IPLD systems as usual. As with
+/// [`SelfDescribingStore`](crate::SelfDescribingStore), the backend's values aren't plain blocks.
+///
+/// No compressor is bundled; implement [`Compressor`] over the codec crate of your choice (e.g.,
+/// `zstd::bulk`). Values are encoded as DAG-CBOR.
+pub struct Compress<B, C>(PhantomData<(B, C)>);
+
+fn encoding_error(e: impl std::fmt::Display) -> StoreError {
+    StoreError::Encoding(e.to_string())
+}
+
+impl<B: BlockBackend, C: Compressor> StaticStore for Compress<B, C> {
+    type Error = B::Error;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        // Hash the uncompressed block, then store the compressed one under its CID.
+        let k = shape.copied().unwrap_or_default().cid(value)?;
+        B::put(&k, C::compress(value)?)?;
+        Ok(k)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        Ok(serde_ipld_dagcbor::to_vec(value).map_err(encoding_error)?)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        Ok(serde_ipld_dagcbor::to_writer(w, value).map_err(encoding_error)?)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        Self::load_bytes_limited(k, usize::MAX)
+    }
+
+    /// Stops decompressing once the block exceeds `max`.
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        let compressed = B::get(k)?.ok_or(StoreError::NotFound(*k))?;
+        let block = C::decompress(&compressed, max)?;
+        store::check_block_size(k, block.len(), max)?;
+        Ok(block)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        B::has(k)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        B::keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        B::remove(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        Ok(serde_ipld_dagcbor::from_slice(bytes).map_err(encoding_error)?)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        Ok(serde_ipld_dagcbor::from_slice(bytes).map_err(encoding_error)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{BlockBackend, Compress, Compressor, Link, MemoryStore, StaticStore, StoreError};
+
+    /// Run-length encoding, as (count, byte) pairs.
+    struct Rle;
+
+    impl Compressor for Rle {
+        fn compress(bytes: &[u8]) -> Result<Vec<u8>, StoreError> {
+            let mut out = Vec::new();
+            for &b in bytes {
+                match out.len() {
+                    n if n >= 2 && out[n - 1] == b && out[n - 2] < u8::MAX => out[n - 2] += 1,
+                    _ => out.extend([1, b]),
+                }
+            }
+            Ok(out)
+        }
+
+        fn decompress(bytes: &[u8], max: usize) -> Result<Vec<u8>, StoreError> {
+            let mut out = Vec::new();
+            for run in bytes.chunks(2) {
+                let &[n, b] = run else {
+                    return Err(StoreError::Encoding("truncated run".into()));
+                };
+                out.extend((0..n).map(|_| b));
+                if out.len() > max {
+                    break;
+                }
+            }
+            Ok(out)
+        }
+    }
+
+    #[test]
+    fn cids_cover_uncompressed_blocks() {
+        struct Tag;
+        type Backend = MemoryStore<Tag>;
+        type Store = Compress<Backend, Rle>;
+
+        let text = "a".repeat(1000);
+        let k = Store::store(&text, None).unwrap();
+        assert_eq!(k, MemoryStore::<()>::store(&text, None).unwrap());
+        let stored = Backend::get(&k).unwrap().unwrap();
+        assert!(stored.len() < 20, "{} bytes", stored.len());
+
+        // Loads decompress (and verify) the block.
+        assert_eq!(Store::load::<String>(&k).unwrap(), text);
+        assert_eq!(
+            Store::load_bytes(&k).unwrap(),
+            Store::encode(&text).unwrap()
+        );
+        let link: Link<String, Store> = Link::from_value("b".repeat(500), None);
+        assert_eq!(
+            link.save().unwrap(),
+            MemoryStore::<()>::store(&"b".repeat(500), None).unwrap()
+        );
+        assert!(Store::load_bytes_limited(&k, 100).is_err());
+    }
+}
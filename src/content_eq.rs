@@ -0,0 +1,64 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use serde::Serialize;
+
+use crate::{AutoLink, Link, MaybeLink, StaticStore};
+
+/// Structural equality across link boundaries, for comparing link-backed structures (e.g., in
+/// tests) without saving every link by hand first. `#[derive(ContentEq)]` in `auto-ipld-derive`
+/// implements it field by field.
+///
+/// Links are compared by content: modified links are saved (through `S`) and their CIDs
+/// compared, so equal values saved in the same shape are equal however they were built.
+pub trait ContentEq<S: StaticStore> {
+    /// Returns true if `self` and `other` have the same content.
+    fn content_eq(&self, other: &Self) -> Result<bool, S::Error>;
+}
+
+/// See [`Link::content_eq`].
+impl<T: Serialize, S: StaticStore> ContentEq<S> for Link<T, S> {
+    fn content_eq(&self, other: &Self) -> Result<bool, S::Error> {
+        Link::content_eq(self, other)
+    }
+}
+
+/// Values stored in their own blocks are compared by CID, and inlined values by their encoding.
+/// An inlined value never equals one stored in its own block, as they're saved differently.
+impl<T: Serialize, S: StaticStore, const N: usize> ContentEq<S> for AutoLink<T, S, N> {
+    fn content_eq(&self, other: &Self) -> Result<bool, S::Error> {
+        Ok(match (self.save()?, other.save()?) {
+            (MaybeLink::Link(a), MaybeLink::Link(b)) => a == b,
+            (MaybeLink::Value(a), MaybeLink::Value(b)) => S::encode(a)? == S::encode(b)?,
+            _ => false,
+        })
+    }
+}
+
+impl<T: ContentEq<S>, S: StaticStore> ContentEq<S> for Option<T> {
+    fn content_eq(&self, other: &Self) -> Result<bool, S::Error> {
+        match (self, other) {
+            (Some(a), Some(b)) => a.content_eq(b),
+            (a, b) => Ok(a.is_none() && b.is_none()),
+        }
+    }
+}
+
+impl<T: ContentEq<S>, S: StaticStore> ContentEq<S> for Vec<T> {
+    fn content_eq(&self, other: &Self) -> Result<bool, S::Error> {
+        if self.len() != other.len() {
+            return Ok(false);
+        }
+        for (a, b) in self.iter().zip(other) {
+            if !a.content_eq(b)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<T: ContentEq<S> + ?Sized, S: StaticStore> ContentEq<S> for Box<T> {
+    fn content_eq(&self, other: &Self) -> Result<bool, S::Error> {
+        (**self).content_eq(other)
+    }
+}
@@ -0,0 +1,591 @@
+use core::{fmt, marker::PhantomData};
+#[cfg(feature = "std")]
+use std::{
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::HashMap,
+    io::Write,
+};
+
+use alloc::{boxed::Box, string::ToString, sync::Arc, vec::Vec};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{CidShape, StaticStore, StoreError};
+
+/// The block operations of a store, as an object-safe trait, so stores can be chosen (and
+/// configured) at runtime and shared as `Arc<dyn DynStore>`. Set one as the thread's [`Ambient`]
+/// store with [`with_dyn_store`] to read and write `Link<T>`s through it.
+///
+/// Only blocks cross the trait: values are encoded and decoded by [`Ambient`] (as DAG-CBOR) at the
+/// call site, where their types are known, so no type-erased serialization is needed. Errors are
+/// erased into [`ContextError`].
+///
+/// Any [`StaticStore`] can be used through [`ErasedStore`].
+pub trait DynStore {
+    /// A name for the store, e.g., for [`current_store`]. Defaults to the type's name.
+    fn name(&self) -> &'static str {
+        core::any::type_name::<Self>()
+    }
+
+    /// Store a block (see [`StaticStore::store_bytes`]).
+    fn store_bytes(&self, value: &[u8], shape: Option<&CidShape>) -> Result<Cid, ContextError>;
+
+    /// Store a block unless it's already present (see [`StaticStore::store_if_absent`]). By
+    /// default, this always writes the block and reports it as new.
+    fn store_if_absent(
+        &self,
+        value: &[u8],
+        shape: Option<&CidShape>,
+    ) -> Result<(Cid, bool), ContextError> {
+        Ok((self.store_bytes(value, shape)?, true))
+    }
+
+    /// Load a block (see [`StaticStore::load_bytes`]).
+    fn load_bytes(&self, k: &Cid) -> Result<Vec<u8>, ContextError>;
+
+    /// Returns true if the block is in the store (see [`StaticStore::has`]). By default, this
+    /// loads the block.
+    fn has(&self, k: &Cid) -> Result<bool, ContextError> {
+        match self.load_bytes(k) {
+            Ok(_) => Ok(true),
+            Err(e) if self.is_not_found(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// List every stored block (see [`StaticStore::keys`]). By default, this returns
+    /// [`StoreError::Unsupported`].
+    fn keys(&self) -> Result<Vec<Cid>, ContextError> {
+        Err(StoreError::Unsupported("keys").into())
+    }
+
+    /// Delete a block (see [`StaticStore::remove`]). By default, this returns
+    /// [`StoreError::Unsupported`].
+    fn remove(&self, k: &Cid) -> Result<bool, ContextError> {
+        let _ = k;
+        Err(StoreError::Unsupported("remove").into())
+    }
+
+    /// Returns true if `err` means a block isn't in the store (see
+    /// [`StaticStore::is_not_found`]). By default, only [`StoreError::NotFound`] is.
+    fn is_not_found(&self, err: &ContextError) -> bool {
+        matches!(err, ContextError::Core(StoreError::NotFound(_)))
+    }
+}
+
+/// A [`DynStore`] that forwards to the [`StaticStore`] `S`, boxing its errors into
+/// [`ContextError::Store`].
+pub struct ErasedStore<S>(PhantomData<fn() -> S>);
+
+impl<S> ErasedStore<S> {
+    pub const fn new() -> Self {
+        ErasedStore(PhantomData)
+    }
+}
+
+impl<S> Default for ErasedStore<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn erase<S: StaticStore, R>(r: Result<R, S::Error>) -> Result<R, ContextError> {
+    r.map_err(|e| ContextError::Store(Box::new(e)))
+}
+
+impl<S: StaticStore> DynStore for ErasedStore<S> {
+    fn name(&self) -> &'static str {
+        core::any::type_name::<S>()
+    }
+
+    fn store_bytes(&self, value: &[u8], shape: Option<&CidShape>) -> Result<Cid, ContextError> {
+        erase::<S, _>(S::store_bytes(value, shape))
+    }
+
+    fn store_if_absent(
+        &self,
+        value: &[u8],
+        shape: Option<&CidShape>,
+    ) -> Result<(Cid, bool), ContextError> {
+        erase::<S, _>(S::store_if_absent(value, shape))
+    }
+
+    fn load_bytes(&self, k: &Cid) -> Result<Vec<u8>, ContextError> {
+        erase::<S, _>(S::load_bytes(k))
+    }
+
+    fn has(&self, k: &Cid) -> Result<bool, ContextError> {
+        erase::<S, _>(S::has(k))
+    }
+
+    fn keys(&self) -> Result<Vec<Cid>, ContextError> {
+        erase::<S, _>(S::keys())
+    }
+
+    fn remove(&self, k: &Cid) -> Result<bool, ContextError> {
+        erase::<S, _>(S::remove(k))
+    }
+
+    fn is_not_found(&self, err: &ContextError) -> bool {
+        match err {
+            ContextError::Store(e) => e.downcast_ref::<S::Error>().is_some_and(S::is_not_found),
+            _ => matches!(err, ContextError::Core(StoreError::NotFound(_))),
+        }
+    }
+}
+
+/// The store set by [`with_store`] or [`with_dyn_store`].
+type Context = Arc<dyn DynStore>;
+
+#[cfg(feature = "std")]
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with `S` as the thread's [`Ambient`] store, so that links without a store type
+/// parameter (`Link<T>`, i.e., `Link<T, Ambient>`) read and write through `S`. Calls can be
+/// nested; the previous store is restored when `f` returns (or panics).
+///
+/// The context is per-thread, so links (and futures) moved to other threads need their own
+/// `with_store` scope there.
+#[cfg(feature = "std")]
+pub fn with_store<S: StaticStore + 'static, R>(f: impl FnOnce() -> R) -> R {
+    with_dyn_store(Arc::new(ErasedStore::<S>::new()), f)
+}
+
+/// Like [`with_store`], but with a store chosen at runtime.
+#[cfg(feature = "std")]
+pub fn with_dyn_store<R>(store: Arc<dyn DynStore>, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous context, even if `f` panics.
+    struct Restore(Option<Context>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CONTEXT.with(|c| *c.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(CONTEXT.with(|c| c.borrow_mut().replace(store)));
+    f()
+}
+
+/// The name of the thread's [`Ambient`] store, if one is set (see [`DynStore::name`]).
+#[cfg(feature = "std")]
+pub fn current_store() -> Option<&'static str> {
+    CONTEXT.with(|c| c.borrow().as_ref().map(|s| s.name()))
+}
+
+#[cfg(feature = "std")]
+fn context() -> Result<Context, ContextError> {
+    CONTEXT.with(|c| c.borrow().clone().ok_or(ContextError::NoStore))
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// The instances set by [`with_store_instance`] (each an `Arc<S>`), by store type.
+    static INSTANCES: RefCell<HashMap<TypeId, Box<dyn Any>>> = RefCell::new(HashMap::new());
+}
+
+/// Run `f` with `store` as the thread's instance of `S`, so links stored in [`Static<S>`] read
+/// and write through it. Calls can be nested (with the same or other store types); the previous
+/// instance of `S` is restored when `f` returns (or panics).
+///
+/// Like [`with_store`], the scope is per-thread.
+#[cfg(feature = "std")]
+pub fn with_store_instance<S: DynStore + 'static, R>(store: Arc<S>, f: impl FnOnce() -> R) -> R {
+    /// Restores the previous instance, even if `f` panics.
+    struct Restore(TypeId, Option<Box<dyn Any>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            INSTANCES.with(|i| match self.1.take() {
+                Some(store) => i.borrow_mut().insert(self.0, store),
+                None => i.borrow_mut().remove(&self.0),
+            });
+        }
+    }
+
+    let id = TypeId::of::<S>();
+    let _restore = Restore(
+        id,
+        INSTANCES.with(|i| i.borrow_mut().insert(id, Box::new(store))),
+    );
+    f()
+}
+
+#[cfg(feature = "std")]
+fn instance<S: DynStore + 'static>() -> Result<Arc<S>, ContextError> {
+    INSTANCES.with(|i| {
+        let instances = i.borrow();
+        let store = instances
+            .get(&TypeId::of::<S>())
+            .ok_or(ContextError::NoStore)?;
+        Ok(store
+            .downcast_ref::<Arc<S>>()
+            .expect("instance stored under another type")
+            .clone())
+    })
+}
+
+/// Without `std`, there's no [`with_store_instance`], so there's never an instance.
+#[cfg(not(feature = "std"))]
+fn instance<S: DynStore + 'static>() -> Result<Arc<S>, ContextError> {
+    Err(ContextError::NoStore)
+}
+
+/// Without `std`, there's no [`with_store`], so there's never a store.
+#[cfg(not(feature = "std"))]
+fn context() -> Result<Context, ContextError> {
+    Err(ContextError::NoStore)
+}
+
+/// An error from the [`Ambient`] store.
+#[derive(Debug)]
+pub enum ContextError {
+    /// No store is set on this thread (see [`with_store`] and [`with_store_instance`]).
+    NoStore,
+    /// The value couldn't be encoded or decoded, or the operation failed before reaching the
+    /// store.
+    Core(StoreError),
+    /// An error from the store set by [`with_store`]. Downcast it to the store's error type to
+    /// inspect it.
+    Store(Box<dyn core::error::Error>),
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextError::NoStore => write!(f, "no store set on this thread (see `with_store`)"),
+            ContextError::Core(e) => e.fmt(f),
+            ContextError::Store(e) => e.fmt(f),
+        }
+    }
+}
+
+impl core::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            ContextError::NoStore => None,
+            ContextError::Core(e) => Some(e),
+            ContextError::Store(e) => Some(&**e),
+        }
+    }
+}
+
+impl From<StoreError> for ContextError {
+    fn from(e: StoreError) -> Self {
+        ContextError::Core(e)
+    }
+}
+
+/// The store set on the current thread by [`with_store`], and the default store of [`Link`] and
+/// [`AutoLink`], so data models can use `Link<T>` without carrying a store type parameter (and
+/// without `#[serde(bound = "")]`).
+///
+/// Deserializing a link only reads its CID, so it doesn't need a store; the store is looked up
+/// when the link is read or saved (including when it's saved by serializing its parent). Outside
+/// of a `with_store` scope, those operations fail with [`ContextError::NoStore`].
+///
+/// Values are always encoded as DAG-CBOR, and loaded blocks are always verified, whatever the
+/// underlying store's [`StaticStore::encode`] and [`StaticStore::VERIFY`].
+///
+/// [`Link`]: crate::Link
+/// [`AutoLink`]: crate::AutoLink
+pub struct Ambient(());
+
+impl StaticStore for Ambient {
+    type Error = ContextError;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        context()?.store_bytes(value, shape)
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        context()?.store_if_absent(value, shape)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        encode(value)
+    }
+
+    #[cfg(feature = "std")]
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        encode_into(value, w)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        context()?.load_bytes(k)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        context()?.has(k)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        context()?.keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        context()?.remove(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        decode(bytes)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        match err {
+            ContextError::Core(StoreError::NotFound(_)) => true,
+            ContextError::Store(_) => context().is_ok_and(|c| c.is_not_found(err)),
+            _ => false,
+        }
+    }
+}
+
+/// A store type standing for the instance of `S` set on the current thread by
+/// [`with_store_instance`], so stores with state (an open database, a connection pool) can be
+/// used as a link's store without stashing that state in a `static`, and two instances of the
+/// same backend can be used side by side: `Link<T, Static<MyDb>>` reads and writes through
+/// whichever `MyDb` is in scope.
+///
+/// `S` implements [`DynStore`], whose methods take `&self`; `Static<S>` bridges it to
+/// [`StaticStore`], so it works everywhere a store type does (e.g., wrapped in
+/// [`Magic`](crate::Magic) for `Deref` access, or in [`CachingStore`](crate::CachingStore)).
+/// Like [`Ambient`], it encodes values as DAG-CBOR, verifies loaded blocks, and fails with
+/// [`ContextError::NoStore`] outside of a scope. Unlike [`Ambient`], each store type has its own
+/// scope, so a thread can use a `Static<A>` and a `Static<B>` (and an [`Ambient`] store) at once.
+pub struct Static<S>(PhantomData<fn() -> S>);
+
+impl<S: DynStore + 'static> StaticStore for Static<S> {
+    type Error = ContextError;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        instance::<S>()?.store_bytes(value, shape)
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        instance::<S>()?.store_if_absent(value, shape)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        encode(value)
+    }
+
+    #[cfg(feature = "std")]
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        encode_into(value, w)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        instance::<S>()?.load_bytes(k)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        instance::<S>()?.has(k)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        instance::<S>()?.keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        instance::<S>()?.remove(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        decode(bytes)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        match err {
+            ContextError::Core(StoreError::NotFound(_)) => true,
+            ContextError::Store(_) => instance::<S>().is_ok_and(|s| s.is_not_found(err)),
+            _ => false,
+        }
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, ContextError> {
+    serde_ipld_dagcbor::to_vec(value).map_err(|e| StoreError::Encoding(e.to_string()).into())
+}
+
+#[cfg(feature = "std")]
+fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), ContextError> {
+    serde_ipld_dagcbor::to_writer(w, value).map_err(|e| StoreError::Encoding(e.to_string()).into())
+}
+
+fn decode<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, ContextError> {
+    serde_ipld_dagcbor::from_slice(bytes).map_err(|e| StoreError::Encoding(e.to_string()).into())
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+    use cid::Cid;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        current_store, with_dyn_store, with_store, with_store_instance, Ambient, CidShape,
+        ContextError, DynStore, ErasedStore, Link, MemoryError, MemoryStore, Static, StaticStore,
+        StoreError,
+    };
+
+    // No store type parameter, `PhantomData`, or `#[serde(bound = "")]`.
+    #[derive(Serialize, Deserialize)]
+    struct Data {
+        field: String,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct State {
+        name: String,
+        data: Link<Data>,
+    }
+
+    #[test]
+    fn links_use_the_ambient_store() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let state: Link<State> = Link::from_value(
+            State {
+                name: "state".into(),
+                data: Link::from_value(
+                    Data {
+                        field: "value".into(),
+                    },
+                    None,
+                ),
+            },
+            None,
+        );
+        let k = with_store::<Store, _>(|| state.save()).unwrap();
+        assert_eq!(Store::len(), 2);
+
+        let state: Link<State> = Link::new(k);
+        let field = with_store::<Store, _>(|| {
+            assert!(current_store().unwrap().contains("MemoryStore"));
+            Ok::<_, ContextError>(state.read()?.data.read()?.field.clone())
+        });
+        assert_eq!(field.unwrap(), "value");
+        assert!(current_store().is_none());
+    }
+
+    #[test]
+    fn missing_store_is_an_error() {
+        struct Tag;
+
+        let data: Link<Data> = Link::from_value(Data { field: "x".into() }, None);
+        assert!(matches!(data.save(), Err(ContextError::NoStore)));
+        let state = State {
+            name: "state".into(),
+            data,
+        };
+        // The typed error survives serialization.
+        assert!(matches!(
+            Ambient::store(&state, None),
+            Err(ContextError::NoStore)
+        ));
+
+        let k = MemoryStore::<()>::store(&"missing", None).unwrap();
+        let missing: Link<String> = Link::new(k);
+        assert!(matches!(missing.read(), Err(ContextError::NoStore)));
+        with_store::<MemoryStore<Tag>, _>(|| {
+            let err = missing.read().unwrap_err();
+            assert!(Ambient::is_not_found(&err), "{err}");
+            let ContextError::Store(e) = err else {
+                panic!("expected a store error")
+            };
+            assert!(matches!(e.downcast_ref(), Some(MemoryError::NotFound(_))));
+        });
+    }
+
+    /// A store configured at runtime.
+    #[derive(Default)]
+    struct MapStore(Mutex<HashMap<Cid, Vec<u8>>>);
+
+    impl DynStore for MapStore {
+        fn store_bytes(&self, value: &[u8], shape: Option<&CidShape>) -> Result<Cid, ContextError> {
+            let k = shape.copied().unwrap_or_default().cid(value)?;
+            self.0.lock().unwrap().insert(k, value.to_vec());
+            Ok(k)
+        }
+
+        fn load_bytes(&self, k: &Cid) -> Result<Vec<u8>, ContextError> {
+            let blocks = self.0.lock().unwrap();
+            let block = blocks.get(k).ok_or(StoreError::NotFound(*k))?;
+            Ok(block.clone())
+        }
+    }
+
+    #[test]
+    fn links_use_runtime_stores() {
+        struct Tag;
+
+        let map = Arc::new(MapStore::default());
+        let stores: [Arc<dyn DynStore>; 2] = [
+            Arc::new(ErasedStore::<MemoryStore<Tag>>::new()),
+            map.clone(),
+        ];
+        let cids = stores.map(|store| {
+            let data: Link<Data> = Link::from_value(Data { field: "x".into() }, None);
+            let k = with_dyn_store(store.clone(), || data.save()).unwrap();
+            assert!(store.has(&k).unwrap());
+            k
+        });
+        assert_eq!(cids[0], cids[1]);
+        assert_eq!(map.0.lock().unwrap().len(), 1);
+
+        let missing: Link<String> = Link::new(MemoryStore::<()>::store(&"missing", None).unwrap());
+        with_dyn_store(map, || {
+            assert!(current_store().unwrap().contains("MapStore"));
+            assert!(Ambient::is_not_found(&missing.read().unwrap_err()));
+        });
+        with_store::<MemoryStore<Tag>, _>(|| {
+            assert!(Ambient::is_not_found(&missing.read().unwrap_err()));
+        });
+    }
+
+    #[test]
+    fn links_use_scoped_instances() {
+        type Db = Static<MapStore>;
+        type Tree = Link<Vec<Link<String, Db>>, Db>;
+
+        let tree = |leaves: &[&str]| -> Tree {
+            let leaves = leaves.iter().map(|l| Link::from_value(l.to_string(), None));
+            Link::from_value(leaves.collect(), None)
+        };
+        let (a, b) = (Arc::new(MapStore::default()), Arc::new(MapStore::default()));
+        let ka = with_store_instance(a.clone(), || tree(&["a1", "a2"]).save()).unwrap();
+        let kb = with_store_instance(b.clone(), || tree(&["b"]).save()).unwrap();
+        assert_eq!(a.0.lock().unwrap().len(), 3);
+        assert_eq!(b.0.lock().unwrap().len(), 2);
+        assert!(matches!(Db::load_bytes(&ka), Err(ContextError::NoStore)));
+
+        let leaves = |k| {
+            let tree: Tree = Link::new(k);
+            let leaves = tree.read()?.iter().map(|l| l.read().cloned());
+            leaves.collect::<Result<Vec<_>, _>>()
+        };
+        with_store_instance(a, || {
+            assert_eq!(leaves(ka).unwrap(), ["a1", "a2"]);
+            assert!(Db::is_not_found(&leaves(kb).unwrap_err()));
+            // Scopes nest, and are independent of the ambient store's.
+            with_store_instance(b, || assert_eq!(leaves(kb).unwrap(), ["b"]));
+            with_store::<MemoryStore<()>, _>(|| assert!(leaves(ka).is_ok()));
+            assert!(leaves(kb).is_err());
+        });
+        assert!(matches!(leaves(ka), Err(ContextError::NoStore)));
+    }
+}
@@ -0,0 +1,470 @@
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use cid::Cid;
+use serde::{
+    de::DeserializeOwned, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{link_as_cid, CodecError, RawBytes, StoreError};
+
+/// A DAG-PB node, the block format used by UnixFS files and directories.
+///
+/// A node serializes in the DAG-PB data model, `{"Data": <bytes>, "Links": [{"Hash": <cid>,
+/// "Name": <string>, "Tsize": <int>}, ...]}` (without the entries that are `None`), so it (or any
+/// type with that shape) can be saved in a DAG-PB block by a link whose shape asks for
+/// [`DAG_PB`](crate::DAG_PB).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PbNode {
+    pub links: Vec<PbLink>,
+    pub data: Option<Vec<u8>>,
+}
+
+/// A link from a DAG-PB node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PbLink {
+    pub hash: Cid,
+    pub name: Option<String>,
+    /// The cumulative size of the linked subtree.
+    pub tsize: Option<u64>,
+}
+
+const WIRE_VARINT: u64 = 0;
+const WIRE_BYTES: u64 = 2;
+
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u64, CodecError> {
+    let mut n = 0u64;
+    for i in 0..10 {
+        let b = *bytes.get(*pos).ok_or(CodecError::Truncated)?;
+        *pos += 1;
+        n |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(CodecError::Malformed("varint overflow"))
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+/// Read a field key, returning the field number and wire type.
+fn read_key(bytes: &[u8], pos: &mut usize) -> Result<(u64, u64), CodecError> {
+    let key = read_varint(bytes, pos)?;
+    Ok((key >> 3, key & 0x7))
+}
+
+fn read_bytes<'a>(bytes: &'a [u8], pos: &mut usize) -> Result<&'a [u8], CodecError> {
+    let len = usize::try_from(read_varint(bytes, pos)?).map_err(|_| CodecError::Truncated)?;
+    let end = pos.checked_add(len).ok_or(CodecError::Truncated)?;
+    let out = bytes.get(*pos..end).ok_or(CodecError::Truncated)?;
+    *pos = end;
+    Ok(out)
+}
+
+fn write_bytes(out: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    write_varint(out, field << 3 | WIRE_BYTES);
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+impl PbNode {
+    /// Decode a DAG-PB block. Only the canonical field order (links before data) is accepted, as
+    /// required by the DAG-PB spec.
+    pub fn decode(bytes: &[u8]) -> Result<Self, CodecError> {
+        let mut node = PbNode::default();
+        let mut pos = 0;
+        while pos < bytes.len() {
+            match read_key(bytes, &mut pos)? {
+                (2, WIRE_BYTES) if node.data.is_none() => node
+                    .links
+                    .push(PbLink::decode(read_bytes(bytes, &mut pos)?)?),
+                (1, WIRE_BYTES) if node.data.is_none() => {
+                    node.data = Some(read_bytes(bytes, &mut pos)?.to_vec())
+                }
+                _ => return Err(CodecError::Malformed("unexpected dag-pb node field")),
+            }
+        }
+        Ok(node)
+    }
+
+    /// Encode this node in canonical DAG-PB form, with its links sorted by name (as bytes, with
+    /// unnamed links sorting as empty names) as the DAG-PB spec requires. Links with the same name
+    /// keep their order.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut links: Vec<&PbLink> = self.links.iter().collect();
+        links.sort_by_key(|l| l.name.as_deref().unwrap_or_default().as_bytes());
+        let mut out = Vec::new();
+        for link in links {
+            write_bytes(&mut out, 2, &link.encode());
+        }
+        if let Some(data) = &self.data {
+            write_bytes(&mut out, 1, data);
+        }
+        out
+    }
+}
+
+impl PbLink {
+    fn decode(bytes: &[u8]) -> Result<Self, CodecError> {
+        let mut pos = 0;
+        let mut last_field = 0;
+        let (mut hash, mut name, mut tsize) = (None, None, None);
+        while pos < bytes.len() {
+            let (field, wire) = read_key(bytes, &mut pos)?;
+            if field <= last_field {
+                return Err(CodecError::Malformed("dag-pb link fields out of order"));
+            }
+            last_field = field;
+            match (field, wire) {
+                (1, WIRE_BYTES) => {
+                    let k = Cid::try_from(read_bytes(bytes, &mut pos)?)
+                        .map_err(CodecError::InvalidCid)?;
+                    hash = Some(k);
+                }
+                (2, WIRE_BYTES) => {
+                    let s = core::str::from_utf8(read_bytes(bytes, &mut pos)?)
+                        .map_err(|_| CodecError::Malformed("dag-pb link name isn't utf-8"))?;
+                    name = Some(s.to_owned());
+                }
+                (3, WIRE_VARINT) => tsize = Some(read_varint(bytes, &mut pos)?),
+                _ => return Err(CodecError::Malformed("unexpected dag-pb link field")),
+            }
+        }
+        Ok(PbLink {
+            hash: hash.ok_or(CodecError::Malformed("dag-pb link without a hash"))?,
+            name,
+            tsize,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_bytes(&mut out, 1, &self.hash.to_bytes());
+        if let Some(name) = &self.name {
+            write_bytes(&mut out, 2, name.as_bytes());
+        }
+        if let Some(tsize) = self.tsize {
+            write_varint(&mut out, 3 << 3 | WIRE_VARINT);
+            write_varint(&mut out, tsize);
+        }
+        out
+    }
+}
+
+/// Serializes a byte slice as a byte string.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Serializes a CID as a link.
+struct AsLink<'a>(&'a Cid);
+
+impl Serialize for AsLink<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        link_as_cid::serialize(self.0, serializer)
+    }
+}
+
+impl Serialize for PbNode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1 + self.data.is_some() as usize))?;
+        if let Some(data) = &self.data {
+            map.serialize_entry("Data", &Bytes(data))?;
+        }
+        map.serialize_entry("Links", &self.links)?;
+        map.end()
+    }
+}
+
+impl Serialize for PbLink {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = 1 + self.name.is_some() as usize + self.tsize.is_some() as usize;
+        let mut map = serializer.serialize_map(Some(len))?;
+        map.serialize_entry("Hash", &AsLink(&self.hash))?;
+        if let Some(name) = &self.name {
+            map.serialize_entry("Name", name)?;
+        }
+        if let Some(tsize) = &self.tsize {
+            map.serialize_entry("Tsize", tsize)?;
+        }
+        map.end()
+    }
+}
+
+/// The data-model form of a [`PbNode`], for deserializing.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NodeRepr {
+    #[serde(rename = "Data", default)]
+    data: Option<RawBytes>,
+    #[serde(rename = "Links")]
+    links: Vec<PbLink>,
+}
+
+/// The data-model form of a [`PbLink`], for deserializing.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LinkRepr {
+    #[serde(rename = "Hash", deserialize_with = "link_as_cid::deserialize")]
+    hash: Cid,
+    #[serde(rename = "Name", default)]
+    name: Option<String>,
+    #[serde(rename = "Tsize", default)]
+    tsize: Option<u64>,
+}
+
+impl<'de> Deserialize<'de> for PbNode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let node = NodeRepr::deserialize(deserializer)?;
+        Ok(PbNode {
+            links: node.links,
+            data: node.data.map(RawBytes::into_inner),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for PbLink {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let link = LinkRepr::deserialize(deserializer)?;
+        Ok(PbLink {
+            hash: link.hash,
+            name: link.name,
+            tsize: link.tsize,
+        })
+    }
+}
+
+/// Encode `value` as a DAG-PB block: it must serialize in the DAG-PB data model (see
+/// [`PbNode`]).
+pub(crate) fn encode_dag_pb<T: Serialize>(value: &T) -> Result<Vec<u8>, StoreError> {
+    let encoded =
+        serde_ipld_dagcbor::to_vec(value).map_err(|e| StoreError::Encoding(e.to_string()))?;
+    let node: PbNode = serde_ipld_dagcbor::from_slice(&encoded)
+        .map_err(|e| StoreError::Encoding(alloc::format!("not a dag-pb node: {e}")))?;
+    Ok(node.encode())
+}
+
+/// Decode a DAG-PB block through its data model.
+pub(crate) fn decode_dag_pb<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, StoreError> {
+    let node = PbNode::decode(bytes).map_err(StoreError::Codec)?;
+    let encoded =
+        serde_ipld_dagcbor::to_vec(&node).map_err(|e| StoreError::Encoding(e.to_string()))?;
+    serde_ipld_dagcbor::from_slice(&encoded).map_err(|e| StoreError::Encoding(e.to_string()))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use cid::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
+
+    use crate::{CidShape, Link, MemoryStore, PbLink, PbNode, StaticStore, Value, DAG_PB};
+
+    /// The empty UnixFS directory.
+    const EMPTY_DIR: &[u8] = &[0x0a, 0x02, 0x08, 0x01];
+    const EMPTY_DIR_CID: &str = "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn";
+    /// The empty UnixFS file.
+    const EMPTY_FILE: &[u8] = &[0x0a, 0x04, 0x08, 0x02, 0x18, 0x00];
+    const EMPTY_FILE_CID: &str = "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH";
+    /// The UnixFS file `hello world\n`, as added by `ipfs add`.
+    const HELLO: &[u8] = b"\x0a\x12\x08\x02\x12\x0chello world\n\x18\x0c";
+    const HELLO_CID: &str = "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o";
+    /// A UnixFS directory holding `HELLO` as `hello.txt` (Tsize 20) and `EMPTY_DIR` as `sub`
+    /// (Tsize 4), with the links in name order before the Data field, as laid out by the spec:
+    /// each link is field 2 holding a `PbLink` (Hash = field 1, Name = field 2, Tsize = field 3),
+    /// and the Data (`08 01`, a UnixFS directory) is field 1.
+    const DIR: &str = "\
+        12310a22122046d44814b9c5af141c3aaab7c05dc5e844ead5f91f12858b021eba45768b4c0e120968656c6c\
+        6f2e7478741814122b0a22122059948439065f29619ef41280cbb932be52c56d99c5966b65e0111239f098bb\
+        ef120373756218040a020801";
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn cid_v0(bytes: &[u8]) -> Cid {
+        Cid::new_v0(Code::Sha2_256.digest(bytes)).unwrap()
+    }
+
+    #[test]
+    fn known_directory_roundtrip() {
+        let node = PbNode::decode(EMPTY_DIR).unwrap();
+        assert!(node.links.is_empty());
+        assert_eq!(node.data.as_deref(), Some(&[0x08, 0x01][..]));
+        assert_eq!(cid_v0(&node.encode()), EMPTY_DIR_CID.parse().unwrap());
+    }
+
+    #[test]
+    fn known_file_vectors() {
+        for (bytes, cid) in [(EMPTY_FILE, EMPTY_FILE_CID), (HELLO, HELLO_CID)] {
+            assert_eq!(cid_v0(bytes), cid.parse().unwrap());
+            let node = PbNode::decode(bytes).unwrap();
+            assert!(node.links.is_empty());
+            assert_eq!(node.encode(), bytes);
+        }
+        let hello = PbNode::decode(HELLO).unwrap();
+        assert_eq!(
+            hello.data.unwrap(),
+            b"\x08\x02\x12\x0chello world\n\x18\x0c"
+        );
+    }
+
+    #[test]
+    fn named_links_vector() {
+        let bytes = hex(DIR);
+        let node = PbNode::decode(&bytes).unwrap();
+        let expected = PbNode {
+            links: vec![
+                PbLink {
+                    hash: HELLO_CID.parse().unwrap(),
+                    name: Some("hello.txt".into()),
+                    tsize: Some(20),
+                },
+                PbLink {
+                    hash: EMPTY_DIR_CID.parse().unwrap(),
+                    name: Some("sub".into()),
+                    tsize: Some(4),
+                },
+            ],
+            data: Some(vec![0x08, 0x01]),
+        };
+        assert_eq!(node, expected);
+        assert_eq!(node.encode(), bytes);
+
+        // Links are sorted by name on encode, whatever order they're in.
+        let mut reversed = expected.clone();
+        reversed.links.reverse();
+        assert_eq!(reversed.encode(), bytes);
+    }
+
+    #[test]
+    fn links_sort_by_name_bytes() {
+        let k: Cid = EMPTY_DIR_CID.parse().unwrap();
+        let link = |name: Option<&str>, tsize| PbLink {
+            hash: k,
+            name: name.map(Into::into),
+            tsize: Some(tsize),
+        };
+        let node = PbNode {
+            links: vec![
+                link(Some("b"), 0),
+                link(Some("ab"), 1),
+                link(None, 2),
+                link(Some("B"), 3),
+                link(Some("a"), 4),
+                link(Some(""), 5),
+            ],
+            data: None,
+        };
+        let order: Vec<u64> = PbNode::decode(&node.encode())
+            .unwrap()
+            .links
+            .iter()
+            .map(|l| l.tsize.unwrap())
+            .collect();
+        // Unnamed and empty names sort first (in their original order), then bytewise.
+        assert_eq!(order, [2, 5, 3, 4, 1, 0]);
+    }
+
+    #[test]
+    fn stored_as_dag_pb() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let bytes = hex(DIR);
+        let node = PbNode::decode(&bytes).unwrap();
+        let shape = CidShape::DEFAULT.with_codec(DAG_PB);
+        assert!(Store::supports_codec(DAG_PB));
+        let k = Store::store(&node, Some(&shape)).unwrap();
+        assert_eq!(k.codec(), DAG_PB);
+        assert_eq!(Store::load_bytes(&k).unwrap(), bytes);
+        assert_eq!(Store::load::<PbNode>(&k).unwrap(), node);
+        assert_eq!(Cid::new_v0(*k.hash()).unwrap(), cid_v0(&bytes));
+
+        // Through the data model, as the DAG-PB spec describes it.
+        let Value::Map(value) = Store::load::<Value>(&k).unwrap() else {
+            panic!("expected a map")
+        };
+        assert_eq!(value["Data"], Value::Bytes(vec![0x08, 0x01]));
+        let Value::List(links) = &value["Links"] else {
+            panic!("expected a list")
+        };
+        let Value::Map(first) = &links[0] else {
+            panic!("expected a map")
+        };
+        assert_eq!(first["Hash"], Value::Link(HELLO_CID.parse().unwrap()));
+        assert_eq!(first["Name"], Value::String("hello.txt".into()));
+        assert_eq!(first["Tsize"], Value::Integer(20));
+
+        // Links to DAG-PB nodes work like any other.
+        let link: Link<PbNode, Store> = Link::from_value(node.clone(), Some(shape));
+        assert_eq!(link.save().unwrap(), k);
+        assert_eq!(Link::<PbNode, Store>::new(k).read().unwrap(), &node);
+
+        // Values that aren't nodes can't be.
+        assert!(Store::store(&"not a node", Some(&shape)).is_err());
+        let json = serde_ipld_dagjson::to_vec(&node).unwrap();
+        assert_eq!(
+            serde_ipld_dagjson::from_slice::<PbNode>(&json).unwrap(),
+            node
+        );
+    }
+
+    #[test]
+    fn directory_with_links_roundtrip() {
+        let empty: Cid = EMPTY_DIR_CID.parse().unwrap();
+        let dir = PbNode {
+            links: vec![
+                PbLink {
+                    hash: empty,
+                    name: Some("a".into()),
+                    tsize: Some(4),
+                },
+                PbLink {
+                    hash: empty,
+                    name: Some("b".into()),
+                    tsize: None,
+                },
+            ],
+            data: Some(vec![0x08, 0x01]),
+        };
+        let bytes = dir.encode();
+        let decoded = PbNode::decode(&bytes).unwrap();
+        assert_eq!(decoded, dir);
+        assert_eq!(cid_v0(&decoded.encode()), cid_v0(&bytes));
+    }
+
+    #[test]
+    fn reject_data_before_links() {
+        let mut bytes = EMPTY_DIR.to_vec();
+        bytes.extend(
+            PbNode {
+                links: vec![PbLink {
+                    hash: EMPTY_DIR_CID.parse().unwrap(),
+                    name: None,
+                    tsize: None,
+                }],
+                data: None,
+            }
+            .encode(),
+        );
+        assert!(PbNode::decode(&bytes).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::{any::Any, cell::RefCell, io};
+
+use alloc::string::String;
+
+use cid::Cid;
+
+use crate::{CodecError, MAX_IDENTITY_SIZE};
+
+/// Errors raised by the crate itself, as opposed to a store backend. Every
+/// [`StaticStore::Error`](crate::StaticStore::Error) must be constructible from this type.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StoreError {
+    /// The store doesn't support the named operation.
+    Unsupported(&'static str),
+    /// A block decoded successfully, but the value violates an invariant checked on load.
+    ValidationFailed { cid: Cid, reason: String },
+    /// The multihash function isn't supported.
+    UnsupportedMultihash(u64),
+    /// A loaded block doesn't hash to its CID.
+    HashMismatch(Cid),
+    /// A block couldn't be scanned.
+    Codec(CodecError),
+    /// The operation would discard a link's unsaved changes.
+    UnsavedChanges,
+    /// The block isn't in the store.
+    NotFound(Cid),
+    /// A value couldn't be encoded or decoded.
+    Encoding(String),
+    /// The store is read-only (see [`ReadOnly`](crate::ReadOnly)).
+    ReadOnly,
+    /// Reading or writing an encoded value failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
+    /// A block is larger than the caller's limit (see
+    /// [`StaticStore::MAX_BLOCK_SIZE`](crate::StaticStore::MAX_BLOCK_SIZE)). If the store stopped
+    /// reading early, `len` is the number of bytes it had read (or the size it was told to expect).
+    BlockTooLarge { cid: Cid, len: usize, max: usize },
+    /// A block is larger than the store will write (see
+    /// [`StaticStore::MAX_WRITE_SIZE`](crate::StaticStore::MAX_WRITE_SIZE)).
+    BlockTooLargeOnWrite { len: usize, max: usize },
+    /// A block is too large to embed in an identity CID (see [`IDENTITY`](crate::IDENTITY)).
+    IdentityTooLarge(usize),
+    /// A modified link's value contains the link itself (e.g., through an `Rc`), so saving it
+    /// would never finish. IPLD DAGs can't have cycles.
+    CycleDetected,
+    /// Saving modified links recursed deeper than the limit (see
+    /// [`StaticStore::MAX_LINK_DEPTH`](crate::StaticStore::MAX_LINK_DEPTH)).
+    DepthExceeded(usize),
+    /// A value pinned inline (see [`AutoLink::pin_inline`](crate::AutoLink::pin_inline)) encodes
+    /// to more than its inline threshold.
+    InlineTooLarge { len: usize, max: usize },
+    /// More blocks were loaded than the [`with_load_budget`](crate::with_load_budget) scope
+    /// allows.
+    BudgetExhausted(usize),
+    /// A path passed to [`resolve_path`](crate::resolve_path) doesn't exist. `depth` is the index
+    /// of the segment that couldn't be followed.
+    PathNotFound { depth: usize, reason: &'static str },
+    /// The [`with_deadline`](crate::with_deadline) scope's deadline passed before the block with
+    /// this CID was loaded.
+    Timeout(Cid),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Unsupported(op) => write!(f, "store doesn't support `{op}`"),
+            StoreError::ValidationFailed { cid, reason } => {
+                write!(f, "block {cid} failed validation: {reason}")
+            }
+            StoreError::UnsupportedMultihash(code) => {
+                write!(f, "unsupported multihash function 0x{code:x}")
+            }
+            StoreError::HashMismatch(k) => write!(f, "block doesn't match its cid {k}"),
+            StoreError::Codec(e) => e.fmt(f),
+            StoreError::UnsavedChanges => write!(f, "link has unsaved changes"),
+            StoreError::NotFound(k) => write!(f, "block {k} not found"),
+            StoreError::Encoding(e) => write!(f, "codec error: {e}"),
+            StoreError::ReadOnly => write!(f, "store is read-only"),
+            #[cfg(feature = "std")]
+            StoreError::Io(e) => write!(f, "i/o error: {e}"),
+            StoreError::BlockTooLarge { cid, len, max } => {
+                write!(f, "block {cid} is too large ({len} bytes, limit {max})")
+            }
+            StoreError::BlockTooLargeOnWrite { len, max } => {
+                write!(f, "block is too large to write ({len} bytes, limit {max})")
+            }
+            StoreError::CycleDetected => write!(f, "link contains itself"),
+            StoreError::DepthExceeded(max) => {
+                write!(f, "links nested more than {max} deep")
+            }
+            StoreError::InlineTooLarge { len, max } => {
+                write!(
+                    f,
+                    "value pinned inline is too large ({len} bytes, limit {max})"
+                )
+            }
+            StoreError::BudgetExhausted(max) => {
+                write!(f, "load budget exhausted (limit {max} blocks)")
+            }
+            StoreError::PathNotFound { depth, reason } => {
+                write!(f, "path segment {depth} not found: {reason}")
+            }
+            StoreError::Timeout(k) => write!(f, "deadline passed before block {k} was loaded"),
+            StoreError::IdentityTooLarge(len) => write!(
+                f,
+                "{len}-byte block is too large for an identity cid (limit {MAX_IDENTITY_SIZE})"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            StoreError::Codec(e) => Some(e),
+            #[cfg(feature = "std")]
+            StoreError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    /// A store error raised inside a `Serialize` impl, waiting to be picked up by the enclosing
+    /// encode (see [`stash_error`]).
+    static STASHED: RefCell<Option<Box<dyn Any>>> = const { RefCell::new(None) };
+}
+
+/// Stash a store error raised while serializing a link. Serde can only carry errors as strings,
+/// so links report a `custom` error to the serializer and leave the typed error here for
+/// [`take_stashed`] to recover once the encode fails.
+///
+/// Without `std`, there's nowhere to stash the error, so it's dropped.
+pub(crate) fn stash_error<E: 'static>(err: E) {
+    #[cfg(feature = "std")]
+    STASHED.with(|s| *s.borrow_mut() = Some(Box::new(err)));
+    #[cfg(not(feature = "std"))]
+    drop(err);
+}
+
+/// Take the stashed error, if there is one and it has type `E`. Any stashed error is cleared.
+#[cfg(feature = "std")]
+pub(crate) fn take_stashed<E: 'static>() -> Option<E> {
+    let err = STASHED.with(|s| s.borrow_mut().take())?;
+    err.downcast().ok().map(|e| *e)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn take_stashed<E: 'static>() -> Option<E> {
+    None
+}
@@ -0,0 +1,291 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    fmt,
+    io::{Read, Write},
+    marker::PhantomData,
+    sync::{LazyLock, Mutex},
+    time::Instant,
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{CidShape, StaticStore, StoreError};
+
+type FailFn = Box<dyn FnMut(usize) -> bool + Send>;
+
+struct Schedule {
+    ops: usize,
+    fail: FailFn,
+}
+
+static SCHEDULES: LazyLock<Mutex<HashMap<TypeId, Schedule>>> = LazyLock::new(Default::default);
+
+/// A store wrapper that deterministically fails selected operations, for testing error paths.
+///
+/// Every `load_bytes`, `block_size`, `has`, `store_bytes`, and `store_if_absent` call counts as
+/// one operation (starting from zero when the schedule is installed). Operations selected by the
+/// schedule return [`FaultyError::Injected`] without touching the inner store; all others are
+/// forwarded to `S`.
+///
+/// The schedule is process-global and keyed by the inner store type, so tests running in parallel
+/// should each wrap a distinct store type.
+pub struct FaultyStore<S>(PhantomData<S>);
+
+#[derive(Debug)]
+pub enum FaultyError<E> {
+    /// The operation was selected to fail by the schedule.
+    Injected { op: usize },
+    /// The inner store failed.
+    Inner(E),
+}
+
+impl<E: fmt::Display> fmt::Display for FaultyError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaultyError::Injected { op } => write!(f, "injected failure on operation {op}"),
+            FaultyError::Inner(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for FaultyError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FaultyError::Injected { .. } => None,
+            FaultyError::Inner(e) => Some(e),
+        }
+    }
+}
+
+impl<E: From<StoreError>> From<StoreError> for FaultyError<E> {
+    fn from(e: StoreError) -> Self {
+        FaultyError::Inner(e.into())
+    }
+}
+
+impl<S: 'static> FaultyStore<S> {
+    /// Fail the operations with the given indices.
+    pub fn fail_on(ops: &[usize]) {
+        let ops = ops.to_vec();
+        Self::fail_when(move |op| ops.contains(&op));
+    }
+
+    /// Fail every operation for which `fail` returns true.
+    pub fn fail_when(fail: impl FnMut(usize) -> bool + Send + 'static) {
+        let schedule = Schedule {
+            ops: 0,
+            fail: Box::new(fail),
+        };
+        SCHEDULES
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<S>(), schedule);
+    }
+
+    /// Remove the schedule, forwarding all further operations.
+    pub fn reset() {
+        SCHEDULES.lock().unwrap().remove(&TypeId::of::<S>());
+    }
+
+    /// The number of operations performed since the schedule was installed.
+    pub fn operations() -> usize {
+        SCHEDULES
+            .lock()
+            .unwrap()
+            .get(&TypeId::of::<S>())
+            .map_or(0, |s| s.ops)
+    }
+
+    /// Count an operation, returning its index if it should fail.
+    fn injected() -> Option<usize> {
+        let mut schedules = SCHEDULES.lock().unwrap();
+        let schedule = schedules.get_mut(&TypeId::of::<S>())?;
+        let op = schedule.ops;
+        schedule.ops += 1;
+        (schedule.fail)(op).then_some(op)
+    }
+}
+
+impl<S> StaticStore for FaultyStore<S>
+where
+    S: StaticStore + 'static,
+    S::Error: 'static,
+{
+    type Error = FaultyError<S::Error>;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        if let Some(op) = Self::injected() {
+            return Err(FaultyError::Injected { op });
+        }
+        S::store_bytes(value, shape).map_err(FaultyError::Inner)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value).map_err(FaultyError::Inner)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec).map_err(FaultyError::Inner)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w).map_err(FaultyError::Inner)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value).map_err(FaultyError::Inner)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape).map_err(FaultyError::Inner)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        if let Some(op) = Self::injected() {
+            return Err(FaultyError::Injected { op });
+        }
+        S::load_bytes(k).map_err(FaultyError::Inner)
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        if let Some(op) = Self::injected() {
+            return Err(FaultyError::Injected { op });
+        }
+        S::load_bytes_limited(k, max).map_err(FaultyError::Inner)
+    }
+
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        if let Some(op) = Self::injected() {
+            return Err(FaultyError::Injected { op });
+        }
+        S::load_bytes_deadline(k, deadline).map_err(FaultyError::Inner)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        if let Some(op) = Self::injected() {
+            return Err(FaultyError::Injected { op });
+        }
+        S::has(k).map_err(FaultyError::Inner)
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        if let Some(op) = Self::injected() {
+            return Err(FaultyError::Injected { op });
+        }
+        S::block_size(k).map_err(FaultyError::Inner)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes).map_err(FaultyError::Inner)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes).map_err(FaultyError::Inner)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec).map_err(FaultyError::Inner)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes).map_err(FaultyError::Inner)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r).map_err(FaultyError::Inner)
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        if let Some(op) = Self::injected() {
+            return Err(FaultyError::Injected { op });
+        }
+        S::store_if_absent(value, shape).map_err(FaultyError::Inner)
+    }
+
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        S::keys_in_range(start, end).map_err(FaultyError::Inner)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        S::keys().map_err(FaultyError::Inner)
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        S::remove(k).map_err(FaultyError::Inner)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        matches!(err, FaultyError::Inner(e) if S::is_not_found(e))
+    }
+
+    /// Injected failures are retryable.
+    fn is_retryable(err: &Self::Error) -> bool {
+        match err {
+            FaultyError::Injected { .. } => true,
+            FaultyError::Inner(e) => S::is_retryable(e),
+        }
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        match err {
+            FaultyError::Inner(e) => FaultyError::Inner(S::with_label(e, label)),
+            e => e,
+        }
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        match err {
+            FaultyError::Inner(e) => FaultyError::Inner(S::with_cid(e, cid)),
+            e => e,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FaultyError, FaultyStore, Link, MemoryStore};
+
+    #[test]
+    fn value_survives_failed_free() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let mut link: Link<String, Store> = Link::from_value("precious".into(), None);
+
+        Store::fail_on(&[0]);
+        assert!(matches!(link.free(), Err(FaultyError::Injected { op: 0 })));
+        assert_eq!(MemoryStore::<Tag>::len(), 0);
+        assert_eq!(link.read().unwrap(), "precious");
+        assert_eq!(Store::operations(), 1);
+
+        let k = link.free().unwrap();
+        assert!(MemoryStore::<Tag>::contains(&k));
+        assert_eq!(link.read().unwrap(), "precious");
+        assert_eq!(Store::operations(), 3);
+        Store::reset();
+    }
+}
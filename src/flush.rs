@@ -0,0 +1,245 @@
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+
+use cid::Cid;
+use serde::Serialize;
+
+use crate::{AutoLink, Link, MaybeLink, StaticStore};
+
+/// A value whose modified links can be saved to `Store` without serializing the value itself.
+///
+/// [`Link::flush`] and [`AutoLink::flush`] use this to save a modified subtree depth-first, so each
+/// node is written after its descendants and encoding it doesn't recurse into them. Use
+/// [`impl_flush!`](crate::impl_flush) to implement this for structs.
+pub trait Flush<Store: StaticStore> {
+    /// Save every modified link in this value (and, recursively, inside their values).
+    fn flush_links(&self) -> Result<(), Store::Error>;
+}
+
+impl<T, Store> Flush<Store> for Link<T, Store>
+where
+    T: Serialize + Flush<Store>,
+    Store: StaticStore,
+{
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        self.flush().map(drop)
+    }
+}
+
+impl<T, Store, const S: usize> Flush<Store> for AutoLink<T, Store, S>
+where
+    T: Serialize + Flush<Store>,
+    Store: StaticStore,
+{
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        self.flush().map(drop)
+    }
+}
+
+impl<T: Flush<Store>, Store: StaticStore> Flush<Store> for MaybeLink<T> {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        match self {
+            MaybeLink::Value(v) => v.flush_links(),
+            MaybeLink::Link(_) => Ok(()),
+        }
+    }
+}
+
+impl<T: Flush<Store> + ?Sized, Store: StaticStore> Flush<Store> for &T {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        (**self).flush_links()
+    }
+}
+
+impl<T: Flush<Store> + ?Sized, Store: StaticStore> Flush<Store> for Box<T> {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        (**self).flush_links()
+    }
+}
+
+impl<T: Flush<Store>, Store: StaticStore> Flush<Store> for Option<T> {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        self.iter().try_for_each(T::flush_links)
+    }
+}
+
+impl<T: Flush<Store>, Store: StaticStore> Flush<Store> for [T] {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        self.iter().try_for_each(T::flush_links)
+    }
+}
+
+impl<T: Flush<Store>, Store: StaticStore, const N: usize> Flush<Store> for [T; N] {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        self[..].flush_links()
+    }
+}
+
+impl<T: Flush<Store>, Store: StaticStore> Flush<Store> for Vec<T> {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        self[..].flush_links()
+    }
+}
+
+impl<K, V: Flush<Store>, Store: StaticStore> Flush<Store> for BTreeMap<K, V> {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        self.values().try_for_each(V::flush_links)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K, V: Flush<Store>, H, Store: StaticStore> Flush<Store> for HashMap<K, V, H> {
+    fn flush_links(&self) -> Result<(), Store::Error> {
+        self.values().try_for_each(V::flush_links)
+    }
+}
+
+/// Values that can't contain links.
+macro_rules! no_flush {
+    ($($ty:ty),*) => {
+        $(
+            impl<Store: StaticStore> Flush<Store> for $ty {
+                fn flush_links(&self) -> Result<(), Store::Error> {
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+no_flush!(bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+no_flush!(f32, f64, str, String, (), Cid);
+
+macro_rules! tuple_flush {
+    ($(($($name:ident),+))*) => {
+        $(
+            impl<$($name: Flush<Store>,)+ Store: StaticStore> Flush<Store> for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn flush_links(&self) -> Result<(), Store::Error> {
+                    let ($($name,)+) = self;
+                    $($name.flush_links()?;)+
+                    Ok(())
+                }
+            }
+        )*
+    };
+}
+
+tuple_flush! {
+    (A)
+    (A, B)
+    (A, B, C)
+    (A, B, C, D)
+    (A, B, C, D, E)
+    (A, B, C, D, E, F)
+}
+
+/// Implement [`Flush`] for a struct by flushing the named fields, in order. Only fields that may
+/// contain links need to be listed. Generic parameters (with their bounds) go in brackets before
+/// the impl, as with [`impl_links!`](crate::impl_links).
+///
+/// ```
+/// # use auto_ipld::{impl_flush, Link, StaticStore};
+/// # use serde::Serialize;
+/// #[derive(Serialize)]
+/// #[serde(bound = "")]
+/// struct Node<Store: StaticStore> {
+///     name: String,
+///     children: Vec<Link<Node<Store>, Store>>,
+/// }
+///
+/// impl_flush!([Store: StaticStore] Flush<Store> for Node<Store> { children });
+/// ```
+#[macro_export]
+macro_rules! impl_flush {
+    ([$($generics:tt)*] Flush<$store:ty> for $ty:ty { $($field:ident),* $(,)? }) => {
+        impl<$($generics)*> $crate::Flush<$store> for $ty {
+            fn flush_links(&self) -> ::std::result::Result<(), <$store as $crate::StaticStore>::Error> {
+                $($crate::Flush::<$store>::flush_links(&self.$field)?;)*
+                Ok(())
+            }
+        }
+    };
+    (Flush<$store:ty> for $ty:ty { $($field:ident),* $(,)? }) => {
+        $crate::impl_flush!([] Flush<$store> for $ty { $($field),* });
+    };
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{AutoLink, Link, MaybeLink, MemoryStore, StaticStore};
+
+    struct Tag;
+    type Store = MemoryStore<Tag>;
+    type Meta = AutoLink<(String, Vec<Link<String, Store>>), Store, 16>;
+
+    #[derive(Serialize, Deserialize)]
+    struct Node {
+        name: String,
+        children: Vec<Link<Node, Store>>,
+        meta: Option<Meta>,
+    }
+
+    impl_flush!(Flush<Store> for Node { children, meta });
+
+    fn node(name: &str, children: Vec<Node>) -> Node {
+        Node {
+            name: name.into(),
+            children: children
+                .into_iter()
+                .map(|c| Link::from_value(c, None))
+                .collect(),
+            meta: None,
+        }
+    }
+
+    #[test]
+    fn flush_saves_descendants_first() {
+        let mut root = node(
+            "root",
+            vec![node("a", vec![node("a1", vec![])]), node("b", vec![])],
+        );
+        root.meta = Some(AutoLink::from_value((
+            "a fairly long description".into(),
+            vec![Link::from_value("tag".into(), None)],
+        )));
+        let root = Link::from_value(root, None);
+
+        let k = root.flush().unwrap();
+        assert!(!root.is_dirty());
+        let root = root.read().unwrap();
+        let a = &root.children[0];
+        assert!(!a.is_dirty() && !a.read().unwrap().children[0].is_dirty());
+        assert!(!root.children[1].is_dirty());
+        let meta = root.meta.as_ref().unwrap();
+        assert!(!meta.is_modified() && !meta.is_inlined());
+        assert!(!meta.read().unwrap().1[0].is_dirty());
+        assert_eq!(Store::len(), 6);
+
+        // Flushing writes the same blocks as saving through serialization.
+        let same = node(
+            "root",
+            vec![node("a", vec![node("a1", vec![])]), node("b", vec![])],
+        );
+        let same = Node {
+            meta: Some(AutoLink::from_value((
+                "a fairly long description".into(),
+                vec![Link::from_value("tag".into(), None)],
+            ))),
+            ..same
+        };
+        assert_eq!(Store::store(&same, None).unwrap(), k);
+        assert_eq!(Store::len(), 6);
+
+        let small = AutoLink::<_, Store, 64>::from_value((
+            "small".to_string(),
+            vec![Link::from_value("x".to_string(), None)],
+        ));
+        assert!(matches!(small.flush().unwrap(), MaybeLink::Value(_)));
+        assert!(!small.read().unwrap().1[0].is_dirty());
+    }
+}
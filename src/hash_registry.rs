@@ -0,0 +1,93 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+};
+
+use cid::multihash::{Code, Multihash};
+
+use crate::{store, StoreError, IDENTITY};
+
+/// A function hashing a block into a multihash (tagged with its own code).
+pub type Hasher = fn(&[u8]) -> Multihash;
+
+static HASHERS: LazyLock<RwLock<HashMap<u64, Hasher>>> = LazyLock::new(Default::default);
+
+/// The process-wide table of multihash functions used to compute CIDs ([`CidShape::cid`]) and to
+/// verify blocks ([`StaticStore::verify`]), so every store supports the same hashes without
+/// matching on codes itself.
+///
+/// The functions built into `multihash` (SHA2-256 and SHA2-512, plus BLAKE3 and the rest with the
+/// default `std` feature) and [`IDENTITY`] are always available. Others can be
+/// [registered](HashRegistry::register), once per code, and should be registered before any
+/// block is hashed with them (e.g., at startup).
+///
+/// [`CidShape::cid`]: crate::CidShape::cid
+/// [`StaticStore::verify`]: crate::StaticStore::verify
+pub struct HashRegistry;
+
+impl HashRegistry {
+    /// Register `hasher` as the multihash function `code`. Returns false (and leaves the registry
+    /// unchanged) if `code` is built in or already registered: a code's hasher never changes, so
+    /// CIDs computed with it stay valid.
+    pub fn register(code: u64, hasher: Hasher) -> bool {
+        if code == IDENTITY || Code::try_from(code).is_ok() {
+            return false;
+        }
+        let mut hashers = HASHERS.write().unwrap();
+        if hashers.contains_key(&code) {
+            return false;
+        }
+        hashers.insert(code, hasher);
+        true
+    }
+
+    /// Hash `bytes` with the multihash function `code`, failing with
+    /// [`StoreError::UnsupportedMultihash`] if it's neither built in nor registered.
+    pub fn hash(code: u64, bytes: &[u8]) -> Result<Multihash, StoreError> {
+        store::digest(code, bytes)
+    }
+}
+
+/// The hasher registered for `code`, if any.
+pub(crate) fn registered(code: u64) -> Option<Hasher> {
+    HASHERS.read().unwrap().get(&code).copied()
+}
+
+#[cfg(test)]
+mod test {
+    use cid::multihash::Multihash;
+
+    use crate::{CidShape, HashRegistry, MemoryStore, StaticStore, StoreError};
+
+    /// A private-use code.
+    const XOR: u64 = 0x30_0001;
+
+    fn xor(bytes: &[u8]) -> Multihash {
+        let mut digest = [0; 4];
+        for (i, b) in bytes.iter().enumerate() {
+            digest[i % 4] ^= b;
+        }
+        Multihash::wrap(XOR, &digest).unwrap()
+    }
+
+    #[test]
+    fn custom_hashers() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        assert!(matches!(
+            HashRegistry::hash(XOR + 1, b"block"),
+            Err(StoreError::UnsupportedMultihash(code)) if code == XOR + 1
+        ));
+        assert!(HashRegistry::register(XOR, xor));
+        assert!(!HashRegistry::register(XOR, xor));
+        assert!(!HashRegistry::register(0x12, xor));
+
+        let shape = CidShape::DEFAULT.with_hash(XOR);
+        let k = Store::store(&"hashed", Some(&shape)).unwrap();
+        assert_eq!(k.hash().code(), XOR);
+        assert_eq!(*k.hash(), xor(&Store::encode(&"hashed").unwrap()));
+        // Loads verify with the registered hasher, too.
+        assert_eq!(Store::load::<String>(&k).unwrap(), "hashed");
+    }
+}
@@ -0,0 +1,118 @@
+use core::{any::Any, marker::PhantomData};
+
+use alloc::{boxed::Box, collections::BTreeMap};
+
+use cid::Cid;
+use serde::Serialize;
+
+use crate::{Link, StaticStore};
+
+/// Deduplicates equal values by content: [`Interner::intern`] stores each distinct value once and
+/// hands out clean links to it, so a DAG with many identical nodes (e.g., repeated leaves) only
+/// encodes and writes each of them once.
+///
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// # use auto_ipld::{Interner, MemoryStore};
+/// # use std::rc::Rc;
+/// let mut interner = Interner::<MemoryStore<()>>::new();
+/// let a = interner.intern(Rc::new("leaf".to_owned())).unwrap();
+/// let b = interner.intern(Rc::new("leaf".to_owned())).unwrap();
+/// assert_eq!(a.cid(), b.cid());
+/// assert!(Rc::ptr_eq(a.read().unwrap(), b.read().unwrap()));
+/// ```
+///
+/// Each link gets a clone of the interned value, so intern values behind an `Rc` or `Arc` (as
+/// above) to share one copy in memory as well. Values are interned in the store's default shape,
+/// and only values of the same type are shared: interning an equal value of another type stores
+/// it again, under the same CID.
+pub struct Interner<Store> {
+    values: BTreeMap<Cid, Box<dyn Any>>,
+    _marker: PhantomData<fn(Store)>,
+}
+
+impl<Store> Default for Interner<Store> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Store> Interner<Store> {
+    /// An empty interner.
+    pub fn new() -> Self {
+        Interner {
+            values: BTreeMap::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The number of distinct values interned.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if nothing has been interned.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Forget every interned value. The blocks stay in the store.
+    pub fn clear(&mut self) {
+        self.values.clear()
+    }
+}
+
+impl<Store: StaticStore> Interner<Store> {
+    /// Return an unmodified link to `value`, storing it first unless an equal value was already
+    /// interned. Either way, the link's value is cached, so reading it doesn't load anything.
+    ///
+    /// Looking up a value computes its CID (see [`StaticStore::compute_cid`]), which encodes it,
+    /// saving any modified links inside it.
+    pub fn intern<T>(&mut self, value: T) -> Result<Link<T, Store>, Store::Error>
+    where
+        T: Serialize + Clone + 'static,
+    {
+        let k = Store::compute_cid(&value, None)?;
+        if let Some(interned) = self.values.get(&k).and_then(|v| v.downcast_ref::<T>()) {
+            return Ok(Link::loaded(k, interned.clone()));
+        }
+        let k = Store::store(&value, None)?;
+        self.values.insert(k, Box::new(value.clone()));
+        Ok(Link::loaded(k, value))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::rc::Rc;
+
+    use crate::{Interner, MemoryStore, StaticStore, StatsStore};
+
+    #[test]
+    fn equal_values_are_stored_once() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let mut interner = Interner::<Store>::new();
+        let a = interner.intern(Rc::new("leaf".to_owned())).unwrap();
+        let b = interner.intern(Rc::new("leaf".to_owned())).unwrap();
+        assert_eq!(Store::snapshot().stores, 1);
+        assert_eq!(a.cid(), b.cid());
+        assert!(!a.is_dirty() && !b.is_dirty());
+        assert!(Rc::ptr_eq(a.read().unwrap(), b.read().unwrap()));
+        assert_eq!(Store::snapshot().loads, 0);
+        assert_eq!(interner.len(), 1);
+
+        let c = interner.intern(Rc::new("other".to_owned())).unwrap();
+        assert_ne!(a.cid(), c.cid());
+        assert_eq!(Store::snapshot().stores, 2);
+
+        interner.clear();
+        interner.intern(Rc::new("leaf".to_owned())).unwrap();
+        assert_eq!(Store::snapshot().stores, 3);
+        assert_eq!(
+            MemoryStore::<Tag>::load::<String>(&a.cid().unwrap()).unwrap(),
+            "leaf"
+        );
+    }
+}
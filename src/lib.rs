@@ -1,9 +1,173 @@
+//! Lazily loaded, automatically persisted IPLD links for serde data models.
+//!
+//! # Enums
+//!
+//! Rust enums use serde's default (externally tagged) representation, which maps onto IPLD as
+//! follows:
+//!
+//! | Rust variant           | IPLD form                | IPLD schema representation |
+//! |------------------------|--------------------------|----------------------------|
+//! | `Unit`                 | `"Unit"`                 | enum, string               |
+//! | `Newtype(1)`           | `{"Newtype": 1}`         | union, keyed               |
+//! | `Tuple(1, "x")`        | `{"Tuple": [1, "x"]}`    | union, keyed (tuple value) |
+//! | `Struct { x: 1 }`      | `{"Struct": {"x": 1}}`   | union, keyed (map value)   |
+//!
+//! So an enum with only data-carrying variants is a keyed union, and an enum with only unit
+//! variants is a string-represented enum. Mixing the two produces data that no single IPLD
+//! schema representation describes; give the unit variants an empty struct (`Unit {}`, encoded as
+//! `{"Unit": {}}`) to keep a mixed enum a keyed union. Avoid `#[serde(untagged)]`, which can't
+//! round-trip reliably, and prefer `#[serde(tag = "...")]` only when interoperating with an
+//! inline-union schema.
+//!
+//! # `no_std`
+//!
+//! Without the default `std` feature, the crate is `no_std` (it still needs `alloc`). What's left
+//! is everything needed to bring your own store:
+//!
+//! - the link types: [`Link`], [`AutoLink`], [`MaybeLink`] (and [`TaggedMaybeLink`]),
+//!   [`SharedLink`], [`CheckedLink`], [`RawLink`], and [`Inline`], the [`Child`] trait over them,
+//!   [`LinkIter`], and [`LinkSlice`];
+//! - [`StaticStore`], [`AsyncStore`], [`RefStore`], [`PinStore`], [`MagicStore`], and [`ReadOnly`];
+//! - [`Value`], [`RawBytes`], the [`Links`], [`Flush`], and [`ContentEq`] traits,
+//!   [`save_as_you_go`], [`Interner`], and block scanning ([`child_links`], [`BlockCursor`], [`PbNode`],
+//!   [`walk`], and [`diff`]).
+//!
+//! The rest needs `std`:
+//!
+//! - the bundled stores and wrappers ([`MemoryStore`], [`CachingStore`], [`StatsStore`], ...),
+//!   CAR files, DAG-JSON, and [`SyncLink`];
+//! - [`StaticStore::encode_into`] and [`StaticStore::decode_from`], which use `std::io`;
+//! - [`with_store`], [`with_dyn_store`], and [`with_store_instance`], so the [`Ambient`] and
+//!   [`Static`] stores always fail with [`ContextError::NoStore`];
+//! - [`Link::save_batched`], [`with_load_budget`], and [`with_deadline`];
+//! - cycle and depth checks while saving: without `std`, [`StaticStore::MAX_LINK_DEPTH`] isn't
+//!   enforced, so saving a deeply nested chain of modified links can overflow the stack, and a
+//!   link whose value contains the link itself recurses until it does, rather than failing with
+//!   [`StoreError::DepthExceeded`] or [`StoreError::CycleDetected`];
+//! - typed errors from links that fail to save while their parent is serialized: without `std`,
+//!   the codec's (stringified) error is returned instead.
+//!
+//! Only SHA2 multihashes can be verified without `std`, and custom ones can't be registered (see
+//! [`HashRegistry`]). Check the `no_std` build with `cargo test --no-default-features`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+mod async_store;
 mod auto_link;
+#[cfg(feature = "std")]
+mod batch;
+mod bytes;
+#[cfg(feature = "std")]
+mod caching;
+#[cfg(feature = "std")]
+mod car;
+mod checked_link;
+mod child;
+mod codec;
+#[cfg(feature = "std")]
+mod compress;
+mod content_eq;
+mod context;
+mod dag_pb;
+mod error;
+#[cfg(any(all(test, feature = "std"), feature = "testing"))]
+mod faulty;
+mod flush;
+#[cfg(feature = "std")]
+mod hash_registry;
+mod intern;
 mod link;
+mod link_iter;
+mod links;
 mod maybe_link;
+#[cfg(feature = "std")]
+mod memory;
+#[cfg(feature = "std")]
+mod negative_cache;
+mod raw_link;
+mod read_only;
+mod refs;
+#[cfg(feature = "std")]
+mod replicated;
+#[cfg(feature = "std")]
+mod retry;
+mod save;
+#[cfg(feature = "std")]
+mod self_describing;
+#[cfg(feature = "std")]
+mod sharded;
+mod shared_link;
+#[cfg(any(all(test, feature = "std"), feature = "testing"))]
+mod sim;
+#[cfg(feature = "std")]
+mod stats;
 mod store;
+#[cfg(feature = "std")]
+mod sync_link;
+mod value;
+mod walk;
 
+pub use async_store::*;
 pub use auto_link::*;
+#[cfg(feature = "std")]
+pub use batch::*;
+pub use bytes::*;
+#[cfg(feature = "std")]
+pub use caching::*;
+#[cfg(feature = "std")]
+pub use car::*;
+pub use checked_link::*;
+pub use child::*;
+pub use codec::*;
+#[cfg(feature = "std")]
+pub use compress::*;
+pub use content_eq::*;
+pub use context::*;
+pub use dag_pb::*;
+pub use error::*;
+#[cfg(any(all(test, feature = "std"), feature = "testing"))]
+pub use faulty::*;
+pub use flush::*;
+#[cfg(feature = "std")]
+pub use hash_registry::*;
+pub use intern::*;
 pub use link::*;
+pub use link_iter::*;
+pub use links::*;
 pub use maybe_link::*;
+#[cfg(feature = "std")]
+pub use memory::*;
+#[cfg(feature = "std")]
+pub use negative_cache::*;
+pub use raw_link::*;
+pub use read_only::*;
+pub use refs::*;
+#[cfg(feature = "std")]
+pub use replicated::*;
+#[cfg(feature = "std")]
+pub use retry::*;
+pub use save::*;
+#[cfg(feature = "std")]
+pub use self_describing::*;
+#[cfg(feature = "std")]
+pub use sharded::*;
+pub use shared_link::*;
+#[cfg(any(all(test, feature = "std"), feature = "testing"))]
+pub use sim::*;
+#[cfg(feature = "std")]
+pub use stats::*;
 pub use store::*;
+#[cfg(feature = "std")]
+pub use sync_link::*;
+pub use value::*;
+pub use walk::*;
+
+#[cfg(any(all(test, feature = "std"), feature = "testing"))]
+pub mod testing;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use cid::Cid;
+}
@@ -1,50 +1,108 @@
-use cid::Cid;
-use serde::{de::DeserializeOwned, ser::Error, Deserialize, Deserializer, Serialize, Serializer};
-use std::{
-    cell::{Cell, OnceCell},
+use core::{
+    cell::{Cell, OnceCell, RefCell},
+    fmt,
+    hash::{Hash, Hasher},
     marker::PhantomData,
+    mem::ManuallyDrop,
     ops::{Deref, DerefMut},
 };
 
-use crate::{CidShape, MagicStore, StaticStore};
+use alloc::{borrow::Cow, string::String, sync::Arc, vec::Vec};
+
+use cid::{multibase::Base, Cid, Version};
+use serde::{
+    de::{DeserializeOwned, DeserializeSeed},
+    ser::Error,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+    async_store, error, store, Ambient, AsyncStore, AutoLink, CidShape, Flush, MagicStore,
+    MaybeLink, StaticStore, StoreError,
+};
 
 /// An IPLD link that loads data via the specified type-level [`Store`], caches the value, and
-/// writes it back on [`Link::save`].
+/// writes it back on [`Link::save`]. Without a store parameter (`Link<T>`), it uses the thread's
+/// [`Ambient`] store, set with [`with_store`](crate::with_store).
 ///
-/// ```
-/// use auto_ipld::{Link, MagicStore, StaticStore};
-/// use serde::{Deserialize, de::DeserializeOwned};
+#[cfg_attr(feature = "std", doc = "```")]
+#[cfg_attr(not(feature = "std"), doc = "```ignore")]
+/// use auto_ipld::{Link, LinkedNode, MemoryStore, StaticStore};
+/// use serde::{Deserialize, Serialize, de::DeserializeOwned};
 ///
-/// #[derive(Deserialize)]
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))]
 /// pub struct Node<T, Store> where Store: StaticStore {
 ///     pub value: T,
 ///     pub next: Option<Link<Box<Node<T, Store>>, Store>>,
 /// }
 ///
+/// // Walk the list with a `LinkIter`, which loads each node when it's reached.
+/// impl<T, Store: StaticStore> LinkedNode for Node<T, Store> {
+///     type Value = T;
+///     type Store = Store;
+///
+///     fn value(&self) -> &T {
+///         &self.value
+///     }
+///
+///     fn next(&self) -> Option<&Link<Box<Self>, Store>> {
+///         self.next.as_ref()
+///     }
+/// }
+///
 /// impl<T, Store> Node<T, Store>
 /// where
-///     Store: MagicStore,
+///     Store: StaticStore,
 ///     Self: DeserializeOwned,
 /// {
-///     pub fn find(&self, mut cond: impl FnMut(&T) -> bool) -> Option<&T>
-///     where
-///         T: DeserializeOwned,
-///     {
-///         if cond(&self.value) {
-///             return Some(&self.value)
-///         } else if let Some(next) = &self.next {
-///             // obviously a horrible idea, bit it's a demo!
-///             next.find(cond)
-///         } else {
-///             None
+///     pub fn find(&self, mut cond: impl FnMut(&T) -> bool) -> Result<Option<&T>, Store::Error> {
+///         for value in self.iter() {
+///             let value = value?;
+///             if cond(value) {
+///                 return Ok(Some(value));
+///             }
 ///         }
+///         Ok(None)
 ///     }
 /// }
+///
+/// // Save a list, then search it from its root CID.
+/// type List = Link<Box<Node<u32, MemoryStore>>, MemoryStore>;
+/// let head = (0..=3).rev().fold(None, |next, value| {
+///     Some(List::from_value(Box::new(Node { value, next }), None))
+/// });
+/// let head = List::new(head.unwrap().save().unwrap());
+/// assert_eq!(head.read().unwrap().find(|v| *v > 1).unwrap(), Some(&2));
 ///```
+///
+/// Links can be used as map values (e.g., `BTreeMap<String, Link<T, Store>>`): each entry loads
+/// independently, only when it's read. The store's encoder determines the key order, so a
+/// canonical encoder (like DAG-CBOR's, which sorts keys) gives a `HashMap` of links a stable CID.
+///
+/// Cloning a `Link` deep-copies any cached value. To share a cached value between clones instead,
+/// convert the link into a `Link<Arc<T>, Store>` with [`Link::into_shared`] and use
+/// [`Link::clone_shared`]. For clones that also share values loaded after cloning, use a
+/// [`SharedLink`](crate::SharedLink).
+///
+/// A `Link<T, Store>` is `Send` whenever `T` is, whatever the store (which is only a type-level
+/// marker), so link-backed structures can be moved to other threads or held across an `.await` in
+/// a `Send` future. It's never `Sync`: reading or saving through a `&Link` updates its cache and
+/// state without synchronization, so a link can't be shared between threads (e.g., in an `Arc`)
+/// even if it's only read. Use a [`SyncLink`](crate::SyncLink) for that.
 #[derive(Clone)]
-pub struct Link<T, Store> {
+pub struct Link<T, Store = Ambient> {
     value: OnceCell<T>,
     state: Cell<LinkState>,
+    /// The block a save encoded but failed to store, kept for the next save until the value (or
+    /// its shape) changes.
+    encoded: RefCell<Option<Vec<u8>>>,
+    /// The CID the link pointed to before it was last edited, so saving an edit that was reverted
+    /// can skip the write (see [`StaticStore::SKIP_UNCHANGED_WRITES`]).
+    prior: Option<Cid>,
+    output_shape: Option<CidShape>,
+    #[cfg(debug_assertions)]
+    label: Option<&'static str>,
     _marker: PhantomData<fn(Store)>,
 }
 
@@ -63,6 +121,112 @@ impl LinkState {
     }
 }
 
+/// An error from [`Link::save_to`].
+#[derive(Debug)]
+pub enum CopyError<S, D> {
+    /// The value couldn't be loaded from the link's own store.
+    Source(S),
+    /// The value couldn't be stored in the destination.
+    Destination(D),
+}
+
+impl<S: fmt::Display, D: fmt::Display> fmt::Display for CopyError<S, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CopyError::Source(e) => write!(f, "failed to load the value to copy: {e}"),
+            CopyError::Destination(e) => write!(f, "failed to store the copy: {e}"),
+        }
+    }
+}
+
+impl<S, D> core::error::Error for CopyError<S, D>
+where
+    S: core::error::Error + 'static,
+    D: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            CopyError::Source(e) => Some(e),
+            CopyError::Destination(e) => Some(e),
+        }
+    }
+}
+
+/// An error from [`Link::try_clone`].
+#[derive(Debug)]
+pub enum CloneCostError<E> {
+    /// The cached value is too large to copy.
+    TooLarge {
+        encoded_len: usize,
+        max_bytes: usize,
+    },
+    /// The cached value couldn't be encoded to measure it.
+    Store(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CloneCostError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloneCostError::TooLarge {
+                encoded_len,
+                max_bytes,
+            } => write!(
+                f,
+                "cloning would copy a {encoded_len} byte value (limit {max_bytes})"
+            ),
+            CloneCostError::Store(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for CloneCostError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            CloneCostError::TooLarge { .. } => None,
+            CloneCostError::Store(e) => Some(e),
+        }
+    }
+}
+
+/// Shows the link's CID (or that it's modified) and whether its value is cached, without loading
+/// anything. The value itself isn't shown, so `T` needn't implement `Debug`.
+impl<T, Store> fmt::Debug for Link<T, Store> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("Link");
+        match self.state.get() {
+            LinkState::Unmodified(k) => s.field("cid", &k),
+            LinkState::Modified(_) => s.field("modified", &true),
+        };
+        s.field("loaded", &self.value.get().is_some()).finish()
+    }
+}
+
+/// Links are equal if both are unmodified and point at the same CID. A modified link isn't equal to
+/// anything (including itself), as it has no CID until it's saved; use [`Link::content_eq`] to
+/// compare modified links.
+impl<T, Store> PartialEq for Link<T, Store> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.state.get(), other.state.get()) {
+            (LinkState::Unmodified(a), LinkState::Unmodified(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Equality is only reflexive for unmodified links, which is enough to use links as `HashSet` or
+/// `HashMap` keys (e.g., to deduplicate references while walking a DAG). Putting a modified link in
+/// such a collection is a logic error: it doesn't equal itself, so it can't be found again (and
+/// saving it changes its hash).
+impl<T, Store> Eq for Link<T, Store> {}
+
+/// Hashes the CID of an unmodified link. Modified links all hash the same, as they have no identity
+/// until they're saved.
+impl<T, Store> Hash for Link<T, Store> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cid().hash(state)
+    }
+}
+
 impl<T, Store> Serialize for Link<T, Store>
 where
     T: Serialize,
@@ -72,8 +236,12 @@ where
     where
         S: Serializer,
     {
-        let k = self.save().map_err(S::Error::custom)?;
-        Serialize::serialize(&k, serializer)
+        let k = self.save().map_err(|e| {
+            let msg = S::Error::custom(&e);
+            error::stash_error(e);
+            msg
+        })?;
+        link_as_cid::serialize(&k, serializer)
     }
 }
 
@@ -86,10 +254,12 @@ where
     where
         D: Deserializer<'de>,
     {
-        Ok(Self::new(Cid::deserialize(deserializer)?))
+        Ok(Self::new(link_as_cid::deserialize(deserializer)?))
     }
 }
 
+/// Wraps a value as a modified link. There's no `From<Cid>`, as it would overlap this impl for
+/// `Link<Cid, _>`: build a link to a CID with [`Link::new`] (or from a [`MaybeLink::Link`]).
 impl<T, Store> From<T> for Link<T, Store>
 where
     Store: StaticStore,
@@ -99,6 +269,77 @@ where
     }
 }
 
+/// A value becomes a modified link, stored on its next save, and a link points at its CID without
+/// loading anything.
+impl<T, Store> From<MaybeLink<T>> for Link<T, Store> {
+    fn from(v: MaybeLink<T>) -> Self {
+        match v {
+            MaybeLink::Value(v) => Self::from_value(v, None),
+            MaybeLink::Link(k) => Self::new(k),
+        }
+    }
+}
+
+/// A saved link becomes a [`MaybeLink::Link`] to its CID (dropping any cached value), and a
+/// modified link becomes a [`MaybeLink::Value`] holding its unsaved value. Neither touches the
+/// store, so this can't fail: a modified link always has its value.
+impl<T, Store> From<Link<T, Store>> for MaybeLink<T> {
+    fn from(link: Link<T, Store>) -> Self {
+        match link.state.get() {
+            LinkState::Unmodified(k) => MaybeLink::Link(k),
+            LinkState::Modified(_) => {
+                MaybeLink::Value(link.into_inner().expect("modified link has no value"))
+            }
+        }
+    }
+}
+
+/// Fails with [`StoreError::UnsavedChanges`] if the link has been modified and not yet saved.
+impl<T, Store> TryFrom<&Link<T, Store>> for Cid {
+    type Error = StoreError;
+
+    fn try_from(link: &Link<T, Store>) -> Result<Self, Self::Error> {
+        link.cid().ok_or(StoreError::UnsavedChanges)
+    }
+}
+
+/// Where a link's value currently lives, as reported by [`Link::status`] and
+/// [`AutoLink::status`]. Querying it never loads or saves anything; whether the value is cached is
+/// a separate question (see [`Link::is_loaded`]).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LinkStatus {
+    /// The value is stored in the block with this CID (which may be an identity CID), and hasn't
+    /// been modified since it was loaded or saved.
+    Stored(Cid),
+    /// The value has been created or modified and not yet saved.
+    Modified,
+    /// The value was embedded in its parent (by an [`AutoLink`]) when it was last saved.
+    Inlined,
+}
+
+/// Anything that may point at a stored block, so generic code can get CIDs out of every kind of
+/// link (and out of bare CIDs) the same way.
+pub trait AsCid {
+    /// The CID of the block this points at, or `None` if there's no such block (yet), e.g., for
+    /// a modified link or an inline value.
+    fn as_cid(&self) -> Option<Cid>;
+}
+
+impl AsCid for Cid {
+    fn as_cid(&self) -> Option<Cid> {
+        Some(*self)
+    }
+}
+
+/// See [`Link::cid`].
+impl<T, Store> AsCid for Link<T, Store> {
+    fn as_cid(&self) -> Option<Cid> {
+        self.cid()
+    }
+}
+
+/// Dereferencing loads the value, panicking (via [`MagicStore::unwrap`]) if that fails. Use
+/// [`Link::get`] to handle errors instead.
 impl<T, Store> Deref for Link<T, Store>
 where
     T: DeserializeOwned,
@@ -106,28 +347,98 @@ where
 {
     type Target = T;
 
+    #[track_caller]
     fn deref(&self) -> &Self::Target {
         Store::unwrap(self.read())
     }
 }
 
+/// Like `Deref`, and marks the value as modified. Use [`Link::get_mut`] to handle errors
+/// instead.
 impl<T, Store> DerefMut for Link<T, Store>
 where
     T: DeserializeOwned + Serialize,
     Store: MagicStore,
 {
+    #[track_caller]
     fn deref_mut(&mut self) -> &mut Self::Target {
         Store::unwrap(self.edit())
     }
 }
 
+/// A mutable borrow of a [`Link`]'s value that saves the link when it's dropped; see
+/// [`Link::edit_guard`].
+///
+/// Dropping the guard can't report errors, so if the save fails, the error is discarded. The edit
+/// isn't lost, though: the link stays modified, so it's saved by its next save (or when its parent
+/// is serialized). Call [`EditGuard::commit`] instead to find out whether the save succeeded.
+#[must_use = "dropping the guard saves the link immediately"]
+pub struct EditGuard<'a, T, Store>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    link: &'a mut Link<T, Store>,
+}
+
+impl<T, Store> EditGuard<'_, T, Store>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    /// Save the link now, returning its CID or the store's error (where dropping the guard would
+    /// discard it).
+    pub fn commit(self) -> Result<Cid, Store::Error> {
+        let guard = ManuallyDrop::new(self);
+        guard.link.save()
+    }
+}
+
+impl<T, Store> Deref for EditGuard<'_, T, Store>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.link.value.get().expect("edited link has no value")
+    }
+}
+
+impl<T, Store> DerefMut for EditGuard<'_, T, Store>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        self.link.value.get_mut().expect("edited link has no value")
+    }
+}
+
+impl<T, Store> Drop for EditGuard<'_, T, Store>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    fn drop(&mut self) {
+        // On failure, the link stays modified (see above).
+        let _ = self.link.save();
+    }
+}
+
 impl<T, Store> Link<T, Store> {
     /// Construct a new `Link` from a `Cid`.
     #[must_use]
     pub const fn new(k: Cid) -> Self {
         Self {
             state: Cell::new(LinkState::Unmodified(k)),
+            encoded: RefCell::new(None),
+            prior: None,
             value: OnceCell::new(),
+            output_shape: None,
+            #[cfg(debug_assertions)]
+            label: None,
             _marker: PhantomData,
         }
     }
@@ -137,113 +448,2854 @@ impl<T, Store> Link<T, Store> {
     pub fn from_value(v: T, shape: Option<CidShape>) -> Self {
         Self {
             state: Cell::new(LinkState::Modified(shape)),
+            encoded: RefCell::new(None),
+            prior: None,
             value: OnceCell::from(v),
+            output_shape: None,
+            #[cfg(debug_assertions)]
+            label: None,
             _marker: PhantomData,
         }
     }
 
+    /// Construct an unmodified `Link` to `k` whose value is already loaded, e.g., when importing
+    /// blocks that were decoded elsewhere. Reading it doesn't touch the store, and saving it
+    /// returns `k` without storing anything. This is the fast path for bulk imports (e.g., from a
+    /// CAR file whose blocks were verified as they were read): nothing is loaded, hashed, or
+    /// decoded again.
+    ///
+    /// The value isn't checked against `k`: it's up to the caller to pass the value `k` decodes to.
+    /// If it doesn't, the link silently reads (and its parents encode) a value that isn't the
+    /// block's, and the mismatch is only caught once the cache is dropped (e.g., by
+    /// [`Link::reload`] or [`Link::free`]) and the block is loaded, and verified, from the store.
+    #[must_use]
+    pub fn loaded(k: Cid, value: T) -> Self {
+        Self::from_cached(k, Some(value))
+    }
+
+    /// Construct an unmodified `Link` to `k`, decoding its value from `r`, which reads the block's
+    /// bytes (e.g., from a socket), so importing it needs neither a load nor a copy of the block.
+    /// Reading the link afterwards doesn't touch the store.
+    ///
+    /// Unless [`StaticStore::VERIFY`] is false, the bytes are checked against `k` as they're read.
+    /// DAG-CBOR blocks with a SHA2 or BLAKE3 multihash are decoded as they stream in (with
+    /// [`StaticStore::decode_from`]), skipping [`StaticStore::pre_decode`]; other blocks are read
+    /// into a buffer first and decoded as [`StaticStore::load`] would. Blocks larger than
+    /// [`StaticStore::MAX_BLOCK_SIZE`] are rejected without reading the rest of them.
+    #[cfg(feature = "std")]
+    pub fn from_reader(k: Cid, r: impl std::io::Read) -> Result<Self, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        let value =
+            store::decode_block_from::<Store, T>(&k, r).map_err(|e| Store::with_cid(e, k))?;
+        Ok(Self::loaded(k, value))
+    }
+
+    /// A link to `k` with an already-loaded value (if any).
+    pub(crate) fn from_cached(k: Cid, value: Option<T>) -> Self {
+        Self {
+            value: value.map_or_else(OnceCell::new, OnceCell::from),
+            ..Self::new(k)
+        }
+    }
+
+    /// Convert this link into an [`AutoLink`], which inlines the value into its parent when it
+    /// encodes to at most `S` bytes.
+    ///
+    /// - A link with a cached value (whether loaded or modified) becomes a modified `AutoLink`, so
+    ///   the next save decides whether to inline the value. If it's too large, it's stored with the
+    ///   link's shape hint (or its CID's shape, if it was loaded).
+    /// - A link whose value isn't loaded keeps pointing at its CID, and nothing is loaded.
+    #[must_use]
+    pub fn into_auto<const S: usize>(self) -> AutoLink<T, Store, S> {
+        match (self.state.get(), self.value.into_inner()) {
+            (LinkState::Unmodified(k), Some(v)) => {
+                AutoLink::from_value_with_shape(v, Some(CidShape::from(&k)))
+            }
+            (LinkState::Modified(shape), Some(v)) => AutoLink::from_value_with_shape(v, shape),
+            (LinkState::Unmodified(k), None) => AutoLink::from_cid(k),
+            (LinkState::Modified(_), None) => unreachable!("modified link has no value"),
+        }
+    }
+
+    /// The CID of the stored block this link points to, or `None` if the link has been modified
+    /// and not yet saved. Unlike [`Link::save`], this never writes anything.
+    pub fn cid(&self) -> Option<Cid> {
+        match self.state.get() {
+            LinkState::Unmodified(k) => Some(k),
+            LinkState::Modified(_) => None,
+        }
+    }
+
+    /// The link's CID in the given multibase (e.g., for logs or text formats), or `None` if the
+    /// link is modified. This never saves the link. CIDv0s can only be written in base58btc, so
+    /// in other bases they're written as the equivalent CIDv1.
+    pub fn cid_string(&self, base: Base) -> Option<String> {
+        self.cid().map(|k| cid_string(k, base))
+    }
+
+    /// Whether the link points at a stored block or has unsaved changes (a `Link` is never
+    /// [`LinkStatus::Inlined`]). [`Link::cid`] and [`Link::is_dirty`] are shorthands for this.
+    pub fn status(&self) -> LinkStatus {
+        match self.state.get() {
+            LinkState::Unmodified(k) => LinkStatus::Stored(k),
+            LinkState::Modified(_) => LinkStatus::Modified,
+        }
+    }
+
+    /// Returns true if the link is unmodified and points at `k`, without loading or saving
+    /// anything: a modified link never matches, even if saving it would produce `k`.
+    pub fn points_to(&self, k: &Cid) -> bool {
+        self.cid().as_ref() == Some(k)
+    }
+
+    /// Returns true if the link has been modified and not yet saved.
+    pub fn is_dirty(&self) -> bool {
+        matches!(self.state.get(), LinkState::Modified(_))
+    }
+
+    /// Returns true if the link's value is cached.
+    pub fn is_loaded(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    /// Attach a debug label (e.g., the link's path in the data model) to this link. Store errors
+    /// surfaced by [`Link::read`], [`Link::edit`], and [`Link::save`] are passed through
+    /// [`StaticStore::with_label`] with this label.
+    ///
+    /// Labels are only recorded in debug builds; in release builds this is a no-op.
+    #[must_use]
+    pub fn with_label(self, label: &'static str) -> Self {
+        #[cfg(debug_assertions)]
+        return Self {
+            label: Some(label),
+            ..self
+        };
+        #[cfg(not(debug_assertions))]
+        {
+            let _ = label;
+            self
+        }
+    }
+
+    /// Save this link with the given codec and multihash instead of those of the block it was
+    /// loaded from, e.g., to migrate data from one codec to another as it's accessed.
+    ///
+    /// This changes the link's CID. A link that's already modified or loaded is re-saved with
+    /// `shape` on the next [`Link::save`]; otherwise, it's converted once it's loaded, and a link
+    /// that's never loaded keeps its original CID. The value is still encoded with
+    /// [`StaticStore::encode`], so the store must support the requested codec.
+    #[must_use]
+    pub fn with_output_shape(mut self, shape: CidShape) -> Self {
+        self.set_shape(shape);
+        self
+    }
+
+    /// Like [`Link::with_output_shape`], but in place.
+    pub fn set_shape(&mut self, shape: CidShape) {
+        self.output_shape = Some(shape);
+        self.encoded = RefCell::new(None);
+        match self.state.get() {
+            LinkState::Modified(_) => self.state.set(LinkState::Modified(Some(shape))),
+            LinkState::Unmodified(k) if self.value.get().is_some() => self.convert(&k),
+            LinkState::Unmodified(_) => (),
+        }
+    }
+
+    /// The shape of the block this link points to, or, if it's modified, the shape it will be
+    /// saved with (`None` if it has no shape hint, in which case the store picks the shape).
+    pub fn shape(&self) -> Option<CidShape> {
+        match self.state.get() {
+            LinkState::Unmodified(k) => Some(CidShape::from(&k)),
+            LinkState::Modified(shape) => shape,
+        }
+    }
+
+    /// Mark a just-loaded link as modified if it must be re-saved with a different shape.
+    fn convert(&self, k: &Cid) {
+        if let Some(shape) = self.output_shape {
+            if shape != CidShape::from(k) {
+                self.state.set(LinkState::Modified(Some(shape)));
+            }
+        }
+    }
+
+    fn annotate(&self, err: Store::Error) -> Store::Error
+    where
+        Store: StaticStore,
+    {
+        #[cfg(debug_assertions)]
+        if let Some(label) = self.label {
+            return Store::with_label(err, label);
+        }
+        err
+    }
+
+    /// Load and decode the value at `k`, rejecting it if `check` fails.
+    fn load_checked(
+        &self,
+        k: &Cid,
+        check: impl FnOnce(&T) -> Result<(), String>,
+    ) -> Result<T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        let val = Store::load(k).map_err(|e| self.annotate(e))?;
+        check(&val).map_err(|reason| {
+            self.annotate(StoreError::ValidationFailed { cid: *k, reason }.into())
+        })?;
+        Ok(val)
+    }
+
+    /// Decode `bytes`, loaded for the link's CID `k`, into the cache (unless it's already filled).
+    fn fill(&self, k: &Cid, bytes: &[u8]) -> Result<(), Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        if self.value.get().is_some() {
+            return Ok(());
+        }
+        let decoded = store::check_block_size(k, bytes.len(), Store::MAX_BLOCK_SIZE)
+            .map_err(Store::Error::from)
+            .and_then(|()| store::decode_block::<Store, T>(k, bytes))
+            .map_err(|e| self.annotate(Store::with_cid(e, *k)))?;
+        self.value.get_or_init(|| decoded);
+        self.convert(k);
+        Ok(())
+    }
+
     /// Read the linked object. This will automatically load and decode the underlying data if
-    /// it isn't cached.
+    /// it isn't cached. Blocks larger than [`StaticStore::MAX_BLOCK_SIZE`] are rejected with
+    /// [`StoreError::BlockTooLarge`].
     pub fn read(&self) -> Result<&T, Store::Error>
     where
         T: DeserializeOwned,
         Store: StaticStore,
     {
-        // TODO: get_or_try_init
-        if let Some(val) = self.value.get() {
-            return Ok(val);
-        }
-        let val = Store::load(&self.state.get().unwrap_unmodified())?;
-        Ok(self.value.get_or_init(|| val))
+        self.read_checked(|_| Ok(()))
     }
 
-    /// Edit the linked object. Like [`Link::read`], this will automatically load and decode the
-    /// object. Additionally, it will mark it as "modified" ensuring: the modified value will be
-    /// persisted when this object is next serialized or `Link::save` is called.
-    pub fn edit(&mut self) -> Result<&mut T, Store::Error>
+    /// Load the linked block and decode it with `seed` (see [`StaticStore::load_seed`]), for
+    /// values that need runtime context to decode. This always loads the block: the value cache
+    /// holds a `T`, not whatever `seed` produces, so it's neither consulted nor filled. Fails
+    /// with [`StoreError::UnsavedChanges`] if the link is modified; save it first.
+    pub fn read_seed<'de, S>(&self, seed: S) -> Result<S::Value, Store::Error>
     where
-        T: DeserializeOwned + Serialize,
+        S: DeserializeSeed<'de>,
         Store: StaticStore,
     {
-        if let LinkState::Unmodified(k) = self.state.get() {
-            if self.value.get().is_none() {
-                self.value = OnceCell::from(Store::load::<T>(&k)?);
+        let res = match self.state.get() {
+            LinkState::Unmodified(k) => Store::load_seed(&k, seed),
+            LinkState::Modified(_) => Err(StoreError::UnsavedChanges.into()),
+        };
+        res.map_err(|e| self.annotate(e))
+    }
+
+    /// The link's block as it's stored (for an unmodified link), or as it would be stored by the
+    /// next save (for a modified link), e.g., to inspect or assert the exact wire format. The
+    /// value cache isn't touched: an unmodified block is loaded (unverified) even if its value is
+    /// cached, and a modified value is encoded without being saved.
+    ///
+    /// Encoding a modified value saves any modified links inside it, as serializing would.
+    pub fn read_bytes(&self) -> Result<Vec<u8>, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let shape = match self.state.get() {
+            LinkState::Unmodified(k) => {
+                return Store::load_block(&k)
+                    .map(Cow::into_owned)
+                    .map_err(|e| self.annotate(e));
             }
-            self.state = Cell::new(LinkState::Modified(Some(CidShape::from(&k))));
+            LinkState::Modified(shape) => shape,
+        };
+        if let Some(bytes) = &*self.encoded.borrow() {
+            return Ok(bytes.clone());
         }
-        Ok(self.value.get_mut().expect("expected value"))
+        let value = self.value.get().expect("modified link has no value");
+        store::encode::<Store, _>(value, shape.as_ref()).map_err(|e| self.annotate(e))
     }
 
-    /// Write-back the value if modified, and return the CID. Links are automatically "saved" when
-    /// serialized, so you only need to call this to store the root object.
-    pub fn save(&self) -> Result<Cid, Store::Error>
+    /// The length of [`Link::read_bytes`], without copying the block where possible: an unmodified
+    /// link asks the store ([`StaticStore::block_size`]), and a modified value is measured with
+    /// [`StaticStore::encoded_len`], e.g., to decide whether to externalize it. Modified values
+    /// with a [`CidShape`] in another codec than the store's default are encoded in that codec,
+    /// and measured by encoding them.
+    ///
+    /// Like `read_bytes`, measuring a modified value saves any modified links inside it.
+    pub fn encoded_size(&self) -> Result<usize, Store::Error>
     where
         T: Serialize,
         Store: StaticStore,
     {
         let shape = match self.state.get() {
-            LinkState::Unmodified(k) => return Ok(k),
+            LinkState::Unmodified(k) => {
+                return Store::describe(&k)
+                    .map(|(_, len)| len)
+                    .map_err(|e| self.annotate(e));
+            }
             LinkState::Modified(shape) => shape,
         };
+        if let Some(bytes) = &*self.encoded.borrow() {
+            return Ok(bytes.len());
+        }
+        let value = self.value.get().expect("modified link has no value");
+        store::encoded_len::<Store, _>(value, shape.as_ref()).map_err(|e| self.annotate(e))
+    }
 
-        let k = Store::store(
-            self.value.get().expect("modified link has no value"),
-            shape.as_ref(),
-        )?;
-        self.state.set(LinkState::Unmodified(k));
-        Ok(k)
+    /// Like [`Link::read`], but runs `check` on the value when it's loaded (not when it's
+    /// already cached).
+    pub(crate) fn read_checked(
+        &self,
+        check: impl FnOnce(&T) -> Result<(), String>,
+    ) -> Result<&T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        // TODO: get_or_try_init
+        if let Some(val) = self.value.get() {
+            return Ok(val);
+        }
+        let k = self.state.get().unwrap_unmodified();
+        let val = self.load_checked(&k, check)?;
+        let val = self.value.get_or_init(|| val);
+        self.convert(&k);
+        Ok(val)
     }
 
-    /// Write-back the value if modified, return the CID, and drop any cached values.
-    pub fn free(&mut self) -> Result<Cid, Store::Error>
+    /// Like [`Link::read`]; the fallible counterpart of dereferencing the link.
+    pub fn get(&self) -> Result<&T, Store::Error>
     where
-        T: Serialize,
+        T: DeserializeOwned,
         Store: StaticStore,
     {
-        let k = self.save()?;
-        self.value = OnceCell::new();
-        Ok(k)
+        self.read()
     }
-}
 
-#[cfg(test)]
-mod test {
-    use std::marker::PhantomData;
+    /// Like [`Link::edit`]; the fallible counterpart of mutably dereferencing the link.
+    pub fn get_mut(&mut self) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+    {
+        self.edit()
+    }
 
-    use cid::Cid;
-    use serde::{Deserialize, Serialize};
+    /// The cached value, or `None` if it hasn't been loaded. This never loads anything, and
+    /// doesn't mark the link as modified.
+    pub fn get_if_loaded(&self) -> Option<&T> {
+        self.value.get()
+    }
 
-    use crate::{Link, MagicStore, StaticStore};
+    /// Like [`Link::edit`], but only if the value is already cached: returns `None` instead of
+    /// loading it, so mutating resident values needs no I/O and can't fail (or panic, as
+    /// `DerefMut` can).
+    ///
+    /// Returning `Some` marks the link as modified, whether or not the value is then changed: to
+    /// mutate only under some condition, check it with [`Link::get_if_loaded`] first.
+    pub fn get_mut_if_loaded(&mut self) -> Option<&mut T> {
+        self.value.get()?;
+        if let LinkState::Unmodified(k) = self.state.get() {
+            let shape = self.output_shape.unwrap_or_else(|| CidShape::from(&k));
+            self.state = Cell::new(LinkState::Modified(Some(shape)));
+            self.prior = Some(k);
+        }
+        self.encoded = RefCell::new(None);
+        self.value.get_mut()
+    }
 
-    // TODO Having the store here is _really_ annoying. We might just want to remove it entirely.
+    /// Edit the linked object. Like [`Link::read`], this will automatically load and decode the
+    /// object. Additionally, it will mark it as "modified" ensuring: the modified value will be
+    /// persisted when this object is next serialized or `Link::save` is called.
+    pub fn edit(&mut self) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+    {
+        self.edit_checked(|_| Ok(()))
+    }
 
-    #[derive(Deserialize, Serialize)]
-    #[serde(bound = "")] // ugh. The generic parameter really needs to go.
+    /// Edit the linked object in `f`, returning its result. This loads the value and marks the
+    /// link as modified exactly like [`Link::edit`], so edits to nested links keep store errors
+    /// explicit instead of panicking in `DerefMut` (which needs a [`MagicStore`]):
+    ///
+    #[cfg_attr(feature = "std", doc = "```")]
+    #[cfg_attr(not(feature = "std"), doc = "```ignore")]
+    /// # use auto_ipld::{Link, MemoryStore, StaticStore};
+    /// # use serde::{Deserialize, Serialize};
+    /// # type Store = MemoryStore<()>;
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Outer {
+    ///     inner: Link<String, Store>,
+    /// }
+    ///
+    /// # fn main() -> Result<(), auto_ipld::MemoryError> {
+    /// # let inner = Store::store(&"before", None)?;
+    /// # let k = Store::store(&Outer { inner: Link::new(inner) }, None)?;
+    /// let mut outer: Link<Outer, Store> = Link::new(k);
+    /// let old = outer.edit_with(|o| o.inner.edit_with(|i| std::mem::replace(i, "after".into())))??;
+    /// assert_eq!(old, "before");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn edit_with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> Result<R, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+    {
+        Ok(f(self.edit()?))
+    }
+
+    /// Edit the linked object like [`Link::edit`], or, if its block isn't in the store (see
+    /// [`StaticStore::is_not_found`]), insert `f()` as the modified value instead, like
+    /// `HashMap::entry(k).or_insert_with(f)`. This is handy for slots of a graph that are
+    /// initialized lazily. Any other load error is returned as is.
+    pub fn read_or_insert_with(&mut self, f: impl FnOnce() -> T) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+    {
+        if let LinkState::Unmodified(k) = self.state.get() {
+            if self.value.get().is_none() {
+                match self.load_checked(&k, |_| Ok(())) {
+                    Ok(val) => self.value = OnceCell::from(val),
+                    Err(e) if Store::is_not_found(&e) => {
+                        self.value = OnceCell::from(f());
+                        let shape = self.output_shape.unwrap_or_else(|| CidShape::from(&k));
+                        self.state = Cell::new(LinkState::Modified(Some(shape)));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        self.edit()
+    }
+
+    /// Edit the linked object through a guard that saves the link when it's dropped, so edits
+    /// can't be left unsaved by mistake. Dropping the guard discards save errors (leaving the link
+    /// modified); use [`EditGuard::commit`] to handle them.
+    pub fn edit_guard(&mut self) -> Result<EditGuard<'_, T, Store>, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+    {
+        self.edit()?;
+        Ok(EditGuard { link: self })
+    }
+
+    /// Like [`Link::edit`], but runs `check` on the value when it's loaded (not when it's
+    /// already cached).
+    pub(crate) fn edit_checked(
+        &mut self,
+        check: impl FnOnce(&T) -> Result<(), String>,
+    ) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+    {
+        if let LinkState::Unmodified(k) = self.state.get() {
+            if self.value.get().is_none() {
+                let val = self.load_checked(&k, check)?;
+                self.value = OnceCell::from(val);
+            }
+            let shape = self.output_shape.unwrap_or_else(|| CidShape::from(&k));
+            self.state = Cell::new(LinkState::Modified(Some(shape)));
+            self.prior = Some(k);
+        }
+        self.encoded = RefCell::new(None);
+        Ok(self.value.get_mut().expect("expected value"))
+    }
+
+    /// Write-back the value if modified, and return the CID. Links are automatically "saved" when
+    /// serialized, so you only need to call this to store the root object.
+    ///
+    /// Each edit is written at most once: the link is marked clean as soon as its block is stored,
+    /// so saving or serializing it again reuses the CID until the next edit. Each edit is encoded
+    /// at most once, too: if the block can't be stored, the encoded block is kept and the next
+    /// save retries with it, until the value is edited again. An edit that leaves the block as it
+    /// was (e.g., one that was reverted) isn't written at all, unless the store turns off
+    /// [`StaticStore::SKIP_UNCHANGED_WRITES`].
+    pub fn save(&self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let shape = match self.state.get() {
+            LinkState::Unmodified(k) => return Ok(k),
+            LinkState::Modified(shape) => shape,
+        };
+
+        let (k, saved) = self
+            .store_encoded(shape.as_ref(), |bytes| {
+                if let Some(k) = self.unchanged(bytes, shape.as_ref())? {
+                    return Ok((k, true));
+                }
+                store::store_link_block::<Store>(bytes, shape.as_ref())
+            })
+            .map_err(|e| self.annotate(e))?;
+        if saved {
+            self.state.set(LinkState::Unmodified(k));
+        }
+        Ok(k)
+    }
+
+    /// Like [`Link::save`], but also return the block, e.g., to export it or cache it without
+    /// loading it back. A modified link's block is the one just stored, from a single encode; an
+    /// unmodified link's is loaded (unverified), as with [`Link::read_bytes`].
+    pub fn save_with_bytes(&self) -> Result<(Cid, Vec<u8>), Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let shape = match self.state.get() {
+            LinkState::Unmodified(k) => return Ok((k, self.read_bytes()?)),
+            LinkState::Modified(shape) => shape,
+        };
+
+        let ((k, saved), bytes) = self
+            .store_encoded(shape.as_ref(), |bytes| {
+                let stored = store::store_link_block::<Store>(bytes, shape.as_ref())?;
+                Ok((stored, bytes.to_vec()))
+            })
+            .map_err(|e| self.annotate(e))?;
+        if saved {
+            self.state.set(LinkState::Unmodified(k));
+        }
+        Ok((k, bytes))
+    }
+
+    /// The CID the link pointed to before it was edited, if `bytes` (its block in `shape`) hashes
+    /// to it again and the store still has it, so the edit needn't be written. The store is asked
+    /// because it may not be the one the link was loaded from (e.g., an [`Ambient`] link saved in
+    /// another [`with_store`](crate::with_store) scope), or the block may have been removed.
+    fn unchanged(&self, bytes: &[u8], shape: Option<&CidShape>) -> Result<Option<Cid>, Store::Error>
+    where
+        Store: StaticStore,
+    {
+        match (self.prior, shape) {
+            (Some(prior), Some(shape))
+                if Store::SKIP_UNCHANGED_WRITES && CidShape::from(&prior) == *shape =>
+            {
+                let k = shape.cid(bytes)?;
+                Ok((k == prior && Store::has(&prior)?).then_some(k))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Encode the modified value in `shape` and pass the block to `write`, reusing the block a
+    /// failed write left behind. If `write` fails, the block is kept for the next attempt (unless
+    /// it was encoded while collecting a batch, as links inside it weren't marked as saved).
+    fn store_encoded<R>(
+        &self,
+        shape: Option<&CidShape>,
+        write: impl FnOnce(&[u8]) -> Result<R, Store::Error>,
+    ) -> Result<R, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let bytes = match self.encoded.take() {
+            Some(bytes) => bytes,
+            None => store::encode::<Store, _>(
+                self.value.get().expect("modified link has no value"),
+                shape,
+            )?,
+        };
+        let written = write(&bytes);
+        if written.is_err() && !store::collecting() {
+            self.encoded.replace(Some(bytes));
+        }
+        written
+    }
+
+    /// Store the value in `Dst` instead of the link's own store, loading it (through `Store`)
+    /// first if it isn't cached, and return its CID in `Dst`. The link itself is left as it is: a
+    /// modified link stays modified, and nothing is written to `Store`.
+    ///
+    /// The value is stored in the link's shape (its CID's, if it's unmodified), so with the same
+    /// encoder the copy has the same CID. Only this one block is copied, though: links inside the
+    /// value still point into `Store` (and modified ones are saved there as the value is
+    /// encoded). To copy a whole DAG, use [`walk`](crate::walk) to visit every block.
+    pub fn save_to<Dst>(&self) -> Result<Cid, CopyError<Store::Error, Dst::Error>>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: StaticStore,
+        Dst: StaticStore,
+    {
+        let shape = match self.state.get() {
+            LinkState::Unmodified(k) => Some(CidShape::from(&k)),
+            LinkState::Modified(shape) => shape,
+        };
+        let value = self.read().map_err(CopyError::Source)?;
+        Dst::store(value, shape.as_ref()).map_err(CopyError::Destination)
+    }
+
+    /// Returns true if both links point at the same content, saving them first if they're
+    /// modified. Equal values saved in the same shape have the same CID, so this compares CIDs.
+    pub fn content_eq(&self, other: &Self) -> Result<bool, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        Ok(self.save()? == other.save()?)
+    }
+
+    /// Save every modified link inside the value depth-first (see [`Flush`]), then the value
+    /// itself, and return the CID. Unlike [`Link::save`], which saves nested links as a side
+    /// effect of encoding the value, each block is written before its parent is encoded.
+    pub fn flush(&self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize + Flush<Store>,
+        Store: StaticStore,
+    {
+        if let (LinkState::Modified(_), Some(v)) = (self.state.get(), self.value.get()) {
+            let _guard = store::enter::<Store, _>(v)?;
+            v.flush_links()?;
+        }
+        self.save()
+    }
+
+    /// Like [`Link::save`], but writes the value and every modified link inside it with a single
+    /// [`StaticStore::store_many`] call, saving a round-trip per block on backends that batch
+    /// writes.
+    ///
+    /// The value is encoded twice (once to collect the blocks and once, after they've been
+    /// written, to mark the links as saved), and CIDs are computed locally as in
+    /// [`Link::save_if_absent`]. If the write fails, every link is left modified.
+    #[cfg(feature = "std")]
+    pub fn save_batched(&self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let shape = match self.state.get() {
+            LinkState::Unmodified(k) => return Ok(k),
+            LinkState::Modified(shape) => shape,
+        };
+
+        let k = store::store_batched::<Store, _>(
+            self.value.get().expect("modified link has no value"),
+            shape.as_ref(),
+        )
+        .map_err(|e| self.annotate(e))?;
+        self.state.set(LinkState::Unmodified(k));
+        Ok(k)
+    }
+
+    /// Like [`Link::save`], but skips the write if [`StaticStore::has`] reports that the store
+    /// already has the block, returning the CID and whether the block was written.
+    ///
+    /// The CID is computed locally from the link's shape (see [`CidShape::cid`]) to check for the
+    /// block, so this assumes the store derives CIDs from shapes in the usual way.
+    pub fn save_if_absent(&self) -> Result<(Cid, bool), Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let shape = match self.state.get() {
+            LinkState::Unmodified(k) => return Ok((k, false)),
+            LinkState::Modified(shape) => shape,
+        };
+
+        let written = self
+            .store_encoded(shape.as_ref(), |bytes| {
+                store::dedup::<Store>(bytes, shape.as_ref())
+            })
+            .map_err(|e| self.annotate(e))?;
+        self.state.set(LinkState::Unmodified(written.0));
+        Ok(written)
+    }
+
+    /// Like [`Link::save`], but skips the write if the store already has the block (see
+    /// [`StaticStore::store_deduped`]). The link's shape is applied before checking, so the CID
+    /// matches the one an earlier save of the same value produced.
+    pub fn save_deduped(&self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        self.save_if_absent().map(|(k, _)| k)
+    }
+
+    /// Like [`Link::read`], but loads through an [`AsyncStore`].
+    ///
+    /// If several reads of the same link are in flight, each loads the value, and the first to
+    /// finish fills the cache. Dropping the future before it completes leaves the link unchanged.
+    pub async fn read_async(&self) -> Result<&T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: AsyncStore,
+    {
+        if let Some(val) = self.value.get() {
+            return Ok(val);
+        }
+        let k = self.state.get().unwrap_unmodified();
+        let val = Store::load(&k).await?;
+        let val = self.value.get_or_init(|| val);
+        if let LinkState::Unmodified(k) = self.state.get() {
+            self.convert(&k);
+        }
+        Ok(val)
+    }
+
+    /// Like [`Link::edit`], but loads through an [`AsyncStore`]. The link is only marked as
+    /// modified once the value has loaded, so dropping the future leaves the link unchanged.
+    pub async fn edit_async(&mut self) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Serialize,
+        Store: AsyncStore,
+    {
+        if let LinkState::Unmodified(k) = self.state.get() {
+            if self.value.get().is_none() {
+                let val = Store::load::<T>(&k).await?;
+                self.value = OnceCell::from(val);
+            }
+            let shape = self.output_shape.unwrap_or_else(|| CidShape::from(&k));
+            self.state = Cell::new(LinkState::Modified(Some(shape)));
+            self.prior = Some(k);
+        }
+        self.encoded = RefCell::new(None);
+        Ok(self.value.get_mut().expect("expected value"))
+    }
+
+    /// Like [`Link::save`], but writes through an [`AsyncStore`]. The link stays modified until
+    /// the write completes, so a dropped save is simply retried by the next one.
+    ///
+    /// Only this link is written asynchronously: nested links are saved when the value is
+    /// encoded, which requires their store to implement [`StaticStore`].
+    pub async fn save_async(&self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: AsyncStore,
+    {
+        let shape = match self.state.get() {
+            LinkState::Unmodified(k) => return Ok(k),
+            LinkState::Modified(shape) => shape,
+        };
+
+        let value = self.value.get().expect("modified link has no value");
+        let encoded = async_store::encode_link::<Store, _>(value, shape.as_ref())?;
+        let k = async_store::store_link_block::<Store>(&encoded, shape.as_ref()).await?;
+        self.state.set(LinkState::Unmodified(k));
+        Ok(k)
+    }
+
+    /// Replace the linked value with `value`, marking the link as modified, and return the
+    /// previously cached value (if any). Unlike assigning through [`Link::edit`], this never loads
+    /// the old value. The new value is saved with the link's output shape (see
+    /// [`Link::with_output_shape`]), or the default shape.
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        let old = self.value.take();
+        self.value = OnceCell::from(value);
+        self.state.set(LinkState::Modified(self.output_shape));
+        self.encoded = RefCell::new(None);
+        old
+    }
+
+    /// Write-back the value if modified, return the CID, and drop any cached values. The link
+    /// stays usable: the next read loads the value again. Use [`Link::freeze`] when you're done
+    /// with the link entirely.
+    pub fn free(&mut self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let k = self.save()?;
+        self.value = OnceCell::new();
+        Ok(k)
+    }
+
+    /// Consume the link, writing back the value if modified, and return its CID. Unlike
+    /// [`Link::save`], nothing can edit the link afterwards, and unlike [`Link::free`], nothing
+    /// is left to reload.
+    pub fn freeze(self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        self.save()
+    }
+
+    /// Consume the link and return its value: the cached (or modified) value if there is one,
+    /// without touching the store, and otherwise the value loaded from the store.
+    pub fn take(mut self) -> Result<T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        match self.value.take() {
+            Some(v) => Ok(v),
+            None => self.load_checked(&self.state.get().unwrap_unmodified(), |_| Ok(())),
+        }
+    }
+
+    /// Consume the link and return its cached value, or `None` if it isn't loaded. Unlike
+    /// [`Link::take`], this never touches the store (so it needs no store bounds and can't fail);
+    /// a modified link always has its value.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+
+    /// Transform the cached value with `f`, e.g., to parse a raw node or unwrap a newtype, without
+    /// touching the store.
+    ///
+    /// The result is always modified: `f` generally changes the value's encoding, so it's
+    /// re-encoded (with this link's shape) on the next save, even if `f` happens to preserve it.
+    ///
+    /// Panics if the value isn't loaded; use [`Link::try_map`] to load it first.
+    #[must_use]
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Link<U, Store> {
+        let shape = match self.state.get() {
+            LinkState::Unmodified(k) => {
+                Some(self.output_shape.unwrap_or_else(|| CidShape::from(&k)))
+            }
+            LinkState::Modified(shape) => shape,
+        };
+        let value = (self.value.into_inner()).expect("expected link to be loaded");
+        Link {
+            value: OnceCell::from(f(value)),
+            state: Cell::new(LinkState::Modified(shape)),
+            encoded: RefCell::new(None),
+            prior: None,
+            output_shape: self.output_shape,
+            #[cfg(debug_assertions)]
+            label: self.label,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Like [`Link::map`], but loads the value first if it isn't cached.
+    pub fn try_map<U>(self, f: impl FnOnce(T) -> U) -> Result<Link<U, Store>, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        self.read()?;
+        Ok(self.map(f))
+    }
+
+    /// Drop the cached value and load it again from the store, e.g., after the store was changed
+    /// out-of-band. Fails with [`StoreError::UnsavedChanges`] if the link is modified rather than
+    /// discard the edits; save it first.
+    pub fn reload(&mut self) -> Result<(), Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        if self.is_dirty() {
+            return Err(self.annotate(StoreError::UnsavedChanges.into()));
+        }
+        self.clear_cache();
+        self.read()?;
+        Ok(())
+    }
+
+    /// Drop the cached value (if any) so the next read loads it from the store. Does nothing if the
+    /// link is modified, as the cached value is then the only copy.
+    pub fn clear_cache(&mut self) {
+        if !self.is_dirty() {
+            self.value = OnceCell::new();
+        }
+    }
+
+    /// Clone this link, unless that would deep-copy a cached value larger than `max_bytes` when
+    /// encoded. Use this to catch accidentally expensive clones; to clone a large link anyway,
+    /// share its value with [`Link::into_shared`] or drop it with [`Link::free`] first.
+    ///
+    /// Unloaded links are always cloned. A loaded value is measured in the link's shape with
+    /// [`StaticStore::encoded_len`] (encoding it only if that's another codec than the store's
+    /// default), which saves any modified links inside it, as serializing would.
+    pub fn try_clone(&self, max_bytes: usize) -> Result<Self, CloneCostError<Store::Error>>
+    where
+        T: Clone + Serialize,
+        Store: StaticStore,
+    {
+        if let Some(value) = self.value.get() {
+            let encoded_len = store::encoded_len::<Store, _>(value, self.shape().as_ref())
+                .map_err(CloneCostError::Store)?;
+            if encoded_len > max_bytes {
+                return Err(CloneCostError::TooLarge {
+                    encoded_len,
+                    max_bytes,
+                });
+            }
+        }
+        Ok(Link {
+            value: self.value.clone(),
+            state: self.state.clone(),
+            encoded: RefCell::new(None),
+            prior: None,
+            output_shape: self.output_shape,
+            #[cfg(debug_assertions)]
+            label: self.label,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Clone this link without its cached value, so `T` needn't implement `Clone`: the clone points
+    /// at the same CID and loads the value again when it's read. Returns `None` if the link has
+    /// been modified and not yet saved, since there's no CID to share; save it first.
+    ///
+    /// The cache is dropped on purpose, so cloning never copies (or even touches) the value. To
+    /// share a cached value between clones instead, see [`Link::into_shared`].
+    #[must_use]
+    pub fn clone_unloaded(&self) -> Option<Self> {
+        let k = self.cid()?;
+        Some(Link {
+            output_shape: self.output_shape,
+            #[cfg(debug_assertions)]
+            label: self.label,
+            ..Self::new(k)
+        })
+    }
+
+    /// Re-tag this link with another store type that reads the same blocks, e.g., to pass a
+    /// `Link<T, StatsStore<S>>` where a `Link<T, S>` is expected. Nothing is loaded or saved: the
+    /// new link points at the same CID (with the same output shape), and loads the value again
+    /// through `Other` when it's read.
+    ///
+    /// A modified link is returned unchanged as the error, since its value has to be saved through
+    /// its own store first.
+    #[allow(clippy::result_large_err)]
+    pub fn map_store<Other>(self) -> Result<Link<T, Other>, Self> {
+        let Some(k) = self.cid() else {
+            return Err(self);
+        };
+        Ok(Link {
+            output_shape: self.output_shape,
+            #[cfg(debug_assertions)]
+            label: self.label,
+            ..Link::new(k)
+        })
+    }
+
+    /// Convert this link into one whose cached value can be cheaply shared between clones. This
+    /// doesn't change the link's CID or serialized form.
+    #[must_use]
+    pub fn into_shared(self) -> Link<Arc<T>, Store> {
+        Link {
+            value: (self.value.into_inner())
+                .map(Arc::new)
+                .map_or_else(OnceCell::new, OnceCell::from),
+            state: self.state,
+            encoded: RefCell::new(None),
+            prior: None,
+            output_shape: self.output_shape,
+            #[cfg(debug_assertions)]
+            label: self.label,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, Store> Link<Arc<T>, Store> {
+    /// Clone this link, sharing the cached value (if any) with the clone.
+    ///
+    /// Edits to either link through [`Link::edit`] replace or [`Arc::make_mut`] the shared value,
+    /// so they never affect the other link.
+    #[must_use]
+    pub fn clone_shared(&self) -> Self {
+        Link {
+            value: self.value.clone(),
+            state: self.state.clone(),
+            encoded: RefCell::new(None),
+            prior: None,
+            output_shape: self.output_shape,
+            #[cfg(debug_assertions)]
+            label: self.label,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Clone this link, copying the cached value (if any) so the clone doesn't share it.
+    #[must_use]
+    pub fn clone_deep(&self) -> Self
+    where
+        T: Clone,
+    {
+        Link {
+            value: (self.value.get())
+                .map(|v| Arc::new(T::clone(v)))
+                .map_or_else(OnceCell::new, OnceCell::from),
+            state: self.state.clone(),
+            encoded: RefCell::new(None),
+            prior: None,
+            output_shape: self.output_shape,
+            #[cfg(debug_assertions)]
+            label: self.label,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// `k` in `base`, upgraded to a CIDv1 if it's a CIDv0 and `base` isn't base58btc.
+pub(crate) fn cid_string(k: Cid, base: Base) -> String {
+    let k = match k.version() {
+        Version::V0 if base != Base::Base58Btc => k.into_v1().expect("CIDv0s convert to CIDv1"),
+        _ => k,
+    };
+    k.to_string_of_base(base)
+        .expect("CIDv1s encode in any base")
+}
+
+/// Load every unloaded, unmodified link in `links` with a single [`StaticStore::load_many`] call,
+/// so that later reads are cache hits (the blocking counterpart of
+/// [`preload`](crate::preload)). Links that are already loaded (or modified) aren't fetched, links
+/// sharing a CID fetch it once, and identity CIDs are decoded without touching the store.
+///
+/// Each block is checked against [`StaticStore::MAX_BLOCK_SIZE`] and decoded as the default
+/// [`StaticStore::load`] would (so a store overriding `load` should preload through `read`
+/// instead). If the batch fails, nothing is cached; otherwise links are filled in order until one
+/// fails to decode, and its error is returned.
+pub fn preload_all<'a, T, Store>(
+    links: impl IntoIterator<Item = &'a Link<T, Store>>,
+) -> Result<(), Store::Error>
+where
+    T: DeserializeOwned + 'a,
+    Store: StaticStore + 'a,
+{
+    let links: Vec<_> = (links.into_iter())
+        .filter(|l| !l.is_loaded() && !l.is_dirty())
+        .map(|l| (l, l.state.get().unwrap_unmodified()))
+        .collect();
+    let keys: Vec<_> = links.iter().map(|(_, k)| *k).collect();
+    let blocks = Store::load_blocks(&keys)?;
+    for ((link, k), bytes) in links.into_iter().zip(blocks) {
+        link.fill(&k, &bytes)?;
+    }
+    Ok(())
+}
+
+/// Serialize a plain [`Cid`] field as an IPLD link, for use with `#[serde(with = "...")]`.
+///
+/// ```
+/// use auto_ipld::{Link, StaticStore};
+/// use cid::Cid;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(bound = "")]
+/// struct Entry<Store: StaticStore> {
+///     #[serde(with = "auto_ipld::link_as_cid")]
+///     previous: Cid,
+///     body: Link<String, Store>,
+/// }
+/// ```
+///
+/// The field is encoded exactly like a [`Link`] to the same CID, but is never loaded or saved.
+///
+/// Every link type is encoded this way. Binary formats get the `cid` crate's encoding (e.g., tag
+/// 42 in DAG-CBOR), and human-readable ones (see [`Serializer::is_human_readable`]) get
+/// DAG-JSON's `{"/": "<cid>"}` map, with the CID as a multibase string, so links stay legible in
+/// plain JSON and DAG-JSON blocks are unchanged. Human-readable formats also accept a bare CID
+/// string.
+///
+/// [`Serializer::is_human_readable`]: serde::Serializer::is_human_readable
+pub mod link_as_cid {
+    use alloc::string::{String, ToString};
+
+    use cid::Cid;
+    use serde::{de::Error, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::MaybeLink;
+
+    pub fn serialize<S: Serializer>(k: &Cid, serializer: S) -> Result<S::Ok, S::Error> {
+        if !serializer.is_human_readable() {
+            return k.serialize(serializer);
+        }
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry("/", &k.to_string())?;
+        map.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Cid, D::Error> {
+        if !deserializer.is_human_readable() {
+            return Cid::deserialize(deserializer);
+        }
+        match MaybeLink::<String>::deserialize(deserializer)? {
+            MaybeLink::Link(k) => Ok(k),
+            MaybeLink::Value(s) => Cid::try_from(s.as_str()).map_err(D::Error::custom),
+        }
+    }
+}
+
+/// Serialize an optional [`Link`] field as absent rather than `null` when it's `None`, for use with
+/// `#[serde(with = "...")]`. Serde can't skip a field from inside `with`, so the field also needs
+/// `skip_serializing_if` (to omit `None`) and `default` (to accept a missing field):
+///
+/// ```
+/// use auto_ipld::{Link, StaticStore};
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// #[serde(bound = "")]
+/// struct Entry<Store: StaticStore> {
+///     #[serde(
+///         default,
+///         skip_serializing_if = "Option::is_none",
+///         with = "auto_ipld::optional_link"
+///     )]
+///     previous: Option<Link<Box<Entry<Store>>, Store>>,
+///     body: String,
+/// }
+/// ```
+///
+/// `Some(link)` is saved and encoded as its CID, as a plain [`Link`] field would be, and an
+/// explicit `null` decodes as `None`.
+pub mod optional_link {
+    use cid::Cid;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{Link, StaticStore};
+
+    pub fn serialize<T, Store, S>(
+        link: &Option<Link<T, Store>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+        S: Serializer,
+    {
+        match link {
+            Some(link) => link.serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, T, Store, D>(
+        deserializer: D,
+    ) -> Result<Option<Link<T, Store>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Option::<Cid>::deserialize(deserializer)?.map(Link::new))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::{
+        cell::RefCell,
+        collections::{BTreeMap, HashMap, HashSet},
+        marker::PhantomData,
+        panic::{self, AssertUnwindSafe},
+        rc::Rc,
+        sync::Arc,
+        time::Duration,
+    };
+
+    use cid::{multibase::Base, Cid};
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        child_links, preload_all, testing::allocations, with_store, AsCid, AutoLink, CidShape,
+        CloneCostError, Codec, CodecError, CopyError, FaultyError, FaultyStore, Flush, Link,
+        LinkStatus, Magic, MagicStore, MaybeLink, MemoryError, MemoryStore, MultihashCode, RawLink,
+        SimStore, StaticStore, StatsStore, StoreError, SyncLink, Value, DAG_CBOR, DAG_JSON, DAG_PB,
+        DEFAULT_MAX_LINK_DEPTH, IDENTITY, MAX_IDENTITY_SIZE, RAW,
+    };
+
+    // TODO Having the store here is _really_ annoying. We might just want to remove it entirely.
+
+    #[derive(Deserialize, Serialize)]
+    #[serde(bound = "")] // ugh. The generic parameter really needs to go.
     struct DataObject<Store: StaticStore> {
         field1: String,
         field2: String,
         _marker: PhantomData<fn(Store)>, // future proof. ew.
     }
-    #[derive(Deserialize, Serialize)]
-    #[serde(bound = "")]
-    struct State<Store: StaticStore> {
-        name: String,
-        data1: Link<DataObject<Store>, Store>,
-        data2: Link<DataObject<Store>, Store>,
+    #[derive(Deserialize, Serialize)]
+    #[serde(bound = "")]
+    struct State<Store: StaticStore> {
+        name: String,
+        data1: Link<DataObject<Store>, Store>,
+        data2: Link<DataObject<Store>, Store>,
+    }
+
+    // Having to specify `MagicStore` here kind of defeats the point of abstracting over different
+    // store types.
+    impl<Store: MagicStore> State<Store> {
+        pub fn set_data1_field1(&mut self, field1: String) {
+            // Lazily loads `data1`, modifies, it, and marks it as dirty (because we mutably
+            // dereference it).
+            self.data1.field1 = field1
+        }
+
+        pub fn save(&self) -> Cid {
+            // Saves the object, returning a CID. This will internally save any _modified_ objects.
+            Store::unwrap(Store::store(self, None))
+        }
+    }
+
+    fn data<Store: StaticStore>(field1: &str, field2: &str) -> Link<DataObject<Store>, Store> {
+        Link::from_value(
+            DataObject {
+                field1: field1.into(),
+                field2: field2.into(),
+                _marker: PhantomData,
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn edit_nested() {
+        struct Tag;
+        type Store = Magic<MemoryStore<Tag>>;
+
+        let state = State::<Store> {
+            name: "state".into(),
+            data1: data("a1", "a2"),
+            data2: data("b1", "b2"),
+        };
+        let k = state.save();
+
+        let mut state: State<Store> = Store::load(&k).unwrap();
+        state.set_data1_field1("changed".into());
+        let k2 = state.save();
+        assert_ne!(k, k2);
+
+        let state: State<Store> = Store::load(&k2).unwrap();
+        assert_eq!(state.name, "state");
+        assert_eq!(state.data1.field1, "changed");
+        assert_eq!(state.data1.field2, "a2");
+        assert_eq!(state.data2.field1, "b1");
+    }
+
+    #[test]
+    fn edit_with_nested() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let state = State::<Store> {
+            name: "state".into(),
+            data1: data("a1", "a2"),
+            data2: data("b1", "b2"),
+        };
+        let k = Store::store(&state, None).unwrap();
+
+        let mut edited = Link::<State<Store>, Store>::new(k);
+        edited.edit().unwrap().data1.edit().unwrap().field1 = "changed".into();
+        let mut nested = Link::<State<Store>, Store>::new(k);
+        let old = nested
+            .edit_with(|s| s.data1.edit_with(|d| std::mem::take(&mut d.field1)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(old, "a1");
+        nested
+            .edit_with(|s| s.data1.edit_with(|d| d.field1 = "changed".into()))
+            .unwrap()
+            .unwrap();
+        assert!(nested.is_dirty());
+        assert_eq!(nested.save().unwrap(), edited.save().unwrap());
+
+        // Errors are returned before `f` runs.
+        Store::fail_when(|_| true);
+        let mut failing = Link::<State<Store>, Store>::new(k);
+        assert!(failing.edit_with(|_| unreachable!()).is_err());
+        assert!(!failing.is_dirty());
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize)]
+        struct Node {
+            next: Option<Link<Rc<RefCell<Node>>, Store>>,
+        }
+
+        impl Flush<Store> for Rc<RefCell<Node>> {
+            fn flush_links(&self) -> Result<(), MemoryError> {
+                self.borrow().next.flush_links()
+            }
+        }
+
+        let node = Rc::new(RefCell::new(Node { next: None }));
+        node.borrow_mut().next = Some(Link::from_value(node.clone(), None));
+        let is_cycle = |e| matches!(e, MemoryError::Store(StoreError::CycleDetected));
+        assert!(is_cycle(Store::store(&node, None).unwrap_err()));
+        let link = Link::<_, Store>::from_value(node.clone(), None);
+        assert!(is_cycle(link.save().unwrap_err()));
+        assert!(is_cycle(link.flush().unwrap_err()));
+        assert_eq!(Store::len(), 0);
+        node.borrow_mut().next = None;
+    }
+
+    #[test]
+    fn deep_nesting_is_limited() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize)]
+        struct List {
+            next: Option<Link<Box<List>, Store>>,
+        }
+
+        let list = |len| {
+            (0..len).fold(List { next: None }, |l, _| List {
+                next: Some(Link::from_value(Box::new(l), None)),
+            })
+        };
+        Store::store(&list(DEFAULT_MAX_LINK_DEPTH - 1), None).unwrap();
+        assert!(matches!(
+            Store::store(&list(DEFAULT_MAX_LINK_DEPTH), None),
+            Err(MemoryError::Store(StoreError::DepthExceeded(
+                DEFAULT_MAX_LINK_DEPTH
+            )))
+        ));
+    }
+
+    #[test]
+    fn replace_without_loading() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let k = MemoryStore::<Tag>::store(&"old", None).unwrap();
+        let mut link = Link::<String, Store>::new(k);
+        Store::fail_when(|_| true);
+        assert_eq!(link.replace("new".into()), None);
+        assert!(link.is_dirty());
+        assert_eq!(link.read().unwrap(), "new");
+        assert_eq!(Store::operations(), 0);
+        Store::reset();
+
+        let k2 = link.save().unwrap();
+        assert_eq!(MemoryStore::<Tag>::load::<String>(&k2).unwrap(), "new");
+        assert_eq!(link.replace("newer".into()).as_deref(), Some("new"));
+        assert_ne!(link.save().unwrap(), k2);
+    }
+
+    #[test]
+    fn identity_links_skip_the_store() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let shape = CidShape::DEFAULT.with_hash(IDENTITY);
+        Store::fail_when(|_| true);
+        let link = Link::<String, Store>::from_value("tiny".into(), Some(shape));
+        let k = link.save().unwrap();
+        assert_eq!(k.hash().code(), IDENTITY);
+        assert_eq!(Link::<String, Store>::new(k).read().unwrap(), "tiny");
+        let block = Link::<String, Store>::new(k).read_bytes().unwrap();
+        assert_eq!(block, k.hash().digest());
+        assert_eq!(&*Store::load_block(&k).unwrap(), block);
+        assert_eq!(&*Store::load_blocks(&[k, k]).unwrap()[1], block);
+        assert!(Store::has_block(&k).unwrap());
+        assert_eq!(Store::describe(&k).unwrap().1, block.len());
+        assert_eq!(crate::walk::<Store, ()>(&k, |_, _| Ok(())).unwrap(), 1);
+        assert_eq!(Store::store(&"tiny", Some(&shape)).unwrap(), k);
+        assert_eq!(
+            Store::store_deduped(b"raw", Some(&shape))
+                .unwrap()
+                .hash()
+                .digest(),
+            b"raw"
+        );
+        assert_eq!(Store::operations(), 0);
+        Store::reset();
+        assert_eq!(MemoryStore::<Tag>::len(), 0);
+
+        // Blocks too large to embed aren't truncated.
+        let big = Link::<String, Store>::from_value("x".repeat(MAX_IDENTITY_SIZE), Some(shape));
+        assert!(matches!(
+            big.save(),
+            Err(FaultyError::Inner(MemoryError::Store(
+                StoreError::IdentityTooLarge(_)
+            )))
+        ));
+        assert!(big.is_dirty());
+    }
+
+    #[test]
+    fn equality() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let [a, b] = ["a", "b"].map(|s| Store::store(&s, None).unwrap());
+        let clean: Link<String, Store> = Link::new(a);
+        assert!(clean == Link::new(a) && clean != Link::new(b));
+        assert!(clean.content_eq(&Link::new(a)).unwrap());
+        assert!(!clean.content_eq(&Link::new(b)).unwrap());
+
+        let dirty: Link<String, Store> = Link::from_value("a".into(), None);
+        let other: Link<String, Store> = Link::from_value("a".into(), None);
+        assert!(dirty != other && dirty != clean);
+        assert!(dirty.content_eq(&other).unwrap());
+        // Comparing saved them.
+        assert!(dirty == other && dirty == clean);
+        let changed: Link<String, Store> = Link::from_value("c".into(), None);
+        assert!(!changed.content_eq(&dirty).unwrap());
+    }
+
+    #[test]
+    fn from_maybe_link() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let value: Link<String, Store> = MaybeLink::Value("value".to_owned()).into();
+        assert!(value.is_dirty());
+        assert_eq!(Store::len(), 0);
+        let k = value.save().unwrap();
+        assert_eq!(Store::load::<String>(&k).unwrap(), "value");
+
+        let link: Link<String, Store> = MaybeLink::Link(k).into();
+        assert_eq!(link.cid(), Some(k));
+        assert!(!link.is_loaded());
+    }
+
+    #[test]
+    fn into_maybe_link() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let dirty: Link<String, Store> = Link::from_value("dirty".to_owned(), None);
+        let maybe = MaybeLink::from(dirty);
+        assert!(matches!(&maybe, MaybeLink::Value(v) if v == "dirty"));
+        assert_eq!(Store::len(), 0);
+
+        let clean: Link<String, Store> = Link::from_value("clean".to_owned(), None);
+        let k = clean.save().unwrap();
+        let maybe: MaybeLink<String> = clean.into();
+        assert_eq!(maybe, MaybeLink::Link(k));
+        assert_eq!(maybe.resolve::<Store>().unwrap(), "clean");
+    }
+
+    #[test]
+    fn optional_link_is_omitted() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize)]
+        struct Entry {
+            #[serde(
+                default,
+                skip_serializing_if = "Option::is_none",
+                with = "crate::optional_link"
+            )]
+            previous: Option<Link<String, Store>>,
+            body: String,
+        }
+
+        #[derive(Serialize, Deserialize)]
+        struct Body {
+            body: String,
+        }
+
+        let entry = Entry {
+            previous: None,
+            body: "first".into(),
+        };
+        let bytes = Store::encode(&entry).unwrap();
+        let body = Body {
+            body: "first".into(),
+        };
+        assert_eq!(bytes, Store::encode(&body).unwrap());
+        assert!(Store::decode::<Entry>(&bytes).unwrap().previous.is_none());
+
+        let entry = Entry {
+            previous: Some(Link::from_value("previous".into(), None)),
+            body: "second".into(),
+        };
+        let bytes = Store::encode(&entry).unwrap();
+        let decoded: Entry = Store::decode(&bytes).unwrap();
+        let previous = decoded.previous.unwrap();
+        assert_eq!(previous.cid(), entry.previous.unwrap().cid());
+        assert_eq!(previous.read().unwrap(), "previous");
+
+        let null = Value::Map(BTreeMap::from([
+            ("previous".into(), Value::Null),
+            ("body".into(), Value::String("third".into())),
+        ]));
+        let decoded: Entry = Store::decode(&Store::encode(&null).unwrap()).unwrap();
+        assert!(decoded.previous.is_none());
+    }
+
+    #[test]
+    fn deref_panic_shows_error() {
+        struct Tag;
+        type Store = Magic<MemoryStore<Tag>>;
+
+        let k = MemoryStore::<()>::store(&"missing", None).unwrap();
+        let link = Link::<String, Store>::new(k);
+        assert!(link.get().is_err());
+        let panic = panic::catch_unwind(AssertUnwindSafe(|| link.len())).unwrap_err();
+        let msg = panic.downcast_ref::<String>().unwrap();
+        assert!(msg.contains(&k.to_string()), "{msg}");
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn label_in_error() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k = Store::store(&"not an object", None).unwrap();
+        let link = Link::<DataObject<Store>, Store>::new(k).with_label("state.data1");
+        let err = link.read().err().unwrap().to_string();
+        assert!(err.contains("link `state.data1`"), "{err}");
+    }
+
+    #[test]
+    fn serialize_independent_of_cache() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let state = State::<Store> {
+            name: "state".into(),
+            data1: data("a1", "a2"),
+            data2: data("b1", "b2"),
+        };
+        let k = Store::store(&state, None).unwrap();
+        let fresh = Store::encode(&Store::load::<State<Store>>(&k).unwrap()).unwrap();
+
+        let loaded: State<Store> = Store::load(&k).unwrap();
+        loaded.data1.read().unwrap();
+        loaded.data2.read().unwrap();
+        assert_eq!(Store::encode(&loaded).unwrap(), fresh);
+
+        let mut reverted: State<Store> = Store::load(&k).unwrap();
+        reverted.data1.edit().unwrap().field1 = "changed".into();
+        reverted.data1.edit().unwrap().field1 = "a1".into();
+        assert_eq!(Store::encode(&reverted).unwrap(), fresh);
+    }
+
+    #[test]
+    fn clone_shared_and_deep() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let link = Link::<String, Store>::from_value("shared".into(), None).into_shared();
+        let shared = link.clone_shared();
+        assert_eq!(Arc::strong_count(link.read().unwrap()), 2);
+        assert!(Arc::ptr_eq(link.read().unwrap(), shared.read().unwrap()));
+
+        let deep = link.clone_deep();
+        assert_eq!(Arc::strong_count(deep.read().unwrap()), 1);
+        assert_eq!(Arc::strong_count(link.read().unwrap()), 2);
+        assert_eq!(**deep.read().unwrap(), "shared");
+
+        // Both kinds of clone keep the link's identity.
+        let k = link.save().unwrap();
+        assert_eq!(shared.save().unwrap(), k);
+        assert_eq!(deep.save().unwrap(), k);
+    }
+
+    #[test]
+    fn nested_save_error_is_typed() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let state = State::<Store> {
+            name: "state".into(),
+            data1: data("a1", "a2"),
+            data2: data("b1", "b2"),
+        };
+        // Fail saving `data2`, after `data1` has been written.
+        Store::fail_on(&[1]);
+        let err = Store::store(&state, None).err().unwrap();
+        assert!(matches!(err, FaultyError::Injected { op: 1 }), "{err}");
+        Store::reset();
+    }
+
+    #[test]
+    fn link_as_cid_alongside_links() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        mod upper {
+            use serde::{Deserialize, Deserializer, Serializer};
+
+            pub fn serialize<S: Serializer>(s: &str, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(&s.to_uppercase())
+            }
+
+            pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<String, D::Error> {
+                Ok(String::deserialize(d)?.to_lowercase())
+            }
+        }
+
+        #[derive(Serialize, Deserialize)]
+        #[serde(bound = "")]
+        struct Mixed<Store: StaticStore> {
+            #[serde(with = "upper")]
+            name: String,
+            #[serde(with = "crate::link_as_cid")]
+            raw: Cid,
+            link: Link<String, Store>,
+        }
+
+        let raw = Store::store(&"raw", None).unwrap();
+        let mixed = Mixed::<Store> {
+            name: "mixed".into(),
+            raw,
+            link: Link::from_value("linked".into(), None),
+        };
+        let k = Store::store(&mixed, None).unwrap();
+
+        let Value::Map(generic) = Store::load::<Value>(&k).unwrap() else {
+            panic!("expected a map")
+        };
+        assert_eq!(generic["name"], Value::String("MIXED".into()));
+        assert_eq!(generic["raw"], Value::Link(raw));
+        assert_eq!(generic["link"], Value::Link(mixed.link.save().unwrap()));
+
+        let loaded: Mixed<Store> = Store::load(&k).unwrap();
+        assert_eq!(loaded.name, "mixed");
+        assert_eq!(loaded.raw, raw);
+        assert_eq!(loaded.link.read().unwrap(), "linked");
+    }
+
+    #[test]
+    fn convert_output_shape() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        const SHA2_512: u64 = 0x13;
+        let shape = CidShape {
+            codec: DAG_CBOR,
+            mh_code: SHA2_512,
+        };
+        let k = Store::store(&"migrate me", None).unwrap();
+
+        let link = Link::<String, Store>::new(k).with_output_shape(shape);
+        assert_eq!(link.read().unwrap(), "migrate me");
+        let k2 = link.save().unwrap();
+        assert_ne!(k2, k);
+        assert_eq!(CidShape::from(&k2), shape);
+        assert_eq!(Store::load::<String>(&k2).unwrap(), "migrate me");
+
+        // Links that are never loaded keep their CID.
+        let untouched = Link::<String, Store>::new(k).with_output_shape(shape);
+        assert_eq!(untouched.save().unwrap(), k);
+
+        // Edited links are saved with the new shape too.
+        let mut edited = Link::<String, Store>::new(k).with_output_shape(shape);
+        edited.edit().unwrap().push('!');
+        assert_eq!(CidShape::from(&edited.save().unwrap()), shape);
     }
 
-    // Having to specify `MagicStore` here kind of defeats the point of abstracting over different
-    // store types.
-    impl<Store: MagicStore> State<Store> {
-        pub fn set_data1_field1(&mut self, field1: String) {
-            // Lazily loads `data1`, modifies, it, and marks it as dirty (because we mutably
-            // dereference it).
-            self.data1.field1 = field1
+    #[test]
+    fn loaded_links_skip_the_store() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = MemoryStore::<Tag>::store(&"imported", None).unwrap();
+        let link = Link::<String, Store>::loaded(k, "imported".into());
+        assert!(link.is_loaded() && !link.is_dirty());
+        assert_eq!(link.read().unwrap(), "imported");
+        assert_eq!(link.save().unwrap(), k);
+        let stats = Store::snapshot();
+        assert_eq!((stats.loads, stats.stores), (0, 0));
+    }
+
+    #[test]
+    fn unsupported_codecs_fail_to_save() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        assert_eq!(Store::default_codec(), DAG_CBOR);
+        // DAG-JOSE.
+        const UNSUPPORTED: u64 = 0x85;
+        assert!(Store::supports_codec(DAG_JSON) && Store::supports_codec(DAG_PB));
+        assert!(!Store::supports_codec(UNSUPPORTED));
+        let link: Link<String, Store> = Link::from_value(
+            "value".into(),
+            Some(CidShape::DEFAULT.with_codec(UNSUPPORTED)),
+        );
+        assert!(matches!(
+            link.save(),
+            Err(MemoryError::Store(StoreError::Codec(
+                CodecError::UnsupportedCodec(UNSUPPORTED)
+            )))
+        ));
+        assert!(link.is_dirty());
+        assert_eq!(Store::len(), 0);
+    }
+
+    #[test]
+    fn dag_pb_links_roundtrip() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct File {
+            #[serde(rename = "Data")]
+            data: crate::RawBytes,
+            #[serde(rename = "Links")]
+            links: Vec<Value>,
         }
 
-        pub fn save(&self) -> Cid {
-            // Saves the object, returning a CID. This will internally save any _modified_ objects.
-            Store::unwrap(Store::store(self, None))
+        let shape = CidShape::DEFAULT.with_codec(DAG_PB);
+        let file = File {
+            data: crate::RawBytes(b"\x08\x02\x18\x00".to_vec()),
+            links: vec![],
+        };
+        let link: Link<File, Store> = Link::from_value(file, Some(shape));
+        let k = link.save().unwrap();
+        assert_eq!(k.codec(), DAG_PB);
+        // The empty UnixFS file.
+        assert_eq!(Store::load_bytes(&k).unwrap(), b"\x0a\x04\x08\x02\x18\x00");
+        let reloaded = Link::<File, Store>::new(k);
+        assert_eq!(reloaded.read().unwrap(), link.read().unwrap());
+        assert_eq!(
+            Cid::new_v0(*k.hash()).unwrap().to_string(),
+            "QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH"
+        );
+    }
+
+    #[test]
+    fn map_store_retags_clean_links() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let link: Link<String, Magic<Store>> = Link::from_value("value".into(), None);
+        let link = link.map_store::<Store>().unwrap_err();
+        let k = link.save().unwrap();
+        assert_eq!(&*link, "value");
+        let link: Link<String, Store> = link.map_store().unwrap();
+        assert_eq!(link.cid(), Some(k));
+        assert!(!link.is_loaded());
+        assert_eq!(link.read().unwrap(), "value");
+    }
+
+    #[test]
+    fn reverted_edits_arent_written() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = MemoryStore::<Tag>::store(&"original", None).unwrap();
+        let mut link = Link::<String, Store>::new(k);
+        *link.edit().unwrap() = "changed".into();
+        *link.edit().unwrap() = "original".into();
+        assert!(link.is_dirty());
+        assert_eq!(link.save().unwrap(), k);
+        assert!(!link.is_dirty());
+        assert_eq!(Store::snapshot().stores, 0);
+
+        // Real changes are still written.
+        link.edit().unwrap().push('!');
+        let changed = link.save().unwrap();
+        assert_ne!(changed, k);
+        assert_eq!(Store::snapshot().stores, 1);
+        assert_eq!(
+            MemoryStore::<Tag>::load::<String>(&changed).unwrap(),
+            "original!"
+        );
+    }
+
+    #[test]
+    fn reverted_edits_are_written_to_other_stores() {
+        struct A;
+        struct B;
+
+        let k = MemoryStore::<A>::store(&"original", None).unwrap();
+        let mut link = Link::<String>::new(k);
+        with_store::<MemoryStore<A>, _>(|| {
+            *link.edit().unwrap() = "changed".into();
+            *link.edit().unwrap() = "original".into();
+        });
+        // The block is the one the link was loaded with, but it isn't in the store saved to.
+        assert_eq!(with_store::<MemoryStore<B>, _>(|| link.save()).unwrap(), k);
+        assert_eq!(MemoryStore::<B>::load::<String>(&k).unwrap(), "original");
+
+        // Nor is a block that was removed since the link was loaded.
+        let mut link = Link::<String, MemoryStore<A>>::new(k);
+        *link.edit().unwrap() = "changed".into();
+        assert!(MemoryStore::<A>::remove(&k).unwrap());
+        *link.edit().unwrap() = "original".into();
+        assert_eq!(link.save().unwrap(), k);
+        assert!(MemoryStore::<A>::has(&k).unwrap());
+    }
+
+    #[test]
+    fn get_mut_if_loaded_never_loads() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = MemoryStore::<Tag>::store(&"value", None).unwrap();
+        let mut link = Link::<String, Store>::new(k);
+        assert!(link.get_if_loaded().is_none());
+        assert!(link.get_mut_if_loaded().is_none());
+        assert!(!link.is_dirty());
+        assert_eq!(Store::snapshot().loads, 0);
+
+        link.read().unwrap();
+        assert_eq!(link.get_if_loaded().unwrap(), "value");
+        assert!(!link.is_dirty());
+        link.get_mut_if_loaded().unwrap().push('!');
+        assert!(link.is_dirty());
+        assert_eq!(Store::snapshot().loads, 1);
+        let changed = link.save().unwrap();
+        assert_ne!(changed, k);
+        assert_eq!(
+            MemoryStore::<Tag>::load::<String>(&changed).unwrap(),
+            "value!"
+        );
+
+        // New values are always cached.
+        let mut link = Link::<u64, Store>::from_value(1, None);
+        *link.get_mut_if_loaded().unwrap() += 1;
+        assert_eq!(
+            MemoryStore::<Tag>::load::<u64>(&link.save().unwrap()).unwrap(),
+            2
+        );
+    }
+
+    #[test]
+    fn bulk_imports_never_touch_the_store() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let blocks: Vec<(Cid, u64)> = (0..1000)
+            .map(|i| (MemoryStore::<Tag>::store(&i, None).unwrap(), i))
+            .collect();
+        let links: Vec<Link<u64, Store>> =
+            blocks.iter().map(|&(k, i)| Link::loaded(k, i)).collect();
+        assert_eq!(
+            links.iter().map(|l| *l.read().unwrap()).sum::<u64>(),
+            499_500
+        );
+        let root = Store::store(&links, None).unwrap();
+        let stats = Store::snapshot();
+        assert_eq!((stats.loads, stats.stores), (0, 1));
+        let cids: Vec<Cid> = MemoryStore::<Tag>::load(&root).unwrap();
+        assert!(cids.iter().zip(&blocks).all(|(k, (b, _))| k == b));
+    }
+
+    #[test]
+    fn encoded_size_matches_read_bytes() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let value: Vec<String> = (0..10).map(|i| format!("entry {i}")).collect();
+        let mut link: Link<Vec<String>, Store> = Link::from_value(value, None);
+        assert_eq!(
+            link.encoded_size().unwrap(),
+            link.read_bytes().unwrap().len()
+        );
+        link.save().unwrap();
+        let stats = Store::snapshot();
+        assert_eq!(
+            link.encoded_size().unwrap(),
+            link.read_bytes().unwrap().len()
+        );
+        // Only `read_bytes` loaded the block.
+        assert_eq!(Store::snapshot().loads - stats.loads, 1);
+
+        link.edit().unwrap().push("more".into());
+        assert_eq!(
+            link.encoded_size().unwrap(),
+            link.read_bytes().unwrap().len()
+        );
+        let json = CidShape::DEFAULT.with_codec(DAG_JSON);
+        let link: Link<String, Store> = Link::from_value("json".into(), Some(json));
+        assert_eq!(
+            link.encoded_size().unwrap(),
+            link.read_bytes().unwrap().len()
+        );
+    }
+
+    #[test]
+    fn links_decode_from_readers() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let value: Vec<String> = (0..100).map(|i| format!("entry {i}")).collect();
+        let block = Store::encode(&value).unwrap();
+        let k = CidShape::DEFAULT.cid(&block).unwrap();
+        let link: Link<Vec<String>, Store> = Link::from_reader(k, &block[..]).unwrap();
+        assert_eq!(*link.read().unwrap(), value);
+        assert_eq!(link.save().unwrap(), k);
+        let stats = Store::snapshot();
+        assert_eq!((stats.loads, stats.stores), (0, 0));
+
+        // The streamed bytes are verified, including any the decoder didn't need.
+        let mut trailing = block.clone();
+        trailing.push(0);
+        assert!(Link::<Vec<String>, Store>::from_reader(k, &trailing[..]).is_err());
+        let other = Store::encode(&"other").unwrap();
+        assert!(matches!(
+            Link::<String, Store>::from_reader(k, &other[..]),
+            Err(MemoryError::Store(StoreError::HashMismatch(_)))
+        ));
+    }
+
+    #[test]
+    fn cids_convert_explicitly() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k = Store::store(&"pointee", None).unwrap();
+        // For `T = Cid`, `From` wraps the CID as a value; `new` points at it.
+        let value: Link<Cid, Store> = k.into();
+        assert!(value.is_dirty() && value.as_cid().is_none());
+        assert_eq!(*value.read().unwrap(), k);
+        let pointer = Link::<Cid, Store>::new(k);
+        assert_eq!(pointer.as_cid(), Some(k));
+        assert_eq!(Cid::try_from(&pointer).unwrap(), k);
+        assert!(matches!(
+            Cid::try_from(&value),
+            Err(StoreError::UnsavedChanges)
+        ));
+
+        fn cids(links: &[&dyn AsCid]) -> Vec<Option<Cid>> {
+            links.iter().map(|l| l.as_cid()).collect()
+        }
+        let auto = AutoLink::<String, Store>::from_cid(k);
+        let inline = MaybeLink::Value("inline");
+        assert_eq!(
+            cids(&[
+                &k,
+                &pointer,
+                &value,
+                &auto,
+                &MaybeLink::<()>::Link(k),
+                &inline
+            ]),
+            [Some(k), Some(k), None, Some(k), Some(k), None]
+        );
+    }
+
+    #[test]
+    fn copies_between_stores() {
+        struct Cache;
+        struct Durable;
+
+        let k = MemoryStore::<Cache>::store(&"pinned", None).unwrap();
+        let link = Link::<String, MemoryStore<Cache>>::new(k);
+        assert_eq!(link.save_to::<MemoryStore<Durable>>().unwrap(), k);
+        assert_eq!(
+            MemoryStore::<Durable>::load::<String>(&k).unwrap(),
+            "pinned"
+        );
+
+        // Modified links are copied in their shape, and stay modified.
+        let shape = CidShape::DEFAULT.with_hash(MultihashCode::Sha2_512.as_u64());
+        let edited = Link::<String, MemoryStore<Cache>>::from_value("edited".into(), Some(shape));
+        let copy = edited.save_to::<MemoryStore<Durable>>().unwrap();
+        assert_eq!(CidShape::from(&copy), shape);
+        assert!(edited.is_dirty() && !MemoryStore::<Cache>::contains(&copy));
+        assert_eq!(MemoryStore::<Durable>::len(), 2);
+
+        let missing = Link::<String, MemoryStore<Cache>>::new(
+            MemoryStore::<()>::store(&"gone", None).unwrap(),
+        );
+        assert!(matches!(
+            missing.save_to::<MemoryStore<Durable>>(),
+            Err(CopyError::Source(MemoryError::NotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn links_are_send() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        // `Send` only depends on the value, even if it isn't `Sync` or the store isn't `Send`.
+        assert_send::<Link<RefCell<u8>, Store>>();
+        assert_send::<Link<u8, Rc<()>>>();
+        assert_send::<AutoLink<RefCell<u8>, Rc<()>>>();
+        assert_send::<RawLink<Rc<()>>>();
+        assert_sync::<SyncLink<u8, Rc<()>>>();
+
+        let k = Store::store(&"sent", None).unwrap();
+        let link = Link::<String, Store>::new(k);
+        let link = std::thread::spawn(move || {
+            link.read().unwrap();
+            link
+        })
+        .join()
+        .unwrap();
+        assert!(link.is_loaded());
+    }
+
+    #[test]
+    fn clone_unloaded_needs_no_clone() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize)]
+        struct Expensive(String);
+
+        let k = Store::store(&Expensive("big".into()), None).unwrap();
+        let link = Link::<Expensive, Store>::new(k);
+        assert_eq!(link.read().unwrap().0, "big");
+        let clone = link.clone_unloaded().unwrap();
+        assert_eq!(clone.cid(), Some(k));
+        assert!(!clone.is_loaded());
+        assert_eq!(clone.read().unwrap().0, "big");
+
+        let dirty = Link::<Expensive, Store>::from_value(Expensive("new".into()), None);
+        assert!(dirty.clone_unloaded().is_none());
+        dirty.save().unwrap();
+        assert!(dirty.clone_unloaded().is_some());
+    }
+
+    #[test]
+    fn edits_are_written_once() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        #[derive(Serialize)]
+        struct Parent {
+            children: Vec<Link<String, Store>>,
+        }
+
+        let child = |s: &str| Link::new(MemoryStore::<Tag>::store(&s, None).unwrap());
+        let mut parent = Parent {
+            children: vec![child("a"), child("b"), child("c")],
+        };
+        parent.children[1].edit().unwrap().push('!');
+        parent.children[1].save().unwrap();
+        let k = Store::store(&parent, None).unwrap();
+        assert_eq!(Store::store(&parent, None).unwrap(), k);
+        // The edited child once, then the parent twice; the clean children are never written.
+        assert_eq!(Store::snapshot().stores, 3);
+
+        // The next edit is a new generation, written once more.
+        parent.children[1].edit().unwrap().push('?');
+        Store::store(&parent, None).unwrap();
+        parent.children[1].save().unwrap();
+        assert_eq!(Store::snapshot().stores, 5);
+    }
+
+    #[test]
+    // Saving mutates a link's identity, but only dirty links can be saved into a new one.
+    #[allow(clippy::mutable_key_type)]
+    fn clean_links_are_hash_keys() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k = Store::store(&"key", None).unwrap();
+        let loaded = Link::<String, Store>::new(k);
+        loaded.read().unwrap();
+        let mut set = HashSet::from([loaded, Link::new(k)]);
+        assert_eq!(set.len(), 1);
+        assert!(set.contains(&Link::new(k)));
+
+        // Dirty links have no identity: each insert adds an entry that can't be found again.
+        let dirty = || Link::<String, Store>::from_value("key".into(), None);
+        assert!(set.insert(dirty()) && set.insert(dirty()));
+        assert_eq!(set.len(), 3);
+        assert!(!set.contains(&dirty()));
+    }
+
+    #[test]
+    fn shape_survives_edits() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        const SHA2_512: u64 = 0x13;
+        let k = Store::store(&"shaped", None).unwrap();
+        let mut link = Link::<String, Store>::new(k);
+        assert_eq!(link.shape(), Some(CidShape::DEFAULT));
+        link.edit().unwrap().push('!');
+        assert_eq!(link.shape(), Some(CidShape::DEFAULT));
+        let k = link.save().unwrap();
+        assert_eq!(k.codec(), DAG_CBOR);
+
+        let sha512 = CidShape::DEFAULT.with_hash(SHA2_512);
+        link.set_shape(sha512);
+        assert_eq!(link.shape(), Some(sha512));
+        link.edit().unwrap().push('!');
+        assert_eq!(CidShape::from(&link.save().unwrap()), sha512);
+        assert_eq!(link.shape(), Some(sha512));
+        assert_eq!(
+            Link::<String, Store>::from_value("new".into(), None).shape(),
+            None
+        );
+    }
+
+    #[test]
+    fn edit_keeps_shape() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        const SHA2_512: u64 = 0x13;
+        let shape = CidShape::default().with_hash(SHA2_512);
+        assert_eq!(
+            shape,
+            CidShape::new(Codec::DagCbor, MultihashCode::Sha2_512)
+        );
+        let k = Link::<String, Store>::from_value("shaped".into(), Some(shape))
+            .save()
+            .unwrap();
+        assert_eq!(CidShape::from(&k), shape);
+
+        // Editing a loaded link saves it in the shape it was loaded with.
+        let mut link = Link::<String, Store>::new(k);
+        link.edit().unwrap().push('!');
+        assert_eq!(CidShape::from(&link.save().unwrap()), shape);
+
+        // Without a shape, the default is used.
+        let k = Link::<String, Store>::from_value("plain".into(), None)
+            .save()
+            .unwrap();
+        assert_eq!(CidShape::from(&k), CidShape::default());
+    }
+
+    #[test]
+    fn debug_shows_cached_state() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        struct Opaque;
+        let link = Link::<Opaque, Store>::from_value(Opaque, None);
+        assert_eq!(format!("{link:?}"), "Link { modified: true, loaded: true }");
+
+        let k = Store::store(&"x", None).unwrap();
+        let link = Link::<String, Store>::new(k);
+        assert_eq!(
+            format!("{link:?}"),
+            format!("Link {{ cid: {k:?}, loaded: false }}")
+        );
+        // Formatting doesn't load the value.
+        assert_eq!(
+            format!("{link:?}"),
+            format!("Link {{ cid: {k:?}, loaded: false }}")
+        );
+        link.read().unwrap();
+        assert_eq!(
+            format!("{link:?}"),
+            format!("Link {{ cid: {k:?}, loaded: true }}")
+        );
+    }
+
+    #[test]
+    fn cid_without_saving() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let mut link = Link::<String, Store>::from_value("x".into(), None);
+        assert_eq!(link.cid(), None);
+        assert!(link.is_dirty());
+        assert_eq!(Store::len(), 0);
+
+        let k = link.save().unwrap();
+        assert_eq!(link.cid(), Some(k));
+        assert!(!link.is_dirty() && link.is_loaded());
+        link.free().unwrap();
+        assert!(!link.is_loaded());
+        link.edit().unwrap().push('y');
+        assert_eq!(link.cid(), None);
+        assert!(link.is_dirty() && link.is_loaded());
+    }
+
+    #[test]
+    fn take_loads_once() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = Store::store(&"stored", None).unwrap();
+        assert_eq!(Link::<String, Store>::new(k).take().unwrap(), "stored");
+        assert_eq!(Store::snapshot().loads, 1);
+
+        let link = Link::<String, Store>::new(k);
+        link.read().unwrap();
+        assert_eq!(link.take().unwrap(), "stored");
+        let modified = Link::<String, Store>::from_value("modified".into(), None);
+        assert_eq!(modified.take().unwrap(), "modified");
+        assert_eq!(Store::snapshot().loads, 2);
+        assert_eq!(Store::snapshot().stores, 1);
+    }
+
+    #[test]
+    fn preload_all_loads_in_one_batch() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static BATCHES: AtomicUsize = AtomicUsize::new(0);
+
+        // Counts `load_many` calls, and fails single loads.
+        struct Batching;
+        type Blocks = MemoryStore<Batching>;
+        type Store = StatsStore<Batching>;
+
+        impl StaticStore for Batching {
+            type Error = MemoryError;
+
+            fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, MemoryError> {
+                Blocks::store_bytes(value, shape)
+            }
+            fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, MemoryError> {
+                Blocks::encode(value)
+            }
+            fn load_bytes(k: &Cid) -> Result<Vec<u8>, MemoryError> {
+                unreachable!("loaded {k} outside a batch")
+            }
+            fn load_many(keys: &[Cid]) -> Result<Vec<Vec<u8>>, MemoryError> {
+                BATCHES.fetch_add(1, Ordering::Relaxed);
+                Blocks::load_many(keys)
+            }
+            fn decode<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> Result<T, MemoryError> {
+                Blocks::decode(bytes)
+            }
+        }
+
+        let children: Vec<Link<u64, Store>> = (0..5)
+            .map(|i| Link::new(Store::store(&i, None).unwrap()))
+            .collect();
+        let inline = Link::<u64, Store>::new(
+            Store::store(&5, Some(&CidShape::DEFAULT.with_hash(IDENTITY))).unwrap(),
+        );
+        let modified = Link::<u64, Store>::from_value(6, None);
+        let twin = Link::<u64, Store>::new(children[0].cid().unwrap());
+        preload_all(children.iter().chain([&inline, &modified, &twin])).unwrap();
+        assert_eq!(BATCHES.load(Ordering::Relaxed), 1);
+        assert_eq!(Store::snapshot().loads, 5);
+
+        assert!(children.iter().all(Link::is_loaded) && twin.is_loaded());
+        let values: Vec<u64> = children.iter().map(|l| *l.read().unwrap()).collect();
+        assert_eq!(values, [0, 1, 2, 3, 4]);
+        assert_eq!((*inline.read().unwrap(), *twin.read().unwrap()), (5, 0));
+
+        // Nothing is left to fetch.
+        preload_all(&children).unwrap();
+        assert_eq!(BATCHES.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn status_tracks_saves() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = Store::store(&"stored", None).unwrap();
+        let mut link = Link::<String, Store>::new(k);
+        assert_eq!(link.status(), LinkStatus::Stored(k));
+        assert_eq!(Store::snapshot().loads, 0);
+
+        link.edit().unwrap().push('!');
+        assert_eq!(link.status(), LinkStatus::Modified);
+        let saved = link.save().unwrap();
+        assert_eq!(link.status(), LinkStatus::Stored(saved));
+
+        let fresh = Link::<String, Store>::from_value("fresh".into(), None);
+        assert_eq!(fresh.status(), LinkStatus::Modified);
+        assert_eq!(Store::snapshot().stores, 2);
+    }
+
+    #[test]
+    fn failed_saves_reuse_the_encoding() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        thread_local! {
+            static ENCODES: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+        }
+
+        #[derive(Deserialize)]
+        struct Counted(u64);
+
+        impl Serialize for Counted {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                ENCODES.with(|n| n.set(n.get() + 1));
+                self.0.serialize(serializer)
+            }
+        }
+        let encodes = || ENCODES.with(|n| n.get());
+
+        let mut link = Link::<Counted, Store>::from_value(Counted(1), None);
+        Store::fail_when(|_| true);
+        assert!(link.save().is_err());
+        assert!(link.save().is_err());
+        assert_eq!(encodes(), 1);
+        Store::reset();
+        let k = link.save().unwrap();
+        assert_eq!(encodes(), 1);
+
+        link.edit().unwrap().0 = 2;
+        Store::fail_when(|_| true);
+        assert!(link.save_if_absent().is_err());
+        Store::reset();
+        let edited = link.save().unwrap();
+        assert_eq!(encodes(), 2);
+        assert_ne!(edited, k);
+        assert_eq!(Store::load::<u64>(&edited).unwrap(), 2);
+    }
+
+    #[test]
+    fn read_bytes_matches_the_stored_block() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let json = CidShape::DEFAULT.with_codec(DAG_JSON);
+        let link = Link::<String, Store>::from_value("wire".into(), Some(json));
+        let pending = link.read_bytes().unwrap();
+        assert_eq!(pending, br#""wire""#);
+        assert!(link.is_dirty());
+        assert_eq!(Store::snapshot().stores, 0);
+
+        let k = link.save().unwrap();
+        assert_eq!(Store::load_bytes(&k).unwrap(), pending);
+
+        let loaded = Link::<String, Store>::new(k);
+        assert_eq!(loaded.read_bytes().unwrap(), pending);
+        assert!(!loaded.is_loaded());
+    }
+
+    #[test]
+    fn into_inner_never_loads() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = Store::store(&"stored", None).unwrap();
+        assert_eq!(Link::<String, Store>::new(k).into_inner(), None);
+        let link = Link::<String, Store>::from_value("fresh".into(), None);
+        assert_eq!(link.into_inner().as_deref(), Some("fresh"));
+        let loaded = Link::<String, Store>::new(k);
+        loaded.read().unwrap();
+        assert_eq!(loaded.into_inner().as_deref(), Some("stored"));
+        assert_eq!(Store::snapshot().loads, 1);
+    }
+
+    #[test]
+    fn save_with_bytes_returns_the_stored_block() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let link = Link::<(String, u64), Store>::from_value(("saved".into(), 7), None);
+        let (k, bytes) = link.save_with_bytes().unwrap();
+        assert_eq!(Store::snapshot().stores, 1);
+        assert_eq!(Store::snapshot().loads, 0);
+        assert_eq!(
+            Store::decode::<(String, u64)>(&bytes).unwrap(),
+            ("saved".into(), 7)
+        );
+        assert_eq!(link.cid(), Some(k));
+
+        // Unmodified links load their block.
+        assert_eq!(link.save_with_bytes().unwrap(), (k, bytes));
+        assert_eq!(Store::snapshot().stores, 1);
+    }
+
+    #[test]
+    fn human_readable_formats_get_cid_strings() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize)]
+        struct Entry {
+            name: String,
+            body: Link<String, Store>,
+            extra: MaybeLink<String>,
+        }
+
+        let body = Store::store(&"body", None).unwrap();
+        let entry = Entry {
+            name: "entry".into(),
+            body: Link::new(body),
+            extra: MaybeLink::Link(body),
+        };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(
+            json,
+            format!(r#"{{"name":"entry","body":{{"/":"{body}"}},"extra":{{"/":"{body}"}}}}"#)
+        );
+        let decoded: Entry = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            (decoded.body.cid(), decoded.extra.link()),
+            (Some(body), Some(body))
+        );
+
+        // Bare CID strings are accepted, too.
+        let k: Link<String, Store> = serde_json::from_str(&format!(r#""{body}""#)).unwrap();
+        assert_eq!(k.cid(), Some(body));
+
+        // Binary formats keep the tagged form.
+        let cbor = Store::encode(&entry).unwrap();
+        assert_eq!(cbor, serde_ipld_dagcbor::to_vec(&entry).unwrap());
+        assert_eq!(child_links(&cbor, DAG_CBOR).unwrap(), [body, body]);
+        let decoded: Entry = Store::decode(&cbor).unwrap();
+        assert_eq!(
+            (decoded.body.cid(), decoded.extra.link()),
+            (Some(body), Some(body))
+        );
+    }
+
+    #[test]
+    fn cid_strings() {
+        type Store = MemoryStore<()>;
+
+        let k = CidShape::DEFAULT.with_codec(RAW).cid(b"foo").unwrap();
+        let link = Link::<String, Store>::new(k);
+        assert_eq!(
+            link.cid_string(Base::Base32Lower).as_deref(),
+            Some("bafkreibme22gw2h7y2h7tg2fhqotaqjucnbc24deqo72b6mkl2egezxhvy")
+        );
+        assert_eq!(
+            link.cid_string(Base::Base64).as_deref(),
+            Some("mAVUSICwmtGto/8aP+ZtFPB0wQTQTQi1wZIO/oPmKXohiZueu")
+        );
+        let v0 = Cid::new_v0(*k.hash()).unwrap();
+        let link = Link::<String, Store>::new(v0);
+        assert_eq!(link.cid_string(Base::Base58Btc), Some(v0.to_string()));
+        assert_eq!(
+            link.cid_string(Base::Base32Lower),
+            Some(Cid::new_v1(DAG_PB, *k.hash()).to_string())
+        );
+
+        let modified = Link::<String, Store>::from_value("unsaved".into(), None);
+        assert_eq!(modified.cid_string(Base::Base32Lower), None);
+        assert!(modified.is_dirty());
+    }
+
+    #[test]
+    fn edit_guards_save_on_drop() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let mut link = Link::<Vec<u64>, Store>::from_value(vec![1], None);
+        link.edit_guard().unwrap().push(2);
+        let k = link.cid().expect("saved on drop");
+        assert_eq!(MemoryStore::<Tag>::load::<Vec<u64>>(&k).unwrap(), [1, 2]);
+
+        let mut guard = link.edit_guard().unwrap();
+        guard.push(3);
+        let k = guard.commit().unwrap();
+        assert_eq!(MemoryStore::<Tag>::load::<Vec<u64>>(&k).unwrap(), [1, 2, 3]);
+
+        // A failed save on drop leaves the edit to be saved later.
+        Store::fail_when(|_| true);
+        link.edit_guard().unwrap().push(4);
+        assert!(link.is_dirty());
+        assert!(link.edit_guard().unwrap().commit().is_err());
+        Store::reset();
+        let k = link.save().unwrap();
+        assert_eq!(
+            MemoryStore::<Tag>::load::<Vec<u64>>(&k).unwrap(),
+            [1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn freeze_saves_once() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let link = Link::<String, Store>::from_value("frozen".into(), None);
+        let k = link.freeze().unwrap();
+        assert_eq!(Store::snapshot().stores, 1);
+        assert_eq!(Store::load::<String>(&k).unwrap(), "frozen");
+
+        // Clean links are frozen without touching the store.
+        assert_eq!(Link::<String, Store>::new(k).freeze().unwrap(), k);
+        assert_eq!(Store::snapshot().stores, 1);
+    }
+
+    #[test]
+    fn read_or_insert_with_fills_missing_blocks() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let stored = Store::store(&vec![1u64], None).unwrap();
+        let mut link = Link::<Vec<u64>, Store>::new(stored);
+        link.read_or_insert_with(Vec::new).unwrap().push(2);
+        assert_eq!(
+            Store::load::<Vec<u64>>(&link.save().unwrap()).unwrap(),
+            [1, 2]
+        );
+
+        // A CID from another store isn't in this one.
+        let missing = MemoryStore::<()>::store(&vec![7u64], None).unwrap();
+        let mut link = Link::<Vec<u64>, Store>::new(missing);
+        link.read_or_insert_with(Vec::new).unwrap().push(3);
+        assert_eq!(Store::load::<Vec<u64>>(&link.save().unwrap()).unwrap(), [3]);
+
+        // Other errors aren't papered over.
+        struct Other;
+        type Faulty = FaultyStore<MemoryStore<Other>>;
+        let k = Faulty::store(&vec![1u64], None).unwrap();
+        Faulty::fail_when(|_| true);
+        let mut link = Link::<Vec<u64>, Faulty>::new(k);
+        let err = link.read_or_insert_with(Vec::new).unwrap_err();
+        assert!(matches!(err, FaultyError::Injected { .. }));
+        assert!(!link.is_dirty());
+        Faulty::reset();
+    }
+
+    #[test]
+    fn read_seed_decodes_by_runtime_tags() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        #[derive(Debug, PartialEq)]
+        enum Shape {
+            Circle(u64),
+            Square(u64),
+        }
+
+        /// Decodes `{"kind": ..., "size": ...}` maps, with kind names chosen at runtime.
+        struct Kinds<'a> {
+            circle: &'a str,
+            square: &'a str,
+        }
+
+        impl<'de> serde::de::DeserializeSeed<'de> for Kinds<'_> {
+            type Value = Shape;
+
+            fn deserialize<D: serde::Deserializer<'de>>(self, d: D) -> Result<Shape, D::Error> {
+                use serde::de::Error;
+
+                let raw = Raw::deserialize(d)?;
+                match raw.kind {
+                    k if k == self.circle => Ok(Shape::Circle(raw.size)),
+                    k if k == self.square => Ok(Shape::Square(raw.size)),
+                    k => Err(D::Error::custom(format!("unknown kind {k}"))),
+                }
+            }
         }
+
+        #[derive(Serialize, Deserialize)]
+        struct Raw {
+            kind: String,
+            size: u64,
+        }
+        let k = Store::store(
+            &Raw {
+                kind: "sq".into(),
+                size: 3,
+            },
+            None,
+        )
+        .unwrap();
+        let link = Link::<Raw, Store>::new(k);
+        let kinds = || Kinds {
+            circle: "ci",
+            square: "sq",
+        };
+        assert_eq!(link.read_seed(kinds()).unwrap(), Shape::Square(3));
+        assert_eq!(link.read_seed(kinds()).unwrap(), Shape::Square(3));
+        // Seeded reads bypass the cache.
+        assert!(!link.is_loaded());
+        assert_eq!(Store::snapshot().loads, 2);
+
+        let unknown = Kinds {
+            circle: "circle",
+            square: "square",
+        };
+        assert!(matches!(
+            link.read_seed(unknown),
+            Err(MemoryError::Store(StoreError::Encoding(e))) if e.contains("unknown kind sq")
+        ));
+        let modified = Link::<Raw, Store>::from_value(
+            Raw {
+                kind: "ci".into(),
+                size: 1,
+            },
+            None,
+        );
+        assert!(matches!(
+            modified.read_seed(kinds()),
+            Err(MemoryError::Store(StoreError::UnsavedChanges))
+        ));
+    }
+
+    #[test]
+    fn points_to_compares_clean_links() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let [a, b] = ["a", "b"].map(|s| Store::store(&s, None).unwrap());
+        let mut link = Link::<String, Store>::new(a);
+        assert!(link.points_to(&a));
+        assert!(!link.points_to(&b));
+
+        // Even an edit that keeps the value unchanged dirties the link until it's saved.
+        link.edit().unwrap();
+        assert!(!link.points_to(&a));
+        link.save().unwrap();
+        assert!(link.points_to(&a));
+    }
+
+    #[test]
+    fn map_marks_modified() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = Store::store(&"raw", None).unwrap();
+        let link = Link::<String, Store>::new(k);
+        link.read().unwrap();
+        let upper = link.map(|s| s.to_uppercase());
+        assert!(upper.is_dirty());
+        assert_eq!(
+            Store::load::<String>(&upper.save().unwrap()).unwrap(),
+            "RAW"
+        );
+
+        // The CID would change even if `f` doesn't.
+        let same = Link::<String, Store>::new(k).try_map(|s| s).unwrap();
+        assert!(same.is_dirty());
+        assert_eq!(same.save().unwrap(), k);
+        assert_eq!(Store::snapshot().loads, 3);
+
+        let unloaded = Link::<String, Store>::new(k);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| unloaded.map(|s| s.len())));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn reload_and_clear_cache() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k = Store::store(&"stored", None).unwrap();
+        let mut link = Link::<String, Store>::new(k);
+        link.read().unwrap();
+        link.clear_cache();
+        assert!(!link.is_loaded());
+        link.reload().unwrap();
+        assert!(link.is_loaded());
+
+        link.edit().unwrap().push('!');
+        link.clear_cache();
+        let err = link.reload().unwrap_err();
+        assert!(err.to_string().contains("unsaved changes"), "{err}");
+        assert_eq!(link.read().unwrap(), "stored!");
+    }
+
+    #[test]
+    fn try_clone_limit() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let link = Link::<String, Store>::from_value("x".repeat(100), None);
+        // Measuring the value streams its encoding instead of buffering it.
+        let before = allocations();
+        assert!(matches!(
+            link.try_clone(64),
+            Err(CloneCostError::TooLarge {
+                encoded_len: 102,
+                max_bytes: 64
+            })
+        ));
+        assert_eq!(allocations(), before);
+        assert_eq!(
+            link.try_clone(128).unwrap().read().unwrap(),
+            &"x".repeat(100)
+        );
+
+        // Unloaded links are cheap to clone whatever their size.
+        let mut link = link;
+        link.free().unwrap();
+        assert!(!link.try_clone(0).unwrap().is_loaded());
+
+        // Values are measured in the codec they'd be saved in.
+        let shape = CidShape::new(Codec::DagJson, MultihashCode::Sha2_256);
+        let link = Link::<Vec<u64>, Store>::from_value(vec![1; 10], Some(shape));
+        assert!(matches!(
+            link.try_clone(16),
+            Err(CloneCostError::TooLarge {
+                encoded_len: 21,
+                max_bytes: 16
+            })
+        ));
+        assert!(link.try_clone(21).is_ok());
+    }
+
+    #[test]
+    fn save_if_absent_skips_existing_blocks() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let existing = MemoryStore::<Tag>::store(&"existing", None).unwrap();
+        Store::fail_when(|_| false);
+
+        let link = Link::<String, Store>::from_value("existing".into(), None);
+        assert_eq!(link.save_if_absent().unwrap(), (existing, false));
+        assert!(!link.is_dirty());
+        // Only the existence check reached the store.
+        assert_eq!(Store::operations(), 1);
+
+        let link = Link::<String, Store>::from_value("new".into(), None);
+        let (k, written) = link.save_if_absent().unwrap();
+        assert!(written);
+        assert!(Store::has(&k).unwrap());
+        assert_eq!(Store::operations(), 4);
+        assert_eq!(link.save_if_absent().unwrap(), (k, false));
+        Store::reset();
+    }
+
+    #[test]
+    fn into_auto_and_back() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k = Store::store(&"small", None).unwrap();
+        let loaded = Link::<String, Store>::new(k);
+        loaded.read().unwrap();
+        let auto = loaded.into_auto::<16>();
+        assert!(auto.is_modified());
+        assert!(matches!(auto.save().unwrap(), MaybeLink::Value(v) if v == "small"));
+
+        // Converting back stores the inlined value as its own block again.
+        let link = auto.into_link();
+        assert!(link.is_dirty());
+        assert_eq!(link.save().unwrap(), k);
+
+        // Unloaded links keep their CIDs both ways, without loading anything.
+        let auto = Link::<String, Store>::new(k).into_auto::<16>();
+        assert_eq!(auto.cid(), Some(k));
+        let link = auto.into_link();
+        assert_eq!(link.cid(), Some(k));
+        assert!(!link.is_loaded());
+    }
+
+    #[test]
+    fn save_batched_writes_once() {
+        struct Tag;
+        type Store = SimStore<MemoryStore<Tag>>;
+
+        let latency = Duration::from_millis(1);
+        Store::configure(1, latency, Duration::ZERO);
+        let state = State::<Store> {
+            name: "state".into(),
+            data1: data("a1", "a2"),
+            data2: data("b1", "b2"),
+        };
+        let root: Link<_, Store> = Link::from_value(state, None);
+        let k = root.save_batched().unwrap();
+        assert!(!root.is_dirty());
+
+        let trace = Store::trace();
+        assert_eq!(trace.len(), 3);
+        assert_eq!(trace.iter().map(|c| c.delay).sum::<Duration>(), latency);
+        assert_eq!(trace.last().unwrap().cid, k);
+        Store::reset();
+
+        // The same tree saved block by block.
+        let loaded = Link::<State<Store>, Store>::new(k);
+        assert_eq!(loaded.read().unwrap().data1.read().unwrap().field1, "a1");
+        assert_eq!(loaded.read().unwrap().data2.read().unwrap().field1, "b1");
+        let state = State::<Store> {
+            name: "state".into(),
+            data1: data("a1", "a2"),
+            data2: data("b1", "b2"),
+        };
+        assert_eq!(Link::<_, Store>::from_value(state, None).save().unwrap(), k);
+    }
+
+    #[test]
+    fn failed_batch_leaves_links_modified() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        let state = State::<Store> {
+            name: "state".into(),
+            data1: data("a1", "a2"),
+            data2: data("b1", "b2"),
+        };
+        let root: Link<_, Store> = Link::from_value(state, None);
+        Store::fail_on(&[2]);
+        assert!(matches!(
+            root.save_batched(),
+            Err(FaultyError::Injected { op: 2 })
+        ));
+        let state = root.read().unwrap();
+        assert!(root.is_dirty() && state.data1.is_dirty() && state.data2.is_dirty());
+
+        Store::reset();
+        let k = root.save_batched().unwrap();
+        assert!(!state.data1.is_dirty() && !state.data2.is_dirty());
+        assert!(MemoryStore::<Tag>::contains(&state.data1.cid().unwrap()));
+        assert!(MemoryStore::<Tag>::contains(&k));
+    }
+
+    #[test]
+    fn maps_of_links() {
+        struct Tag;
+        type Store = FaultyStore<MemoryStore<Tag>>;
+
+        #[derive(Serialize, Deserialize)]
+        struct Tree<M> {
+            entries: M,
+        }
+        type Ordered = Tree<BTreeMap<String, Link<String, Store>>>;
+        type Unordered = Tree<HashMap<String, Link<String, Store>>>;
+
+        let names = ["zebra", "apple", "mango", "kiwi", "banana"];
+        let entry = |name: &str| (name.to_string(), Link::from_value(name.repeat(2), None));
+        let ordered = Ordered {
+            entries: names.iter().map(|n| entry(n)).collect(),
+        };
+        let k = Store::store(&ordered, None).unwrap();
+
+        // HashMap iteration order varies between instances, but the encoding doesn't.
+        for _ in 0..10 {
+            let unordered = Unordered {
+                entries: names.iter().rev().map(|n| entry(n)).collect(),
+            };
+            assert_eq!(Store::store(&unordered, None).unwrap(), k);
+        }
+
+        // Entries load lazily and independently.
+        let loaded: Ordered = Store::load(&k).unwrap();
+        Store::fail_when(|_| false);
+        assert_eq!(loaded.entries["mango"].read().unwrap(), "mangomango");
+        assert_eq!(Store::operations(), 1);
+        let unloaded: Vec<_> = loaded
+            .entries
+            .iter()
+            .filter(|(_, l)| !l.is_loaded())
+            .map(|(n, _)| n.as_str())
+            .collect();
+        assert_eq!(unloaded, ["apple", "banana", "kiwi", "zebra"]);
+        Store::reset();
+
+        // Re-encoding the loaded tree (whatever's cached) gives the same CID.
+        assert_eq!(Store::store(&loaded, None).unwrap(), k);
+        let unordered: Unordered = Store::load(&k).unwrap();
+        assert_eq!(Store::store(&unordered, None).unwrap(), k);
+    }
+
+    #[test]
+    fn children_pick_their_codecs() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize)]
+        struct File {
+            name: Link<String, Store>,
+            contents: Link<Value, Store>,
+        }
+
+        let raw = CidShape::new(Codec::Raw, MultihashCode::Sha2_256);
+        let file = File {
+            name: Link::from_value("notes.txt".into(), None),
+            contents: Link::from_value(Value::Bytes(b"hello".to_vec()), Some(raw)),
+        };
+        let k = Store::store(&file, None).unwrap();
+
+        let loaded: File = Store::load(&k).unwrap();
+        let contents = loaded.contents.cid().unwrap();
+        assert_eq!(loaded.name.cid().unwrap().codec(), DAG_CBOR);
+        assert_eq!(contents.codec(), RAW);
+        // The raw block is just the bytes, not a CBOR byte string.
+        assert_eq!(Store::load_bytes(&contents).unwrap(), b"hello");
+        assert_eq!(loaded.name.read().unwrap(), "notes.txt");
+        assert_eq!(
+            *loaded.contents.read().unwrap(),
+            Value::Bytes(b"hello".to_vec())
+        );
+
+        // Raw blocks can only hold bytes.
+        let mut name = Link::<String, Store>::from_value("not bytes".into(), Some(raw));
+        assert!(matches!(
+            name.save(),
+            Err(MemoryError::Store(StoreError::Encoding(_)))
+        ));
+        name.set_shape(CidShape::DEFAULT);
+        assert_eq!(name.save().unwrap().codec(), DAG_CBOR);
     }
 }
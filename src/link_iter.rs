@@ -0,0 +1,294 @@
+use core::{iter::FusedIterator, slice};
+
+use alloc::boxed::Box;
+
+use serde::de::DeserializeOwned;
+
+use crate::{Link, StaticStore};
+
+/// A node of a singly-linked structure (a list, or a chain of versions) whose successor sits
+/// behind a [`Link`], so it can be walked with a [`LinkIter`].
+///
+/// With the list from the [`Link`] docs:
+///
+/// ```
+/// # use auto_ipld::{Link, LinkedNode, StaticStore};
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)]
+/// # pub struct Node<T, Store: StaticStore> {
+/// #     pub value: T,
+/// #     pub next: Option<Link<Box<Node<T, Store>>, Store>>,
+/// # }
+/// impl<T, Store: StaticStore> LinkedNode for Node<T, Store> {
+///     type Value = T;
+///     type Store = Store;
+///
+///     fn value(&self) -> &T {
+///         &self.value
+///     }
+///
+///     fn next(&self) -> Option<&Link<Box<Self>, Store>> {
+///         self.next.as_ref()
+///     }
+/// }
+/// ```
+pub trait LinkedNode: Sized {
+    /// The value held by each node.
+    type Value;
+    /// The store the nodes are loaded from.
+    type Store;
+
+    /// This node's value.
+    fn value(&self) -> &Self::Value;
+
+    /// The link to the next node, or `None` if this is the last one.
+    fn next(&self) -> Option<&Link<Box<Self>, Self::Store>>;
+
+    /// Iterate over the values of this node and its successors (see [`LinkIter`]).
+    fn iter(&self) -> LinkIter<'_, Self> {
+        LinkIter::new(self)
+    }
+}
+
+/// An iterator over the values of a chain of [`LinkedNode`]s, loading each node only when the
+/// iterator reaches it.
+///
+/// Loaded nodes are cached in their links, so the values are borrowed from the root for as long as
+/// it lives, and walking the chain again doesn't reload anything. If a node fails to load, the
+/// iterator yields the error and then stops.
+pub struct LinkIter<'a, N: LinkedNode> {
+    next: Option<Next<'a, N>>,
+}
+
+enum Next<'a, N: LinkedNode> {
+    Node(&'a N),
+    Link(&'a Link<Box<N>, N::Store>),
+}
+
+impl<'a, N: LinkedNode> LinkIter<'a, N> {
+    /// Iterate over the values of `root` and its successors.
+    pub fn new(root: &'a N) -> Self {
+        LinkIter {
+            next: Some(Next::Node(root)),
+        }
+    }
+}
+
+impl<'a, N> Iterator for LinkIter<'a, N>
+where
+    N: LinkedNode + DeserializeOwned,
+    N::Store: StaticStore,
+{
+    type Item = Result<&'a N::Value, <N::Store as StaticStore>::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = match self.next.take()? {
+            Next::Node(node) => node,
+            Next::Link(link) => match link.read() {
+                Ok(node) => node,
+                Err(e) => return Some(Err(e)),
+            },
+        };
+        self.next = node.next().map(Next::Link);
+        Some(Ok(node.value()))
+    }
+}
+
+impl<N> FusedIterator for LinkIter<'_, N>
+where
+    N: LinkedNode + DeserializeOwned,
+    N::Store: StaticStore,
+{
+}
+
+/// Lazy iteration over a list of links, e.g., a directory's `Vec<Link<Entry, S>>`, loading each
+/// value only when the iteration reaches it.
+pub trait LinkSlice<T, Store> {
+    /// Iterate over the values, loading each one as the iterator reaches it (see [`LoadedLinks`]).
+    fn iter_loaded(&self) -> LoadedLinks<'_, T, Store>;
+
+    /// Walk the values keeping at most `n` (at least one) of them cached, to bound the memory a
+    /// scan of a large list needs (see [`LinkWindow`]).
+    fn iter_window(&mut self, n: usize) -> LinkWindow<'_, T, Store>;
+}
+
+impl<T, Store> LinkSlice<T, Store> for [Link<T, Store>] {
+    fn iter_loaded(&self) -> LoadedLinks<'_, T, Store> {
+        LoadedLinks { links: self.iter() }
+    }
+
+    fn iter_window(&mut self, n: usize) -> LinkWindow<'_, T, Store> {
+        LinkWindow {
+            links: self,
+            next: 0,
+            size: n.max(1),
+        }
+    }
+}
+
+/// An iterator over the values of a slice of links, loading each link as the iterator reaches it
+/// (see [`LinkSlice::iter_loaded`]).
+///
+/// Loaded values stay cached in their links, so the values are borrowed from the slice. A link
+/// that fails to load yields its error, and the iteration continues with the next one.
+pub struct LoadedLinks<'a, T, Store> {
+    links: slice::Iter<'a, Link<T, Store>>,
+}
+
+impl<'a, T, Store> Iterator for LoadedLinks<'a, T, Store>
+where
+    T: DeserializeOwned,
+    Store: StaticStore,
+{
+    type Item = Result<&'a T, Store::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.links.next().map(Link::read)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.links.size_hint()
+    }
+}
+
+impl<T, Store> ExactSizeIterator for LoadedLinks<'_, T, Store>
+where
+    T: DeserializeOwned,
+    Store: StaticStore,
+{
+}
+
+impl<T, Store> FusedIterator for LoadedLinks<'_, T, Store>
+where
+    T: DeserializeOwned,
+    Store: StaticStore,
+{
+}
+
+/// A walk over the values of a slice of links that keeps only the last few cached (see
+/// [`LinkSlice::iter_window`]).
+///
+/// Each step loads the next value and evicts the value that falls out of the window (with
+/// [`Link::clear_cache`], so modified values are kept). Since later steps evict earlier values,
+/// each value is only borrowed until the next step, so this is a lending iterator: call
+/// [`LinkWindow::next_value`] in a `while let` loop rather than using `for`.
+pub struct LinkWindow<'a, T, Store> {
+    links: &'a mut [Link<T, Store>],
+    next: usize,
+    size: usize,
+}
+
+impl<T, Store> LinkWindow<'_, T, Store> {
+    /// Load and return the next value, or `None` at the end of the slice. A link that fails to
+    /// load yields its error, and the walk continues with the next one.
+    pub fn next_value(&mut self) -> Option<Result<&T, Store::Error>>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        let i = self.next;
+        if i >= self.links.len() {
+            return None;
+        }
+        self.next += 1;
+        if let Some(evicted) = i.checked_sub(self.size) {
+            self.links[evicted].clear_cache();
+        }
+        Some(self.links[i].read())
+    }
+
+    /// The whole slice, e.g., to check which values are cached.
+    pub fn links(&self) -> &[Link<T, Store>] {
+        self.links
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Link, LinkSlice, LinkedNode, MemoryError, MemoryStore, StaticStore, StatsStore};
+
+    struct Tag;
+    type Store = StatsStore<MemoryStore<Tag>>;
+
+    #[derive(Serialize, Deserialize)]
+    struct Node {
+        value: u32,
+        next: Option<Link<Box<Node>, Store>>,
+    }
+
+    impl LinkedNode for Node {
+        type Value = u32;
+        type Store = Store;
+
+        fn value(&self) -> &u32 {
+            &self.value
+        }
+
+        fn next(&self) -> Option<&Link<Box<Self>, Store>> {
+            self.next.as_ref()
+        }
+    }
+
+    #[test]
+    fn loads_nodes_lazily() {
+        let list = (1..4).rev().fold(None, |next, value| {
+            let node = Node { value, next };
+            Some(Link::new(Store::store(&node, None).unwrap()))
+        });
+        let root = Node {
+            value: 0,
+            next: list,
+        };
+
+        let mut iter = root.iter();
+        assert_eq!(iter.next().unwrap().unwrap(), &0);
+        assert_eq!(iter.next().unwrap().unwrap(), &1);
+        assert_eq!(Store::snapshot().loads, 1);
+        let rest: Vec<_> = iter.map(Result::unwrap).collect();
+        assert_eq!(rest, [&2, &3]);
+        assert_eq!(Store::snapshot().loads, 3);
+
+        // The nodes stay cached in their links.
+        assert_eq!(root.iter().count(), 4);
+        assert_eq!(Store::snapshot().loads, 3);
+
+        let missing = MemoryStore::<()>::store(&1u32, None).unwrap();
+        let broken = Node {
+            value: 0,
+            next: Some(Link::new(missing)),
+        };
+        let mut iter = broken.iter();
+        assert!(iter.next().unwrap().is_ok());
+        assert!(matches!(iter.next(), Some(Err(MemoryError::NotFound(_)))));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn windows_bound_the_cache() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let mut links: Vec<Link<u32, Store>> = (0..100)
+            .map(|i| Link::new(Store::store(&i, None).unwrap()))
+            .collect();
+        let mut loaded = links.iter_loaded();
+        assert_eq!(loaded.len(), 100);
+        assert_eq!(loaded.nth(1).unwrap().unwrap(), &1);
+        assert_eq!(Store::snapshot().loads, 2);
+
+        links.iter_mut().for_each(Link::clear_cache);
+        let mut window = links.iter_window(3);
+        let (mut sum, mut peak) = (0, 0);
+        while let Some(value) = window.next_value() {
+            sum += value.unwrap();
+            peak = peak.max(window.links().iter().filter(|l| l.is_loaded()).count());
+        }
+        assert_eq!(sum, 4950);
+        assert_eq!(peak, 3);
+        assert_eq!(Store::snapshot().loads, 102);
+        let cached: Vec<_> = (0..100).filter(|&i| links[i].is_loaded()).collect();
+        assert_eq!(cached, [97, 98, 99]);
+    }
+}
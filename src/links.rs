@@ -0,0 +1,267 @@
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec::Vec};
+
+use cid::Cid;
+
+use crate::{AutoLink, Link, MaybeLink};
+
+/// A value that can list the CIDs it links to directly, without loading or decoding any linked
+/// blocks (e.g., to walk a DAG for garbage collection, pinning, or replication).
+///
+/// Only links with a stable CID are reported: a modified [`Link`] reports nothing, as it has no CID
+/// until it's saved. Use [`impl_links!`](crate::impl_links) to implement this for structs.
+pub trait Links {
+    /// Append the CIDs this value links to directly to `out`.
+    fn links(&self, out: &mut Vec<Cid>);
+
+    /// Returns true if this value links to `k` directly (as listed by [`Links::links`]), e.g., to
+    /// check a structure against a set of pinned CIDs. Nothing is loaded or saved, so modified
+    /// links never match.
+    fn references(&self, k: &Cid) -> bool {
+        let mut out = Vec::new();
+        self.links(&mut out);
+        out.contains(k)
+    }
+}
+
+impl Links for Cid {
+    fn links(&self, out: &mut Vec<Cid>) {
+        out.push(*self);
+    }
+}
+
+impl<T, Store> Links for Link<T, Store> {
+    fn links(&self, out: &mut Vec<Cid>) {
+        out.extend(self.cid());
+    }
+}
+
+/// A value stored in its own block reports its CID. An inlined value is part of the parent block,
+/// so its links are reported instead; so are a modified value's, as it may be inlined when saved.
+impl<T: Links, Store, const S: usize> Links for AutoLink<T, Store, S> {
+    fn links(&self, out: &mut Vec<Cid>) {
+        match (self.cid(), self.cached()) {
+            (Some(k), _) => out.push(k),
+            (None, Some(v)) => v.links(out),
+            (None, None) => {}
+        }
+    }
+}
+
+impl<T: Links> Links for MaybeLink<T> {
+    fn links(&self, out: &mut Vec<Cid>) {
+        match self {
+            MaybeLink::Value(v) => v.links(out),
+            MaybeLink::Link(k) => out.push(*k),
+        }
+    }
+}
+
+impl<T: Links + ?Sized> Links for &T {
+    fn links(&self, out: &mut Vec<Cid>) {
+        (**self).links(out)
+    }
+}
+
+impl<T: Links + ?Sized> Links for Box<T> {
+    fn links(&self, out: &mut Vec<Cid>) {
+        (**self).links(out)
+    }
+}
+
+impl<T: Links> Links for Option<T> {
+    fn links(&self, out: &mut Vec<Cid>) {
+        if let Some(v) = self {
+            v.links(out)
+        }
+    }
+}
+
+impl<T: Links> Links for [T] {
+    fn links(&self, out: &mut Vec<Cid>) {
+        for v in self {
+            v.links(out)
+        }
+    }
+}
+
+impl<T: Links, const N: usize> Links for [T; N] {
+    fn links(&self, out: &mut Vec<Cid>) {
+        self[..].links(out)
+    }
+}
+
+impl<T: Links> Links for Vec<T> {
+    fn links(&self, out: &mut Vec<Cid>) {
+        self[..].links(out)
+    }
+}
+
+/// Map keys are strings in IPLD, so only the values are walked.
+impl<K, V: Links> Links for BTreeMap<K, V> {
+    fn links(&self, out: &mut Vec<Cid>) {
+        for v in self.values() {
+            v.links(out)
+        }
+    }
+}
+
+/// Map keys are strings in IPLD, so only the values are walked. The links are reported in the
+/// map's iteration order (not the encoded order).
+#[cfg(feature = "std")]
+impl<K, V: Links, H> Links for HashMap<K, V, H> {
+    fn links(&self, out: &mut Vec<Cid>) {
+        for v in self.values() {
+            v.links(out)
+        }
+    }
+}
+
+/// Values that can't contain links.
+macro_rules! no_links {
+    ($($ty:ty),*) => {
+        $(
+            impl Links for $ty {
+                fn links(&self, _out: &mut Vec<Cid>) {}
+            }
+        )*
+    };
+}
+
+no_links!(
+    bool,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    f32,
+    f64,
+    str,
+    String,
+    ()
+);
+
+macro_rules! tuple_links {
+    ($(($($name:ident),+))*) => {
+        $(
+            impl<$($name: Links),+> Links for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn links(&self, out: &mut Vec<Cid>) {
+                    let ($($name,)+) = self;
+                    $($name.links(out);)+
+                }
+            }
+        )*
+    };
+}
+
+tuple_links! {
+    (A)
+    (A, B)
+    (A, B, C)
+    (A, B, C, D)
+    (A, B, C, D, E)
+    (A, B, C, D, E, F)
+}
+
+/// Implement [`Links`] for a struct by walking the named fields, in order. Generic parameters
+/// (with their bounds) go in brackets before the type.
+///
+/// ```
+/// # use auto_ipld::{impl_links, Link};
+/// struct Node<Store> {
+///     name: String,
+///     children: Vec<Link<Node<Store>, Store>>,
+/// }
+///
+/// impl_links!([Store] Node<Store> { name, children });
+/// ```
+#[macro_export]
+macro_rules! impl_links {
+    ([$($generics:tt)*] $ty:ty { $($field:ident),* $(,)? }) => {
+        impl<$($generics)*> $crate::Links for $ty {
+            fn links(&self, out: &mut ::std::vec::Vec<$crate::__private::Cid>) {
+                $($crate::Links::links(&self.$field, out);)*
+            }
+        }
+    };
+    ($ty:ty { $($field:ident),* $(,)? }) => {
+        $crate::impl_links!([] $ty { $($field),* });
+    };
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::collections::BTreeMap;
+
+    use cid::Cid;
+
+    use crate::{AutoLink, Link, Links, MaybeLink, MemoryStore, StaticStore};
+
+    struct Tag;
+    type Store = MemoryStore<Tag>;
+
+    struct Node {
+        name: String,
+        children: Vec<Link<String, Store>>,
+        small: AutoLink<(Cid, u64), Store>,
+        named: BTreeMap<String, (u64, MaybeLink<Cid>)>,
+    }
+
+    impl_links!(Node {
+        name,
+        children,
+        small,
+        named
+    });
+
+    fn links(v: &impl Links) -> Vec<Cid> {
+        let mut out = Vec::new();
+        v.links(&mut out);
+        out
+    }
+
+    #[test]
+    fn walks_fields_in_order() {
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|s| Store::store(&s, None).unwrap());
+        let node = Node {
+            name: "node".into(),
+            children: vec![Link::new(a), Link::from_value("dirty".into(), None)],
+            small: AutoLink::from_value((b, 1)),
+            named: BTreeMap::from([
+                ("x".into(), (1, MaybeLink::Link(c))),
+                ("y".into(), (2, MaybeLink::Value(d))),
+            ]),
+        };
+        // The dirty link has no CID yet, and the inlined value's link is reported directly.
+        assert_eq!(links(&node), [a, b, c, d]);
+
+        node.children[1].save().unwrap();
+        let k = node.children[1].cid().unwrap();
+        assert_eq!(links(&node), [a, k, b, c, d]);
+    }
+
+    #[test]
+    fn references_direct_links_only() {
+        let [a, b, c] = ["a", "b", "c"].map(|s| Store::store(&s, None).unwrap());
+        let node = Node {
+            name: "node".into(),
+            children: vec![Link::new(a)],
+            small: AutoLink::from_value((b, 1)),
+            named: BTreeMap::new(),
+        };
+        assert!(node.references(&a) && node.references(&b));
+        assert!(!node.references(&c));
+
+        let dirty: Link<String, Store> = Link::from_value("c".into(), None);
+        assert!(!dirty.references(&c));
+    }
+}
@@ -1,20 +1,99 @@
-use std::marker::PhantomData;
+use core::marker::PhantomData;
 
-use cid::{serde::BytesToCidVisitor, Cid};
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+
+use cid::{serde::CID_SERDE_PRIVATE_IDENTIFIER, Cid};
 use serde::{
     de::{
         value::{MapAccessDeserializer, SeqAccessDeserializer},
-        IntoDeserializer, Visitor,
+        DeserializeOwned, DeserializeSeed, Error as _, IgnoredAny, IntoDeserializer, MapAccess,
+        Visitor,
     },
-    forward_to_deserialize_any, Deserialize, Deserializer, Serialize,
+    forward_to_deserialize_any,
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize,
 };
 
+use crate::{link_as_cid, AsCid, StaticStore, Value, ValueDeserializer};
+
 /// An type to represent IPLD values that can either be link, or any other value.
+///
+/// Links are serialized like any other link (see [`link_as_cid`]): binary formats get the `cid`
+/// crate's encoding, which DAG-CBOR (`serde_ipld_dagcbor`) writes as a tag 42 and other formats as
+/// a newtype of the CID's bytes, and human-readable formats (like DAG-JSON or plain JSON) get
+/// DAG-JSON's `{"/": "<cid>"}` map. When deserializing, links are recognized both in the `cid`
+/// crate's form and as a `{"/": "<cid>"}` map, for formats that present the latter as a plain map.
+/// Strings are always values, even if they parse as CIDs.
+#[derive(PartialEq, Eq)]
 pub enum MaybeLink<T> {
     Value(T),
     Link(Cid),
 }
 
+impl<T> MaybeLink<T> {
+    /// Borrow the value, if any.
+    pub fn as_ref(&self) -> MaybeLink<&T> {
+        match self {
+            MaybeLink::Value(v) => MaybeLink::Value(v),
+            MaybeLink::Link(k) => MaybeLink::Link(*k),
+        }
+    }
+
+    /// Transform the value, if any, leaving links as they are.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> MaybeLink<U> {
+        match self {
+            MaybeLink::Value(v) => MaybeLink::Value(f(v)),
+            MaybeLink::Link(k) => MaybeLink::Link(k),
+        }
+    }
+
+    /// The value, or `None` if this is a link.
+    pub fn into_value(self) -> Option<T> {
+        match self {
+            MaybeLink::Value(v) => Some(v),
+            MaybeLink::Link(_) => None,
+        }
+    }
+
+    /// The link, or `None` if this is a value.
+    pub fn link(&self) -> Option<Cid> {
+        match self {
+            MaybeLink::Value(_) => None,
+            MaybeLink::Link(k) => Some(*k),
+        }
+    }
+
+    /// The value, loading it from `S` if this is a link (e.g.,
+    /// `maybe.resolve::<MemoryStore>()`).
+    pub fn resolve<S: StaticStore>(self) -> Result<T, S::Error>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            MaybeLink::Value(v) => Ok(v),
+            MaybeLink::Link(k) => S::load(&k),
+        }
+    }
+}
+
+/// See [`MaybeLink::link`].
+impl<T> AsCid for MaybeLink<T> {
+    fn as_cid(&self) -> Option<Cid> {
+        self.link()
+    }
+}
+
+/// Shows `Value(..)` or `Link(cid)`. The value itself isn't shown, so `T` needn't implement
+/// `Debug`.
+impl<T> core::fmt::Debug for MaybeLink<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MaybeLink::Value(_) => write!(f, "Value(..)"),
+            MaybeLink::Link(k) => f.debug_tuple("Link").field(k).finish(),
+        }
+    }
+}
+
 impl<T> Serialize for MaybeLink<T>
 where
     T: Serialize,
@@ -25,7 +104,7 @@ where
     {
         match self {
             MaybeLink::Value(v) => Serialize::serialize(v, serializer),
-            MaybeLink::Link(k) => Serialize::serialize(k, serializer),
+            MaybeLink::Link(k) => link_as_cid::serialize(k, serializer),
         }
     }
 }
@@ -38,7 +117,144 @@ where
     where
         D: serde::Deserializer<'de>,
     {
-        deserializer.deserialize_any(MaybeLinkVisitor(PhantomData))
+        deserializer.deserialize_any(MaybeLinkVisitor::new(false, 0))
+    }
+}
+
+/// A [`MaybeLink`] in the tagged representation some IPLD schemas use for "inline or link" fields:
+/// a value is serialized as `{"value": <value>}`, and a link as `{"link": <cid>}` (with the CID
+/// encoded as any other link, see [`link_as_cid`]).
+///
+/// Use this (or [`tagged_auto_link`](crate::tagged_auto_link) for an [`AutoLink`] field) to
+/// consume or produce data for such schemas; [`MaybeLink`]'s untagged representation is the
+/// default everywhere else. Deserializing expects a map with exactly one of the two keys.
+///
+/// [`AutoLink`]: crate::AutoLink
+#[derive(PartialEq, Eq)]
+pub enum TaggedMaybeLink<T> {
+    Value(T),
+    Link(Cid),
+}
+
+/// The key of an inlined value in a [`TaggedMaybeLink`].
+const VALUE_KEY: &str = "value";
+/// The key of a link in a [`TaggedMaybeLink`].
+const LINK_KEY: &str = "link";
+
+impl<T> From<MaybeLink<T>> for TaggedMaybeLink<T> {
+    fn from(v: MaybeLink<T>) -> Self {
+        match v {
+            MaybeLink::Value(v) => TaggedMaybeLink::Value(v),
+            MaybeLink::Link(k) => TaggedMaybeLink::Link(k),
+        }
+    }
+}
+
+impl<T> From<TaggedMaybeLink<T>> for MaybeLink<T> {
+    fn from(v: TaggedMaybeLink<T>) -> Self {
+        match v {
+            TaggedMaybeLink::Value(v) => MaybeLink::Value(v),
+            TaggedMaybeLink::Link(k) => MaybeLink::Link(k),
+        }
+    }
+}
+
+/// See [`MaybeLink::link`].
+impl<T> AsCid for TaggedMaybeLink<T> {
+    fn as_cid(&self) -> Option<Cid> {
+        match self {
+            TaggedMaybeLink::Value(_) => None,
+            TaggedMaybeLink::Link(k) => Some(*k),
+        }
+    }
+}
+
+/// Shows `Value(..)` or `Link(cid)`, as for [`MaybeLink`].
+impl<T> core::fmt::Debug for TaggedMaybeLink<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TaggedMaybeLink::Value(_) => write!(f, "Value(..)"),
+            TaggedMaybeLink::Link(k) => f.debug_tuple("Link").field(k).finish(),
+        }
+    }
+}
+
+/// Serializes a CID as a link, for [`TaggedMaybeLink`]'s `link` entry.
+struct AsLink<'a>(&'a Cid);
+
+impl Serialize for AsLink<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        link_as_cid::serialize(self.0, serializer)
+    }
+}
+
+/// Deserializes a link, for [`TaggedMaybeLink`]'s `link` entry.
+struct FromLink(Cid);
+
+impl<'de> Deserialize<'de> for FromLink {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        link_as_cid::deserialize(deserializer).map(FromLink)
+    }
+}
+
+impl<T> Serialize for TaggedMaybeLink<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(1))?;
+        match self {
+            TaggedMaybeLink::Value(v) => map.serialize_entry(VALUE_KEY, v)?,
+            TaggedMaybeLink::Link(k) => map.serialize_entry(LINK_KEY, &AsLink(k))?,
+        }
+        map.end()
+    }
+}
+
+impl<'de, T> Deserialize<'de> for TaggedMaybeLink<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_map(TaggedVisitor(PhantomData))
+    }
+}
+
+struct TaggedVisitor<T>(PhantomData<fn() -> T>);
+
+impl<'de, T> Visitor<'de> for TaggedVisitor<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = TaggedMaybeLink<T>;
+
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            formatter,
+            "a map with a single '{VALUE_KEY}' or '{LINK_KEY}' key"
+        )
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let tagged = match map.next_key::<String>()?.as_deref() {
+            Some(VALUE_KEY) => TaggedMaybeLink::Value(map.next_value()?),
+            Some(LINK_KEY) => TaggedMaybeLink::Link(map.next_value::<FromLink>()?.0),
+            Some(key) => return Err(A::Error::unknown_field(key, &[VALUE_KEY, LINK_KEY])),
+            None => return Err(A::Error::invalid_length(0, &self)),
+        };
+        if map.next_key::<IgnoredAny>()?.is_some() {
+            return Err(A::Error::invalid_length(2, &self));
+        }
+        Ok(tagged)
     }
 }
 
@@ -63,28 +279,117 @@ where
     }
 }
 
-struct SomeDeserializer<D>(D);
-impl<'de, D> Deserializer<'de> for SomeDeserializer<D>
-where
-    D: Deserializer<'de>,
-{
+/// Forwards to `D`, except that a request for a newtype struct is answered with the newtype's
+/// contents. Values seen by [`MaybeLinkVisitor`] are passed to `T` through this so that newtype
+/// structs (e.g., `struct Wrapper(Vec<u8>)`) decode from their contents, as they do without a
+/// `MaybeLink`.
+struct Transparent<D>(D);
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                self.0.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for Transparent<D> {
     type Error = D::Error;
 
-    forward_to_deserialize_any! {
-        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
-        bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
-        map struct enum identifier ignored_any option
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
     }
 
-    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_some(self.0)
+        visitor.visit_newtype_struct(self.0)
+    }
+
+    forward_deserialize! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]);
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+        deserialize_ignored_any();
     }
 }
 
-struct MaybeLinkVisitor<T>(PhantomData<fn() -> T>);
+fn value<'de, D, T>(deserializer: D) -> Result<MaybeLink<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(Transparent(deserializer)).map(MaybeLink::Value)
+}
+
+/// Visits a value that's either a link or a `T`.
+///
+/// Visitors don't learn a newtype's name, so to tell a CID apart from any other newtype struct we
+/// ask the newtype's contents for the `cid` crate's sentinel newtype, visiting them with
+/// `in_newtype` set. Deserializers that recognize the sentinel (e.g., DAG-CBOR for a tag 42) hand
+/// over the CID's bytes, and anything that re-presents itself as a plain newtype is decoded as a
+/// value. Deserializers that ignore newtype names entirely (e.g., serde's buffering for untagged
+/// enums) can't be told apart from the former, so their bytes are a link only if they parse as a
+/// CID.
+///
+/// `Some` is unwrapped by visiting its contents with the same visitor, at most
+/// [`MAX_OPTION_DEPTH`] levels deep, so nested options from a hostile input can't overflow the
+/// stack.
+struct MaybeLinkVisitor<T> {
+    in_newtype: bool,
+    /// The number of `Some`s unwrapped so far.
+    depth: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// How many nested `Some`s [`MaybeLinkVisitor`] unwraps before giving up.
+const MAX_OPTION_DEPTH: usize = 64;
+
+impl<T> MaybeLinkVisitor<T> {
+    fn new(in_newtype: bool, depth: usize) -> Self {
+        MaybeLinkVisitor {
+            in_newtype,
+            depth,
+            _marker: PhantomData,
+        }
+    }
+}
 
 fn visit_value<'de, V, E, T>(
     v: V,
@@ -94,7 +399,7 @@ where
     V: IntoDeserializer<'de, E>,
     E: serde::de::Error,
 {
-    Deserialize::deserialize(v.into_deserializer()).map(MaybeLink::Value)
+    value(v.into_deserializer())
 }
 
 impl<'de, T> Visitor<'de> for MaybeLinkVisitor<T>
@@ -103,7 +408,7 @@ where
 {
     type Value = MaybeLink<T>;
 
-    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    fn expecting(&self, formatter: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(formatter, "failed to decode into a 'maybe link'")
     }
 
@@ -230,6 +535,11 @@ where
     where
         E: serde::de::Error,
     {
+        if self.in_newtype {
+            if let Ok(k) = Cid::try_from(v) {
+                return Ok(MaybeLink::Link(k));
+            }
+        }
         visit_value(v)
     }
 
@@ -237,6 +547,9 @@ where
     where
         E: serde::de::Error,
     {
+        if self.in_newtype {
+            return self.visit_bytes(v);
+        }
         visit_value(v)
     }
 
@@ -244,6 +557,9 @@ where
     where
         E: serde::de::Error,
     {
+        if self.in_newtype {
+            return self.visit_bytes(&v);
+        }
         visit_value(v)
     }
 
@@ -251,39 +567,353 @@ where
     where
         E: serde::de::Error,
     {
-        T::deserialize(NoneDeserializer(PhantomData)).map(MaybeLink::Value)
+        value(NoneDeserializer(PhantomData))
     }
 
     fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        // TODO: is this right? This will just recurse on "some", which is likely the best we can
-        // do.
-        deserializer.deserialize_any(self)
+        // Options are transparent: `Some(x)` is read as `x`.
+        if self.depth >= MAX_OPTION_DEPTH {
+            return Err(serde::de::Error::custom(format_args!(
+                "more than {MAX_OPTION_DEPTH} nested options"
+            )));
+        }
+        deserializer.deserialize_any(MaybeLinkVisitor::new(self.in_newtype, self.depth + 1))
     }
 
     fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        // TODO: is this safe?
-        deserializer
-            .deserialize_bytes(BytesToCidVisitor)
-            .map(MaybeLink::Link)
+        if self.in_newtype {
+            return value(deserializer);
+        }
+        deserializer.deserialize_newtype_struct(
+            CID_SERDE_PRIVATE_IDENTIFIER,
+            MaybeLinkVisitor::new(true, self.depth),
+        )
     }
 
     fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
     where
         A: serde::de::SeqAccess<'de>,
     {
-        Deserialize::deserialize(SeqAccessDeserializer::new(seq)).map(MaybeLink::Value)
+        value(SeqAccessDeserializer::new(seq))
     }
 
     fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
     where
         A: serde::de::MapAccess<'de>,
     {
-        Deserialize::deserialize(MapAccessDeserializer::new(map)).map(MaybeLink::Value)
+        // A DAG-JSON link is a map with a single "/" key whose value is the CID as a string. Any
+        // other map (including other "/" maps, like DAG-JSON's bytes) is replayed into `T`.
+        let mut replay = Replay {
+            pending: VecDeque::new(),
+            value: None,
+            map,
+        };
+        match replay.map.next_key::<Value>()? {
+            Some(key) if matches!(&key, Value::String(k) if k == "/") => {
+                let slash: Value = replay.map.next_value()?;
+                let next = replay.map.next_key::<Value>()?;
+                if let (Value::String(s), None) = (&slash, &next) {
+                    if let Ok(k) = Cid::try_from(s.as_str()) {
+                        return Ok(MaybeLink::Link(k));
+                    }
+                }
+                replay.pending.push_back((key, Some(slash)));
+                replay.pending.extend(next.map(|k| (k, None)));
+            }
+            Some(key) => replay.pending.push_back((key, None)),
+            None => {}
+        }
+        value(MapAccessDeserializer::new(replay))
+    }
+}
+
+/// Yields the keys (and values, if they were read) already read from `map` by
+/// [`MaybeLinkVisitor::visit_map`], then the rest of `map`.
+struct Replay<A> {
+    pending: VecDeque<(Value, Option<Value>)>,
+    value: Option<Value>,
+    map: A,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for Replay<A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.pending.pop_front() {
+            Some((key, value)) => {
+                self.value = value;
+                seed.deserialize(ValueDeserializer::new(key)).map(Some)
+            }
+            None => self.map.next_key_seed(seed),
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(v) => seed.deserialize(ValueDeserializer::new(v)),
+            None => self.map.next_value_seed(seed),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::collections::BTreeMap;
+
+    use cid::Cid;
+    use serde::{
+        de::{
+            value::{Error, MapDeserializer, SeqDeserializer},
+            Visitor,
+        },
+        forward_to_deserialize_any, Deserialize, Deserializer, Serialize,
+    };
+
+    use crate::{MaybeLink, MemoryStore, StaticStore, TaggedMaybeLink};
+
+    #[test]
+    fn roundtrip() {
+        type Store = MemoryStore;
+
+        let k: Cid = Store::store(&"linked", None).unwrap();
+        let map = BTreeMap::from([("a".to_owned(), 1u64)]);
+        let values = (
+            MaybeLink::Value("inline".to_owned()),
+            MaybeLink::<String>::Link(k),
+            MaybeLink::Value(map.clone()),
+        );
+
+        let bytes = Store::encode(&values).unwrap();
+        let decoded: (
+            MaybeLink<String>,
+            MaybeLink<String>,
+            MaybeLink<BTreeMap<String, u64>>,
+        ) = Store::decode(&bytes).unwrap();
+        assert!(matches!(&decoded.0, MaybeLink::Value(v) if v == "inline"));
+        assert!(matches!(decoded.1, MaybeLink::Link(l) if l == k));
+        assert!(matches!(decoded.2, MaybeLink::Value(v) if v == map));
+
+        assert_eq!(format!("{:?}", decoded.0), "Value(..)");
+        assert_eq!(format!("{:?}", decoded.1), format!("Link({k:?})"));
+    }
+
+    #[derive(Deserialize, Serialize, Debug, PartialEq)]
+    struct Wrapper(Vec<u8>);
+
+    /// Presents the inner deserializer as a newtype struct, like formats that record newtypes.
+    struct Newtype<D>(D);
+
+    impl<'de, D: Deserializer<'de>> Deserializer<'de> for Newtype<D> {
+        type Error = D::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+            visitor.visit_newtype_struct(self.0)
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
+            map struct enum identifier ignored_any option
+        }
+    }
+
+    #[test]
+    fn newtypes_are_not_links() {
+        type Store = MemoryStore;
+
+        let k: Cid = Store::store(&"linked", None).unwrap();
+        let decoded: MaybeLink<Wrapper> = Store::decode(&Store::encode(&k).unwrap()).unwrap();
+        assert!(matches!(decoded, MaybeLink::Link(l) if l == k));
+
+        let wrapper = Wrapper(vec![1, 2, 3]);
+        let decoded: MaybeLink<Wrapper> = Store::decode(&Store::encode(&wrapper).unwrap()).unwrap();
+        assert_eq!(decoded.into_value(), Some(wrapper));
+
+        // A newtype from a format that records them goes through the same path as the CID above,
+        // but decodes as a value, even if its contents are a CID's bytes.
+        let contents = SeqDeserializer::<_, serde::de::value::Error>::new(k.to_bytes().into_iter());
+        let decoded = MaybeLink::<Wrapper>::deserialize(Newtype(contents)).unwrap();
+        assert_eq!(decoded.into_value(), Some(Wrapper(k.to_bytes())));
+    }
+
+    type Links = (
+        MaybeLink<String>,
+        MaybeLink<String>,
+        MaybeLink<BTreeMap<String, u64>>,
+    );
+
+    #[test]
+    fn roundtrip_json() {
+        let k: Cid = MemoryStore::<()>::store(&"linked", None).unwrap();
+        let map = BTreeMap::from([("a".to_owned(), 1u64)]);
+        let values = (
+            MaybeLink::Value("inline".to_owned()),
+            MaybeLink::<String>::Link(k),
+            MaybeLink::Value(map.clone()),
+        );
+
+        let cbor = serde_ipld_dagcbor::to_vec(&values).unwrap();
+        let json = serde_ipld_dagjson::to_vec(&values).unwrap();
+        for decoded in [
+            serde_ipld_dagcbor::from_slice::<Links>(&cbor).unwrap(),
+            serde_ipld_dagjson::from_slice::<Links>(&json).unwrap(),
+        ] {
+            assert!(matches!(&decoded.0, MaybeLink::Value(v) if v == "inline"));
+            assert!(matches!(decoded.1, MaybeLink::Link(l) if l == k));
+            assert!(matches!(decoded.2, MaybeLink::Value(v) if v == map));
+        }
+    }
+
+    #[test]
+    fn json_style_link_maps() {
+        let k: Cid = MemoryStore::<()>::store(&"linked", None).unwrap();
+        let decode = |entries: &[(&str, String)]| {
+            let map = MapDeserializer::<_, Error>::new(entries.iter().cloned());
+            MaybeLink::<BTreeMap<String, String>>::deserialize(map).unwrap()
+        };
+
+        let link = decode(&[("/", k.to_string())]);
+        assert_eq!(link.link(), Some(k));
+
+        // Anything else decodes as a map, with all of its entries.
+        for entries in [
+            &[("/", "not a cid".to_owned())][..],
+            &[("/", k.to_string()), ("other", "x".to_owned())],
+            &[("other", "x".to_owned()), ("/", k.to_string())],
+            &[],
+        ] {
+            let expected: BTreeMap<_, _> = entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect();
+            assert_eq!(decode(entries).into_value(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn combinators() {
+        let k: Cid = MemoryStore::<()>::store(&"linked", None).unwrap();
+        let value = MaybeLink::Value("inline".to_owned());
+        let link = MaybeLink::<String>::Link(k);
+
+        assert_eq!(value.as_ref().map(String::len).into_value(), Some(6));
+        assert_eq!(link.as_ref().map(String::len).into_value(), None);
+        assert_eq!((value.link(), link.link()), (None, Some(k)));
+        assert_eq!(value.into_value().as_deref(), Some("inline"));
+    }
+
+    #[test]
+    fn resolve_loads_links() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k: Cid = Store::store(&"linked", None).unwrap();
+        let value = MaybeLink::Value("inline".to_owned());
+        assert_eq!(value.resolve::<Store>().unwrap(), "inline");
+        let link = MaybeLink::<String>::Link(k);
+        assert_eq!(link.resolve::<Store>().unwrap(), "linked");
+
+        let missing = MaybeLink::<String>::Link(MemoryStore::<()>::store(&"other", None).unwrap());
+        assert!(Store::is_not_found(
+            &missing.resolve::<Store>().unwrap_err()
+        ));
+    }
+
+    /// A deserializer for a value wrapped in this many `Some`s.
+    struct Nested(usize);
+
+    impl<'de> Deserializer<'de> for Nested {
+        type Error = Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self.0 {
+                0 => visitor.visit_u64(1),
+                n => visitor.visit_some(Nested(n - 1)),
+            }
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
+            map struct enum identifier ignored_any option
+        }
+    }
+
+    #[test]
+    fn nested_options_are_limited() {
+        assert!(matches!(
+            MaybeLink::<u64>::deserialize(Nested(3)),
+            Ok(MaybeLink::Value(1))
+        ));
+        let err = MaybeLink::<u64>::deserialize(Nested(1_000_000))
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "more than 64 nested options");
+    }
+
+    #[test]
+    fn tagged_roundtrip() {
+        let k: Cid = MemoryStore::<()>::store(&"linked", None).unwrap();
+        let tagged = (
+            TaggedMaybeLink::Value("inline".to_owned()),
+            TaggedMaybeLink::<String>::Link(k),
+        );
+        let untagged = (
+            MaybeLink::Value("inline".to_owned()),
+            MaybeLink::<String>::Link(k),
+        );
+
+        let json = serde_ipld_dagjson::to_vec(&tagged).unwrap();
+        assert_eq!(
+            String::from_utf8(json.clone()).unwrap(),
+            format!(r#"[{{"value":"inline"}},{{"link":{{"/":"{k}"}}}}]"#)
+        );
+        let cbor = serde_ipld_dagcbor::to_vec(&tagged).unwrap();
+        for decoded in [
+            serde_ipld_dagcbor::from_slice::<(TaggedMaybeLink<String>, TaggedMaybeLink<String>)>(
+                &cbor,
+            )
+            .unwrap(),
+            serde_ipld_dagjson::from_slice(&json).unwrap(),
+        ] {
+            assert_eq!(decoded, tagged);
+            let (value, link) = (MaybeLink::from(decoded.0), MaybeLink::from(decoded.1));
+            assert!(matches!(value, MaybeLink::Value(v) if v == "inline"));
+            assert_eq!(link.link(), Some(k));
+        }
+
+        // The representations don't mix: the tagged form is a map to an untagged `MaybeLink`.
+        let untagged_json = serde_ipld_dagjson::to_vec(&untagged).unwrap();
+        assert_eq!(
+            String::from_utf8(untagged_json.clone()).unwrap(),
+            format!(r#"["inline",{{"/":"{k}"}}]"#)
+        );
+        let decoded: Vec<MaybeLink<BTreeMap<String, crate::Value>>> =
+            serde_ipld_dagjson::from_slice(&json).unwrap();
+        assert!(decoded.iter().all(|v| v.link().is_none()));
+        assert!(
+            serde_ipld_dagjson::from_slice::<(TaggedMaybeLink<String>, TaggedMaybeLink<String>)>(
+                &untagged_json
+            )
+            .is_err()
+        );
+
+        for bad in [r#"{}"#, r#"{"other":1}"#, r#"{"value":"a","link":"b"}"#] {
+            assert!(
+                serde_ipld_dagjson::from_slice::<TaggedMaybeLink<String>>(bad.as_bytes()).is_err()
+            );
+        }
     }
 }
@@ -0,0 +1,375 @@
+use std::{
+    any::TypeId,
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{BufReader, Read, Write},
+    marker::PhantomData,
+    sync::{LazyLock, Mutex},
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{store, CasError, CidShape, PinStore, RefStore, StaticStore, StoreError};
+
+type Blocks = HashMap<Cid, Vec<u8>>;
+
+static BLOCKS: LazyLock<Mutex<HashMap<TypeId, Blocks>>> = LazyLock::new(Default::default);
+static REFS: LazyLock<Mutex<HashMap<TypeId, HashMap<String, Cid>>>> =
+    LazyLock::new(Default::default);
+static PINS: LazyLock<Mutex<HashMap<TypeId, HashSet<Cid>>>> = LazyLock::new(Default::default);
+
+/// An in-memory DAG-CBOR store, for tests and examples.
+///
+/// Blocks are kept in a process-global map, so they're shared by everything that names the same
+/// store type and no handle needs to be passed around. Each distinct `Tag` gets its own set of
+/// blocks, so tests running in parallel can stay isolated by each using a local tag type:
+///
+/// ```
+/// use auto_ipld::{Link, MemoryStore, StaticStore};
+///
+/// struct Tag;
+/// type Store = MemoryStore<Tag>;
+///
+/// let link: Link<String, Store> = Link::from_value("hello".into(), None);
+/// let k = link.save().unwrap();
+/// assert!(Store::contains(&k));
+/// assert_eq!(Store::load::<String>(&k).unwrap(), "hello");
+/// ```
+///
+/// CIDs are computed from the requested [`CidShape`], defaulting to DAG-CBOR with SHA2-256.
+///
+/// `MemoryStore` is also a [`RefStore`], with references guarded by a process-global mutex, and a
+/// [`PinStore`], with pins kept (and lost) with its blocks.
+pub struct MemoryStore<Tag = ()>(PhantomData<Tag>);
+
+/// An error returned by [`MemoryStore`].
+#[derive(Debug)]
+pub enum MemoryError {
+    /// The block isn't in the store.
+    NotFound(Cid),
+    /// The value couldn't be encoded or decoded, or the multihash isn't supported.
+    Codec(String),
+    Store(StoreError),
+    /// An error from a labeled link (see [`Link::with_label`](crate::Link::with_label)).
+    Labeled {
+        label: &'static str,
+        source: Box<MemoryError>,
+    },
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryError::NotFound(k) => write!(f, "block {k} not found"),
+            MemoryError::Codec(e) => write!(f, "codec error: {e}"),
+            MemoryError::Store(e) => e.fmt(f),
+            MemoryError::Labeled { label, source } => write!(f, "link `{label}`: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for MemoryError {}
+
+impl From<StoreError> for MemoryError {
+    fn from(e: StoreError) -> Self {
+        MemoryError::Store(e)
+    }
+}
+
+impl<Tag: 'static> MemoryStore<Tag> {
+    pub(crate) fn with_blocks<R>(f: impl FnOnce(&mut Blocks) -> R) -> R {
+        f(BLOCKS
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<Tag>())
+            .or_default())
+    }
+
+    /// The number of blocks in the store.
+    pub fn len() -> usize {
+        Self::with_blocks(|b| b.len())
+    }
+
+    /// Returns true if the store contains no blocks.
+    pub fn is_empty() -> bool {
+        Self::len() == 0
+    }
+
+    /// Returns true if the store contains the block.
+    pub fn contains(k: &Cid) -> bool {
+        Self::with_blocks(|b| b.contains_key(k))
+    }
+
+    /// Remove every block from the store.
+    pub fn clear() {
+        Self::with_blocks(|b| b.clear())
+    }
+}
+
+impl<Tag: 'static> StaticStore for MemoryStore<Tag> {
+    type Error = MemoryError;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        Self::store_if_absent(value, shape).map(|(k, _)| k)
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        let k = shape.copied().unwrap_or_default().cid(value)?;
+        let new = Self::with_blocks(|b| b.insert(k, value.to_vec()).is_none());
+        Ok((k, new))
+    }
+
+    /// Inserts every block under one lock, so other threads see all of them or none.
+    fn store_many(blocks: &[(Vec<u8>, Option<CidShape>)]) -> Result<Vec<(Cid, bool)>, Self::Error> {
+        let keys = blocks
+            .iter()
+            .map(|(block, shape)| shape.unwrap_or_default().cid(block))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::with_blocks(|b| {
+            keys.into_iter()
+                .zip(blocks)
+                .map(|(k, (block, _))| (k, b.insert(k, block.clone()).is_none()))
+                .collect()
+        }))
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        serde_ipld_dagcbor::to_vec(value).map_err(|e| MemoryError::Codec(e.to_string()))
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        serde_ipld_dagcbor::to_writer(w, value).map_err(|e| MemoryError::Codec(e.to_string()))
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        Self::with_blocks(|b| b.get(k).cloned()).ok_or(MemoryError::NotFound(*k))
+    }
+
+    /// Checks the block's size before copying it.
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        Self::with_blocks(|b| {
+            let block = b.get(k).ok_or(MemoryError::NotFound(*k))?;
+            store::check_block_size(k, block.len(), max)?;
+            Ok(block.clone())
+        })
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(Self::contains(k))
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        Self::with_blocks(|b| b.get(k).map(Vec::len)).ok_or(MemoryError::NotFound(*k))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        serde_ipld_dagcbor::from_slice(bytes).map_err(|e| MemoryError::Codec(e.to_string()))
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        serde_ipld_dagcbor::from_slice(bytes).map_err(|e| MemoryError::Codec(e.to_string()))
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        serde_ipld_dagcbor::from_reader(BufReader::new(r))
+            .map_err(|e| MemoryError::Codec(e.to_string()))
+    }
+
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        let (start, end) = (start.to_bytes(), end.to_bytes());
+        let mut keys: Vec<Cid> = Self::with_blocks(|b| {
+            b.keys()
+                .filter(|k| (start.as_slice()..end.as_slice()).contains(&k.to_bytes().as_slice()))
+                .copied()
+                .collect()
+        });
+        keys.sort_by_key(Cid::to_bytes);
+        Ok(keys)
+    }
+
+    /// Returns the keys sorted by their binary encoding, like `keys_in_range`.
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        let mut keys: Vec<Cid> = Self::with_blocks(|b| b.keys().copied().collect());
+        keys.sort_by_key(Cid::to_bytes);
+        Ok(keys)
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(Self::with_blocks(|b| b.remove(k).is_some()))
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        match err {
+            MemoryError::NotFound(_) | MemoryError::Store(StoreError::NotFound(_)) => true,
+            MemoryError::Labeled { source, .. } => Self::is_not_found(source),
+            _ => false,
+        }
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        MemoryError::Labeled {
+            label,
+            source: Box::new(err),
+        }
+    }
+}
+
+impl<Tag: 'static> RefStore for MemoryStore<Tag> {
+    fn get_ref(name: &str) -> Result<Option<Cid>, Self::Error> {
+        let refs = REFS.lock().unwrap();
+        Ok(refs
+            .get(&TypeId::of::<Tag>())
+            .and_then(|r| r.get(name))
+            .copied())
+    }
+
+    fn compare_and_swap_ref(
+        name: &str,
+        expected: Option<&Cid>,
+        new: Option<&Cid>,
+    ) -> Result<(), CasError<Self::Error>> {
+        let mut refs = REFS.lock().unwrap();
+        let refs = refs.entry(TypeId::of::<Tag>()).or_default();
+        let actual = refs.get(name).copied();
+        if actual.as_ref() != expected {
+            return Err(CasError::Conflict { actual });
+        }
+        match new {
+            Some(k) => refs.insert(name.to_owned(), *k),
+            None => refs.remove(name),
+        };
+        Ok(())
+    }
+}
+
+impl<Tag: 'static> MemoryStore<Tag> {
+    fn with_pins<R>(f: impl FnOnce(&mut HashSet<Cid>) -> R) -> R {
+        f(PINS.lock().unwrap().entry(TypeId::of::<Tag>()).or_default())
+    }
+}
+
+impl<Tag: 'static> PinStore for MemoryStore<Tag> {
+    fn pin(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(Self::with_pins(|p| p.insert(*k)))
+    }
+
+    fn unpin(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(Self::with_pins(|p| p.remove(k)))
+    }
+
+    fn pins() -> Result<Vec<Cid>, Self::Error> {
+        Ok(Self::with_pins(|p| p.iter().copied().collect()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::Cid;
+
+    use std::{sync::Barrier, thread};
+
+    use crate::{CasError, MemoryStore, RefStore, StaticStore};
+
+    #[test]
+    fn keys_in_range() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let mut keys: Vec<Cid> = (0..10).map(|i| Store::store(&i, None).unwrap()).collect();
+        keys.sort_by_key(Cid::to_bytes);
+
+        assert_eq!(
+            Store::keys_in_range(&keys[2], &keys[7]).unwrap(),
+            keys[2..7]
+        );
+        assert_eq!(Store::keys_in_range(&keys[0], &keys[9]).unwrap(), keys[..9]);
+        assert!(Store::keys_in_range(&keys[5], &keys[5]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn borrowed_decodes_dont_copy() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Entry<'a> {
+            name: &'a str,
+            kind: &'a str,
+        }
+
+        let bytes = MemoryStore::<()>::encode(&Entry {
+            name: "notes.txt",
+            kind: "file",
+        })
+        .unwrap();
+        let entry: Entry = MemoryStore::<()>::decode_borrowed(&bytes).unwrap();
+        assert_eq!((entry.name, entry.kind), ("notes.txt", "file"));
+        // Both fields point into the block.
+        let block = bytes.as_ptr_range();
+        assert!(block.contains(&entry.name.as_ptr()) && block.contains(&entry.kind.as_ptr()));
+    }
+
+    #[test]
+    fn store_many_reports_new_blocks() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        // Build a small tree bottom-up, one level per batch.
+        let leaves: Vec<_> = (0..4).map(|i| (Store::encode(&i).unwrap(), None)).collect();
+        let leaf_keys: Vec<Cid> = Store::store_many(&leaves)
+            .unwrap()
+            .into_iter()
+            .map(|(k, new)| {
+                assert!(new);
+                k
+            })
+            .collect();
+        let root = vec![(Store::encode(&leaf_keys).unwrap(), None)];
+        let [(root_key, true)] = Store::store_many(&root).unwrap()[..] else {
+            panic!("expected the root to be new")
+        };
+
+        // Saving the same tree again writes nothing new.
+        let all: Vec<_> = leaves.into_iter().chain(root).collect();
+        let again = Store::store_many(&all).unwrap();
+        assert_eq!(again.len(), 5);
+        assert!(again.iter().all(|(_, new)| !new));
+        assert_eq!(again[4].0, root_key);
+        assert_eq!(Store::len(), 5);
+    }
+
+    #[test]
+    fn racing_ref_updates() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let old = Store::store(&"old", None).unwrap();
+        Store::compare_and_swap_ref("root", None, Some(&old)).unwrap();
+
+        let barrier = Barrier::new(2);
+        let results: Vec<_> = thread::scope(|s| {
+            let racers: Vec<_> = ["a", "b"]
+                .into_iter()
+                .map(|name| {
+                    let barrier = &barrier;
+                    s.spawn(move || {
+                        let new = Store::store(&name, None).unwrap();
+                        barrier.wait();
+                        (
+                            new,
+                            Store::compare_and_swap_ref("root", Some(&old), Some(&new)),
+                        )
+                    })
+                })
+                .collect();
+            racers.into_iter().map(|r| r.join().unwrap()).collect()
+        });
+
+        let (winners, losers): (Vec<_>, Vec<_>) = results.into_iter().partition(|(_, r)| r.is_ok());
+        let ([(winner, _)], [(_, Err(CasError::Conflict { actual }))]) =
+            (&winners[..], &losers[..])
+        else {
+            panic!("expected exactly one winner and one conflict")
+        };
+        assert_eq!(*actual, Some(*winner));
+        assert_eq!(Store::get_ref("root").unwrap(), Some(*winner));
+    }
+}
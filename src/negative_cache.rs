@@ -0,0 +1,321 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    io::{Read, Write},
+    marker::PhantomData,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{CidShape, StaticStore, StoreError};
+
+struct Cache {
+    capacity: usize,
+    ttl: Duration,
+    /// When each missing block was last found to be missing.
+    missing: HashMap<Cid, Instant>,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache {
+            capacity: 1024,
+            ttl: Duration::from_secs(30),
+            missing: HashMap::new(),
+        }
+    }
+}
+
+static CACHES: LazyLock<Mutex<HashMap<TypeId, Cache>>> = LazyLock::new(Default::default);
+
+/// A store wrapper that remembers which blocks `S` recently reported missing, and fails further
+/// loads of them with [`StoreError::NotFound`] (and [`StaticStore::has`] checks with `false`)
+/// without asking `S` again. This saves round-trips
+/// when repeatedly probing a remote store for blocks that haven't arrived yet.
+///
+/// Misses are recognized with [`StaticStore::is_not_found`], so `S` must override it. A miss is
+/// remembered for the configured TTL (30 seconds by default) or until the block is stored through
+/// this wrapper. Blocks written to `S` directly (or by another process) aren't noticed until their
+/// entries expire.
+///
+/// At most `capacity` misses (1024 by default) are remembered; when full, the oldest is forgotten.
+/// The cache is process-global and keyed by the inner store type, like
+/// [`FaultyStore`](crate::FaultyStore)'s schedule.
+pub struct NegativeCacheStore<S>(PhantomData<S>);
+
+impl<S: 'static> NegativeCacheStore<S> {
+    fn with_cache<R>(f: impl FnOnce(&mut Cache) -> R) -> R {
+        f(CACHES.lock().unwrap().entry(TypeId::of::<S>()).or_default())
+    }
+
+    /// Set the maximum number of remembered misses and how long each is remembered, forgetting
+    /// any remembered so far.
+    pub fn configure(capacity: usize, ttl: Duration) {
+        Self::with_cache(|c| {
+            *c = Cache {
+                capacity,
+                ttl,
+                missing: HashMap::new(),
+            }
+        })
+    }
+
+    /// Returns true if `k` is currently remembered as missing.
+    pub fn is_known_missing(k: &Cid) -> bool {
+        Self::with_cache(|c| {
+            let ttl = c.ttl;
+            match c.missing.get(k) {
+                Some(at) if at.elapsed() < ttl => true,
+                Some(_) => {
+                    c.missing.remove(k);
+                    false
+                }
+                None => false,
+            }
+        })
+    }
+
+    fn remember(k: &Cid) {
+        Self::with_cache(|c| {
+            if c.capacity == 0 {
+                return;
+            }
+            if c.missing.len() >= c.capacity && !c.missing.contains_key(k) {
+                let ttl = c.ttl;
+                c.missing.retain(|_, at| at.elapsed() < ttl);
+                if c.missing.len() >= c.capacity {
+                    let oldest = c.missing.iter().min_by_key(|(_, at)| **at).map(|(k, _)| *k);
+                    c.missing.remove(&oldest.expect("cache is full"));
+                }
+            }
+            c.missing.insert(*k, Instant::now());
+        })
+    }
+
+    fn forget(k: &Cid) {
+        Self::with_cache(|c| c.missing.remove(k));
+    }
+}
+
+impl<S> StaticStore for NegativeCacheStore<S>
+where
+    S: StaticStore + 'static,
+{
+    type Error = S::Error;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        let k = S::store_bytes(value, shape)?;
+        Self::forget(&k);
+        Ok(k)
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        let (k, new) = S::store_if_absent(value, shape)?;
+        Self::forget(&k);
+        Ok((k, new))
+    }
+
+    fn store_many(blocks: &[(Vec<u8>, Option<CidShape>)]) -> Result<Vec<(Cid, bool)>, Self::Error> {
+        let stored = S::store_many(blocks)?;
+        for (k, _) in &stored {
+            Self::forget(k);
+        }
+        Ok(stored)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        if Self::is_known_missing(k) {
+            return Err(StoreError::NotFound(*k).into());
+        }
+        S::load_bytes(k).inspect_err(|e| {
+            if S::is_not_found(e) {
+                Self::remember(k);
+            }
+        })
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        if Self::is_known_missing(k) {
+            return Err(StoreError::NotFound(*k).into());
+        }
+        S::load_bytes_limited(k, max).inspect_err(|e| {
+            if S::is_not_found(e) {
+                Self::remember(k);
+            }
+        })
+    }
+
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        if Self::is_known_missing(k) {
+            return Err(StoreError::NotFound(*k).into());
+        }
+        S::load_bytes_deadline(k, deadline).inspect_err(|e| {
+            if S::is_not_found(e) {
+                Self::remember(k);
+            }
+        })
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        if Self::is_known_missing(k) {
+            return Ok(false);
+        }
+        let has = S::has(k)?;
+        if !has {
+            Self::remember(k);
+        }
+        Ok(has)
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        S::block_size(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        S::keys_in_range(start, end)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        S::keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        S::remove(k)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use crate::{FaultyStore, MemoryStore, NegativeCacheStore, StaticStore};
+
+    #[test]
+    fn remembers_missing_blocks() {
+        struct Tag;
+        type Inner = FaultyStore<MemoryStore<Tag>>;
+        type Store = NegativeCacheStore<Inner>;
+
+        // Compute the CID without storing the block.
+        let k = MemoryStore::<()>::store(&"not yet", None).unwrap();
+        Inner::fail_when(|_| false);
+
+        let err = Store::load::<String>(&k).unwrap_err();
+        assert!(Store::is_not_found(&err), "{err}");
+        assert_eq!(Inner::operations(), 1);
+        assert!(Store::is_known_missing(&k));
+
+        let err = Store::load::<String>(&k).unwrap_err();
+        assert!(Store::is_not_found(&err), "{err}");
+        assert_eq!(Inner::operations(), 1);
+
+        // Storing the block clears the entry.
+        assert_eq!(Store::store(&"not yet", None).unwrap(), k);
+        assert!(!Store::is_known_missing(&k));
+        assert_eq!(Store::load::<String>(&k).unwrap(), "not yet");
+        assert_eq!(Inner::operations(), 3);
+        Inner::reset();
+    }
+
+    #[test]
+    fn entries_expire_and_are_bounded() {
+        struct Tag;
+        type Store = NegativeCacheStore<MemoryStore<Tag>>;
+
+        Store::configure(2, Duration::from_secs(60));
+        let keys: Vec<_> = (0..3)
+            .map(|i| MemoryStore::<()>::store(&i, None).unwrap())
+            .collect();
+        for k in &keys {
+            Store::load_bytes(k).unwrap_err();
+        }
+        // The oldest miss was evicted.
+        let known: Vec<_> = keys.iter().map(Store::is_known_missing).collect();
+        assert_eq!(known, [false, true, true]);
+
+        Store::configure(2, Duration::ZERO);
+        Store::load_bytes(&keys[0]).unwrap_err();
+        assert!(!Store::is_known_missing(&keys[0]));
+    }
+}
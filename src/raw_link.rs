@@ -0,0 +1,234 @@
+use core::{
+    cell::{Cell, OnceCell},
+    fmt,
+    marker::PhantomData,
+};
+
+use alloc::vec::Vec;
+
+use cid::Cid;
+use serde::{ser::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{error, link_as_cid, store, AsCid, CidShape, StaticStore, RAW};
+
+/// The shape of the blocks [`RawLink`]s save: raw, hashed with SHA2-256.
+const RAW_SHAPE: CidShape = CidShape::DEFAULT.with_codec(RAW);
+
+/// A link to an opaque blob of bytes (e.g., an image, or a chunk of a file) stored as a
+/// [`RAW`] block. The bytes are the block: they're stored with [`StaticStore::store_bytes`] and
+/// read with [`StaticStore::load_bytes`], never encoded or decoded, where a `Link<Vec<u8>, _>`
+/// would frame them as a DAG-CBOR byte string (or array).
+///
+/// Like a [`Link`](crate::Link), it's loaded lazily, saved when serialized, serializes as a CID,
+/// and is `Send` but not `Sync`.
+pub struct RawLink<Store> {
+    bytes: OnceCell<Vec<u8>>,
+    state: Cell<RawState>,
+    _marker: PhantomData<fn(Store)>,
+}
+
+#[derive(Copy, Clone)]
+enum RawState {
+    Unmodified(Cid),
+    Modified,
+}
+
+impl<Store> Clone for RawLink<Store> {
+    fn clone(&self) -> Self {
+        Self {
+            bytes: self.bytes.clone(),
+            state: self.state.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Shows the link's CID (or that it's modified) and whether its bytes are cached, like
+/// [`Link`](crate::Link)'s.
+impl<Store> fmt::Debug for RawLink<Store> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("RawLink");
+        match self.state.get() {
+            RawState::Unmodified(k) => s.field("cid", &k),
+            RawState::Modified => s.field("modified", &true),
+        };
+        s.field("loaded", &self.bytes.get().is_some()).finish()
+    }
+}
+
+impl<Store: StaticStore> Serialize for RawLink<Store> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let k = self.save().map_err(|e| {
+            let msg = S::Error::custom(&e);
+            error::stash_error(e);
+            msg
+        })?;
+        link_as_cid::serialize(&k, serializer)
+    }
+}
+
+impl<'de, Store> Deserialize<'de> for RawLink<Store> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Self::new(link_as_cid::deserialize(deserializer)?))
+    }
+}
+
+impl<Store> From<Vec<u8>> for RawLink<Store> {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+/// See [`RawLink::cid`].
+impl<Store> AsCid for RawLink<Store> {
+    fn as_cid(&self) -> Option<Cid> {
+        self.cid()
+    }
+}
+
+impl<Store> RawLink<Store> {
+    /// Construct a new `RawLink` from a `Cid`.
+    #[must_use]
+    pub fn new(k: Cid) -> Self {
+        Self {
+            bytes: OnceCell::new(),
+            state: Cell::new(RawState::Unmodified(k)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Construct a new `RawLink` holding `bytes`, stored on its next save.
+    #[must_use]
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes: OnceCell::from(bytes),
+            state: Cell::new(RawState::Modified),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The CID of the stored block this link points to, or `None` if the link has been modified
+    /// and not yet saved.
+    pub fn cid(&self) -> Option<Cid> {
+        match self.state.get() {
+            RawState::Unmodified(k) => Some(k),
+            RawState::Modified => None,
+        }
+    }
+
+    /// Returns true if the link has been modified and not yet saved.
+    pub fn is_dirty(&self) -> bool {
+        matches!(self.state.get(), RawState::Modified)
+    }
+
+    /// Returns true if the bytes are cached.
+    pub fn is_loaded(&self) -> bool {
+        self.bytes.get().is_some()
+    }
+
+    /// Read the linked bytes, loading (and, unless [`StaticStore::VERIFY`] is false, verifying)
+    /// the block if it isn't cached. Blocks larger than [`StaticStore::MAX_BLOCK_SIZE`] are
+    /// rejected, and identity CIDs are read from the CID itself.
+    pub fn read(&self) -> Result<&[u8], Store::Error>
+    where
+        Store: StaticStore,
+    {
+        if let Some(bytes) = self.bytes.get() {
+            return Ok(bytes);
+        }
+        let RawState::Unmodified(k) = self.state.get() else {
+            unreachable!("modified link has no bytes")
+        };
+        let bytes = Store::load_block(&k)?;
+        if Store::VERIFY {
+            Store::verify(&k, &bytes)?;
+        }
+        Ok(self.bytes.get_or_init(|| bytes.into_owned()))
+    }
+
+    /// Edit the linked bytes, loading them if needed and marking the link as modified.
+    pub fn edit(&mut self) -> Result<&mut Vec<u8>, Store::Error>
+    where
+        Store: StaticStore,
+    {
+        self.read()?;
+        self.state.set(RawState::Modified);
+        Ok(self.bytes.get_mut().expect("expected bytes"))
+    }
+
+    /// Take the linked bytes, loading them if needed.
+    pub fn into_bytes(self) -> Result<Vec<u8>, Store::Error>
+    where
+        Store: StaticStore,
+    {
+        self.read()?;
+        Ok(self.bytes.into_inner().expect("expected bytes"))
+    }
+
+    /// Write-back the bytes as a raw block if modified, and return the CID. Like
+    /// [`Link::save`](crate::Link::save), this happens automatically when the link is serialized.
+    pub fn save(&self) -> Result<Cid, Store::Error>
+    where
+        Store: StaticStore,
+    {
+        if let RawState::Unmodified(k) = self.state.get() {
+            return Ok(k);
+        }
+        let bytes = self.bytes.get().expect("modified link has no bytes");
+        let (k, saved) = store::store_link_block::<Store>(bytes, Some(&RAW_SHAPE))?;
+        if saved {
+            self.state.set(RawState::Unmodified(k));
+        }
+        Ok(k)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Link, MemoryStore, RawLink, StaticStore, RAW};
+
+    struct Tag;
+    type Store = MemoryStore<Tag>;
+
+    #[derive(Serialize, Deserialize)]
+    struct File {
+        name: String,
+        chunks: Vec<RawLink<Store>>,
+    }
+
+    #[test]
+    fn stores_bytes_as_raw_blocks() {
+        let file = File {
+            name: "photo.jpg".into(),
+            chunks: vec![
+                b"\xff\xd8".to_vec().into(),
+                RawLink::from_bytes(b"\xff\xd9".into()),
+            ],
+        };
+        let root: Link<File, Store> = Link::new(Store::store(&file, None).unwrap());
+
+        let chunks = &root.read().unwrap().chunks;
+        let k = chunks[0].cid().unwrap();
+        assert_eq!(k.codec(), RAW);
+        assert_eq!(Store::load_bytes(&k).unwrap(), b"\xff\xd8");
+        assert!(!chunks[1].is_loaded());
+        assert_eq!(chunks[1].read().unwrap(), b"\xff\xd9");
+
+        let mut chunk = RawLink::<Store>::new(k);
+        chunk.edit().unwrap().push(0);
+        let edited = chunk.save().unwrap();
+        assert_eq!(edited.codec(), RAW);
+        assert_eq!(
+            RawLink::<Store>::new(edited).into_bytes().unwrap(),
+            b"\xff\xd8\0"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::io::{Read, Write};
+
+use alloc::vec::Vec;
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{CidShape, StaticStore, StoreError};
+
+/// A store wrapper that forwards reads to `S` and fails every write with
+/// [`StoreError::ReadOnly`], to guarantee a code path never mutates the store (e.g., when replaying
+/// or verifying a DAG, or serving blocks to untrusted callers).
+///
+/// Encoding is still allowed, so unmodified links serialize as usual; saving a modified link (or
+/// storing a value containing one) fails.
+pub struct ReadOnly<S>(PhantomData<S>);
+
+impl<S: StaticStore> StaticStore for ReadOnly<S> {
+    type Error = S::Error;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
+    fn store_bytes(_value: &[u8], _shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        Err(StoreError::ReadOnly.into())
+    }
+
+    fn store_if_absent(
+        _value: &[u8],
+        _shape: Option<&CidShape>,
+    ) -> Result<(Cid, bool), Self::Error> {
+        Err(StoreError::ReadOnly.into())
+    }
+
+    fn store_many(
+        _blocks: &[(Vec<u8>, Option<CidShape>)],
+    ) -> Result<Vec<(Cid, bool)>, Self::Error> {
+        Err(StoreError::ReadOnly.into())
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    #[cfg(feature = "std")]
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        S::load_bytes(k)
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        S::load_bytes_limited(k, max)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_bytes_deadline(k: &Cid, deadline: std::time::Instant) -> Result<Vec<u8>, Self::Error> {
+        S::load_bytes_deadline(k, deadline)
+    }
+
+    fn load_many(keys: &[Cid]) -> Result<Vec<Vec<u8>>, Self::Error> {
+        S::load_many(keys)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        S::has(k)
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        S::block_size(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    #[cfg(feature = "std")]
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        S::keys_in_range(start, end)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        S::keys()
+    }
+
+    fn remove(_k: &Cid) -> Result<bool, Self::Error> {
+        Err(StoreError::ReadOnly.into())
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use crate::{Link, MemoryError, MemoryStore, ReadOnly, StaticStore, StoreError};
+
+    #[derive(Serialize, Deserialize)]
+    struct Node {
+        name: String,
+        children: Vec<Link<Node, Store>>,
+    }
+
+    struct Tag;
+    type Store = ReadOnly<MemoryStore<Tag>>;
+
+    fn is_read_only(e: &MemoryError) -> bool {
+        matches!(e, MemoryError::Store(StoreError::ReadOnly))
+    }
+
+    #[test]
+    fn reads_but_never_writes() {
+        let node = |name: &str, children| Node {
+            name: name.into(),
+            children,
+        };
+        let leaf = MemoryStore::<Tag>::store(&node("leaf", vec![]), None).unwrap();
+        let root = MemoryStore::<Tag>::store(&node("root", vec![Link::new(leaf)]), None).unwrap();
+        let blocks = MemoryStore::<Tag>::len();
+
+        let root: Link<Node, Store> = Link::new(root);
+        let child = &root.read().unwrap().children[0];
+        assert_eq!(child.read().unwrap().name, "leaf");
+        // Encoding unmodified links is fine.
+        assert_eq!(
+            Store::encode(&root).unwrap(),
+            Store::encode(&root.cid()).unwrap()
+        );
+
+        let mut child: Link<Node, Store> = Link::new(leaf);
+        child.edit().unwrap().name = "changed".into();
+        assert!(is_read_only(&child.save().unwrap_err()));
+        let parent = node("parent", vec![child]);
+        assert!(is_read_only(&Store::store(&parent, None).unwrap_err()));
+        assert_eq!(MemoryStore::<Tag>::len(), blocks);
+    }
+}
@@ -0,0 +1,84 @@
+use core::fmt;
+
+use alloc::vec::Vec;
+
+use cid::Cid;
+
+use crate::StaticStore;
+
+/// An error from [`RefStore::compare_and_swap_ref`].
+#[derive(Debug)]
+pub enum CasError<E> {
+    /// The reference didn't point at the expected block. `actual` is what it pointed at instead.
+    Conflict {
+        actual: Option<Cid>,
+    },
+    Store(E),
+}
+
+impl<E: fmt::Display> fmt::Display for CasError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CasError::Conflict { actual: Some(k) } => write!(f, "reference conflict: now {k}"),
+            CasError::Conflict { actual: None } => write!(f, "reference conflict: now unset"),
+            CasError::Store(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error for CasError<E> {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            CasError::Conflict { .. } => None,
+            CasError::Store(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<E> for CasError<E> {
+    fn from(e: E) -> Self {
+        CasError::Store(e)
+    }
+}
+
+/// A store that also holds named, mutable references to blocks, e.g., the current roots of
+/// mutable data structures.
+pub trait RefStore: StaticStore {
+    /// The block `name` currently points at, if any.
+    fn get_ref(name: &str) -> Result<Option<Cid>, Self::Error>;
+
+    /// Point `name` at `new` (or remove it if `None`), but only if it currently points at
+    /// `expected` (or is unset if `None`). Otherwise, nothing changes and
+    /// [`CasError::Conflict`] reports the current value.
+    ///
+    /// This must be atomic with respect to concurrent updates, using the backend's own
+    /// compare-and-swap where it has one. It's the only way to update a reference, so a
+    /// read-modify-write of a root is: read it with [`RefStore::get_ref`], build and save the new
+    /// root, then swap, retrying from the start on conflict.
+    fn compare_and_swap_ref(
+        name: &str,
+        expected: Option<&Cid>,
+        new: Option<&Cid>,
+    ) -> Result<(), CasError<Self::Error>>;
+}
+
+/// A store that also holds a set of pinned blocks, the roots of the DAGs it must keep.
+/// [`gc_pinned`](crate::gc_pinned) collects everything else: each pin is live along with every
+/// block reachable from it, so pinning a root keeps its whole DAG.
+///
+/// The trait doesn't say whether pins outlive the process. [`MemoryStore`](crate::MemoryStore)'s
+/// pins are as transient as its blocks. A durable store should keep its pins next to its blocks
+/// (e.g., as a table or as marker keys in the same database), update them atomically, and make
+/// `pin` durable before returning, so a crash never leaves a pinned root collectible.
+pub trait PinStore: StaticStore {
+    /// Pin `k`, returning false if it was already pinned. The block doesn't have to be stored
+    /// yet, so a root can be pinned before its DAG is written.
+    fn pin(k: &Cid) -> Result<bool, Self::Error>;
+
+    /// Unpin `k`, returning false if it wasn't pinned. Its blocks stay in the store until they're
+    /// collected.
+    fn unpin(k: &Cid) -> Result<bool, Self::Error>;
+
+    /// Every pinned CID, in no particular order.
+    fn pins() -> Result<Vec<Cid>, Self::Error>;
+}
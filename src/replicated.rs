@@ -0,0 +1,208 @@
+use std::{
+    io::{Read, Write},
+    marker::PhantomData,
+    time::Instant,
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{CidShape, StaticStore};
+
+/// A store that writes every block to both `A` and `B`, and reads from whichever has it.
+///
+/// A write succeeds if at least `QUORUM` backends accept it. The default quorum of 2 requires
+/// both writes to succeed, a quorum of 1 tolerates one failed backend. Reads try `A` first,
+/// then fall back to `B`.
+///
+/// Blocks are encoded and decoded with `A`'s codec. Both backends must compute CIDs the same
+/// way for a given [`CidShape`], which content addressing guarantees for well-behaved stores.
+pub struct ReplicatedStore<A, B, const QUORUM: usize = 2>(PhantomData<(A, B)>);
+
+impl<A, B, const QUORUM: usize> ReplicatedStore<A, B, QUORUM> {
+    /// Combine the results of writing to both replicas, requiring `QUORUM` successes.
+    fn quorum<T, E>(
+        a: Result<T, E>,
+        b: Result<T, E>,
+        merge: impl FnOnce(T, T) -> T,
+    ) -> Result<T, E> {
+        let succeeded = a.is_ok() as usize + b.is_ok() as usize;
+        match (a, b) {
+            (Ok(a), Ok(b)) => Ok(merge(a, b)),
+            (Ok(v), Err(e)) | (Err(e), Ok(v)) => {
+                if succeeded >= QUORUM {
+                    Ok(v)
+                } else {
+                    Err(e)
+                }
+            }
+            (Err(e), Err(_)) => Err(e),
+        }
+    }
+}
+
+impl<A, B, const QUORUM: usize> StaticStore for ReplicatedStore<A, B, QUORUM>
+where
+    A: StaticStore,
+    B: StaticStore,
+    B::Error: Into<A::Error>,
+{
+    type Error = A::Error;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        Self::quorum(
+            A::store_bytes(value, shape),
+            B::store_bytes(value, shape).map_err(Into::into),
+            |ka, kb| {
+                debug_assert_eq!(ka, kb, "replicas computed different CIDs");
+                ka
+            },
+        )
+    }
+
+    /// A block is reported as newly written if any replica didn't have it.
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        Self::quorum(
+            A::store_if_absent(value, shape),
+            B::store_if_absent(value, shape).map_err(Into::into),
+            |(ka, new_a), (kb, new_b)| {
+                debug_assert_eq!(ka, kb, "replicas computed different CIDs");
+                (ka, new_a || new_b)
+            },
+        )
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        A::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        A::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        A::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        A::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        A::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        A::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        A::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        A::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        A::load_bytes(k).or_else(|_| B::load_bytes(k).map_err(Into::into))
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        A::load_bytes_limited(k, max).or_else(|_| B::load_bytes_limited(k, max).map_err(Into::into))
+    }
+
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        A::load_bytes_deadline(k, deadline)
+            .or_else(|_| B::load_bytes_deadline(k, deadline).map_err(Into::into))
+    }
+
+    /// Checks `A` first, then `B`.
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(A::has(k)? || B::has(k).map_err(Into::into)?)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        A::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        A::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        A::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        A::pre_decode(k, bytes)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        A::decode_from(r)
+    }
+
+    /// Returns the union of both replicas' keys.
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        let mut keys = A::keys_in_range(start, end)?;
+        keys.extend(B::keys_in_range(start, end).map_err(Into::into)?);
+        keys.sort_by_key(Cid::to_bytes);
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// Returns the union of both replicas' keys.
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        let mut keys = A::keys()?;
+        keys.extend(B::keys().map_err(Into::into)?);
+        keys.sort_by_key(Cid::to_bytes);
+        keys.dedup();
+        Ok(keys)
+    }
+
+    /// Removes the block from both replicas, returning true if either had it.
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        let in_a = A::remove(k)?;
+        Ok(B::remove(k).map_err(Into::into)? || in_a)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        A::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        A::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        A::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        A::with_cid(err, cid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Link, MemoryStore, ReplicatedStore, StaticStore};
+
+    #[test]
+    fn read_survives_lost_replica() {
+        struct A;
+        struct B;
+        type Store = ReplicatedStore<MemoryStore<A>, MemoryStore<B>>;
+
+        let link: Link<String, Store> = Link::from_value("replicated".into(), None);
+        let k = link.save().unwrap();
+        assert!(MemoryStore::<A>::contains(&k));
+        assert!(MemoryStore::<B>::contains(&k));
+
+        MemoryStore::<A>::clear();
+        assert_eq!(MemoryStore::<A>::len(), 0);
+        assert_eq!(Store::load::<String>(&k).unwrap(), "replicated");
+
+        let link: Link<String, Store> = Link::new(k);
+        assert_eq!(link.read().unwrap(), "replicated");
+    }
+}
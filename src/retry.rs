@@ -0,0 +1,311 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    io::{Read, Write},
+    marker::PhantomData,
+    sync::{LazyLock, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{CidShape, StaticStore, StoreError};
+
+static WAITING: LazyLock<Mutex<HashMap<TypeId, Waiting>>> = LazyLock::new(Default::default);
+
+/// How a [`Retry`] type waits between attempts.
+#[derive(Copy, Clone)]
+struct Waiting {
+    backoff: Backoff,
+    sleep: fn(Duration),
+}
+
+impl Default for Waiting {
+    fn default() -> Self {
+        Waiting {
+            backoff: Backoff::None,
+            sleep: thread::sleep,
+        }
+    }
+}
+
+/// How long [`Retry`] waits between attempts.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Backoff {
+    /// Retry immediately.
+    #[default]
+    None,
+    /// Wait the same delay before every retry.
+    Fixed(Duration),
+    /// Wait the given delay before the first retry, doubling it before each retry after that.
+    Exponential(Duration),
+}
+
+impl Backoff {
+    /// The delay before the given retry (counting from zero).
+    fn delay(self, retry: u32) -> Duration {
+        match self {
+            Backoff::None => Duration::ZERO,
+            Backoff::Fixed(d) => d,
+            Backoff::Exponential(d) => d.saturating_mul(1 << retry.min(31)),
+        }
+    }
+}
+
+/// A store wrapper that retries failed reads and writes up to `N` times (so `N + 1` attempts in
+/// all) before returning the last error, for backends with transient failures (e.g., a network
+/// store that sometimes times out).
+///
+/// Only errors for which [`StaticStore::is_retryable`] returns true are retried, and never
+/// [not-found](StaticStore::is_not_found) errors. Writes can be retried safely because blocks are
+/// content-addressed: storing the same block twice is a no-op. Batched calls
+/// ([`StaticStore::load_many`], [`StaticStore::store_many`]) are retried as a whole.
+///
+/// There's no delay between attempts unless one is configured with [`Retry::set_backoff`], and
+/// delays block the calling thread unless another way to wait is configured with
+/// [`Retry::set_sleep`]. Both are process-global and keyed by the wrapper type, like
+/// [`CachingStore`](crate::CachingStore)'s cache.
+pub struct Retry<S, const N: usize>(PhantomData<S>);
+
+impl<S: StaticStore + 'static, const N: usize> Retry<S, N> {
+    /// Wait according to `backoff` between attempts.
+    pub fn set_backoff(backoff: Backoff) {
+        Self::configure(|w| w.backoff = backoff);
+    }
+
+    /// Wait between attempts by calling `sleep` with the backoff delay, instead of
+    /// [`thread::sleep`] (e.g., to record the delays in a test).
+    pub fn set_sleep(sleep: fn(Duration)) {
+        Self::configure(|w| w.sleep = sleep);
+    }
+
+    fn configure(f: impl FnOnce(&mut Waiting)) {
+        f(WAITING
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<Self>())
+            .or_default());
+    }
+
+    fn waiting() -> Waiting {
+        WAITING
+            .lock()
+            .unwrap()
+            .get(&TypeId::of::<Self>())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Run `op` until it succeeds, fails with an error that shouldn't be retried, or has been
+    /// retried `N` times.
+    fn retry<R>(mut op: impl FnMut() -> Result<R, S::Error>) -> Result<R, S::Error> {
+        let mut retry = 0;
+        loop {
+            match op() {
+                Err(e) if retry < N && S::is_retryable(&e) && !S::is_not_found(&e) => {
+                    let waiting = Self::waiting();
+                    let delay = waiting.backoff.delay(retry as u32);
+                    if !delay.is_zero() {
+                        (waiting.sleep)(delay);
+                    }
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<S, const N: usize> StaticStore for Retry<S, N>
+where
+    S: StaticStore + 'static,
+{
+    type Error = S::Error;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        Self::retry(|| S::store_bytes(value, shape))
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        Self::retry(|| S::store_if_absent(value, shape))
+    }
+
+    fn store_many(blocks: &[(Vec<u8>, Option<CidShape>)]) -> Result<Vec<(Cid, bool)>, Self::Error> {
+        Self::retry(|| S::store_many(blocks))
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        Self::retry(|| S::load_bytes(k))
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        Self::retry(|| S::load_bytes_limited(k, max))
+    }
+
+    /// Retries until the deadline passes.
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        Self::retry(|| {
+            if Instant::now() >= deadline {
+                return Err(StoreError::Timeout(*k).into());
+            }
+            S::load_bytes_deadline(k, deadline)
+        })
+    }
+
+    fn load_many(keys: &[Cid]) -> Result<Vec<Vec<u8>>, Self::Error> {
+        Self::retry(|| S::load_many(keys))
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        Self::retry(|| S::has(k))
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        Self::retry(|| S::block_size(k))
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        S::keys_in_range(start, end)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        S::keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        S::remove(k)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::Mutex, time::Duration};
+
+    use crate::{Backoff, FaultyError, FaultyStore, MemoryStore, Retry, StaticStore};
+
+    #[test]
+    fn retries_until_success() {
+        struct Tag;
+        type Inner = FaultyStore<MemoryStore<Tag>>;
+        type Store = Retry<Inner, 3>;
+
+        static SLEPT: Mutex<Vec<Duration>> = Mutex::new(Vec::new());
+
+        // Fail the first three attempts at each of the two calls.
+        Inner::fail_when(|op| op % 4 != 3);
+        Store::set_backoff(Backoff::Exponential(Duration::from_millis(1)));
+        Store::set_sleep(|d| SLEPT.lock().unwrap().push(d));
+        let k = Store::store(&"flaky", None).unwrap();
+        assert_eq!(Store::load::<String>(&k).unwrap(), "flaky");
+        assert_eq!(Inner::operations(), 8);
+        // 1, 2, then 4 milliseconds of backoff per call.
+        let backoff = [1, 2, 4].map(Duration::from_millis);
+        assert_eq!(*SLEPT.lock().unwrap(), [backoff, backoff].concat());
+    }
+
+    #[test]
+    fn gives_up_after_n_retries() {
+        struct Tag;
+        type Inner = FaultyStore<MemoryStore<Tag>>;
+        type Store = Retry<Inner, 2>;
+
+        Inner::fail_when(|op| op < 3);
+        let err = Store::store(&"flaky", None).unwrap_err();
+        assert!(matches!(err, FaultyError::Injected { op: 2 }));
+        assert_eq!(Inner::operations(), 3);
+        Store::store(&"flaky", None).unwrap();
+    }
+
+    #[test]
+    fn missing_blocks_arent_retried() {
+        struct Tag;
+        type Inner = FaultyStore<MemoryStore<Tag>>;
+        type Store = Retry<Inner, 3>;
+
+        let k = MemoryStore::<Tag>::store(&"gone", None).unwrap();
+        MemoryStore::<Tag>::remove(&k).unwrap();
+        Inner::fail_when(|_| false);
+        assert!(Store::is_not_found(&Store::load_bytes(&k).unwrap_err()));
+        assert_eq!(Inner::operations(), 1);
+    }
+}
@@ -0,0 +1,73 @@
+use core::marker::PhantomData;
+
+use cid::Cid;
+use serde::Serialize;
+
+use crate::{CidShape, StaticStore};
+
+/// Store each value produced by `values` as it's produced, yielding the resulting CIDs.
+///
+/// Only one value is encoded at a time, so a large sequence of nodes (e.g., the leaves of an AMT
+/// built from a stream) can be written without holding them all in memory.
+pub fn save_as_you_go<Store, I>(
+    values: I,
+    shape: Option<CidShape>,
+) -> SaveAsYouGo<Store, I::IntoIter>
+where
+    Store: StaticStore,
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    SaveAsYouGo {
+        values: values.into_iter(),
+        shape,
+        _marker: PhantomData,
+    }
+}
+
+/// The iterator returned by [`save_as_you_go`].
+pub struct SaveAsYouGo<Store, I> {
+    values: I,
+    shape: Option<CidShape>,
+    _marker: PhantomData<fn(Store)>,
+}
+
+impl<Store, I> Iterator for SaveAsYouGo<Store, I>
+where
+    Store: StaticStore,
+    I: Iterator,
+    I::Item: Serialize,
+{
+    type Item = Result<Cid, Store::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.values.next()?;
+        Some(Store::store(&value, self.shape.as_ref()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use crate::{save_as_you_go, MemoryStore, StaticStore};
+
+    #[test]
+    fn stores_each_value_before_the_next() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let leaves = (0..10_000u64).inspect(|&i| {
+            // Every earlier leaf has already been written by the time the next one is produced.
+            assert_eq!(Store::len() as u64, i);
+        });
+        let keys = save_as_you_go::<Store, _>(leaves, None)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(keys.len(), 10_000);
+        assert_eq!(Store::len(), 10_000);
+        assert_eq!(Store::load::<u64>(&keys[1234]).unwrap(), 1234);
+    }
+}
@@ -0,0 +1,217 @@
+use std::marker::PhantomData;
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    car::{read_varint, write_varint},
+    CidShape, CodecError, MemoryStore, StaticStore, StoreError, DAG_CBOR, DAG_JSON,
+};
+
+/// A raw key-value backend that keeps blocks under caller-supplied CIDs without interpreting or
+/// verifying them.
+pub trait BlockBackend {
+    type Error: std::error::Error + From<StoreError> + 'static;
+
+    fn put(k: &Cid, bytes: Vec<u8>) -> Result<(), Self::Error>;
+    fn get(k: &Cid) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Returns true if the backend has a value for `k`. By default, this gets the value.
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(Self::get(k)?.is_some())
+    }
+
+    /// List every key, for [`StaticStore::keys`]. By default, this returns
+    /// [`StoreError::Unsupported`].
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        Err(StoreError::Unsupported("keys").into())
+    }
+
+    /// Delete the value for `k`, returning true if there was one, for [`StaticStore::remove`]. By
+    /// default, this returns [`StoreError::Unsupported`].
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        let _ = k;
+        Err(StoreError::Unsupported("remove").into())
+    }
+}
+
+/// A store that prefixes every block with its multicodec (as a varint) before handing it to the
+/// backend `B`, so a stored value can be decoded even without its CID (see
+/// [`SelfDescribingStore::decode_raw`]).
+///
+/// CIDs are computed over the logical, un-prefixed block (from the requested [`CidShape`],
+/// defaulting to DAG-CBOR with SHA2-256), so they match those of any other store. The price is a
+/// byte or two per block, and that the backend's values are no longer plain blocks: they can't
+/// be served to other IPLD systems (or verified against their CIDs) without stripping the prefix.
+///
+/// Values are encoded as DAG-CBOR, and DAG-CBOR and DAG-JSON blocks are decoded according to
+/// their codec.
+pub struct SelfDescribingStore<B>(PhantomData<B>);
+
+fn encoding_error(e: impl std::fmt::Display) -> StoreError {
+    StoreError::Encoding(e.to_string())
+}
+
+impl<B: BlockBackend> SelfDescribingStore<B> {
+    /// Split a stored value into its codec and the logical block.
+    pub fn split_raw(raw: &[u8]) -> Result<(u64, &[u8]), B::Error> {
+        let mut rest = raw;
+        let codec = read_varint(&mut rest)
+            .ok()
+            .flatten()
+            .ok_or(StoreError::Codec(CodecError::Truncated))?;
+        Ok((codec, rest))
+    }
+
+    /// Decode a value exactly as stored in the backend, using the codec recorded in its prefix.
+    pub fn decode_raw<T: DeserializeOwned>(raw: &[u8]) -> Result<T, B::Error> {
+        let (codec, bytes) = Self::split_raw(raw)?;
+        Self::decode_as(codec, bytes)
+    }
+
+    fn decode_as<T: DeserializeOwned>(codec: u64, bytes: &[u8]) -> Result<T, B::Error> {
+        match codec {
+            DAG_CBOR => Self::decode(bytes),
+            DAG_JSON => Ok(serde_ipld_dagjson::from_slice(bytes).map_err(encoding_error)?),
+            other => Err(StoreError::Codec(CodecError::UnsupportedCodec(other)).into()),
+        }
+    }
+}
+
+impl<B: BlockBackend> StaticStore for SelfDescribingStore<B> {
+    type Error = B::Error;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        let shape = shape.copied().unwrap_or_default();
+        let k = shape.cid(value)?;
+
+        let mut raw = Vec::with_capacity(value.len() + 9);
+        write_varint(&mut raw, shape.codec).expect("writing to a vec can't fail");
+        raw.extend_from_slice(value);
+        B::put(&k, raw)?;
+        Ok(k)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        Ok(serde_ipld_dagcbor::to_vec(value).map_err(encoding_error)?)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        let raw = B::get(k)?.ok_or(StoreError::NotFound(*k))?;
+        let (codec, bytes) = Self::split_raw(&raw)?;
+        if codec != k.codec() {
+            return Err(StoreError::Codec(CodecError::Malformed(
+                "block prefix doesn't match its cid",
+            ))
+            .into());
+        }
+        Ok(bytes.to_vec())
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        B::has(k)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        B::keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        B::remove(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        Ok(serde_ipld_dagcbor::from_slice(bytes).map_err(encoding_error)?)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        Ok(serde_ipld_dagcbor::from_slice(bytes).map_err(encoding_error)?)
+    }
+
+    /// Decodes the block according to the codec in `key`.
+    fn load<T: DeserializeOwned>(key: &Cid) -> Result<T, Self::Error> {
+        let load = || {
+            let bytes = Self::load_block(key)?;
+            if Self::VERIFY {
+                Self::verify(key, &bytes)?;
+            }
+            match Self::pre_decode(key, &bytes)? {
+                Some(upgraded) => Self::decode_as(key.codec(), &upgraded),
+                None => Self::decode_as(key.codec(), &bytes),
+            }
+        };
+        load().map_err(|e| Self::with_cid(e, *key))
+    }
+}
+
+/// Stores blocks in the same map as [`StaticStore::store_bytes`], under the given CIDs.
+impl<Tag: 'static> BlockBackend for MemoryStore<Tag> {
+    type Error = <Self as StaticStore>::Error;
+
+    fn put(k: &Cid, bytes: Vec<u8>) -> Result<(), Self::Error> {
+        Self::with_blocks(|b| b.insert(*k, bytes));
+        Ok(())
+    }
+
+    fn get(k: &Cid) -> Result<Option<Vec<u8>>, Self::Error> {
+        Ok(Self::with_blocks(|b| b.get(k).cloned()))
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(Self::contains(k))
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        <Self as StaticStore>::keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        <Self as StaticStore>::remove(k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
+
+    use crate::{
+        BlockBackend, CidShape, MemoryStore, SelfDescribingStore, StaticStore, Value, DAG_JSON,
+    };
+
+    #[test]
+    fn decode_without_cid() {
+        struct Tag;
+        type Backend = MemoryStore<Tag>;
+        type Store = SelfDescribingStore<Backend>;
+
+        let json_shape = CidShape::default().with_codec(DAG_JSON);
+        let json = Store::store_bytes(br#"{"codec":"json"}"#, Some(&json_shape)).unwrap();
+        let cbor = Store::store(&Value::String("cbor".into()), None).unwrap();
+
+        // The CIDs only cover the logical blocks.
+        assert_eq!(
+            json,
+            Cid::new_v1(DAG_JSON, Code::Sha2_256.digest(br#"{"codec":"json"}"#))
+        );
+        assert_eq!(cbor, MemoryStore::<()>::store(&"cbor", None).unwrap());
+
+        // Raw values from the backend decode without knowing their CIDs.
+        let raw_json = Backend::get(&json).unwrap().unwrap();
+        let raw_cbor = Backend::get(&cbor).unwrap().unwrap();
+        let Value::Map(m) = Store::decode_raw::<Value>(&raw_json).unwrap() else {
+            panic!("expected a map")
+        };
+        assert_eq!(m["codec"], Value::String("json".into()));
+        assert_eq!(
+            Store::decode_raw::<Value>(&raw_cbor).unwrap(),
+            Value::String("cbor".into())
+        );
+
+        // Loading by CID strips the prefix (and verifies the logical block).
+        assert_eq!(Store::load::<String>(&cbor).unwrap(), "cbor");
+        assert_eq!(Store::load_bytes(&json).unwrap(), br#"{"codec":"json"}"#);
+    }
+}
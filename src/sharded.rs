@@ -0,0 +1,459 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    io::{Read, Write},
+    marker::PhantomData,
+    sync::{LazyLock, Mutex},
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{CidShape, StaticStore};
+
+/// A block's CID and whether it was newly stored.
+type Stored<E> = Result<(Cid, bool), E>;
+/// The blocks returned by a batched load.
+type Blocks<E> = Result<Vec<Vec<u8>>, E>;
+
+/// A shard backend, erased to its operations so shards of different store types can share a
+/// [`ShardedStore`].
+pub struct Shard<E> {
+    store_bytes: fn(&[u8], Option<&CidShape>) -> Result<Cid, E>,
+    store_if_absent: fn(&[u8], Option<&CidShape>) -> Stored<E>,
+    load_bytes: fn(&Cid) -> Result<Vec<u8>, E>,
+    load_bytes_limited: fn(&Cid, usize) -> Result<Vec<u8>, E>,
+    load_many: fn(&[Cid]) -> Blocks<E>,
+    has: fn(&Cid) -> Result<bool, E>,
+    block_size: fn(&Cid) -> Result<usize, E>,
+    keys_in_range: fn(&Cid, &Cid) -> Result<Vec<Cid>, E>,
+    keys: fn() -> Result<Vec<Cid>, E>,
+    remove: fn(&Cid) -> Result<bool, E>,
+}
+
+impl<E> Clone for Shard<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for Shard<E> {}
+
+impl<E> Shard<E> {
+    /// The shard backed by the store `B`.
+    pub fn of<B>() -> Self
+    where
+        B: StaticStore,
+        B::Error: Into<E>,
+    {
+        Shard {
+            store_bytes: |v, s| B::store_bytes(v, s).map_err(Into::into),
+            store_if_absent: |v, s| B::store_if_absent(v, s).map_err(Into::into),
+            load_bytes: |k| B::load_bytes(k).map_err(Into::into),
+            load_bytes_limited: |k, max| B::load_bytes_limited(k, max).map_err(Into::into),
+            load_many: |keys| B::load_many(keys).map_err(Into::into),
+            has: |k| B::has(k).map_err(Into::into),
+            block_size: |k| B::block_size(k).map_err(Into::into),
+            keys_in_range: |a, b| B::keys_in_range(a, b).map_err(Into::into),
+            keys: || B::keys().map_err(Into::into),
+            remove: |k| B::remove(k).map_err(Into::into),
+        }
+    }
+}
+
+/// Picks the shard that owns a CID, given the number of shards.
+pub type ShardFn = fn(&Cid, usize) -> usize;
+
+/// The default [`ShardFn`]: the first eight bytes of the CID's digest, modulo the number of shards.
+pub fn shard_by_digest(k: &Cid, shards: usize) -> usize {
+    let mut prefix = [0u8; 8];
+    let digest = k.hash().digest();
+    let n = digest.len().min(8);
+    prefix[..n].copy_from_slice(&digest[..n]);
+    (u64::from_be_bytes(prefix) % shards as u64) as usize
+}
+
+struct Config<E> {
+    shards: Vec<Shard<E>>,
+    shard_fn: ShardFn,
+}
+
+static CONFIGS: LazyLock<Mutex<HashMap<TypeId, Box<dyn Any + Send>>>> =
+    LazyLock::new(Default::default);
+
+/// A store that distributes blocks across several backends by CID. Content addressing means a
+/// block always maps to the same shard, so reads go straight to the block's owner.
+///
+/// Blocks are encoded and decoded with `S`'s codec, and every shard's error must convert into
+/// `S::Error`. `S` isn't a shard unless it's added as one. The shards are process-global and keyed
+/// by `S`, like the state of the other type-level stores.
+///
+/// `ShardedStore` computes each block's CID itself (from the requested [`CidShape`], defaulting to
+/// DAG-CBOR with SHA2-256) to pick the owning shard, and passes the shape on to the shard
+/// explicitly. Every shard must compute CIDs the same way.
+pub struct ShardedStore<S>(PhantomData<S>);
+
+impl<S> ShardedStore<S>
+where
+    S: StaticStore + 'static,
+{
+    /// Replace the shards and sharding function.
+    pub fn configure(shards: Vec<Shard<S::Error>>, shard_fn: ShardFn) {
+        let config = Config { shards, shard_fn };
+        CONFIGS
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<S>(), Box::new(config));
+    }
+
+    fn with_config<R>(f: impl FnOnce(&mut Config<S::Error>) -> R) -> R {
+        let mut configs = CONFIGS.lock().unwrap();
+        let config = configs.entry(TypeId::of::<S>()).or_insert_with(|| {
+            Box::new(Config::<S::Error> {
+                shards: Vec::new(),
+                shard_fn: shard_by_digest,
+            })
+        });
+        f(config
+            .downcast_mut()
+            .expect("shard config has the wrong type"))
+    }
+
+    /// Add a shard.
+    ///
+    /// Adding a shard changes the owner of existing blocks: with [`shard_by_digest`], going from
+    /// `n` to `n + 1` shards moves about `n / (n + 1)` of all blocks. Blocks aren't moved
+    /// automatically, and a block that's still on its old shard can't be read until it is, so
+    /// call [`ShardedStore::rebalance`] over the stored key space afterwards. A custom
+    /// [`ShardFn`] based on consistent hashing reduces this to about `1 / (n + 1)` of the blocks.
+    pub fn add_shard(shard: Shard<S::Error>) {
+        Self::with_config(|c| c.shards.push(shard))
+    }
+
+    /// The number of shards.
+    pub fn shards() -> usize {
+        Self::with_config(|c| c.shards.len())
+    }
+
+    /// The index of the shard that owns `k`.
+    ///
+    /// Panics if there are no shards.
+    pub fn shard_of(k: &Cid) -> usize {
+        Self::owner(k).0
+    }
+
+    /// The sorted union of `f` over every shard.
+    fn union(
+        f: impl Fn(&Shard<S::Error>) -> Result<Vec<Cid>, S::Error>,
+    ) -> Result<Vec<Cid>, S::Error> {
+        let shards = Self::with_config(|c| c.shards.clone());
+        let mut keys = Vec::new();
+        for shard in &shards {
+            keys.extend(f(shard)?);
+        }
+        keys.sort_by_key(Cid::to_bytes);
+        keys.dedup();
+        Ok(keys)
+    }
+
+    fn owner(k: &Cid) -> (usize, Shard<S::Error>) {
+        Self::with_config(|c| {
+            assert!(!c.shards.is_empty(), "sharded store has no shards");
+            let i = (c.shard_fn)(k, c.shards.len());
+            (i, c.shards[i])
+        })
+    }
+
+    /// Copy every block with a CID in `start..end` that's stored on a shard other than its owner to
+    /// its owner, returning the number of blocks copied. The stale copies are left in place.
+    pub fn rebalance(start: &Cid, end: &Cid) -> Result<usize, S::Error> {
+        let shards = Self::with_config(|c| c.shards.clone());
+        let mut copied = 0;
+        for (i, shard) in shards.iter().enumerate() {
+            for k in (shard.keys_in_range)(start, end)? {
+                let (owner, dest) = Self::owner(&k);
+                if owner == i {
+                    continue;
+                }
+                let block = (shard.load_bytes)(&k)?;
+                (dest.store_bytes)(&block, Some(&CidShape::from(&k)))?;
+                copied += 1;
+            }
+        }
+        Ok(copied)
+    }
+}
+
+impl<S> StaticStore for ShardedStore<S>
+where
+    S: StaticStore + 'static,
+{
+    type Error = S::Error;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        let shape = shape.copied().unwrap_or(CidShape::DEFAULT);
+        let k = shape.cid(value)?;
+        let stored = (Self::owner(&k).1.store_bytes)(value, Some(&shape))?;
+        debug_assert_eq!(stored, k, "shard computed a different CID");
+        Ok(k)
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        let shape = shape.copied().unwrap_or(CidShape::DEFAULT);
+        let k = shape.cid(value)?;
+        let (stored, new) = (Self::owner(&k).1.store_if_absent)(value, Some(&shape))?;
+        debug_assert_eq!(stored, k, "shard computed a different CID");
+        Ok((k, new))
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        (Self::owner(k).1.load_bytes)(k)
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        (Self::owner(k).1.load_bytes_limited)(k, max)
+    }
+
+    /// Loads each shard's blocks with one call to that shard's `load_many`.
+    fn load_many(keys: &[Cid]) -> Result<Vec<Vec<u8>>, Self::Error> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (shards, shard_fn) = Self::with_config(|c| (c.shards.clone(), c.shard_fn));
+        assert!(!shards.is_empty(), "sharded store has no shards");
+        let mut owned = vec![Vec::new(); shards.len()];
+        for (i, k) in keys.iter().enumerate() {
+            owned[shard_fn(k, shards.len())].push(i);
+        }
+        let mut blocks = vec![Vec::new(); keys.len()];
+        for (shard, indices) in shards.iter().zip(owned) {
+            if indices.is_empty() {
+                continue;
+            }
+            let batch: Vec<Cid> = indices.iter().map(|&i| keys[i]).collect();
+            for (i, block) in indices.into_iter().zip((shard.load_many)(&batch)?) {
+                blocks[i] = block;
+            }
+        }
+        Ok(blocks)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        (Self::owner(k).1.has)(k)
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        (Self::owner(k).1.block_size)(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    /// Returns the sorted union of every shard's keys in the range.
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        Self::union(|shard| (shard.keys_in_range)(start, end))
+    }
+
+    /// Returns the sorted union of every shard's keys, including stale copies left on their old
+    /// shards by [`ShardedStore::rebalance`].
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        Self::union(|shard| (shard.keys)())
+    }
+
+    /// Removes the block from its owner. Stale copies on other shards are left in place.
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        (Self::owner(k).1.remove)(k)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::{
+        multihash::{Code, MultihashDigest},
+        Cid,
+    };
+
+    use crate::{
+        MemoryError, MemoryStore, Shard, ShardedStore, StaticStore, StatsStore, StoreError,
+    };
+
+    struct A;
+    struct B;
+    struct C;
+    struct D;
+    struct E;
+    struct F;
+
+    fn by_last_byte(k: &Cid, shards: usize) -> usize {
+        *k.hash().digest().last().unwrap() as usize % shards
+    }
+
+    #[test]
+    fn blocks_land_on_their_shard() {
+        type Store = ShardedStore<MemoryStore<A>>;
+        Store::configure(
+            vec![
+                Shard::of::<MemoryStore<A>>(),
+                Shard::of::<MemoryStore<B>>(),
+                Shard::of::<MemoryStore<C>>(),
+            ],
+            by_last_byte,
+        );
+        let shards = [
+            MemoryStore::<A>::contains,
+            MemoryStore::<B>::contains,
+            MemoryStore::<C>::contains,
+        ];
+
+        let keys: Vec<Cid> = (0..30).map(|i| Store::store(&i, None).unwrap()).collect();
+        for (i, k) in keys.iter().enumerate() {
+            let owner = by_last_byte(k, 3);
+            assert_eq!(Store::shard_of(k), owner);
+            for (shard, contains) in shards.iter().enumerate() {
+                assert_eq!(contains(k), shard == owner);
+            }
+            assert_eq!(Store::load::<i32>(k).unwrap(), i as i32);
+        }
+        let mut sorted = keys.clone();
+        sorted.sort_by_key(Cid::to_bytes);
+        assert_eq!(
+            Store::keys_in_range(&sorted[0], &sorted[29]).unwrap(),
+            sorted[..29]
+        );
+
+        // After adding a shard, blocks that changed owner are readable once rebalanced. The
+        // bounds sort below and above every CIDv1 in the store.
+        Store::add_shard(Shard::of::<MemoryStore<D>>());
+        let start = Cid::new_v1(0, Code::Sha2_256.digest(&[]));
+        let end = Cid::new_v0(Code::Sha2_256.digest(&[])).unwrap();
+        let moved = keys
+            .iter()
+            .filter(|k| by_last_byte(k, 4) != by_last_byte(k, 3))
+            .count();
+        assert!(moved > 0);
+        assert_eq!(Store::rebalance(&start, &end).unwrap(), moved);
+        for (i, k) in keys.iter().enumerate() {
+            assert_eq!(Store::load::<i32>(k).unwrap(), i as i32);
+        }
+    }
+
+    #[test]
+    fn every_operation_reaches_the_owner() {
+        type Store = ShardedStore<MemoryStore<E>>;
+        Store::configure(
+            vec![
+                Shard::of::<StatsStore<MemoryStore<E>>>(),
+                Shard::of::<StatsStore<MemoryStore<F>>>(),
+            ],
+            by_last_byte,
+        );
+
+        let blocks: Vec<Vec<u8>> = (0..20u8).map(|i| vec![i; i as usize + 1]).collect();
+        let mut keys = Vec::new();
+        for block in &blocks {
+            let (k, new) = Store::store_if_absent(block, None).unwrap();
+            assert!(new);
+            assert!(!Store::store_if_absent(block, None).unwrap().1);
+            keys.push(k);
+        }
+        for (k, block) in keys.iter().zip(&blocks) {
+            assert_eq!(Store::block_size(k).unwrap(), block.len());
+            assert_eq!(Store::load_bytes_limited(k, block.len()).unwrap(), *block);
+        }
+
+        // The cap is checked by the shard, which doesn't read (or count) the block.
+        let loads = || {
+            StatsStore::<MemoryStore<E>>::snapshot().loads
+                + StatsStore::<MemoryStore<F>>::snapshot().loads
+        };
+        let before = loads();
+        assert!(matches!(
+            Store::load_bytes_limited(&keys[19], 4),
+            Err(MemoryError::Store(StoreError::BlockTooLarge {
+                len: 20,
+                max: 4,
+                ..
+            }))
+        ));
+        assert_eq!(loads(), before);
+
+        // Batches keep their order across shards.
+        let mut shuffled = keys.clone();
+        shuffled.reverse();
+        let loaded = Store::load_many(&shuffled).unwrap();
+        assert!(loaded.iter().rev().eq(blocks.iter()));
+
+        let mut sorted = keys.clone();
+        sorted.sort_by_key(Cid::to_bytes);
+        assert_eq!(Store::keys().unwrap(), sorted);
+        assert!(Store::remove(&keys[0]).unwrap());
+        assert!(!Store::remove(&keys[0]).unwrap());
+        assert!(!Store::has(&keys[0]).unwrap());
+        assert_eq!(Store::keys().unwrap().len(), 19);
+    }
+}
@@ -0,0 +1,226 @@
+use core::{
+    cell::{Cell, OnceCell},
+    fmt,
+    marker::PhantomData,
+};
+
+use alloc::rc::Rc;
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, ser::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{error, link_as_cid, store, AsCid, CidShape, StaticStore};
+
+/// A [`Link`](crate::Link) whose clones share one lazily-loaded value: the cache is an
+/// `Rc<OnceCell<T>>`, so reading through any clone loads the block once and fills the cache for
+/// all of them (e.g., for nodes reachable via several paths in a tree). Cloning doesn't require
+/// `T: Clone`.
+///
+/// Edits are copy-on-write: [`SharedLink::edit`] copies the value out of the shared cache (if
+/// another clone still refers to it) before handing out a mutable reference, so an edit is only
+/// visible through the link it was made on. The other clones keep pointing at the original CID and
+/// value, and the edited link stops sharing with them; clones made after the edit share the edited
+/// value.
+///
+/// Like `Link`, a `SharedLink` isn't `Sync`; see [`SyncLink`](crate::SyncLink) for that.
+pub struct SharedLink<T, Store> {
+    value: Rc<OnceCell<T>>,
+    state: Cell<SharedState>,
+    _marker: PhantomData<fn(Store)>,
+}
+
+#[derive(Copy, Clone)]
+enum SharedState {
+    Unmodified(Cid),
+    Modified(Option<CidShape>),
+}
+
+/// The clone shares this link's cache.
+impl<T, Store> Clone for SharedLink<T, Store> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            state: self.state.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Shows the link's CID (or that it's modified) and whether its value is cached, like
+/// [`Link`](crate::Link)'s.
+impl<T, Store> fmt::Debug for SharedLink<T, Store> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("SharedLink");
+        match self.state.get() {
+            SharedState::Unmodified(k) => s.field("cid", &k),
+            SharedState::Modified(_) => s.field("modified", &true),
+        };
+        s.field("loaded", &self.value.get().is_some()).finish()
+    }
+}
+
+impl<T, Store> Serialize for SharedLink<T, Store>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let k = self.save().map_err(|e| {
+            let msg = S::Error::custom(&e);
+            error::stash_error(e);
+            msg
+        })?;
+        link_as_cid::serialize(&k, serializer)
+    }
+}
+
+impl<'de, T, Store> Deserialize<'de> for SharedLink<T, Store> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Self::new(link_as_cid::deserialize(deserializer)?))
+    }
+}
+
+/// See [`SharedLink::cid`].
+impl<T, Store> AsCid for SharedLink<T, Store> {
+    fn as_cid(&self) -> Option<Cid> {
+        self.cid()
+    }
+}
+
+impl<T, Store> SharedLink<T, Store> {
+    /// Construct a new `SharedLink` from a `Cid`.
+    #[must_use]
+    pub fn new(k: Cid) -> Self {
+        Self {
+            value: Rc::default(),
+            state: Cell::new(SharedState::Unmodified(k)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Construct a new `SharedLink` from a value (with an optional link-shape hint).
+    #[must_use]
+    pub fn from_value(v: T, shape: Option<CidShape>) -> Self {
+        Self {
+            value: Rc::new(OnceCell::from(v)),
+            state: Cell::new(SharedState::Modified(shape)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The CID of the stored block this link points to, or `None` if the link has been modified
+    /// and not yet saved.
+    pub fn cid(&self) -> Option<Cid> {
+        match self.state.get() {
+            SharedState::Unmodified(k) => Some(k),
+            SharedState::Modified(_) => None,
+        }
+    }
+
+    /// Returns true if the link has been modified and not yet saved.
+    pub fn is_dirty(&self) -> bool {
+        matches!(self.state.get(), SharedState::Modified(_))
+    }
+
+    /// Returns true if the value is cached (by this link or a clone sharing its cache).
+    pub fn is_loaded(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    /// Returns true if `self` and `other` share a cache, i.e., one is a clone of the other and
+    /// neither has been edited since.
+    pub fn shares_cache_with(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.value, &other.value)
+    }
+
+    /// Read the linked object, loading and decoding it (into the shared cache) if it isn't cached.
+    pub fn read(&self) -> Result<&T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        if let Some(val) = self.value.get() {
+            return Ok(val);
+        }
+        let SharedState::Unmodified(k) = self.state.get() else {
+            unreachable!("modified link has no value")
+        };
+        let val = Store::load(&k)?;
+        Ok(self.value.get_or_init(|| val))
+    }
+
+    /// Edit the linked object, loading it if needed and marking the link as modified. If a clone
+    /// still shares the value, it's copied first, so the edit isn't visible through other clones.
+    pub fn edit(&mut self) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned + Clone,
+        Store: StaticStore,
+    {
+        if let SharedState::Unmodified(k) = self.state.get() {
+            self.read()?;
+            self.state
+                .set(SharedState::Modified(Some(CidShape::from(&k))));
+        }
+        Ok(Rc::make_mut(&mut self.value)
+            .get_mut()
+            .expect("expected value"))
+    }
+
+    /// Write-back the value if modified, and return the CID.
+    pub fn save(&self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let shape = match self.state.get() {
+            SharedState::Unmodified(k) => return Ok(k),
+            SharedState::Modified(shape) => shape,
+        };
+
+        let encoded = store::encode::<Store, _>(
+            self.value.get().expect("modified link has no value"),
+            shape.as_ref(),
+        )?;
+        let (k, saved) = store::store_link_block::<Store>(&encoded, shape.as_ref())?;
+        if saved {
+            self.state.set(SharedState::Unmodified(k));
+        }
+        Ok(k)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use crate::{MemoryStore, SharedLink, StaticStore, StatsStore};
+
+    #[test]
+    fn clones_share_one_load() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = Store::store(&"shared", None).unwrap();
+        let a = SharedLink::<String, Store>::new(k);
+        let mut b = a.clone();
+        assert_eq!(a.read().unwrap(), "shared");
+        assert!(b.is_loaded());
+        assert_eq!(b.read().unwrap(), "shared");
+        assert_eq!(Store::snapshot().loads, 1);
+
+        // Edits are copy-on-write.
+        b.edit().unwrap().push('!');
+        assert!(!a.shares_cache_with(&b) && !a.is_dirty());
+        assert_eq!(a.read().unwrap(), "shared");
+        let c = b.clone();
+        let saved = b.save().unwrap();
+        assert_eq!(c.read().unwrap(), "shared!");
+        assert_eq!(c.save().unwrap(), saved);
+        assert_eq!(a.cid(), Some(k));
+        assert_eq!(Store::snapshot().loads, 1);
+    }
+}
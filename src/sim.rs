@@ -0,0 +1,488 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    future::Future,
+    io::{Read, Write},
+    marker::PhantomData,
+    pin::Pin,
+    sync::{LazyLock, Mutex},
+    task::{Context, Poll},
+    thread,
+    time::{Duration, Instant},
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{AsyncStore, CidShape, StaticStore, StoreError};
+
+/// A block operation performed by a [`SimStore`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SimOp {
+    Load,
+    Has,
+    Store,
+    Remove,
+}
+
+/// An entry in a [`SimStore`]'s call trace.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SimCall {
+    pub op: SimOp,
+    pub cid: Cid,
+    /// The latency simulated for the call. A batch of blocks is delayed once, so only the first
+    /// block in a batch has a delay.
+    pub delay: Duration,
+    /// The simulation's logical clock when the call started and when it finished. Every delayed
+    /// call ticks the clock once as it starts and once as it finishes (blocks in a batch share
+    /// both), so two calls overlapped if each started before the other finished.
+    pub started: u64,
+    pub finished: u64,
+}
+
+impl SimCall {
+    /// Returns true if the two calls were in progress at the same time.
+    pub fn overlaps(&self, other: &SimCall) -> bool {
+        self.started < other.finished && other.started < self.finished
+    }
+}
+
+struct Sim {
+    rng: u64,
+    latency: Duration,
+    jitter: Duration,
+    clock: u64,
+    trace: Vec<SimCall>,
+}
+
+impl Sim {
+    /// The next delay: the base latency plus a pseudo-random amount of jitter (xorshift64*).
+    fn next_delay(&mut self) -> Duration {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        let r = self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        let jitter = self.jitter.as_nanos() as u64;
+        self.latency + Duration::from_nanos(if jitter == 0 { 0 } else { r % (jitter + 1) })
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+}
+
+static SIMS: LazyLock<Mutex<HashMap<TypeId, Sim>>> = LazyLock::new(Default::default);
+
+/// A store wrapper that simulates a slow (e.g., remote) store, for measuring the benefit of
+/// batching and prefetching.
+///
+/// Every block operation is delayed by the configured latency plus up to `jitter` extra, drawn
+/// from a pseudo-random sequence determined by the seed, and is recorded in a call trace. The
+/// blocking [`StaticStore`] methods sleep the calling thread (once per call, so batched calls like
+/// [`StaticStore::store_many`] pay the latency once), while the [`AsyncStore`] methods
+/// wait without blocking so that concurrent operations overlap. Until configured, operations
+/// are forwarded without delay or tracing.
+///
+/// Like [`FaultyStore`](crate::FaultyStore), the configuration is process-global and keyed by the
+/// inner store type.
+pub struct SimStore<S>(PhantomData<S>);
+
+impl<S: 'static> SimStore<S> {
+    /// Start simulating, clearing the trace. The same seed gives the same sequence of delays.
+    pub fn configure(seed: u64, latency: Duration, jitter: Duration) {
+        let sim = Sim {
+            // Xorshift gets stuck at zero.
+            rng: seed | 1,
+            latency,
+            jitter,
+            clock: 0,
+            trace: Vec::new(),
+        };
+        SIMS.lock().unwrap().insert(TypeId::of::<S>(), sim);
+    }
+
+    /// Stop simulating, forwarding all further operations without delay.
+    pub fn reset() {
+        SIMS.lock().unwrap().remove(&TypeId::of::<S>());
+    }
+
+    /// The operations performed since the simulation was configured, in the order they completed.
+    pub fn trace() -> Vec<SimCall> {
+        SIMS.lock()
+            .unwrap()
+            .get(&TypeId::of::<S>())
+            .map_or_else(Vec::new, |s| s.trace.clone())
+    }
+
+    /// Start a call, returning its delay and its start time on the simulation's clock.
+    fn start() -> (Duration, u64) {
+        SIMS.lock()
+            .unwrap()
+            .get_mut(&TypeId::of::<S>())
+            .map_or((Duration::ZERO, 0), |sim| (sim.next_delay(), sim.tick()))
+    }
+
+    /// Finish a call started at `started`, tracing it once for each block in `cids`.
+    fn record(op: SimOp, cids: &[Cid], delay: Duration, started: u64) {
+        if let Some(sim) = SIMS.lock().unwrap().get_mut(&TypeId::of::<S>()) {
+            let finished = sim.tick();
+            for (i, &cid) in cids.iter().enumerate() {
+                sim.trace.push(SimCall {
+                    op,
+                    cid,
+                    delay: if i == 0 { delay } else { Duration::ZERO },
+                    started,
+                    finished,
+                });
+            }
+        }
+    }
+}
+
+/// A future that completes at a deadline, woken by a helper thread.
+struct Delay {
+    deadline: Instant,
+    waiting: bool,
+}
+
+impl Delay {
+    fn new(delay: Duration) -> Self {
+        Delay {
+            deadline: Instant::now() + delay,
+            waiting: false,
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let now = Instant::now();
+        if now >= self.deadline {
+            return Poll::Ready(());
+        }
+        if !self.waiting {
+            self.waiting = true;
+            let (waker, wait) = (cx.waker().clone(), self.deadline - now);
+            thread::spawn(move || {
+                thread::sleep(wait);
+                waker.wake();
+            });
+        }
+        Poll::Pending
+    }
+}
+
+impl<S> StaticStore for SimStore<S>
+where
+    S: StaticStore + 'static,
+{
+    type Error = S::Error;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        let (delay, started) = Self::start();
+        thread::sleep(delay);
+        let k = S::store_bytes(value, shape)?;
+        Self::record(SimOp::Store, &[k], delay, started);
+        Ok(k)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        let (delay, started) = Self::start();
+        thread::sleep(delay);
+        let bytes = S::load_bytes(k)?;
+        Self::record(SimOp::Load, &[*k], delay, started);
+        Ok(bytes)
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        let (delay, started) = Self::start();
+        thread::sleep(delay);
+        let bytes = S::load_bytes_limited(k, max)?;
+        Self::record(SimOp::Load, &[*k], delay, started);
+        Ok(bytes)
+    }
+
+    /// Gives up with [`StoreError::Timeout`] (after sleeping until the deadline) if the simulated
+    /// latency would pass the deadline.
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        let (delay, started) = Self::start();
+        let left = deadline.saturating_duration_since(Instant::now());
+        if delay > left {
+            thread::sleep(left);
+            return Err(StoreError::Timeout(*k).into());
+        }
+        thread::sleep(delay);
+        let bytes = S::load_bytes_deadline(k, deadline)?;
+        Self::record(SimOp::Load, &[*k], delay, started);
+        Ok(bytes)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        let (delay, started) = Self::start();
+        thread::sleep(delay);
+        let has = S::has(k)?;
+        Self::record(SimOp::Has, &[*k], delay, started);
+        Ok(has)
+    }
+
+    /// Delayed, but not traced, as the call has no CID.
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        thread::sleep(Self::start().0);
+        S::keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        let (delay, started) = Self::start();
+        thread::sleep(delay);
+        let removed = S::remove(k)?;
+        Self::record(SimOp::Remove, &[*k], delay, started);
+        Ok(removed)
+    }
+
+    fn store_many(blocks: &[(Vec<u8>, Option<CidShape>)]) -> Result<Vec<(Cid, bool)>, Self::Error> {
+        let (delay, started) = Self::start();
+        thread::sleep(delay);
+        let stored = S::store_many(blocks)?;
+        let cids: Vec<_> = stored.iter().map(|(k, _)| *k).collect();
+        Self::record(SimOp::Store, &cids, delay, started);
+        Ok(stored)
+    }
+
+    fn load_many(keys: &[Cid]) -> Result<Vec<Vec<u8>>, Self::Error> {
+        let (delay, started) = Self::start();
+        thread::sleep(delay);
+        let blocks = S::load_many(keys)?;
+        Self::record(SimOp::Load, keys, delay, started);
+        Ok(blocks)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
+}
+
+impl<S> AsyncStore for SimStore<S>
+where
+    S: StaticStore + 'static,
+    S::Error: Send,
+{
+    type Error = S::Error;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    async fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        let (delay, started) = Self::start();
+        Delay::new(delay).await;
+        let k = S::store_bytes(value, shape)?;
+        Self::record(SimOp::Store, &[k], delay, started);
+        Ok(k)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    async fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        let (delay, started) = Self::start();
+        Delay::new(delay).await;
+        let bytes = S::load_bytes(k)?;
+        Self::record(SimOp::Load, &[*k], delay, started);
+        Ok(bytes)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn verify(k: &Cid, bytes: &[u8]) -> Result<(), Self::Error> {
+        S::verify(k, bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        future::{poll_fn, Future},
+        pin::Pin,
+        task::Poll,
+        time::Duration,
+    };
+
+    use crate::{testing::block_on, AsyncStore, MemoryStore, SimOp, SimStore, StaticStore};
+
+    /// Poll all the futures until they've all completed.
+    fn join_all<F: Future>(futures: Vec<F>) -> Vec<F::Output> {
+        let mut futures: Vec<_> = futures.into_iter().map(Box::pin).collect();
+        let mut outputs: Vec<_> = futures.iter().map(|_| None).collect();
+        block_on(poll_fn(|cx| {
+            for (fut, out) in futures.iter_mut().zip(&mut outputs) {
+                if out.is_none() {
+                    if let Poll::Ready(v) = Pin::new(fut).poll(cx) {
+                        *out = Some(v);
+                    }
+                }
+            }
+            if outputs.iter().all(Option::is_some) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+        outputs.into_iter().map(Option::unwrap).collect()
+    }
+
+    #[test]
+    fn concurrent_loads_overlap() {
+        struct Tag;
+        type Store = SimStore<MemoryStore<Tag>>;
+
+        let keys: Vec<_> = (0..8u64)
+            .map(|i| MemoryStore::<Tag>::store(&i, None).unwrap())
+            .collect();
+        Store::configure(42, Duration::from_millis(2), Duration::from_millis(1));
+
+        for k in &keys {
+            <Store as StaticStore>::load_bytes(k).unwrap();
+        }
+        let sequential = Store::trace();
+        assert_eq!(sequential.len(), 8);
+        assert!(sequential.windows(2).all(|w| !w[0].overlaps(&w[1])));
+
+        let loaded = join_all(
+            keys.iter()
+                .map(<Store as AsyncStore>::load::<u64>)
+                .collect(),
+        );
+        assert_eq!(
+            loaded.into_iter().map(Result::unwrap).collect::<Vec<_>>(),
+            (0..8).collect::<Vec<_>>()
+        );
+
+        let trace = Store::trace();
+        assert_eq!(trace.len(), 16);
+        assert!(trace.iter().all(|c| c.op == SimOp::Load));
+        // Every concurrent load started before any of them finished.
+        let concurrent = &trace[8..];
+        for (i, a) in concurrent.iter().enumerate() {
+            assert!(concurrent[i + 1..].iter().all(|b| a.overlaps(b)));
+        }
+        Store::reset();
+    }
+
+    #[test]
+    fn delays_are_deterministic() {
+        struct Tag;
+        type Store = SimStore<MemoryStore<Tag>>;
+
+        let k = MemoryStore::<Tag>::store(&"block", None).unwrap();
+        let delays = || {
+            Store::configure(7, Duration::from_micros(10), Duration::from_micros(100));
+            for _ in 0..5 {
+                <Store as StaticStore>::load_bytes(&k).unwrap();
+            }
+            Store::trace().iter().map(|c| c.delay).collect::<Vec<_>>()
+        };
+        let first = delays();
+        assert_eq!(delays(), first);
+        assert!(first.windows(2).any(|w| w[0] != w[1]));
+        assert!(first
+            .iter()
+            .all(|d| (Duration::from_micros(10)..=Duration::from_micros(110)).contains(d)));
+        Store::reset();
+    }
+}
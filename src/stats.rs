@@ -0,0 +1,302 @@
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    io::{Read, Write},
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock, Mutex,
+    },
+    time::Instant,
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{CidShape, StaticStore};
+
+#[derive(Default)]
+struct Counters {
+    loads: AtomicU64,
+    stores: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+/// The counters are leaked so they can be updated without holding the registry lock.
+static COUNTERS: LazyLock<Mutex<HashMap<TypeId, &'static Counters>>> =
+    LazyLock::new(Default::default);
+
+/// A snapshot of the operations a [`StatsStore`] forwarded to its inner store.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    /// Blocks successfully loaded.
+    pub loads: u64,
+    /// Blocks successfully stored (including blocks the store already had).
+    pub stores: u64,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// A store wrapper that counts the blocks read from and written to `S`, for tuning
+/// [`AutoLink`](crate::AutoLink) thresholds and cache sizes.
+///
+/// Only successful operations are counted. Wrapping the backend beneath a cache (e.g.,
+/// `CachingStore<StatsStore<S>, CAP>`) counts the loads that missed the cache.
+///
+/// The counters are process-global and keyed by the inner store type, like
+/// [`FaultyStore`](crate::FaultyStore)'s schedule, so they include operations from every thread.
+pub struct StatsStore<S>(PhantomData<S>);
+
+impl<S: 'static> StatsStore<S> {
+    fn counters() -> &'static Counters {
+        COUNTERS
+            .lock()
+            .unwrap()
+            .entry(TypeId::of::<S>())
+            .or_insert_with(|| Box::leak(Box::default()))
+    }
+
+    /// The current counts.
+    pub fn snapshot() -> StoreStats {
+        let c = Self::counters();
+        StoreStats {
+            loads: c.loads.load(Ordering::Relaxed),
+            stores: c.stores.load(Ordering::Relaxed),
+            bytes_read: c.bytes_read.load(Ordering::Relaxed),
+            bytes_written: c.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Reset the counts to zero.
+    pub fn reset() {
+        let c = Self::counters();
+        for counter in [&c.loads, &c.stores, &c.bytes_read, &c.bytes_written] {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn record_load(block: &[u8]) {
+        let c = Self::counters();
+        c.loads.fetch_add(1, Ordering::Relaxed);
+        c.bytes_read
+            .fetch_add(block.len() as u64, Ordering::Relaxed);
+    }
+
+    fn record_store(block: &[u8]) {
+        let c = Self::counters();
+        c.stores.fetch_add(1, Ordering::Relaxed);
+        c.bytes_written
+            .fetch_add(block.len() as u64, Ordering::Relaxed);
+    }
+}
+
+impl<S> StaticStore for StatsStore<S>
+where
+    S: StaticStore + 'static,
+{
+    type Error = S::Error;
+
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
+    fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        let k = S::store_bytes(value, shape)?;
+        Self::record_store(value);
+        Ok(k)
+    }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        let stored = S::store_if_absent(value, shape)?;
+        Self::record_store(value);
+        Ok(stored)
+    }
+
+    fn store_many(blocks: &[(Vec<u8>, Option<CidShape>)]) -> Result<Vec<(Cid, bool)>, Self::Error> {
+        let stored = S::store_many(blocks)?;
+        for (block, _) in blocks {
+            Self::record_store(block);
+        }
+        Ok(stored)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        S::encode(value)
+    }
+
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
+    fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        let block = S::load_bytes(k)?;
+        Self::record_load(&block);
+        Ok(block)
+    }
+
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        let block = S::load_bytes_limited(k, max)?;
+        Self::record_load(&block);
+        Ok(block)
+    }
+
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        let block = S::load_bytes_deadline(k, deadline)?;
+        Self::record_load(&block);
+        Ok(block)
+    }
+
+    fn load_many(keys: &[Cid]) -> Result<Vec<Vec<u8>>, Self::Error> {
+        let blocks = S::load_many(keys)?;
+        for block in &blocks {
+            Self::record_load(block);
+        }
+        Ok(blocks)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        S::has(k)
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        S::block_size(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        S::decode(bytes)
+    }
+
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        S::keys_in_range(start, end)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        S::keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        S::remove(k)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use cid::Cid;
+
+    use crate::{CachingStore, CidShape, Link, MemoryStore, StaticStore, StatsStore, StoreStats};
+
+    #[test]
+    fn counts_backend_operations() {
+        struct Tag;
+        type Inner = StatsStore<MemoryStore<Tag>>;
+        type Store = CachingStore<Inner, 1024>;
+
+        let k = Store::store(&"data", None).unwrap();
+        assert_eq!(Store::load::<String>(&k).unwrap(), "data");
+        // The load was answered by the cache.
+        let written = StoreStats {
+            stores: 1,
+            bytes_written: 5,
+            ..Default::default()
+        };
+        assert_eq!(Inner::snapshot(), written);
+
+        Store::clear();
+        Store::load::<String>(&k).unwrap();
+        assert!(Inner::load_bytes(&Cid::default()).is_err());
+        assert_eq!(
+            Inner::snapshot(),
+            StoreStats {
+                loads: 1,
+                bytes_read: 5,
+                ..written
+            }
+        );
+
+        Inner::reset();
+        assert_eq!(Inner::snapshot(), StoreStats::default());
+    }
+
+    #[test]
+    fn deduped_stores_skip_writes() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let bytes = Store::encode(&"data").unwrap();
+        let k = Store::store_deduped(&bytes, None).unwrap();
+        assert_eq!(Store::store_deduped(&bytes, None).unwrap(), k);
+        assert_eq!(Store::snapshot().stores, 1);
+
+        // The shape is applied before probing, so a SHA2-512 block isn't mistaken for (or hidden
+        // by) the SHA2-256 one.
+        let sha512 = CidShape::DEFAULT.with_hash(0x13);
+        let first = Store::store_deduped(&bytes, Some(&sha512)).unwrap();
+        assert_ne!(first, k);
+        let link: Link<String, Store> = Link::from_value("data".into(), Some(sha512));
+        assert_eq!(link.save_deduped().unwrap(), first);
+        assert_eq!(Store::snapshot().stores, 2);
+    }
+}
@@ -1,14 +1,78 @@
-use std::marker::PhantomData;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, Read, Write},
+    time::Instant,
+};
 
-use cid::Cid;
-use serde::{Deserialize, Serialize};
+use alloc::{borrow::Cow, collections::BTreeMap, string::ToString, vec::Vec};
 
-#[derive(Copy, Clone)]
+use cid::{
+    multihash::{Code, Multihash, MultihashDigest},
+    Cid,
+};
+use serde::{
+    de::{DeserializeOwned, DeserializeSeed},
+    Deserialize, Serialize,
+};
+
+use crate::{
+    codec, dag_pb, error, Codec, MultihashCode, StoreError, Value, ValueDeserializer, RAW,
+};
+
+/// How a block is addressed: the codec it's encoded with and the multihash function its CID
+/// uses.
+///
+/// A shape serializes as a map with two integer fields, `codec` and `mh_code` (their multicodec
+/// codes, e.g., `{"codec": 113, "mh_code": 18}` for [`CidShape::DEFAULT`]), so nodes can record
+/// how their children should be stored. This form is stable.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CidShape {
     pub codec: u64,
     pub mh_code: u64,
 }
 
+impl CidShape {
+    /// DAG-CBOR with a SHA2-256 multihash, the shape stores use when none is requested.
+    pub const DEFAULT: CidShape = CidShape::new(Codec::DagCbor, MultihashCode::Sha2_256);
+
+    /// A shape with the given codec and multihash function. Codes without a [`Codec`] or
+    /// [`MultihashCode`] variant can be set through the raw fields.
+    pub const fn new(codec: Codec, hash: MultihashCode) -> Self {
+        CidShape {
+            codec: codec.as_u64(),
+            mh_code: hash.as_u64(),
+        }
+    }
+
+    /// This shape with a different codec.
+    #[must_use]
+    pub const fn with_codec(self, codec: u64) -> Self {
+        CidShape { codec, ..self }
+    }
+
+    /// This shape with a different multihash function.
+    #[must_use]
+    pub const fn with_hash(self, mh_code: u64) -> Self {
+        CidShape { mh_code, ..self }
+    }
+
+    /// The CID of `bytes` in this shape, hashed with a built-in or
+    /// [registered](crate::HashRegistry) hasher. Identity CIDs can only embed up to
+    /// [`MAX_IDENTITY_SIZE`] bytes; larger blocks fail with [`StoreError::IdentityTooLarge`].
+    pub fn cid(&self, bytes: &[u8]) -> Result<Cid, StoreError> {
+        Ok(Cid::new_v1(self.codec, digest(self.mh_code, bytes)?))
+    }
+}
+
+/// [`CidShape::DEFAULT`].
+impl Default for CidShape {
+    fn default() -> Self {
+        CidShape::DEFAULT
+    }
+}
+
 impl From<&Cid> for CidShape {
     fn from(k: &Cid) -> Self {
         CidShape {
@@ -18,30 +82,1232 @@ impl From<&Cid> for CidShape {
     }
 }
 
+/// Hash `bytes` with the multihash function `code`: a hasher registered with
+/// [`HashRegistry::register`](crate::HashRegistry::register), or one built into `multihash`.
+pub(crate) fn digest(code: u64, bytes: &[u8]) -> Result<Multihash, StoreError> {
+    if code == IDENTITY {
+        return Multihash::wrap(IDENTITY, bytes)
+            .map_err(|_| StoreError::IdentityTooLarge(bytes.len()));
+    }
+    #[cfg(feature = "std")]
+    if let Some(hash) = crate::hash_registry::registered(code) {
+        return Ok(hash(bytes));
+    }
+    let code = Code::try_from(code).map_err(|_| StoreError::UnsupportedMultihash(code))?;
+    Ok(code.digest(bytes))
+}
+
+/// The identity multihash function, whose "digest" is the block itself. Links saved with an
+/// identity shape (e.g., `CidShape::DEFAULT.with_hash(IDENTITY)`) embed their block in the CID, so
+/// saving and loading them never touches the store.
+pub const IDENTITY: u64 = 0x00;
+
+/// The largest block that can be embedded in an identity CID.
+pub const MAX_IDENTITY_SIZE: usize = 64;
+
+/// The default [`StaticStore::MAX_BLOCK_SIZE`]: 2 MiB, the largest block most IPFS
+/// implementations will transfer.
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 2 << 20;
+
+/// The default [`StaticStore::MAX_LINK_DEPTH`], low enough that saving that many nested links fits
+/// in a spawned thread's default 2 MiB stack, even in debug builds.
+pub const DEFAULT_MAX_LINK_DEPTH: usize = 256;
+
 pub trait StaticStore {
-    type Error: std::error::Error;
+    type Error: core::error::Error + From<StoreError> + 'static;
+
+    /// Whether [`StaticStore::load`] checks that loaded blocks match their CIDs (see
+    /// [`StaticStore::verify`]). Trusted stores (e.g., local stores that verify on write) can
+    /// disable this to skip rehashing every block.
+    const VERIFY: bool = true;
+
+    /// The largest block [`StaticStore::load`] (and so [`Link`](crate::Link) and
+    /// [`AutoLink`](crate::AutoLink) reads) will accept, so a link to a hostile block can't force a
+    /// huge allocation. Defaults to [`DEFAULT_MAX_BLOCK_SIZE`]; set it to `usize::MAX` to disable
+    /// the limit.
+    const MAX_BLOCK_SIZE: usize = DEFAULT_MAX_BLOCK_SIZE;
 
+    /// How deeply modified links can be nested when they're saved (or flushed), since each level
+    /// recurses. Deeper nesting fails with [`StoreError::DepthExceeded`] rather than overflowing
+    /// the stack, and a link whose value contains the link itself fails with
+    /// [`StoreError::CycleDetected`]. Defaults to [`DEFAULT_MAX_LINK_DEPTH`].
+    ///
+    /// Without `std`, neither is checked.
+    const MAX_LINK_DEPTH: usize = DEFAULT_MAX_LINK_DEPTH;
+
+    /// The largest block [`StaticStore::store`] and saving links (e.g., [`Link::save`],
+    /// [`AutoLink::save`]) will write, so nothing is stored that peers can't transfer (many IPFS
+    /// implementations cap blocks at 1–2 MiB). Larger blocks fail with
+    /// [`StoreError::BlockTooLargeOnWrite`] before anything is written; a value whose block is
+    /// too large needs to be split into smaller linked nodes. Direct calls to
+    /// [`StaticStore::store_bytes`] aren't checked, and neither are identity CIDs, which are never
+    /// written.
+    ///
+    /// Defaults to `None`, which disables the check.
+    ///
+    /// [`Link::save`]: crate::Link::save
+    /// [`AutoLink::save`]: crate::AutoLink::save
+    const MAX_WRITE_SIZE: Option<usize> = None;
+
+    /// Whether saving an edited link first hashes its block and skips the write if it hashes to
+    /// the CID the link pointed to before the edit (e.g., an edit that was reverted) and the store
+    /// still has that block (see [`StaticStore::has`]). This costs an extra hash per saved edit,
+    /// plus an existence check when the hash matches. A block removed between that check and the
+    /// write being skipped is lost, so stores that garbage collect (or otherwise remove blocks)
+    /// concurrently should turn it off.
+    ///
+    /// Defaults to `true`.
+    const SKIP_UNCHANGED_WRITES: bool = true;
+
+    /// Store a block, returning its CID. The `shape` is a hint for the CID's codec and multihash;
+    /// `None` means [`CidShape::default()`].
     fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error>;
     fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error>;
 
+    /// The codec of the blocks [`StaticStore::encode`] produces, and so of values saved without a
+    /// shape. Defaults to DAG-CBOR.
+    fn default_codec() -> u64 {
+        crate::DAG_CBOR
+    }
+
+    /// Returns true if the store can encode and decode blocks of `codec` (with
+    /// [`StaticStore::encode_with`] and [`StaticStore::decode_with`]). Values saved with a shape
+    /// of any other codec fail with [`CodecError::UnsupportedCodec`] before they're encoded,
+    /// rather than being stored under CIDs whose codec doesn't match their blocks. Direct calls to
+    /// [`StaticStore::store_bytes`] aren't checked.
+    ///
+    /// By default, the store supports its [default codec](StaticStore::default_codec), and the
+    /// codecs `encode_with` handles itself: raw, DAG-PB, and DAG-JSON (with `std`). Stores that
+    /// override `encode_with` should override this to match.
+    ///
+    /// [`CodecError::UnsupportedCodec`]: crate::CodecError::UnsupportedCodec
+    fn supports_codec(codec: u64) -> bool {
+        codec == Self::default_codec() || builtin_codec(codec)
+    }
+
+    /// Encode `value` in the given codec, so one store can hold blocks of several codecs (each
+    /// link picks one with its [`CidShape`]). Links with a shape are saved with this.
+    ///
+    /// By default, raw blocks hold the value's bytes (the value must serialize as a byte string),
+    /// DAG-PB blocks hold a node (the value must serialize in the DAG-PB data model, see
+    /// [`PbNode`](crate::PbNode)), DAG-JSON is encoded with `serde_ipld_dagjson` (with `std`), and every other codec falls back
+    /// to [`StaticStore::encode`] (pre-sized with [`StaticStore::size_hint`]). Stores that can't
+    /// encode some of these should override it.
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        match encode_builtin(value, codec) {
+            Some(encoded) => Ok(encoded?),
+            None => encode_sized::<Self, _>(value),
+        }
+    }
+
+    /// Encode `value` into `w`, as [`StaticStore::encode`] would. By default, this encodes into a
+    /// buffer first; stores should override it to stream the encoded bytes.
+    #[cfg(feature = "std")]
+    fn encode_into<T: Serialize, W: Write>(value: &T, mut w: W) -> Result<(), Self::Error> {
+        w.write_all(&Self::encode(value)?)
+            .map_err(|e| StoreError::Io(e).into())
+    }
+
+    /// The length of `value` encoded with [`StaticStore::encode`]. By default, this counts the
+    /// bytes written by [`StaticStore::encode_into`], so it only buffers the value if that does.
+    /// Without `std`, it encodes the value into a buffer.
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        #[cfg(feature = "std")]
+        {
+            let mut counter = ByteCounter(0);
+            Self::encode_into(value, &mut counter)?;
+            Ok(counter.0)
+        }
+        #[cfg(not(feature = "std"))]
+        Ok(Self::encode(value)?.len())
+    }
+
+    /// A guess at the length of `value` encoded with [`StaticStore::encode`], used to pre-size the
+    /// buffer links and [`StaticStore::store`] encode into, so large values aren't regrown (and
+    /// copied) as they're serialized. `None` (the default) means no guess; a wrong guess only
+    /// costs an allocation. Stores that know their blocks' sizes (from a schema, or recent blocks)
+    /// can override it.
+    ///
+    /// With a hint, values are encoded with [`StaticStore::encode_into`] instead of
+    /// [`StaticStore::encode`], so stores providing hints should stream their encoding. Raw,
+    /// DAG-PB, and DAG-JSON blocks (see [`StaticStore::encode_with`]) aren't pre-sized.
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        let _ = value;
+        None
+    }
+
+    /// The CID [`StaticStore::store`] would return for `value`, without storing it (e.g., to check
+    /// whether a locally-built value is already stored with [`StaticStore::has`]).
+    ///
+    /// The default implementation encodes the value and hashes it in the requested shape (see
+    /// [`CidShape::cid`]); stores whose `store_bytes` computes CIDs differently must override this.
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        let bytes = encode::<Self, _>(value, shape)?;
+        Ok(shape.copied().unwrap_or_default().cid(&bytes)?)
+    }
+
+    /// Load a block from the store. Identity CIDs aren't stored, so this only finds their blocks
+    /// if the store happens to hold them; [`StaticStore::load_block`] reads them from the CID.
     fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error>;
 
+    /// Load a block, failing with [`StoreError::BlockTooLarge`] if it's larger than `max` bytes.
+    ///
+    /// The default implementation loads the whole block and then checks its length. Stores that
+    /// read blocks incrementally (or know their sizes up front) should override this to give up
+    /// before buffering an oversized block.
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        let bytes = Self::load_bytes(k)?;
+        check_block_size(k, bytes.len(), max)?;
+        Ok(bytes)
+    }
+
+    /// Load a block for [`StaticStore::load`] inside a [`with_deadline`] scope, failing with
+    /// [`StoreError::Timeout`] if `deadline` passes first. Loads are only started before the
+    /// deadline, so this bounds how long a chain of loads can take; stores with interruptible I/O
+    /// (e.g., network requests with a timeout) should override this to also give up on a load in
+    /// progress.
+    ///
+    /// The default implementation ignores the deadline and loads the block with
+    /// [`StaticStore::load_bytes_limited`], up to [`StaticStore::MAX_BLOCK_SIZE`].
+    #[cfg(feature = "std")]
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        let _ = deadline;
+        Self::load_bytes_limited(k, Self::MAX_BLOCK_SIZE)
+    }
+
+    /// Load the block `k` addresses, as [`StaticStore::load`], links, and walks do. An identity
+    /// CID's block is borrowed from the CID itself (see [`IDENTITY`]) without touching the store;
+    /// any other block is charged to the current [`with_load_budget`] scope and loaded up to
+    /// [`StaticStore::MAX_BLOCK_SIZE`] with [`StaticStore::load_bytes_limited`] (or
+    /// [`StaticStore::load_bytes_deadline`] inside a [`with_deadline`] scope). The block isn't
+    /// verified (see [`StaticStore::verify`]).
+    fn load_block(k: &Cid) -> Result<Cow<'_, [u8]>, Self::Error> {
+        if let Some(bytes) = inline_block(k) {
+            return Ok(Cow::Borrowed(bytes));
+        }
+        charge_load()?;
+        load_stored::<Self>(k).map(Cow::Owned)
+    }
+
+    /// Returns true if the store has the block.
+    ///
+    /// By default, this loads the block (treating [`StaticStore::is_not_found`] errors as absent),
+    /// so backends should override it with a cheaper existence check.
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        match Self::load_bytes(k) {
+            Ok(_) => Ok(true),
+            Err(e) if Self::is_not_found(&e) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The length of the stored block, which is what [`StaticStore::load_bytes`] would return.
+    ///
+    /// By default, this loads the block, so backends should override it if they know the length
+    /// without reading the block.
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        Ok(Self::load_bytes(k)?.len())
+    }
+
+    /// The codec and multihash function of the block `k` addresses, which are part of the CID, so
+    /// this never touches the store (e.g., to route blocks by codec).
+    fn shape_of(k: &Cid) -> CidShape {
+        CidShape::from(k)
+    }
+
+    /// Returns true if the block `k` addresses can be loaded with [`StaticStore::load_block`]:
+    /// identity CIDs always can, and other blocks can if the store has them (see
+    /// [`StaticStore::has`]).
+    fn has_block(k: &Cid) -> Result<bool, Self::Error> {
+        Ok(inline_block(k).is_some() || Self::has(k)?)
+    }
+
+    /// The shape (see [`StaticStore::shape_of`]) and length (see [`StaticStore::block_size`]) of
+    /// the block `k` addresses, without decoding it. Identity CIDs are described without touching
+    /// the store.
+    fn describe(k: &Cid) -> Result<(CidShape, usize), Self::Error> {
+        let len = match inline_block(k) {
+            Some(bytes) => bytes.len(),
+            None => Self::block_size(k)?,
+        };
+        Ok((Self::shape_of(k), len))
+    }
+
     /// Decode an object.
-    fn decode<'a, T: Deserialize<'a>>(bytes: &[u8]) -> Result<T, Self::Error>;
+    ///
+    /// The value must own its data: [`StaticStore::load`] and [`Link::read`](crate::Link::read)
+    /// decode from the owned buffer [`StaticStore::load_bytes`] returns, which is dropped after
+    /// decoding, so nothing can borrow from it. Use [`StaticStore::decode_borrowed`] to decode a
+    /// value that borrows from a buffer you hold.
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error>;
+
+    /// Decode an object that may borrow from `bytes` (e.g., a `&str` or a `&[u8]` with
+    /// `#[serde(borrow)]`), without copying them.
+    ///
+    /// By default, this fails with [`StoreError::Unsupported`]; the bundled stores decode
+    /// DAG-CBOR.
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        let _ = bytes;
+        Err(StoreError::Unsupported("decode_borrowed").into())
+    }
+
+    /// Decode an object from a block of the given codec, the inverse of
+    /// [`StaticStore::encode_with`]. [`StaticStore::load`] decodes blocks according to their CIDs'
+    /// codecs with this.
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        match decode_builtin(bytes, codec) {
+            Some(decoded) => Ok(decoded?),
+            None => Self::decode(bytes),
+        }
+    }
+
+    /// Decode an object from `r`, as [`StaticStore::decode`] would. By default, this reads `r`
+    /// into a buffer first; stores should override it to decode as they read.
+    #[cfg(feature = "std")]
+    fn decode_from<T: DeserializeOwned, R: Read>(mut r: R) -> Result<T, Self::Error> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes).map_err(StoreError::Io)?;
+        Self::decode(&bytes)
+    }
+
+    /// Load an object, verifying the block first unless [`StaticStore::VERIFY`] is false, and
+    /// decoding it according to its codec (see [`StaticStore::decode_with`]). Errors are passed
+    /// through [`StaticStore::with_cid`]. Blocks
+    /// larger than [`StaticStore::MAX_BLOCK_SIZE`] are rejected. Identity CIDs are decoded from
+    /// the CID itself, without loading anything. Inside a [`with_deadline`] scope, blocks are
+    /// loaded with [`StaticStore::load_bytes_deadline`].
+    fn load<T: DeserializeOwned>(key: &Cid) -> Result<T, Self::Error> {
+        let load = || decode_block::<Self, T>(key, &Self::load_block(key)?);
+        load().map_err(|e| Self::with_cid(e, *key))
+    }
+
+    /// Load an object whose decoding needs runtime context (e.g., a type registry choosing the
+    /// concrete type by a tag), decoding it with `seed`. The block is loaded exactly as
+    /// [`StaticStore::load`] loads it and decoded as a [`Value`](crate::Value) first, which is
+    /// then handed to `seed` (with links presented as DAG-CBOR presents them), so any codec
+    /// [`StaticStore::decode_with`] supports works.
+    fn load_seed<'de, S: DeserializeSeed<'de>>(
+        key: &Cid,
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        let value: Value = Self::load(key)?;
+        seed.deserialize(ValueDeserializer::<serde::de::value::Error>::new(value))
+            .map_err(|e| Self::with_cid(StoreError::Encoding(e.to_string()).into(), *key))
+    }
+
+    /// Rewrite a block before [`StaticStore::load`] decodes it, returning the replacement (or
+    /// `None` to decode the block as it is). Use this to upgrade blocks written in older versions
+    /// of a format in one place, so every read (including [`Link::read`](crate::Link::read)) sees
+    /// the current version. The block has already been verified against `k`, which keeps naming
+    /// the original block. By default, every block is decoded as it is.
+    ///
+    /// For example, to upgrade a versioned enum as it's loaded:
+    ///
+    #[cfg_attr(feature = "std", doc = "```")]
+    #[cfg_attr(not(feature = "std"), doc = "```ignore")]
+    /// # use auto_ipld::{CidShape, MemoryError, MemoryStore, StaticStore, Value};
+    /// # use cid::Cid;
+    /// # use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    /// #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    /// enum Config {
+    ///     V1 { name: String },
+    ///     V2 { name: String, retries: u64 },
+    /// }
+    ///
+    /// struct Upgrading;
+    /// type Blocks = MemoryStore<Upgrading>;
+    ///
+    /// impl StaticStore for Upgrading {
+    ///     type Error = MemoryError;
+    ///
+    ///     fn pre_decode(_k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, MemoryError> {
+    ///         // Blocks that aren't old configs are decoded as they are.
+    ///         let Ok(Value::Map(mut m)) = Self::decode::<Value>(bytes) else {
+    ///             return Ok(None);
+    ///         };
+    ///         let Some(Value::Map(mut fields)) = m.remove("V1") else {
+    ///             return Ok(None);
+    ///         };
+    ///         fields.insert("retries".into(), Value::Integer(3));
+    ///         let v2 = Value::Map([("V2".into(), Value::Map(fields))].into());
+    ///         Ok(Some(Self::encode(&v2)?))
+    ///     }
+    ///
+    ///     // Everything else goes to a `MemoryStore`.
+    /// #   fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, MemoryError> {
+    /// #       Blocks::store_bytes(value, shape)
+    /// #   }
+    /// #   fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, MemoryError> {
+    /// #       Blocks::encode(value)
+    /// #   }
+    /// #   fn load_bytes(k: &Cid) -> Result<Vec<u8>, MemoryError> {
+    /// #       Blocks::load_bytes(k)
+    /// #   }
+    /// #   fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, MemoryError> {
+    /// #       Blocks::decode(bytes)
+    /// #   }
+    /// }
+    ///
+    /// let old = Upgrading::store(&Config::V1 { name: "db".into() }, None).unwrap();
+    /// assert_eq!(
+    ///     Upgrading::load::<Config>(&old).unwrap(),
+    ///     Config::V2 { name: "db".into(), retries: 3 }
+    /// );
+    /// ```
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        let _ = (k, bytes);
+        Ok(None)
+    }
 
-    /// Load an object.
-    fn load<'a, T: Deserialize<'a>>(key: &Cid) -> Result<T, Self::Error> {
-        Self::decode(&Self::load_bytes(key)?)
+    /// Check that `bytes` hash to the multihash in `k` (with a built-in or
+    /// [registered](crate::HashRegistry) hasher), returning [`StoreError::HashMismatch`] if they
+    /// don't.
+    fn verify(k: &Cid, bytes: &[u8]) -> Result<(), Self::Error> {
+        Ok(verify_hash(k, bytes)?)
     }
-    /// Store an object. The `shape` is a hint.
+    /// Store an object. The `shape` is a hint; with an [`IDENTITY`] shape, the encoded object is
+    /// embedded in the returned CID and nothing is written.
+    ///
+    /// If saving a modified link inside `value` fails, that link's error is returned rather than
+    /// the codec's (stringified) serialization error.
     fn store<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
-        Self::store_bytes(&Self::encode(value)?, shape)
+        let bytes = encode::<Self, _>(value, shape)?;
+        match identity_cid(&bytes, shape)? {
+            Some(k) => Ok(k),
+            None => {
+                check_write_size::<Self>(bytes.len())?;
+                Self::store_bytes(&bytes, shape)
+            }
+        }
+    }
+
+    /// Store a block unless it's already present, returning its CID and whether it was newly
+    /// written.
+    ///
+    /// The default implementation can't tell whether the block existed, so it always writes it
+    /// and reports it as new. Stores that can cheaply detect existing blocks should override this.
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        Ok((Self::store_bytes(value, shape)?, true))
+    }
+
+    /// Store a block unless [`StaticStore::has`] reports that it's already present, returning its
+    /// CID either way. Unlike [`StaticStore::store_if_absent`], this always probes the store first,
+    /// so it avoids rewriting blocks even in stores that don't override `store_if_absent`.
+    ///
+    /// The CID is computed locally in the requested shape (see [`CidShape::cid`]) before the probe,
+    /// so this assumes the store derives CIDs from shapes in the usual way.
+    fn store_deduped(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        dedup::<Self>(value, shape).map(|(k, _)| k)
+    }
+
+    /// Store a batch of blocks, returning each block's CID and whether it was newly written (see
+    /// [`StaticStore::store_if_absent`]). By default, the blocks are stored one at a time.
+    fn store_many(blocks: &[(Vec<u8>, Option<CidShape>)]) -> Result<Vec<(Cid, bool)>, Self::Error> {
+        blocks
+            .iter()
+            .map(|(bytes, shape)| Self::store_if_absent(bytes, shape.as_ref()))
+            .collect()
+    }
+
+    /// Load a batch of blocks, in the order of `keys`. By default, the blocks are loaded one at a
+    /// time; backends that can pipeline requests should override this.
+    fn load_many(keys: &[Cid]) -> Result<Vec<Vec<u8>>, Self::Error> {
+        keys.iter().map(Self::load_bytes).collect()
+    }
+
+    /// Load the blocks `keys` address with one [`StaticStore::load_many`] call, in the order of
+    /// `keys`, as [`StaticStore::load_block`] would load each: identity CIDs are borrowed from the
+    /// CIDs themselves, and each distinct stored block is loaded once and charged to the current
+    /// [`with_load_budget`] scope.
+    fn load_blocks(keys: &[Cid]) -> Result<Vec<Cow<'_, [u8]>>, Self::Error> {
+        let mut index = BTreeMap::new();
+        let mut stored = Vec::new();
+        let mut uses = Vec::new();
+        for k in keys {
+            if inline_block(k).is_none() {
+                let i = *index.entry(*k).or_insert_with(|| {
+                    stored.push(*k);
+                    uses.push(0);
+                    stored.len() - 1
+                });
+                uses[i] += 1;
+            }
+        }
+        let mut blocks = Vec::new();
+        if !stored.is_empty() {
+            for _ in &stored {
+                charge_load()?;
+            }
+            blocks = Self::load_many(&stored)?;
+        }
+        // Move each block into its last use, copying it for any earlier ones.
+        Ok(keys
+            .iter()
+            .map(|k| match inline_block(k) {
+                Some(bytes) => Cow::Borrowed(bytes),
+                None => {
+                    let i = index[k];
+                    uses[i] -= 1;
+                    match uses[i] {
+                        0 => Cow::Owned(core::mem::take(&mut blocks[i])),
+                        _ => Cow::Owned(blocks[i].clone()),
+                    }
+                }
+            })
+            .collect())
+    }
+
+    /// List the stored blocks with CIDs in `start..end`, compared by their binary encoding (so CIDs
+    /// sharing a byte prefix are contiguous). Sharded backends can use this to answer which blocks
+    /// a shard holds.
+    ///
+    /// By default, this returns [`StoreError::Unsupported`].
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        let _ = (start, end);
+        Err(StoreError::Unsupported("keys_in_range").into())
+    }
+
+    /// List every stored block (e.g., to find unreachable blocks with [`gc`](crate::gc)).
+    ///
+    /// By default, this returns [`StoreError::Unsupported`].
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        Err(StoreError::Unsupported("keys").into())
+    }
+
+    /// Delete a block, returning true if it was stored. Links to the block (from other blocks, or
+    /// loaded links) aren't affected, and will fail to load it.
+    ///
+    /// By default, this returns [`StoreError::Unsupported`].
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        let _ = k;
+        Err(StoreError::Unsupported("remove").into())
+    }
+
+    /// Returns true if `err` means that a requested block isn't in the store. By default, no error
+    /// is recognized as such.
+    fn is_not_found(err: &Self::Error) -> bool {
+        let _ = err;
+        false
+    }
+
+    /// Returns true if the operation that failed with `err` may succeed if it's tried again (e.g.,
+    /// a timeout), as opposed to a terminal error like a missing block or a malformed request.
+    /// [`Retry`](crate::Retry) only retries retryable errors. By default, every error is
+    /// considered retryable.
+    fn is_retryable(err: &Self::Error) -> bool {
+        let _ = err;
+        true
+    }
+
+    /// Attach a link's debug label (see [`Link::with_label`](crate::Link::with_label)) to an
+    /// error. By default, the label is discarded.
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        let _ = label;
+        err
+    }
+
+    /// Attach the CID of the block that was being loaded to an error from [`StaticStore::load`]
+    /// (whether loading, verifying, or decoding the block failed), so errors from deep inside a
+    /// DAG say which block they came from. By default, the CID is discarded.
+    ///
+    /// To keep it, give the error type a variant (or field) holding the CID and the original
+    /// error, e.g., `Loading { cid: Cid, source: Box<Self> }`, and build it here. Errors that
+    /// already name their block (like a [`StoreError::NotFound`]) can be returned as they are.
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        let _ = cid;
+        err
+    }
+}
+
+/// Encode `value` with `S`, in the codec of `shape` if there is one (see
+/// [`StaticStore::encode_with`]), recovering the typed error of any link that failed to save during
+/// serialization.
+pub(crate) fn encode<S: StaticStore + ?Sized, T: Serialize>(
+    value: &T,
+    shape: Option<&CidShape>,
+) -> Result<Vec<u8>, S::Error> {
+    recover_stashed(|| {
+        let _guard = enter::<S, _>(value)?;
+        match shape {
+            Some(shape) if !S::supports_codec(shape.codec) => {
+                Err(StoreError::Codec(codec::CodecError::UnsupportedCodec(shape.codec)).into())
+            }
+            Some(shape) => S::encode_with(value, shape.codec),
+            None => encode_sized::<S, _>(value),
+        }
+    })
+}
+
+/// Returns true if `codec` is one [`encode_builtin`] and [`decode_builtin`] handle: raw, DAG-PB,
+/// and DAG-JSON (with `std`).
+pub(crate) fn builtin_codec(codec: u64) -> bool {
+    codec == RAW || codec == crate::DAG_PB || (cfg!(feature = "std") && codec == crate::DAG_JSON)
+}
+
+/// Encode `value` in a codec stores handle themselves (see [`builtin_codec`]), or return `None`
+/// for the store's own encoder.
+pub(crate) fn encode_builtin<T: Serialize>(
+    value: &T,
+    codec: u64,
+) -> Option<Result<Vec<u8>, StoreError>> {
+    Some(match codec {
+        RAW => codec::encode_raw(value),
+        crate::DAG_PB => dag_pb::encode_dag_pb(value),
+        #[cfg(feature = "std")]
+        crate::DAG_JSON => {
+            serde_ipld_dagjson::to_vec(value).map_err(|e| StoreError::Encoding(e.to_string()))
+        }
+        _ => return None,
+    })
+}
+
+/// Decode a block in a codec stores handle themselves (see [`builtin_codec`]), or return `None`
+/// for the store's own decoder.
+pub(crate) fn decode_builtin<T: DeserializeOwned>(
+    bytes: &[u8],
+    codec: u64,
+) -> Option<Result<T, StoreError>> {
+    Some(match codec {
+        RAW => codec::decode_raw(bytes),
+        crate::DAG_PB => dag_pb::decode_dag_pb(bytes),
+        #[cfg(feature = "std")]
+        crate::DAG_JSON => {
+            serde_ipld_dagjson::from_slice(bytes).map_err(|e| StoreError::Encoding(e.to_string()))
+        }
+        _ => return None,
+    })
+}
+
+/// Check that `bytes` hash to the multihash in `k`, as [`StaticStore::verify`] does by default.
+pub(crate) fn verify_hash(k: &Cid, bytes: &[u8]) -> Result<(), StoreError> {
+    let hash = k.hash();
+    let matches = match hash.code() {
+        IDENTITY => hash.digest() == bytes,
+        code => digest(code, bytes)? == *hash,
+    };
+    if !matches {
+        return Err(StoreError::HashMismatch(*k));
+    }
+    Ok(())
+}
+
+/// Encode `value` with `S`, into a buffer pre-sized with [`StaticStore::size_hint`] if there's a
+/// hint.
+fn encode_sized<S: StaticStore + ?Sized, T: Serialize>(value: &T) -> Result<Vec<u8>, S::Error> {
+    #[cfg(feature = "std")]
+    if let Some(capacity) = S::size_hint(value) {
+        let mut buf = Vec::with_capacity(capacity);
+        S::encode_into(value, &mut buf)?;
+        return Ok(buf);
+    }
+    S::encode(value)
+}
+
+/// Like [`encode`], but only measures the encoded value. Values in the store's default codec are
+/// counted with [`StaticStore::encoded_len`], without buffering them; other codecs are encoded.
+pub(crate) fn encoded_len<S: StaticStore + ?Sized, T: Serialize>(
+    value: &T,
+    shape: Option<&CidShape>,
+) -> Result<usize, S::Error> {
+    recover_stashed(|| {
+        let _guard = enter::<S, _>(value)?;
+        match shape {
+            Some(shape) if !S::supports_codec(shape.codec) => {
+                Err(StoreError::Codec(codec::CodecError::UnsupportedCodec(shape.codec)).into())
+            }
+            Some(shape) if shape.codec != S::default_codec() => {
+                S::encode_with(value, shape.codec).map(|b| b.len())
+            }
+            _ => S::encoded_len(value),
+        }
+    })
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static IN_PROGRESS: RefCell<InProgressSet> = const {
+        RefCell::new(InProgressSet {
+            inline: [(0, 0); INLINE_IN_PROGRESS],
+            spilled: Vec::new(),
+            len: 0,
+        })
+    };
+}
+
+/// How many in-progress values are tracked without allocating, so that saving shallow values
+/// (e.g., inlined [`AutoLink`](crate::AutoLink)s) doesn't allocate.
+#[cfg(feature = "std")]
+const INLINE_IN_PROGRESS: usize = 16;
+
+/// The values being encoded or flushed on this thread, outermost first, by address and size.
+#[cfg(feature = "std")]
+struct InProgressSet {
+    inline: [(usize, usize); INLINE_IN_PROGRESS],
+    spilled: Vec<(usize, usize)>,
+    len: usize,
+}
+
+#[cfg(feature = "std")]
+impl InProgressSet {
+    fn contains(&self, key: &(usize, usize)) -> bool {
+        self.inline[..self.len.min(INLINE_IN_PROGRESS)].contains(key) || self.spilled.contains(key)
+    }
+
+    fn push(&mut self, key: (usize, usize)) {
+        match self.inline.get_mut(self.len) {
+            Some(slot) => *slot = key,
+            None => self.spilled.push(key),
+        }
+        self.len += 1;
+    }
+
+    fn pop(&mut self) {
+        self.len -= 1;
+        if self.len >= INLINE_IN_PROGRESS {
+            self.spilled.pop();
+        }
+    }
+}
+
+/// Mark `value` as being encoded or flushed (and so possibly saving the links inside it) until
+/// the returned guard is dropped, failing with [`StoreError::CycleDetected`] if it's already being
+/// encoded further up the stack, or with [`StoreError::DepthExceeded`] if
+/// [`StaticStore::MAX_LINK_DEPTH`] values already are.
+///
+/// Values are compared by address and size: a value nested inside another (e.g., a link's value
+/// inside its parent) may share its address, but it's always smaller.
+///
+/// The values in progress are tracked per thread, so without `std` nothing is checked (see the
+/// crate docs).
+pub(crate) fn enter<S: StaticStore + ?Sized, T: ?Sized>(
+    value: &T,
+) -> Result<InProgress, StoreError> {
+    enter_limited(value, S::MAX_LINK_DEPTH)
+}
+
+/// Like [`enter`], but with the depth limit given directly (e.g., an
+/// [`AsyncStore`](crate::AsyncStore)'s).
+#[cfg_attr(not(feature = "std"), allow(unused_variables))]
+pub(crate) fn enter_limited<T: ?Sized>(
+    value: &T,
+    max_depth: usize,
+) -> Result<InProgress, StoreError> {
+    #[cfg(feature = "std")]
+    {
+        let key = (
+            value as *const T as *const () as usize,
+            core::mem::size_of_val(value),
+        );
+        IN_PROGRESS.with(|s| {
+            let mut s = s.borrow_mut();
+            if s.contains(&key) {
+                return Err(StoreError::CycleDetected);
+            }
+            if s.len >= max_depth {
+                return Err(StoreError::DepthExceeded(max_depth));
+            }
+            s.push(key);
+            Ok(InProgress(()))
+        })
+    }
+    #[cfg(not(feature = "std"))]
+    Ok(InProgress(()))
+}
+
+/// Returned by [`enter`]; unmarks the value when dropped (even if encoding it panicked).
+pub(crate) struct InProgress(());
+
+#[cfg(feature = "std")]
+impl Drop for InProgress {
+    fn drop(&mut self) {
+        IN_PROGRESS.with(|s| s.borrow_mut().pop());
+    }
+}
+
+/// Store `bytes` unless the store already has them, returning the CID and whether it was written.
+pub(crate) fn dedup<S: StaticStore + ?Sized>(
+    bytes: &[u8],
+    shape: Option<&CidShape>,
+) -> Result<(Cid, bool), S::Error> {
+    if let Some(k) = identity_cid(bytes, shape)? {
+        return Ok((k, false));
+    }
+    check_write_size::<S>(bytes.len())?;
+    let shape = shape.copied().unwrap_or_default();
+    let k = shape.cid(bytes)?;
+    if S::has(&k)? {
+        return Ok((k, false));
+    }
+    let stored = S::store_bytes(bytes, Some(&shape))?;
+    debug_assert_eq!(stored, k, "store computed a different CID");
+    Ok((k, true))
+}
+
+/// The identity CID embedding `bytes`, if `shape` asks for one.
+pub(crate) fn identity_cid(
+    bytes: &[u8],
+    shape: Option<&CidShape>,
+) -> Result<Option<Cid>, StoreError> {
+    match shape {
+        Some(shape) if shape.mh_code == IDENTITY => shape.cid(bytes).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// The block embedded in `k`, if it's an identity CID.
+pub(crate) fn inline_block(k: &Cid) -> Option<&[u8]> {
+    (k.hash().code() == IDENTITY).then(|| k.hash().digest())
+}
+
+/// Decode a block loaded for `k` as [`StaticStore::load`] does by default: verify it (unless
+/// [`StaticStore::VERIFY`] is false), run [`StaticStore::pre_decode`], and decode it in `k`'s codec.
+pub(crate) fn decode_block<S, T>(k: &Cid, bytes: &[u8]) -> Result<T, S::Error>
+where
+    S: StaticStore + ?Sized,
+    T: DeserializeOwned,
+{
+    if S::VERIFY {
+        S::verify(k, bytes)?;
+    }
+    match S::pre_decode(k, bytes)? {
+        Some(upgraded) => S::decode_with(&upgraded, k.codec()),
+        None => S::decode_with(bytes, k.codec()),
+    }
+}
+
+/// Fail with [`StoreError::BlockTooLargeOnWrite`] if a `len`-byte block is too large for `S` to
+/// write (see [`StaticStore::MAX_WRITE_SIZE`]).
+pub(crate) fn check_write_size<S: StaticStore + ?Sized>(len: usize) -> Result<(), StoreError> {
+    check_write_limit(len, S::MAX_WRITE_SIZE)
+}
+
+/// Fail with [`StoreError::BlockTooLargeOnWrite`] if a `len`-byte block is larger than `max`.
+pub(crate) fn check_write_limit(len: usize, max: Option<usize>) -> Result<(), StoreError> {
+    match max {
+        Some(max) if len > max => Err(StoreError::BlockTooLargeOnWrite { len, max }),
+        _ => Ok(()),
+    }
+}
+
+/// Fail with [`StoreError::BlockTooLarge`] if a `len`-byte block for `k` exceeds `max`.
+pub(crate) fn check_block_size(k: &Cid, len: usize, max: usize) -> Result<(), StoreError> {
+    if len > max {
+        return Err(StoreError::BlockTooLarge { cid: *k, len, max });
+    }
+    Ok(())
+}
+
+pub(crate) fn recover_stashed<R, E: 'static>(f: impl FnOnce() -> Result<R, E>) -> Result<R, E> {
+    // Drop anything left over from a serialization that didn't go through here.
+    error::take_stashed::<()>();
+    f().map_err(|e| error::take_stashed().unwrap_or(e))
+}
+
+/// An [`io::Write`] that counts the bytes written to it, for measuring encoded values without
+/// buffering them.
+#[cfg(feature = "std")]
+pub(crate) struct ByteCounter(pub(crate) usize);
+
+#[cfg(feature = "std")]
+impl Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A multihash function that can hash a block as it's read.
+#[cfg(feature = "std")]
+enum StreamHasher {
+    Sha2_256(cid::multihash::Sha2_256),
+    Sha2_512(cid::multihash::Sha2_512),
+    Blake3_256(alloc::boxed::Box<cid::multihash::Blake3_256>),
+}
+
+#[cfg(feature = "std")]
+impl StreamHasher {
+    /// A hasher for the built-in multihash `code`, if it can be streamed.
+    fn new(code: u64) -> Option<Self> {
+        match Code::try_from(code).ok()? {
+            Code::Sha2_256 => Some(StreamHasher::Sha2_256(Default::default())),
+            Code::Sha2_512 => Some(StreamHasher::Sha2_512(Default::default())),
+            Code::Blake3_256 => Some(StreamHasher::Blake3_256(Default::default())),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        use cid::multihash::Hasher;
+        match self {
+            StreamHasher::Sha2_256(h) => h.update(bytes),
+            StreamHasher::Sha2_512(h) => h.update(bytes),
+            StreamHasher::Blake3_256(h) => h.update(bytes),
+        }
+    }
+
+    fn finish(mut self, code: u64) -> Multihash {
+        use cid::multihash::Hasher;
+        let digest = match &mut self {
+            StreamHasher::Sha2_256(h) => h.finalize(),
+            StreamHasher::Sha2_512(h) => h.finalize(),
+            StreamHasher::Blake3_256(h) => h.finalize(),
+        };
+        Multihash::wrap(code, digest).expect("digest fits in a multihash")
+    }
+}
+
+/// A reader that counts (and, when verifying, hashes) the bytes read through it.
+#[cfg(feature = "std")]
+struct HashingReader<R> {
+    inner: R,
+    hasher: Option<StreamHasher>,
+    len: usize,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(&buf[..n]);
+        }
+        self.len += n;
+        Ok(n)
+    }
+}
+
+/// Like [`decode_block`], but reads the block for `k` from `r`. DAG-CBOR blocks are decoded as
+/// they're read (with [`StaticStore::decode_from`]), and verified by hashing them as they pass
+/// through, if they use a multihash that can be streamed (SHA2 or BLAKE3). Other blocks are
+/// read into a buffer and decoded with [`decode_block`].
+///
+/// Either way, at most [`StaticStore::MAX_BLOCK_SIZE`] bytes are read. Streamed blocks skip
+/// [`StaticStore::pre_decode`], which needs the whole block.
+#[cfg(feature = "std")]
+pub(crate) fn decode_block_from<S, T>(k: &Cid, r: impl Read) -> Result<T, S::Error>
+where
+    S: StaticStore + ?Sized,
+    T: DeserializeOwned,
+{
+    let code = k.hash().code();
+    let max = S::MAX_BLOCK_SIZE;
+    let mut r = r.take(max.saturating_add(1) as u64);
+    let hasher = if S::VERIFY {
+        StreamHasher::new(code)
+    } else {
+        None
+    };
+    if k.codec() != crate::DAG_CBOR || (S::VERIFY && hasher.is_none()) {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes).map_err(StoreError::Io)?;
+        check_block_size(k, bytes.len(), max)?;
+        return decode_block::<S, T>(k, &bytes);
+    }
+
+    let mut r = HashingReader {
+        inner: r,
+        hasher,
+        len: 0,
+    };
+    let decoded = S::decode_from(&mut r);
+    // Hash (and count) whatever the decoder didn't read, too.
+    let drained = io::copy(&mut r, &mut io::sink());
+    check_block_size(k, r.len, max)?;
+    drained.map_err(StoreError::Io)?;
+    if let Some(hasher) = r.hasher {
+        if hasher.finish(code) != *k.hash() {
+            return Err(StoreError::HashMismatch(*k).into());
+        }
+    }
+    decoded
+}
+
+/// A block written while saving a link, as passed to [`StaticStore::store_many`].
+#[cfg(feature = "std")]
+pub(crate) type Block = (Vec<u8>, Option<CidShape>);
+
+/// How links write their blocks when saved (see [`Link::save_batched`](crate::Link::save_batched)).
+#[cfg(feature = "std")]
+enum SaveMode {
+    /// Write each block as it's encoded.
+    Write,
+    /// Collect blocks without writing them, leaving the links modified.
+    Collect(Vec<Block>),
+    /// The collected blocks have been written, so mark the links as saved without writing.
+    Commit,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static SAVE_MODE: RefCell<SaveMode> = const { RefCell::new(SaveMode::Write) };
+}
+
+/// Run `f` in the given save mode, returning its result and the final mode.
+#[cfg(feature = "std")]
+fn with_mode<R>(mode: SaveMode, f: impl FnOnce() -> R) -> (R, SaveMode) {
+    /// Restores the previous mode, even if `f` panics.
+    struct Restore(Option<SaveMode>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            if let Some(prev) = self.0.take() {
+                SAVE_MODE.with(|m| *m.borrow_mut() = prev);
+            }
+        }
     }
+
+    let mut restore = Restore(Some(SAVE_MODE.with(|m| m.replace(mode))));
+    let out = f();
+    let prev = restore.0.take().expect("mode already restored");
+    (out, SAVE_MODE.with(|m| m.replace(prev)))
+}
+
+/// The loads left in the innermost [`with_load_budget`] scope, and that scope's limit.
+#[cfg(feature = "std")]
+#[derive(Copy, Clone)]
+struct LoadBudget {
+    remaining: usize,
+    max: usize,
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static LOAD_BUDGET: Cell<Option<LoadBudget>> = const { Cell::new(None) };
+}
+
+/// Run `f`, allowing it to load at most `max_loads` blocks on this thread: later loads fail with
+/// [`StoreError::BudgetExhausted`]. Use this to cap how much of a DAG one request can pull in,
+/// e.g., through a chain of [`MagicStore`] dereferences, whatever the store.
+///
+/// Every block fetched by [`StaticStore::load`] (and so [`Link::read`](crate::Link::read)),
+/// [`RawLink::read`](crate::RawLink::read), and [`preload_all`](crate::preload_all) counts, but
+/// cached values and identity CIDs don't, and neither do direct calls to
+/// [`StaticStore::load_bytes`]. Nested scopes can only lower the budget, and their loads count
+/// towards the enclosing scope's too. The previous budget is restored when `f` returns or
+/// panics.
+#[cfg(feature = "std")]
+pub fn with_load_budget<R>(max_loads: usize, f: impl FnOnce() -> R) -> R {
+    /// Restores the enclosing budget, minus the loads made in the scope, even if `f` panics.
+    struct Restore {
+        prev: Option<LoadBudget>,
+        start: usize,
+    }
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let left = LOAD_BUDGET.with(|b| b.get()).map_or(0, |b| b.remaining);
+            let used = self.start - left;
+            let prev = self.prev.map(|b| LoadBudget {
+                remaining: b.remaining - used,
+                ..b
+            });
+            LOAD_BUDGET.with(|b| b.set(prev));
+        }
+    }
+
+    let prev = LOAD_BUDGET.with(|b| b.get());
+    let budget = match prev {
+        Some(outer) if outer.remaining < max_loads => outer,
+        _ => LoadBudget {
+            remaining: max_loads,
+            max: max_loads,
+        },
+    };
+    LOAD_BUDGET.with(|b| b.set(Some(budget)));
+    let _restore = Restore {
+        prev,
+        start: budget.remaining,
+    };
+    f()
+}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Run `f`, failing loads on this thread with [`StoreError::Timeout`] once `deadline` has passed,
+/// e.g., to bound how long a request can wait on a slow network store.
+///
+/// Like [`with_load_budget`], this covers every block fetched by [`StaticStore::load`] (and so
+/// [`Link::read`](crate::Link::read)), through [`StaticStore::load_bytes_deadline`]: loads
+/// aren't started after the deadline, and stores that support it give up on loads in progress
+/// when it passes. Nested scopes can only bring the deadline forward. The previous deadline is
+/// restored when `f` returns or panics.
+#[cfg(feature = "std")]
+pub fn with_deadline<R>(deadline: Instant, f: impl FnOnce() -> R) -> R {
+    /// Restores the enclosing deadline, even if `f` panics.
+    struct Restore(Option<Instant>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DEADLINE.with(|d| d.set(self.0));
+        }
+    }
+
+    let prev = DEADLINE.with(|d| d.get());
+    let deadline = prev.map_or(deadline, |prev| prev.min(deadline));
+    let _restore = Restore(DEADLINE.with(|d| d.replace(Some(deadline))));
+    f()
+}
+
+/// Load `k`'s block for [`StaticStore::load_block`], up to [`StaticStore::MAX_BLOCK_SIZE`],
+/// honoring the current [`with_deadline`] scope.
+fn load_stored<S: StaticStore + ?Sized>(k: &Cid) -> Result<Vec<u8>, S::Error> {
+    #[cfg(feature = "std")]
+    if let Some(deadline) = DEADLINE.with(|d| d.get()) {
+        if Instant::now() >= deadline {
+            return Err(StoreError::Timeout(*k).into());
+        }
+        let bytes = S::load_bytes_deadline(k, deadline)?;
+        check_block_size(k, bytes.len(), S::MAX_BLOCK_SIZE)?;
+        return Ok(bytes);
+    }
+    S::load_bytes_limited(k, S::MAX_BLOCK_SIZE)
+}
+
+/// Count a block load against the current [`with_load_budget`] scope, failing with
+/// [`StoreError::BudgetExhausted`] if it's used up.
+pub(crate) fn charge_load() -> Result<(), StoreError> {
+    #[cfg(feature = "std")]
+    return LOAD_BUDGET.with(|b| match b.get() {
+        None => Ok(()),
+        Some(LoadBudget { remaining: 0, max }) => Err(StoreError::BudgetExhausted(max)),
+        Some(budget) => {
+            b.set(Some(LoadBudget {
+                remaining: budget.remaining - 1,
+                ..budget
+            }));
+            Ok(())
+        }
+    });
+    #[cfg(not(feature = "std"))]
+    Ok(())
+}
+
+/// Returns true while links' blocks are being collected for a batch rather than written; links
+/// are then left modified as they're encoded.
+pub(crate) fn collecting() -> bool {
+    #[cfg(feature = "std")]
+    return SAVE_MODE.with(|m| matches!(*m.borrow(), SaveMode::Collect(_)));
+    #[cfg(not(feature = "std"))]
+    false
+}
+
+/// Store a modified link's encoded value, returning its CID and whether the link should be marked
+/// as saved. Identity-shaped blocks are embedded in their CIDs rather than written (or batched).
+pub(crate) fn store_link_block<S: StaticStore + ?Sized>(
+    bytes: &[u8],
+    shape: Option<&CidShape>,
+) -> Result<(Cid, bool), S::Error> {
+    if let Some(k) = prepare_link_block(bytes, shape, S::MAX_WRITE_SIZE)? {
+        return Ok((k, true));
+    }
+    #[cfg(feature = "std")]
+    if let Some(batched) = batched_block(bytes, shape)? {
+        return Ok(batched);
+    }
+    Ok((S::store_bytes(bytes, shape)?, true))
+}
+
+/// Everything saving a modified link's encoded value does before writing it, for both
+/// [`StaticStore`]s and [`AsyncStore`](crate::AsyncStore)s: return the identity CID embedding the
+/// block if `shape` asks for one, and otherwise check that it isn't larger than `max_write`.
+pub(crate) fn prepare_link_block(
+    bytes: &[u8],
+    shape: Option<&CidShape>,
+    max_write: Option<usize>,
+) -> Result<Option<Cid>, StoreError> {
+    if let Some(k) = identity_cid(bytes, shape)? {
+        return Ok(Some(k));
+    }
+    check_write_limit(bytes.len(), max_write)?;
+    Ok(None)
+}
+
+/// Handle a link's block according to the save mode, returning `None` if it should be written as
+/// usual.
+#[cfg(feature = "std")]
+fn batched_block(
+    bytes: &[u8],
+    shape: Option<&CidShape>,
+) -> Result<Option<(Cid, bool)>, StoreError> {
+    let cid = || shape.unwrap_or(&CidShape::DEFAULT).cid(bytes);
+    SAVE_MODE.with(|m| {
+        Ok(match &mut *m.borrow_mut() {
+            SaveMode::Write => None,
+            SaveMode::Collect(blocks) => {
+                blocks.push((bytes.to_vec(), shape.copied()));
+                Some((cid()?, false))
+            }
+            SaveMode::Commit => Some((cid()?, true)),
+        })
+    })
+}
+
+/// Store `value` and every modified link inside it with a single [`StaticStore::store_many`]
+/// call, returning the value's CID.
+///
+/// The value is encoded twice: the first pass collects the links' blocks without marking the
+/// links as saved (so they stay modified if the write fails), and the second marks them as saved
+/// once the blocks have been written.
+#[cfg(feature = "std")]
+pub(crate) fn store_batched<S: StaticStore + ?Sized, T: Serialize>(
+    value: &T,
+    shape: Option<&CidShape>,
+) -> Result<Cid, S::Error> {
+    let (encoded, mode) = with_mode(SaveMode::Collect(Vec::new()), || {
+        encode::<S, _>(value, shape)
+    });
+    let SaveMode::Collect(mut blocks) = mode else {
+        unreachable!("save mode changed while collecting")
+    };
+    let encoded = encoded?;
+    check_write_size::<S>(encoded.len())?;
+    blocks.push((encoded, shape.copied()));
+    let stored = S::store_many(&blocks)?;
+    let (committed, _) = with_mode(SaveMode::Commit, || encode::<S, _>(value, shape));
+    debug_assert_eq!(
+        committed?,
+        blocks.last().unwrap().0,
+        "batched value encoded differently"
+    );
+    Ok(stored.last().expect("stored no blocks").0)
 }
 
+/// A store whose errors are unexpected enough to panic on, enabling `Deref` and `DerefMut` on
+/// [`Link`](crate::Link) and [`AutoLink`](crate::AutoLink). Only use this for stores that can't
+/// fail in practice (e.g., in-memory stores); otherwise, use the fallible accessors
+/// ([`Link::get`](crate::Link::get), [`Link::get_mut`](crate::Link::get_mut)).
+///
+/// What a failed dereference does can be customized with [`MagicStore::on_error`], e.g., to
+/// unwind with the typed error so a server can catch it at a request boundary.
 pub trait MagicStore: StaticStore {
+    /// Called with the error when dereferencing a link fails. By default, this panics with the
+    /// error's message.
+    ///
+    /// This must diverge (panic, unwind with a custom payload via
+    /// `std::panic::resume_unwind`, or abort): `Deref` has to return a `&T`, and a link whose
+    /// value failed to load has nothing to borrow. Handing out a fallback instead would need a
+    /// `T` that outlives the link (e.g., a leaked or `static` default), and code reading through
+    /// it couldn't tell the fallback from real data, so a later save could silently overwrite
+    /// the stored value with it. Use the fallible accessors where errors are expected.
+    #[track_caller]
+    fn on_error(e: Self::Error) -> ! {
+        panic!("failed to dereference link: {e}")
+    }
+
+    /// Unwrap the result of dereferencing a link, calling [`MagicStore::on_error`] on failure.
+    #[track_caller]
     fn unwrap<T>(r: Result<T, Self::Error>) -> T {
-        r.unwrap()
+        match r {
+            Ok(v) => v,
+            Err(e) => Self::on_error(e),
+        }
     }
 }
 
@@ -53,6 +1319,16 @@ where
 {
     type Error = S::Error;
 
+    const VERIFY: bool = S::VERIFY;
+
+    const MAX_BLOCK_SIZE: usize = S::MAX_BLOCK_SIZE;
+
+    const MAX_LINK_DEPTH: usize = S::MAX_LINK_DEPTH;
+
+    const MAX_WRITE_SIZE: Option<usize> = S::MAX_WRITE_SIZE;
+
+    const SKIP_UNCHANGED_WRITES: bool = S::SKIP_UNCHANGED_WRITES;
+
     fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
         S::store_bytes(value, shape)
     }
@@ -61,21 +1337,795 @@ where
         S::encode(value)
     }
 
+    fn encode_with<T: Serialize>(value: &T, codec: u64) -> Result<Vec<u8>, Self::Error> {
+        S::encode_with(value, codec)
+    }
+
+    fn default_codec() -> u64 {
+        S::default_codec()
+    }
+
+    fn supports_codec(codec: u64) -> bool {
+        S::supports_codec(codec)
+    }
+
+    #[cfg(feature = "std")]
+    fn encode_into<T: Serialize, W: Write>(value: &T, w: W) -> Result<(), Self::Error> {
+        S::encode_into(value, w)
+    }
+
+    fn encoded_len<T: Serialize>(value: &T) -> Result<usize, Self::Error> {
+        S::encoded_len(value)
+    }
+
+    fn size_hint<T: Serialize>(value: &T) -> Option<usize> {
+        S::size_hint(value)
+    }
+
+    fn compute_cid<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::compute_cid(value, shape)
+    }
+
     fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
         S::load_bytes(k)
     }
 
-    fn decode<'a, T: Deserialize<'a>>(bytes: &[u8]) -> Result<T, Self::Error> {
+    fn load_bytes_limited(k: &Cid, max: usize) -> Result<Vec<u8>, Self::Error> {
+        S::load_bytes_limited(k, max)
+    }
+
+    #[cfg(feature = "std")]
+    fn load_bytes_deadline(k: &Cid, deadline: Instant) -> Result<Vec<u8>, Self::Error> {
+        S::load_bytes_deadline(k, deadline)
+    }
+
+    fn has(k: &Cid) -> Result<bool, Self::Error> {
+        S::has(k)
+    }
+
+    fn block_size(k: &Cid) -> Result<usize, Self::Error> {
+        S::block_size(k)
+    }
+
+    fn shape_of(k: &Cid) -> CidShape {
+        S::shape_of(k)
+    }
+
+    fn describe(k: &Cid) -> Result<(CidShape, usize), Self::Error> {
+        S::describe(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
         S::decode(bytes)
     }
 
-    fn load<'a, T: Deserialize<'a>>(key: &Cid) -> Result<T, Self::Error> {
+    fn decode_borrowed<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, Self::Error> {
+        S::decode_borrowed(bytes)
+    }
+
+    fn decode_with<T: DeserializeOwned>(bytes: &[u8], codec: u64) -> Result<T, Self::Error> {
+        S::decode_with(bytes, codec)
+    }
+
+    fn pre_decode(k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+        S::pre_decode(k, bytes)
+    }
+
+    #[cfg(feature = "std")]
+    fn decode_from<T: DeserializeOwned, R: Read>(r: R) -> Result<T, Self::Error> {
+        S::decode_from(r)
+    }
+
+    fn load<T: DeserializeOwned>(key: &Cid) -> Result<T, Self::Error> {
         S::load(key)
     }
 
+    fn load_seed<'de, D: DeserializeSeed<'de>>(
+        key: &Cid,
+        seed: D,
+    ) -> Result<D::Value, Self::Error> {
+        S::load_seed(key, seed)
+    }
+
+    fn verify(k: &Cid, bytes: &[u8]) -> Result<(), Self::Error> {
+        S::verify(k, bytes)
+    }
+
     fn store<T: Serialize>(value: &T, shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
         S::store(value, shape)
     }
+
+    fn store_if_absent(value: &[u8], shape: Option<&CidShape>) -> Result<(Cid, bool), Self::Error> {
+        S::store_if_absent(value, shape)
+    }
+
+    fn store_deduped(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        S::store_deduped(value, shape)
+    }
+
+    fn store_many(blocks: &[(Vec<u8>, Option<CidShape>)]) -> Result<Vec<(Cid, bool)>, Self::Error> {
+        S::store_many(blocks)
+    }
+
+    fn load_many(keys: &[Cid]) -> Result<Vec<Vec<u8>>, Self::Error> {
+        S::load_many(keys)
+    }
+
+    fn keys_in_range(start: &Cid, end: &Cid) -> Result<Vec<Cid>, Self::Error> {
+        S::keys_in_range(start, end)
+    }
+
+    fn keys() -> Result<Vec<Cid>, Self::Error> {
+        S::keys()
+    }
+
+    fn remove(k: &Cid) -> Result<bool, Self::Error> {
+        S::remove(k)
+    }
+
+    fn is_not_found(err: &Self::Error) -> bool {
+        S::is_not_found(err)
+    }
+
+    fn is_retryable(err: &Self::Error) -> bool {
+        S::is_retryable(err)
+    }
+
+    fn with_label(err: Self::Error, label: &'static str) -> Self::Error {
+        S::with_label(err, label)
+    }
+
+    fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+        S::with_cid(err, cid)
+    }
 }
 
 impl<S> MagicStore for Magic<S> where S: StaticStore {}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::{
+        fmt,
+        marker::PhantomData,
+        panic::{self, AssertUnwindSafe},
+        time::{Duration, Instant},
+    };
+
+    use cid::Cid;
+    use serde::{
+        de::{DeserializeOwned, DeserializeSeed},
+        Deserialize, Serialize,
+    };
+
+    use crate::{
+        with_deadline, with_load_budget, AutoLink, CidShape, Codec, Link, Magic, MagicStore,
+        MemoryError, MemoryStore, MultihashCode, ReadOnly, SimStore, StaticStore, StatsStore,
+        StoreError, Value, ValueDeserializer, DAG_JSON, IDENTITY,
+    };
+
+    use super::DEADLINE;
+
+    /// A store that flips the last bit of every block it returns.
+    struct Corrupting<S, const VERIFY: bool>(PhantomData<S>);
+
+    impl<S: StaticStore, const VERIFY: bool> StaticStore for Corrupting<S, VERIFY> {
+        type Error = S::Error;
+
+        const VERIFY: bool = VERIFY;
+
+        fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+            S::store_bytes(value, shape)
+        }
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            S::encode(value)
+        }
+
+        fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+            let mut bytes = S::load_bytes(k)?;
+            *bytes.last_mut().unwrap() ^= 1;
+            Ok(bytes)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+            S::decode(bytes)
+        }
+    }
+
+    /// A store that rejects loaded blocks larger than `MAX` bytes.
+    struct Limited<S, const MAX: usize>(PhantomData<S>);
+
+    impl<S: StaticStore, const MAX: usize> StaticStore for Limited<S, MAX> {
+        type Error = S::Error;
+
+        const MAX_BLOCK_SIZE: usize = MAX;
+
+        fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+            S::store_bytes(value, shape)
+        }
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            S::encode(value)
+        }
+
+        fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+            S::load_bytes(k)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+            S::decode(bytes)
+        }
+    }
+
+    /// A store that refuses to write blocks larger than `MAX` bytes.
+    struct Capped<S, const MAX: usize>(PhantomData<S>);
+
+    impl<S: StaticStore, const MAX: usize> StaticStore for Capped<S, MAX> {
+        type Error = S::Error;
+
+        const MAX_WRITE_SIZE: Option<usize> = Some(MAX);
+
+        fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+            S::store_bytes(value, shape)
+        }
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            S::encode(value)
+        }
+
+        fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+            S::load_bytes(k)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+            S::decode(bytes)
+        }
+    }
+
+    /// A store whose loads take `SECS` seconds of virtual time, and ignore deadlines: each load
+    /// moves the current deadline (see [`with_deadline`]) that much closer, instead of sleeping.
+    struct Slow<S, const SECS: u64>(PhantomData<S>);
+
+    impl<S: StaticStore, const SECS: u64> StaticStore for Slow<S, SECS> {
+        type Error = S::Error;
+
+        fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+            S::store_bytes(value, shape)
+        }
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            S::encode(value)
+        }
+
+        fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+            let elapsed = Duration::from_secs(SECS);
+            DEADLINE.with(|d| d.set(d.get().map(|deadline| deadline - elapsed)));
+            S::load_bytes(k)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+            S::decode(bytes)
+        }
+    }
+
+    /// A store that describes every block as an empty SHA2-512 raw block, and seeds every load
+    /// with the string "seeded".
+    struct Described<S>(PhantomData<S>);
+
+    impl<S: StaticStore> StaticStore for Described<S> {
+        type Error = S::Error;
+
+        fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+            S::store_bytes(value, shape)
+        }
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            S::encode(value)
+        }
+
+        fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+            S::load_bytes(k)
+        }
+
+        fn shape_of(_k: &Cid) -> CidShape {
+            CidShape::new(Codec::Raw, MultihashCode::Sha2_512)
+        }
+
+        fn describe(k: &Cid) -> Result<(CidShape, usize), Self::Error> {
+            Ok((Self::shape_of(k), 0))
+        }
+
+        fn load_seed<'de, D: DeserializeSeed<'de>>(
+            _key: &Cid,
+            seed: D,
+        ) -> Result<D::Value, Self::Error> {
+            let value = Value::String("seeded".into());
+            seed.deserialize(ValueDeserializer::<serde::de::value::Error>::new(value))
+                .map_err(|e| StoreError::Encoding(e.to_string()).into())
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+            S::decode(bytes)
+        }
+    }
+
+    /// A store that upgrades "v1: ..." strings to "v2: ..." as they're loaded.
+    struct Upgrading<S>(PhantomData<S>);
+
+    impl<S: StaticStore> StaticStore for Upgrading<S> {
+        type Error = S::Error;
+
+        fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+            S::store_bytes(value, shape)
+        }
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            S::encode(value)
+        }
+
+        fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+            S::load_bytes(k)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+            S::decode(bytes)
+        }
+
+        fn pre_decode(_k: &Cid, bytes: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+            match S::decode::<String>(bytes) {
+                Ok(old) if old.starts_with("v1: ") => {
+                    Ok(Some(S::encode(&old.replacen("v1", "v2", 1))?))
+                }
+                _ => Ok(None),
+            }
+        }
+    }
+
+    /// An error that remembers which block was being loaded.
+    #[derive(Debug)]
+    struct LocatedError {
+        cid: Option<Cid>,
+        source: MemoryError,
+    }
+
+    impl fmt::Display for LocatedError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.cid {
+                Some(k) => write!(f, "loading {k}: {}", self.source),
+                None => self.source.fmt(f),
+            }
+        }
+    }
+
+    impl std::error::Error for LocatedError {}
+
+    impl From<StoreError> for LocatedError {
+        fn from(e: StoreError) -> Self {
+            MemoryError::from(e).into()
+        }
+    }
+
+    impl From<MemoryError> for LocatedError {
+        fn from(source: MemoryError) -> Self {
+            LocatedError { cid: None, source }
+        }
+    }
+
+    /// A store whose load errors carry the CID being loaded.
+    struct Locating<Tag>(PhantomData<Tag>);
+
+    impl<Tag: 'static> StaticStore for Locating<Tag> {
+        type Error = LocatedError;
+
+        fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+            Ok(MemoryStore::<Tag>::store_bytes(value, shape)?)
+        }
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            Ok(MemoryStore::<Tag>::encode(value)?)
+        }
+
+        fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+            Ok(MemoryStore::<Tag>::load_bytes(k)?)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+            Ok(MemoryStore::<Tag>::decode(bytes)?)
+        }
+
+        fn with_cid(err: Self::Error, cid: Cid) -> Self::Error {
+            LocatedError {
+                cid: Some(cid),
+                ..err
+            }
+        }
+    }
+
+    /// A magic store that unwinds with the typed error when dereferencing fails.
+    struct Unwinding<S>(PhantomData<S>);
+
+    impl<S: StaticStore> StaticStore for Unwinding<S>
+    where
+        S::Error: Send,
+    {
+        type Error = S::Error;
+
+        fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+            S::store_bytes(value, shape)
+        }
+
+        fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+            S::encode(value)
+        }
+
+        fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+            S::load_bytes(k)
+        }
+
+        fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+            S::decode(bytes)
+        }
+    }
+
+    impl<S: StaticStore> MagicStore for Unwinding<S>
+    where
+        S::Error: Send,
+    {
+        fn on_error(e: Self::Error) -> ! {
+            panic::resume_unwind(Box::new(e))
+        }
+    }
+
+    #[test]
+    fn describe_blocks() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let shape = CidShape::new(Codec::Raw, MultihashCode::Sha2_512);
+        let k = Store::store_bytes(b"raw block", Some(&shape)).unwrap();
+        assert_eq!(Store::shape_of(&k), shape);
+        assert_eq!(
+            Store::shape_of(&k),
+            CidShape {
+                codec: k.codec(),
+                mh_code: k.hash().code(),
+            }
+        );
+        assert_eq!(Store::snapshot().loads, 0);
+        assert_eq!(Store::describe(&k).unwrap(), (shape, 9));
+
+        let k = Store::store(&"cbor", None).unwrap();
+        assert_eq!(Store::describe(&k).unwrap(), (CidShape::DEFAULT, 5));
+        let missing = MemoryStore::<()>::store(&"missing", None).unwrap();
+        assert!(Store::is_not_found(&Store::describe(&missing).unwrap_err()));
+    }
+
+    #[test]
+    fn magic_forwards_descriptions_and_seeded_loads() {
+        struct Tag;
+        type Store = Magic<Described<MemoryStore<Tag>>>;
+
+        let k = Store::store(&"block", None).unwrap();
+        let shape = CidShape::new(Codec::Raw, MultihashCode::Sha2_512);
+        assert_eq!(Store::shape_of(&k), shape);
+        assert_eq!(Store::describe(&k).unwrap(), (shape, 0));
+        assert_eq!(
+            Store::load_seed(&k, PhantomData::<String>).unwrap(),
+            "seeded"
+        );
+    }
+
+    #[test]
+    fn load_budgets_stop_traversals() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        #[derive(Serialize, Deserialize)]
+        struct List {
+            value: u32,
+            next: Option<Link<Box<List>, Store>>,
+        }
+
+        let mut head = None;
+        for value in (0..10).rev() {
+            head = Some(Link::from_value(Box::new(List { value, next: head }), None));
+        }
+        let head = Link::<Box<List>, Store>::new(head.unwrap().save().unwrap());
+        let find = |head: &Link<Box<List>, Store>, value| {
+            let mut node = head.read()?;
+            while node.value != value {
+                node = node.next.as_ref().expect("value not in list").read()?;
+            }
+            Ok::<_, MemoryError>(node.value)
+        };
+
+        let err = with_load_budget(5, || find(&head, 9)).unwrap_err();
+        assert!(matches!(
+            err,
+            MemoryError::Store(StoreError::BudgetExhausted(5))
+        ));
+        // The loaded prefix is cached, so the rest of the list fits in a new budget.
+        assert_eq!(with_load_budget(5, || find(&head, 9)).unwrap(), 9);
+
+        // Nested scopes count towards the enclosing one, which is restored even on a panic.
+        let fresh = Link::<Box<List>, Store>::new(head.cid().unwrap());
+        with_load_budget(3, || {
+            let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                with_load_budget(10, || {
+                    fresh.read().unwrap();
+                    panic!("request failed");
+                })
+            }));
+            assert!(res.is_err());
+            assert_eq!(find(&fresh, 2).unwrap(), 2);
+            assert!(find(&fresh, 3).is_err());
+        });
+        assert_eq!(find(&fresh, 9).unwrap(), 9);
+    }
+
+    #[test]
+    fn deadlines_stop_slow_loads() {
+        struct Tag;
+        type Backend = StatsStore<MemoryStore<Tag>>;
+        type Store = Slow<Backend, 3600>;
+
+        #[derive(Serialize, Deserialize)]
+        struct Node {
+            value: u32,
+            next: Option<Link<Box<Node>, Store>>,
+        }
+
+        let chain = (0..3).fold(None, |next, value| {
+            Some(Link::from_value(Box::new(Node { value, next }), None))
+        });
+        let head = chain.unwrap().save().unwrap();
+        let walk = || {
+            let head = Link::<Box<Node>, Store>::new(head);
+            let mut node = head.read()?;
+            while let Some(next) = &node.next {
+                node = next.read()?;
+            }
+            Ok::<_, MemoryError>(node.value)
+        };
+
+        // Nothing is loaded once the deadline has passed.
+        Backend::reset();
+        let err = with_deadline(Instant::now(), walk).unwrap_err();
+        assert!(matches!(err, MemoryError::Store(StoreError::Timeout(k)) if k == head));
+        assert_eq!(Backend::snapshot().loads, 0);
+
+        // Each load takes an hour, so the first starts before the deadline and finishes after it,
+        // and the second isn't started.
+        let err = with_deadline(Instant::now() + Duration::from_secs(60), walk).unwrap_err();
+        assert!(
+            matches!(err, MemoryError::Store(StoreError::Timeout(k)) if k != head),
+            "{err}"
+        );
+        assert_eq!(Backend::snapshot().loads, 1);
+        Backend::reset();
+        assert_eq!(
+            with_deadline(Instant::now() + Duration::from_secs(4 * 3600), walk).unwrap(),
+            0
+        );
+        assert_eq!(Backend::snapshot().loads, 3);
+
+        // Stores that support it give up on loads in progress.
+        type Sim = SimStore<Backend>;
+        Backend::reset();
+        Sim::configure(1, Duration::from_secs(10), Duration::ZERO);
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let err = with_deadline(deadline, || Sim::load::<Value>(&head)).unwrap_err();
+        assert!(matches!(err, MemoryError::Store(StoreError::Timeout(k)) if k == head));
+        assert_eq!(Backend::snapshot().loads, 0);
+        assert!(Sim::trace().is_empty());
+        Sim::reset();
+    }
+
+    #[test]
+    fn shapes_round_trip() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let shapes = [
+            CidShape::DEFAULT,
+            CidShape::DEFAULT.with_codec(DAG_JSON).with_hash(IDENTITY),
+        ];
+        let k = Store::store(&shapes, None).unwrap();
+        assert_eq!(Store::load::<[CidShape; 2]>(&k).unwrap(), shapes);
+
+        let Value::List(stored) = Store::load::<Value>(&k).unwrap() else {
+            panic!("expected a list");
+        };
+        let Value::Map(fields) = &stored[0] else {
+            panic!("expected a map");
+        };
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields["codec"], Value::Integer(0x71));
+        assert_eq!(fields["mh_code"], Value::Integer(0x12));
+    }
+
+    #[test]
+    fn deref_errors_can_be_customized() {
+        struct Tag;
+        type Store = Unwinding<MemoryStore<Tag>>;
+
+        let k = MemoryStore::<Tag>::store(&"present", None).unwrap();
+        assert_eq!(*Link::<String, Store>::new(k), "present");
+
+        let missing = MemoryStore::<()>::store(&"missing", None).unwrap();
+        let link = Link::<String, Store>::new(missing);
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| link.len())).unwrap_err();
+        assert!(matches!(
+            payload.downcast_ref(),
+            Some(MemoryError::NotFound(k)) if *k == missing
+        ));
+    }
+
+    #[test]
+    fn old_blocks_are_upgraded_on_load() {
+        struct Tag;
+        type Store = Upgrading<MemoryStore<Tag>>;
+
+        let old = MemoryStore::<Tag>::store(&"v1: config", None).unwrap();
+        let link = Link::<String, Store>::new(old);
+        assert_eq!(link.read().unwrap(), "v2: config");
+        // The link still names the original block.
+        assert_eq!(link.cid(), Some(old));
+
+        let inline =
+            Store::store(&"v1: tiny", Some(&CidShape::DEFAULT.with_hash(IDENTITY))).unwrap();
+        assert_eq!(Store::load::<String>(&inline).unwrap(), "v2: tiny");
+        // Wrappers run the inner store's hook.
+        assert_eq!(
+            ReadOnly::<Store>::load::<String>(&old).unwrap(),
+            "v2: config"
+        );
+        let current = Store::store(&"v2: current", None).unwrap();
+        assert_eq!(Store::load::<String>(&current).unwrap(), "v2: current");
+    }
+
+    #[test]
+    fn load_errors_name_the_block() {
+        struct Tag;
+        type Store = Locating<Tag>;
+
+        #[derive(Serialize, Deserialize)]
+        struct Parent {
+            child: Link<u64, Store>,
+        }
+
+        let child = Store::store(&"not a number", None).unwrap();
+        let parent = Store::store(
+            &Parent {
+                child: Link::new(child),
+            },
+            None,
+        )
+        .unwrap();
+        let loaded: Parent = Store::load(&parent).unwrap();
+        let err = loaded.child.read().unwrap_err();
+        assert_eq!(err.cid, Some(child));
+        assert!(matches!(err.source, MemoryError::Codec(_)));
+        assert!(err.to_string().starts_with(&format!("loading {child}: ")));
+
+        // Successful loads are unaffected, and other errors aren't attributed to a block.
+        assert_eq!(Store::load::<String>(&child).unwrap(), "not a number");
+        assert_eq!(Store::decode::<u64>(&[0xff]).unwrap_err().cid, None);
+    }
+
+    #[test]
+    fn oversized_blocks_are_rejected() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let big = Store::store(&"a string that's too long", None).unwrap();
+        let len = Store::load_bytes(&big).unwrap().len();
+        assert!(matches!(
+            Store::load_bytes_limited(&big, len - 1),
+            Err(MemoryError::Store(StoreError::BlockTooLarge { cid, len: l, max }))
+                if cid == big && l == len && max == len - 1
+        ));
+        assert_eq!(Store::load_bytes_limited(&big, len).unwrap().len(), len);
+
+        let small = Store::store(&"short", None).unwrap();
+        let link: Link<String, Limited<Store, 16>> = Link::new(small);
+        assert_eq!(link.read().unwrap(), "short");
+        let link: Link<String, Limited<Store, 16>> = Link::new(big);
+        assert!(matches!(
+            link.read(),
+            Err(MemoryError::Store(StoreError::BlockTooLarge { .. }))
+        ));
+        // Disabling the limit.
+        let link: Link<String, Limited<Store, { usize::MAX }>> = Link::new(big);
+        assert!(link.read().is_ok());
+    }
+
+    #[test]
+    fn oversized_writes_are_rejected() {
+        struct Tag;
+        type Store = Capped<MemoryStore<Tag>, 16>;
+
+        let too_large = |e| {
+            matches!(
+                e,
+                MemoryError::Store(StoreError::BlockTooLargeOnWrite { len: 26, max: 16 })
+            )
+        };
+        let link = Link::<String, Store>::from_value("a string that's too long".into(), None);
+        assert!(too_large(link.save().unwrap_err()));
+        assert!(link.is_dirty());
+        assert!(too_large(link.save_if_absent().unwrap_err()));
+        assert!(too_large(
+            Store::store(&"a string that's too long", None).unwrap_err()
+        ));
+        let auto = AutoLink::<String, Store, 8>::from_value("a string that's too long".into());
+        assert!(too_large(auto.save().unwrap_err()));
+        assert!(MemoryStore::<Tag>::is_empty());
+
+        // Small blocks and identity CIDs are fine.
+        Link::<String, Store>::from_value("short".into(), None)
+            .save()
+            .unwrap();
+        let inline = CidShape::DEFAULT.with_hash(IDENTITY);
+        Store::store(&"a string that's too long", Some(&inline)).unwrap();
+        assert_eq!(MemoryStore::<Tag>::len(), 1);
+    }
+
+    #[test]
+    fn streaming_matches_buffered() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+        // Only overrides `encode` and `decode`, so it uses the buffering defaults.
+        type Buffered = Corrupting<Store, false>;
+
+        let value = ("streamed", vec![1u64, 2, 3]);
+        let bytes = Store::encode(&value).unwrap();
+        let mut streamed = Vec::new();
+        Store::encode_into(&value, &mut streamed).unwrap();
+        assert_eq!(streamed, bytes);
+        let mut buffered = Vec::new();
+        Buffered::encode_into(&value, &mut buffered).unwrap();
+        assert_eq!(buffered, bytes);
+        assert_eq!(Store::encoded_len(&value).unwrap(), bytes.len());
+        assert_eq!(Buffered::encoded_len(&value).unwrap(), bytes.len());
+
+        let decoded: (String, Vec<u64>) = Store::decode_from(&bytes[..]).unwrap();
+        assert_eq!(decoded, ("streamed".to_string(), vec![1, 2, 3]));
+        let decoded: (String, Vec<u64>) = Buffered::decode_from(&bytes[..]).unwrap();
+        assert_eq!(decoded.0, "streamed");
+    }
+
+    #[test]
+    fn load_verifies_blocks() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let k = Store::store(&2u8, None).unwrap();
+        assert_eq!(Store::load::<u8>(&k).unwrap(), 2);
+        assert!(matches!(
+            Corrupting::<Store, true>::load::<u8>(&k),
+            Err(MemoryError::Store(StoreError::HashMismatch(bad))) if bad == k
+        ));
+        // With verification disabled, the corrupt block decodes as a different value.
+        assert_eq!(Corrupting::<Store, false>::load::<u8>(&k).unwrap(), 3);
+    }
+
+    #[test]
+    fn compute_cid_matches_store() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let value = ("computed", 7u64);
+        let sha512 = CidShape::DEFAULT.with_hash(0x13);
+        for shape in [None, Some(&sha512)] {
+            let k = Store::compute_cid(&value, shape).unwrap();
+            assert!(!Store::has(&k).unwrap());
+            assert_eq!(Store::store(&value, shape).unwrap(), k);
+            assert!(Store::has(&k).unwrap());
+        }
+        assert!(matches!(
+            Store::compute_cid(&value, Some(&CidShape::DEFAULT.with_hash(0x99))),
+            Err(MemoryError::Store(StoreError::UnsupportedMultihash(0x99)))
+        ));
+    }
+}
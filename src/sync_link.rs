@@ -0,0 +1,210 @@
+use std::{
+    fmt,
+    marker::PhantomData,
+    sync::{Mutex, OnceLock},
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, ser::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{error, link_as_cid, store, AsCid, CidShape, StaticStore};
+
+/// A [`Link`](crate::Link) that can be shared between threads: the value is cached in a
+/// [`OnceLock`] and the link's state is behind a [`Mutex`], so `SyncLink` is `Sync` whenever `T`
+/// is.
+///
+/// [`SyncLink::read`], [`SyncLink::save`], and serialization take `&self` and can be called
+/// concurrently. Concurrent reads of an unloaded link may each load the value, but the first to
+/// finish fills the cache and every read returns that value. Concurrent saves write the block once:
+/// the state lock is held while the value is encoded and stored. [`SyncLink::edit`] takes
+/// `&mut self`, so it can't race with anything.
+pub struct SyncLink<T, Store> {
+    value: OnceLock<T>,
+    state: Mutex<SyncState>,
+    _marker: PhantomData<fn(Store)>,
+}
+
+#[derive(Copy, Clone)]
+enum SyncState {
+    Unmodified(Cid),
+    Modified(Option<CidShape>),
+}
+
+/// Shows the link's CID (or that it's modified) and whether its value is cached, like
+/// [`Link`](crate::Link)'s.
+impl<T, Store> fmt::Debug for SyncLink<T, Store> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("SyncLink");
+        match self.state() {
+            SyncState::Unmodified(k) => s.field("cid", &k),
+            SyncState::Modified(_) => s.field("modified", &true),
+        };
+        s.field("loaded", &self.value.get().is_some()).finish()
+    }
+}
+
+impl<T, Store> Serialize for SyncLink<T, Store>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let k = self.save().map_err(|e| {
+            let msg = S::Error::custom(&e);
+            error::stash_error(e);
+            msg
+        })?;
+        link_as_cid::serialize(&k, serializer)
+    }
+}
+
+impl<'de, T, Store> Deserialize<'de> for SyncLink<T, Store> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Self::new(link_as_cid::deserialize(deserializer)?))
+    }
+}
+
+/// See [`SyncLink::cid`].
+impl<T, Store> AsCid for SyncLink<T, Store> {
+    fn as_cid(&self) -> Option<Cid> {
+        self.cid()
+    }
+}
+
+impl<T, Store> SyncLink<T, Store> {
+    /// Construct a new `SyncLink` from a `Cid`.
+    #[must_use]
+    pub fn new(k: Cid) -> Self {
+        Self {
+            value: OnceLock::new(),
+            state: Mutex::new(SyncState::Unmodified(k)),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Construct a new `SyncLink` from a value (with an optional link-shape hint).
+    #[must_use]
+    pub fn from_value(v: T, shape: Option<CidShape>) -> Self {
+        Self {
+            value: OnceLock::from(v),
+            state: Mutex::new(SyncState::Modified(shape)),
+            _marker: PhantomData,
+        }
+    }
+
+    fn state(&self) -> SyncState {
+        *self.state.lock().unwrap()
+    }
+
+    /// The CID of the stored block this link points to, or `None` if the link has been modified
+    /// and not yet saved.
+    pub fn cid(&self) -> Option<Cid> {
+        match self.state() {
+            SyncState::Unmodified(k) => Some(k),
+            SyncState::Modified(_) => None,
+        }
+    }
+
+    /// Returns true if the link has been modified and not yet saved.
+    pub fn is_dirty(&self) -> bool {
+        matches!(self.state(), SyncState::Modified(_))
+    }
+
+    /// Read the linked object, loading and decoding it if it isn't cached.
+    pub fn read(&self) -> Result<&T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        if let Some(val) = self.value.get() {
+            return Ok(val);
+        }
+        let SyncState::Unmodified(k) = self.state() else {
+            unreachable!("modified link has no value")
+        };
+        let val = Store::load(&k)?;
+        Ok(self.value.get_or_init(|| val))
+    }
+
+    /// Edit the linked object, loading it if needed and marking the link as modified.
+    pub fn edit(&mut self) -> Result<&mut T, Store::Error>
+    where
+        T: DeserializeOwned,
+        Store: StaticStore,
+    {
+        let state = self.state.get_mut().unwrap();
+        if let SyncState::Unmodified(k) = *state {
+            if self.value.get().is_none() {
+                self.value = OnceLock::from(Store::load::<T>(&k)?);
+            }
+            *state = SyncState::Modified(Some(CidShape::from(&k)));
+        }
+        Ok(self.value.get_mut().expect("expected value"))
+    }
+
+    /// Write-back the value if modified, and return the CID.
+    pub fn save(&self) -> Result<Cid, Store::Error>
+    where
+        T: Serialize,
+        Store: StaticStore,
+    {
+        let mut state = self.state.lock().unwrap();
+        let shape = match *state {
+            SyncState::Unmodified(k) => return Ok(k),
+            SyncState::Modified(shape) => shape,
+        };
+
+        let encoded = store::encode::<Store, _>(
+            self.value.get().expect("modified link has no value"),
+            shape.as_ref(),
+        )?;
+        let (k, saved) = store::store_link_block::<Store>(&encoded, shape.as_ref())?;
+        if saved {
+            *state = SyncState::Unmodified(k);
+        }
+        Ok(k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::thread;
+
+    use crate::{MemoryStore, StaticStore, StatsStore, SyncLink};
+
+    #[test]
+    fn shared_between_threads() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        let k = Store::store(&"shared", None).unwrap();
+        let link = SyncLink::<String, Store>::new(k);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| assert_eq!(link.read().unwrap(), "shared"));
+            }
+        });
+        let loads = Store::snapshot().loads;
+        assert!((1..=4).contains(&loads));
+        assert_eq!(link.read().unwrap(), "shared");
+        assert_eq!(Store::snapshot().loads, loads);
+
+        let mut link = link;
+        link.edit().unwrap().push('!');
+        assert!(link.is_dirty());
+        let saved: Vec<_> = thread::scope(|s| {
+            let saves: Vec<_> = (0..4).map(|_| s.spawn(|| link.save().unwrap())).collect();
+            saves.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(saved.iter().all(|k| Some(*k) == link.cid()));
+        // Only the first save wrote the block.
+        assert_eq!(Store::snapshot().stores, 2);
+        assert_eq!(Store::load::<String>(&saved[0]).unwrap(), "shared!");
+    }
+}
@@ -0,0 +1,209 @@
+//! Helpers for testing data models built on links (with the `testing` feature).
+//!
+//! ```
+//! use auto_ipld::{testing::{assert_roundtrip, TestStore}, Link};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize, PartialEq, Debug)]
+//! struct Post {
+//!     title: String,
+//!     body: Link<String, TestStore>,
+//! }
+//!
+//! let post = Post {
+//!     title: "hello".into(),
+//!     body: Link::from_value("world".into(), None),
+//! };
+//! assert_roundtrip::<TestStore, _>(&post);
+//! ```
+
+use std::fmt::Debug;
+#[cfg(test)]
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    future::Future,
+    marker::PhantomData,
+    pin::pin,
+    task::{Context, Poll, Waker},
+};
+
+use cid::Cid;
+use serde::{de::DeserializeOwned, Serialize};
+
+#[cfg(test)]
+use crate::{AsyncStore, CidShape, MemoryError};
+use crate::{MemoryStore, StaticStore};
+
+/// The store to test against: a [`MemoryStore`], whose CIDs are content hashes, so they're the
+/// same from run to run. Use a local `Tag` type to isolate tests running in parallel.
+pub type TestStore<Tag = ()> = MemoryStore<Tag>;
+
+/// Store `value` in `S`, load it back, and assert that the loaded value equals `value` and
+/// re-encodes to the stored block. Also asserts that the CID is the one
+/// [`StaticStore::compute_cid`] predicts. Returns the CID.
+///
+/// Modified links inside `value` are saved along the way, so they compare equal to the links in the
+/// loaded value.
+#[track_caller]
+pub fn assert_roundtrip<S, T>(value: &T) -> Cid
+where
+    S: StaticStore,
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    let k = S::store(value, None).unwrap_or_else(|e| panic!("failed to store value: {e}"));
+    let expected = S::compute_cid(value, None).unwrap_or_else(|e| panic!("failed to hash: {e}"));
+    assert_eq!(k, expected, "stored under an unexpected cid");
+    let block = S::load_bytes(&k).unwrap_or_else(|e| panic!("failed to load {k}: {e}"));
+    let loaded: T = S::decode(&block).unwrap_or_else(|e| panic!("failed to decode {k}: {e}"));
+    assert_eq!(&loaded, value, "loaded value differs from the stored one");
+    let encoded = S::encode(&loaded).unwrap_or_else(|e| panic!("failed to re-encode: {e}"));
+    assert!(encoded == block, "loaded value re-encodes differently");
+    k
+}
+
+/// An [`AsyncStore`] over [`MemoryStore`]'s blocks, where every block operation yields once
+/// before completing (so tests can interleave and cancel operations).
+#[cfg(test)]
+pub struct AsyncMemoryStore<Tag = ()>(PhantomData<Tag>);
+
+/// A future that's pending the first time it's polled.
+#[cfg(test)]
+async fn yield_now() {
+    let mut yielded = false;
+    std::future::poll_fn(|_| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Run a future to completion by polling it in a loop.
+#[cfg(test)]
+pub fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(out) = fut.as_mut().poll(&mut cx) {
+            return out;
+        }
+    }
+}
+
+#[cfg(test)]
+impl<Tag: 'static> AsyncStore for AsyncMemoryStore<Tag> {
+    type Error = MemoryError;
+
+    async fn store_bytes(value: &[u8], shape: Option<&CidShape>) -> Result<Cid, Self::Error> {
+        yield_now().await;
+        MemoryStore::<Tag>::store_bytes(value, shape)
+    }
+
+    fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, Self::Error> {
+        MemoryStore::<Tag>::encode(value)
+    }
+
+    async fn load_bytes(k: &Cid) -> Result<Vec<u8>, Self::Error> {
+        yield_now().await;
+        MemoryStore::<Tag>::load_bytes(k)
+    }
+
+    fn decode<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Self::Error> {
+        MemoryStore::<Tag>::decode(bytes)
+    }
+}
+
+/// The system allocator, counting allocations per thread.
+#[cfg(test)]
+struct CountingAlloc;
+
+#[cfg(test)]
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+#[cfg(test)]
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[cfg(test)]
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+/// The number of allocations (including reallocations) made by the current thread so far.
+#[cfg(test)]
+pub fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[cfg(test)]
+mod test {
+    use serde::{Deserialize, Serialize};
+
+    use super::{assert_roundtrip, TestStore};
+    use crate::{Link, MaybeLink, StaticStore};
+
+    struct Tag;
+    type Store = TestStore<Tag>;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(bound = "")]
+    struct Node {
+        name: String,
+        child: Option<Link<Box<Node>, Store>>,
+        note: MaybeLink<String>,
+    }
+
+    #[test]
+    fn links_roundtrip() {
+        let leaf = Node {
+            name: "leaf".into(),
+            child: None,
+            note: MaybeLink::Value("inline".into()),
+        };
+        let note = Store::store(&"linked", None).unwrap();
+        let root = Node {
+            name: "root".into(),
+            child: Some(Link::from_value(Box::new(leaf), None)),
+            note: MaybeLink::Link(note),
+        };
+        let k = assert_roundtrip::<Store, _>(&root);
+        assert_eq!(assert_roundtrip::<Store, _>(&root), k);
+
+        let loaded: Node = Store::load(&k).unwrap();
+        assert_eq!(loaded.child.unwrap().read().unwrap().name, "leaf");
+    }
+
+    #[test]
+    #[should_panic(expected = "loaded value differs")]
+    fn lossy_values_fail() {
+        #[derive(Serialize, Deserialize, PartialEq, Debug)]
+        struct Cached {
+            value: u32,
+            #[serde(skip)]
+            doubled: Option<u32>,
+        }
+
+        assert_roundtrip::<Store, _>(&Cached {
+            value: 1,
+            doubled: Some(2),
+        });
+    }
+}
@@ -0,0 +1,544 @@
+use core::{fmt, marker::PhantomData};
+
+use alloc::{
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use cid::{serde::BytesToCidVisitor, Cid};
+use serde::{
+    de::DeserializeOwned,
+    de::{
+        value::{BytesDeserializer, MapDeserializer, SeqDeserializer},
+        IntoDeserializer, MapAccess, SeqAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{link_as_cid, store, StaticStore, StoreError};
+
+/// A generic IPLD value, for inspecting data without a schema.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Integer(i128),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+    Link(Cid),
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Null => serializer.serialize_none(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Integer(i) => {
+                if let Ok(i) = i64::try_from(*i) {
+                    serializer.serialize_i64(i)
+                } else if let Ok(u) = u64::try_from(*i) {
+                    serializer.serialize_u64(u)
+                } else {
+                    serializer.serialize_i128(*i)
+                }
+            }
+            Value::Float(f) => serializer.serialize_f64(*f),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Bytes(b) => serializer.serialize_bytes(b),
+            Value::List(l) => serializer.collect_seq(l),
+            Value::Map(m) => serializer.collect_map(m),
+            Value::Link(k) => link_as_cid::serialize(k, serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "an IPLD value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Value::Integer(v.into()))
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        i128::try_from(v)
+            .map(Value::Integer)
+            .map_err(|_| E::custom("integer out of range"))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Value::String(v.into()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v.into()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(Value::Bytes(v))
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_bytes(BytesToCidVisitor)
+            .map(Value::Link)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(v) = seq.next_element()? {
+            list.push(v);
+        }
+        Ok(Value::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut out = BTreeMap::new();
+        while let Some((k, v)) = map.next_entry()? {
+            out.insert(k, v);
+        }
+        Ok(Value::Map(out))
+    }
+}
+
+/// Deserializes from a buffered [`Value`], presenting links as the `cid` crate's newtype of the
+/// CID's bytes (as DAG-CBOR does).
+pub(crate) struct ValueDeserializer<E>(Value, PhantomData<fn() -> E>);
+
+impl<E> ValueDeserializer<E> {
+    pub(crate) fn new(value: Value) -> Self {
+        ValueDeserializer(value, PhantomData)
+    }
+}
+
+impl<'de, E: serde::de::Error> IntoDeserializer<'de, E> for ValueDeserializer<E> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de, E: serde::de::Error> Deserializer<'de> for ValueDeserializer<E> {
+    type Error = E;
+
+    /// Values hold links natively, so they're decoded like a binary format's.
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Integer(i) => {
+                if let Ok(i) = i64::try_from(i) {
+                    visitor.visit_i64(i)
+                } else if let Ok(u) = u64::try_from(i) {
+                    visitor.visit_u64(u)
+                } else {
+                    visitor.visit_i128(i)
+                }
+            }
+            Value::Float(f) => visitor.visit_f64(f),
+            Value::String(s) => visitor.visit_string(s),
+            Value::Bytes(b) => visitor.visit_byte_buf(b),
+            Value::List(l) => visitor.visit_seq(SeqDeserializer::new(l.into_iter().map(Self::new))),
+            Value::Map(m) => visitor.visit_map(MapDeserializer::new(
+                m.into_iter().map(|(k, v)| (k, Self::new(v))),
+            )),
+            Value::Link(k) => visitor.visit_newtype_struct(BytesDeserializer::new(&k.to_bytes())),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, E>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple tuple_struct
+        map struct enum identifier ignored_any
+    }
+}
+
+/// Convert `root` into a [`Value`], saving any modified links. Links are kept as [`Value::Link`]s
+/// rather than followed; see [`inline_all`] to replace them with the linked values.
+pub fn to_value<T, Store>(root: &T) -> Result<Value, Store::Error>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    Store::decode(&store::encode::<Store, _>(root, None)?)
+}
+
+/// Convert `root` into a self-contained [`Value`] by recursively loading every link it references
+/// (directly or indirectly) and inlining the linked value in place of the link. The result contains
+/// no [`Value::Link`]s and can be encoded as a single block for systems that can't resolve CIDs.
+///
+/// WARNING: this loads the _entire_ DAG into memory, and the result may be far larger than the
+/// maximum block size supported by many IPLD systems.
+pub fn inline_all<T, Store>(root: &T) -> Result<Value, Store::Error>
+where
+    T: Serialize,
+    Store: StaticStore,
+{
+    inline_links::<Store>(to_value::<_, Store>(root)?)
+}
+
+/// One step of a path passed to [`resolve_path`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// A map key.
+    Key(String),
+    /// A list index.
+    Index(usize),
+}
+
+impl From<&str> for PathSegment {
+    fn from(key: &str) -> Self {
+        PathSegment::Key(key.into())
+    }
+}
+
+impl From<usize> for PathSegment {
+    fn from(index: usize) -> Self {
+        PathSegment::Index(index)
+    }
+}
+
+/// Decode the value at `path` in the DAG rooted at `root`, as `ipld dag get <root>/a/b/0` would.
+///
+/// Each block is decoded as a generic [`Value`] and walked segment by segment: keys index maps,
+/// and indices index lists. Links reached before the end of the path (including `root`) are
+/// followed into their blocks, so the path crosses block boundaries transparently; a link at the
+/// end is decoded as is (into a [`Cid`] or a link type). A segment that doesn't exist fails with
+/// [`StoreError::PathNotFound`], and a dangling link with the store's not-found error.
+pub fn resolve_path<V, Store>(root: &Cid, path: &[PathSegment]) -> Result<V, Store::Error>
+where
+    V: DeserializeOwned,
+    Store: StaticStore,
+{
+    let mut value = Value::Link(*root);
+    for (depth, segment) in path.iter().enumerate() {
+        if let Value::Link(k) = value {
+            value = Store::load(&k)?;
+        }
+        let not_found = |reason| StoreError::PathNotFound { depth, reason };
+        value = match (value, segment) {
+            (Value::Map(mut m), PathSegment::Key(key)) => {
+                m.remove(key).ok_or(not_found("missing key"))?
+            }
+            (Value::List(mut l), &PathSegment::Index(i)) if i < l.len() => l.swap_remove(i),
+            (Value::List(_), PathSegment::Index(_)) => Err(not_found("index out of range"))?,
+            (_, PathSegment::Key(_)) => Err(not_found("not a map"))?,
+            (_, PathSegment::Index(_)) => Err(not_found("not a list"))?,
+        };
+    }
+    let value = match (path.is_empty(), value) {
+        (true, Value::Link(k)) => Store::load(&k)?,
+        (_, value) => value,
+    };
+    Ok(
+        V::deserialize(ValueDeserializer::<serde::de::value::Error>::new(value))
+            .map_err(|e| StoreError::Encoding(e.to_string()))?,
+    )
+}
+
+fn inline_links<Store: StaticStore>(value: Value) -> Result<Value, Store::Error> {
+    Ok(match value {
+        Value::Link(k) => inline_links::<Store>(Store::load(&k)?)?,
+        Value::List(l) => Value::List(
+            l.into_iter()
+                .map(inline_links::<Store>)
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Map(m) => Value::Map(
+            m.into_iter()
+                .map(|(k, v)| Ok::<_, Store::Error>((k, inline_links::<Store>(v)?)))
+                .collect::<Result<_, _>>()?,
+        ),
+        v => v,
+    })
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use cid::Cid;
+    use serde::{Deserialize, Serialize};
+
+    use crate::{
+        inline_all, resolve_path, to_value, Link, MemoryError, MemoryStore, PathSegment,
+        StaticStore, StoreError, Value,
+    };
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(bound = "")]
+    struct Tree<Store: StaticStore> {
+        name: String,
+        children: Vec<Link<Tree<Store>, Store>>,
+    }
+
+    fn tree<Store: StaticStore>(name: &str, children: Vec<Tree<Store>>) -> Tree<Store> {
+        Tree {
+            name: name.into(),
+            children: children
+                .into_iter()
+                .map(|c| Link::from_value(c, None))
+                .collect(),
+        }
+    }
+
+    fn has_links(v: &Value) -> bool {
+        match v {
+            Value::Link(_) => true,
+            Value::List(l) => l.iter().any(has_links),
+            Value::Map(m) => m.values().any(has_links),
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn inline_two_levels() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let root = tree::<Store>(
+            "root",
+            vec![tree("a", vec![tree("a1", vec![])]), tree("b", vec![])],
+        );
+        let k = Store::store(&root, None).unwrap();
+        assert!(has_links(&Store::load::<Value>(&k).unwrap()));
+
+        let root: Link<Tree<Store>, Store> = Link::new(k);
+        let inlined = inline_all::<_, Store>(&root).unwrap();
+        assert!(!has_links(&inlined));
+
+        let Value::Map(root) = inlined else {
+            panic!("expected a map")
+        };
+        assert_eq!(root["name"], Value::String("root".into()));
+        let Value::List(children) = &root["children"] else {
+            panic!("expected a list")
+        };
+        assert_eq!(children.len(), 2);
+
+        // The inlined value decodes as the same tree with the links replaced by values.
+        #[derive(Deserialize)]
+        struct Plain {
+            name: String,
+            children: Vec<Plain>,
+        }
+        let plain: Plain = Store::decode(&Store::encode(&Value::Map(root)).unwrap()).unwrap();
+        assert_eq!(plain.name, "root");
+        assert_eq!(plain.children[0].children[0].name, "a1");
+        assert_eq!(plain.children[1].name, "b");
+    }
+
+    #[test]
+    fn paths_cross_links() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let root = tree::<Store>("root", vec![tree("a", vec![tree("a1", vec![])])]);
+        let k = Store::store(&root, None).unwrap();
+        let get = |path: &[PathSegment]| resolve_path::<String, Store>(&k, path);
+
+        assert_eq!(get(&["name".into()]).unwrap(), "root");
+        assert_eq!(
+            get(&["children".into(), 0.into(), "name".into()]).unwrap(),
+            "a"
+        );
+        let path = ["children".into(), 0.into(), "children".into(), 0.into()];
+        let name = [&path[..], &["name".into()]].concat();
+        assert_eq!(get(&name).unwrap(), "a1");
+        // A link at the end of the path isn't followed.
+        let a1: Cid = resolve_path::<_, Store>(&k, &path).unwrap();
+        assert_eq!(Store::load::<Tree<Store>>(&a1).unwrap().name, "a1");
+        assert_eq!(
+            resolve_path::<Tree<Store>, Store>(&k, &[]).unwrap().name,
+            "root"
+        );
+
+        let not_found = |path: &[PathSegment]| match get(path) {
+            Err(MemoryError::Store(StoreError::PathNotFound { depth, reason })) => (depth, reason),
+            r => panic!("expected a missing path, got {r:?}"),
+        };
+        assert_eq!(not_found(&["nope".into()]), (0, "missing key"));
+        assert_eq!(
+            not_found(&["children".into(), 1.into()]),
+            (1, "index out of range")
+        );
+        assert_eq!(not_found(&["name".into(), 0.into()]), (1, "not a list"));
+        assert_eq!(
+            not_found(&["children".into(), "x".into()]),
+            (1, "not a map")
+        );
+
+        let dangling = MemoryStore::<()>::store(&tree::<Store>("gone", vec![]), None).unwrap();
+        let k = Store::store(&("dangling", [dangling]), None).unwrap();
+        let err = resolve_path::<String, Store>(&k, &[1.into(), 0.into(), "name".into()]);
+        assert!(matches!(err, Err(MemoryError::NotFound(k)) if k == dangling));
+    }
+
+    #[test]
+    fn to_value_keeps_links() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let root = tree::<Store>("root", vec![tree("a", vec![]), tree("b", vec![])]);
+        let value = to_value::<_, Store>(&root).unwrap();
+        let Value::Map(root) = value else {
+            panic!("expected a map")
+        };
+        assert_eq!(root["name"], Value::String("root".into()));
+        let Value::List(children) = &root["children"] else {
+            panic!("expected a list")
+        };
+        assert_eq!(children.len(), 2);
+        for child in children {
+            // Converting saved the children, so the links resolve.
+            let Value::Link(k) = child else {
+                panic!("expected a link, got {child:?}")
+            };
+            assert!(Store::contains(k));
+        }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Union {
+        Unit,
+        Newtype(u64),
+        Tuple(u64, String),
+        Struct { x: u64 },
+        Empty {},
+    }
+
+    fn map(entries: &[(&str, Value)]) -> Value {
+        Value::Map(
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn enum_representations() {
+        type Store = MemoryStore;
+
+        let cids = [
+            "bafyreiclcc5mufzmbu4dvntvoq7yvgqaz2kaudrpydzayhnde4ghu43uhm",
+            "bafyreid4eb6kf45swurwcmdl4g3q32dzolpja7px7hvel4oq43lgd6upci",
+            "bafyreif3ak33pu5k4tt35x4o5kq3h42k36gbtcifsbn3f4hzfupnnfijkm",
+            "bafyreidb6riqrxec4323vmvtvvueou4sphoeudoyjyimydzpv7gbzjqy5u",
+        ];
+        let cases = [
+            (Union::Unit, Value::String("Unit".into())),
+            (Union::Newtype(1), map(&[("Newtype", Value::Integer(1))])),
+            (
+                Union::Tuple(1, "x".into()),
+                map(&[(
+                    "Tuple",
+                    Value::List(vec![Value::Integer(1), Value::String("x".into())]),
+                )]),
+            ),
+            (
+                Union::Struct { x: 1 },
+                map(&[("Struct", map(&[("x", Value::Integer(1))]))]),
+            ),
+        ];
+        for ((variant, expected), cid) in cases.into_iter().zip(cids) {
+            let bytes = Store::encode(&variant).unwrap();
+            assert_eq!(Store::decode::<Value>(&bytes).unwrap(), expected);
+            assert_eq!(Store::decode::<Union>(&bytes).unwrap(), variant);
+            // The generic form encodes to the same bytes, so the CID only depends on the data.
+            assert_eq!(Store::encode(&expected).unwrap(), bytes);
+            let k = Store::store_bytes(&bytes, None).unwrap();
+            assert_eq!(k.to_string(), cid);
+        }
+
+        let bytes = Store::encode(&Union::Empty {}).unwrap();
+        assert_eq!(
+            Store::decode::<Value>(&bytes).unwrap(),
+            map(&[("Empty", map(&[]))])
+        );
+    }
+}
@@ -0,0 +1,424 @@
+use core::{convert::Infallible, fmt};
+
+use alloc::{
+    borrow::Cow,
+    collections::{BTreeSet, VecDeque},
+    vec::Vec,
+};
+
+use cid::Cid;
+
+use crate::{codec, CodecError, PinStore, StaticStore, StoreError};
+
+/// An error encountered while walking a DAG with [`walk`].
+#[derive(Debug)]
+pub enum WalkError<S, E> {
+    /// A block couldn't be loaded.
+    Store(S),
+    /// A block couldn't be scanned for links (e.g., its codec isn't supported).
+    Codec(CodecError),
+    /// The visitor failed.
+    Visit(E),
+    /// The DAG has more than this many distinct blocks (see [`walk_limited`]).
+    TooManyBlocks(usize),
+}
+
+impl<S: fmt::Display, E: fmt::Display> fmt::Display for WalkError<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WalkError::Store(e) => e.fmt(f),
+            WalkError::Codec(e) => write!(f, "failed to scan block: {e}"),
+            WalkError::Visit(e) => e.fmt(f),
+            WalkError::TooManyBlocks(max) => write!(f, "dag has more than {max} blocks"),
+        }
+    }
+}
+
+impl<S, E> core::error::Error for WalkError<S, E>
+where
+    S: core::error::Error + 'static,
+    E: core::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            WalkError::Store(e) => Some(e),
+            WalkError::Codec(e) => Some(e),
+            WalkError::Visit(e) => Some(e),
+            WalkError::TooManyBlocks(_) => None,
+        }
+    }
+}
+
+/// Visit every block reachable from `root` exactly once, breadth-first, passing each block's CID
+/// and raw bytes to `visit` (e.g., to replicate a DAG or check its integrity).
+///
+/// Blocks are loaded from `S` as they're reached and dropped once they've been visited and scanned,
+/// so only the frontier and the set of queued CIDs are kept in memory. Links are found with
+/// [`child_links`](crate::child_links), so every reachable block must be DAG-CBOR, DAG-PB, raw, or
+/// (with `std`) DAG-JSON. Blocks are loaded as [`StaticStore::load`] loads them: blocks larger than
+/// [`StaticStore::MAX_BLOCK_SIZE`] are rejected, each block is verified against its CID unless
+/// [`StaticStore::VERIFY`] is false, and blocks embedded in identity CIDs are visited without
+/// touching the store.
+///
+/// Returns the number of blocks visited. The walk stops at the first error.
+pub fn walk<S, E>(
+    root: &Cid,
+    visit: impl FnMut(&Cid, &[u8]) -> Result<(), E>,
+) -> Result<usize, WalkError<S::Error, E>>
+where
+    S: StaticStore,
+{
+    walk_limited::<S, E>(root, usize::MAX, visit)
+}
+
+/// Like [`walk`], but fails with [`WalkError::TooManyBlocks`] (before loading any more blocks) if
+/// more than `max_blocks` distinct blocks are reachable, bounding the memory the walk uses on
+/// untrusted DAGs.
+pub fn walk_limited<S, E>(
+    root: &Cid,
+    max_blocks: usize,
+    mut visit: impl FnMut(&Cid, &[u8]) -> Result<(), E>,
+) -> Result<usize, WalkError<S::Error, E>>
+where
+    S: StaticStore,
+{
+    walk_from::<S, E>(
+        BTreeSet::from([*root]),
+        &BTreeSet::new(),
+        max_blocks,
+        |k, block| visit(k, block),
+    )
+}
+
+/// Walk the blocks reachable from `roots` without passing through `skip`. The roots count towards
+/// `max_blocks`. Blocks are passed to `visit` as [`StaticStore::load_block`] returns them, so
+/// blocks borrowed from identity CIDs can be told apart from stored ones.
+fn walk_from<S, E>(
+    mut roots: BTreeSet<Cid>,
+    skip: &BTreeSet<Cid>,
+    max_blocks: usize,
+    mut visit: impl FnMut(&Cid, &Cow<'_, [u8]>) -> Result<(), E>,
+) -> Result<usize, WalkError<S::Error, E>>
+where
+    S: StaticStore,
+{
+    roots.retain(|k| !skip.contains(k));
+    if roots.len() > max_blocks {
+        return Err(WalkError::TooManyBlocks(max_blocks));
+    }
+    let mut queue: VecDeque<Cid> = roots.iter().copied().collect();
+    let mut queued = roots;
+    let mut visited = 0;
+    while let Some(k) = queue.pop_front() {
+        let block = S::load_block(&k).map_err(WalkError::Store)?;
+        if S::VERIFY {
+            S::verify(&k, &block).map_err(WalkError::Store)?;
+        }
+        visit(&k, &block).map_err(WalkError::Visit)?;
+        visited += 1;
+        for child in codec::child_links(&block, k.codec()).map_err(WalkError::Codec)? {
+            if !skip.contains(&child) && queued.insert(child) {
+                if queued.len() > max_blocks {
+                    return Err(WalkError::TooManyBlocks(max_blocks));
+                }
+                queue.push_back(child);
+            }
+        }
+    }
+    Ok(visited)
+}
+
+/// What [`gc`] does with roots that aren't in the store.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MissingRoots {
+    /// Fail with [`StoreError::NotFound`] before deleting anything.
+    Fail,
+    /// Ignore them, e.g., when the roots come from a list that may be stale.
+    Skip,
+}
+
+/// Delete every block in `S` that isn't reachable from `roots`, returning the deleted CIDs.
+///
+/// Every reachable block is marked (with a [`walk`] from all the roots, so shared subgraphs are
+/// only walked once) before anything is deleted, so a failure while marking (e.g., a dangling link
+/// or a block in an unsupported codec) deletes nothing. The store must support
+/// [`StaticStore::keys`] and [`StaticStore::remove`], and nothing should write to it while it's
+/// collected: a block stored (or linked) between the mark and the sweep may be deleted.
+pub fn gc<S: StaticStore>(roots: &[Cid], missing: MissingRoots) -> Result<Vec<Cid>, S::Error> {
+    let mut live = BTreeSet::new();
+    for root in roots {
+        if S::has_block(root)? {
+            live.insert(*root);
+        } else if missing == MissingRoots::Fail {
+            return Err(StoreError::NotFound(*root).into());
+        }
+    }
+    let mut marked = BTreeSet::new();
+    walk_from::<S, Infallible>(live, &BTreeSet::new(), usize::MAX, |k, _| {
+        marked.insert(*k);
+        Ok(())
+    })
+    .map_err(unlimited_walk_error::<S>)?;
+
+    let mut deleted = Vec::new();
+    for k in S::keys()? {
+        if !marked.contains(&k) && S::remove(&k)? {
+            deleted.push(k);
+        }
+    }
+    Ok(deleted)
+}
+
+/// Like [`gc`], with `S`'s [pins](PinStore::pins) as the roots: delete every block that isn't
+/// pinned or reachable from a pin. `missing` says what to do with pins whose blocks aren't stored.
+pub fn gc_pinned<S: PinStore>(missing: MissingRoots) -> Result<Vec<Cid>, S::Error> {
+    gc::<S>(&S::pins()?, missing)
+}
+
+/// The CIDs of the blocks reachable from `new` but not from `old`, in the order a [`walk`] from
+/// `new` reaches them: the blocks a peer holding `old` needs to fetch to have all of `new`.
+///
+/// Every block reachable from `old` is visited first (so `old` must be complete in `S`), then
+/// `new` is walked without descending into any of them: an unchanged subtree has the same CID in
+/// both DAGs, so it's pruned as soon as it's reached, and only the changed parts of `new` are
+/// loaded. Blocks embedded in identity CIDs are never transferred, so they aren't reported.
+pub fn diff<S: StaticStore>(old: &Cid, new: &Cid) -> Result<Vec<Cid>, S::Error> {
+    let mut reachable = BTreeSet::new();
+    walk::<S, Infallible>(old, |k, _| {
+        reachable.insert(*k);
+        Ok(())
+    })
+    .map_err(unlimited_walk_error::<S>)?;
+
+    let mut added = Vec::new();
+    walk_from::<S, Infallible>(
+        BTreeSet::from([*new]),
+        &reachable,
+        usize::MAX,
+        |k, block| {
+            if let Cow::Owned(_) = block {
+                added.push(*k);
+            }
+            Ok(())
+        },
+    )
+    .map_err(unlimited_walk_error::<S>)?;
+    Ok(added)
+}
+
+/// Convert the error of a walk that neither limits its size nor fails to visit.
+fn unlimited_walk_error<S: StaticStore>(e: WalkError<S::Error, Infallible>) -> S::Error {
+    match e {
+        WalkError::Store(e) => e,
+        WalkError::Codec(e) => StoreError::Codec(e).into(),
+        WalkError::Visit(e) => match e {},
+        WalkError::TooManyBlocks(_) => unreachable!("the walk isn't limited"),
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use std::convert::Infallible;
+
+    use crate::{
+        diff, gc, gc_pinned, shard_by_digest, walk, walk_limited, Batch, CidShape, MemoryError,
+        MemoryStore, MissingRoots, PinStore, Shard, ShardedStore, SimStore, StaticStore,
+        StatsStore, StoreError, WalkError, DAG_JSON,
+    };
+
+    #[test]
+    fn walks_dag_json_and_verifies_blocks() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let json = CidShape::DEFAULT.with_codec(DAG_JSON);
+        let leaf = Store::store(&"leaf", None).unwrap();
+        let other = Store::store(&"other", Some(&json)).unwrap();
+        let root = Store::store(&("root", [leaf, other]), Some(&json)).unwrap();
+        let mut seen = Vec::new();
+        walk::<Store, Infallible>(&root, |k, _| {
+            seen.push(*k);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(seen, [root, leaf, other]);
+
+        // A block that doesn't match its CID stops the walk.
+        <Store as crate::BlockBackend>::put(&leaf, Store::encode(&"tampered").unwrap()).unwrap();
+        assert!(matches!(
+            walk::<Store, Infallible>(&root, |_, _| Ok(())),
+            Err(WalkError::Store(MemoryError::Store(StoreError::HashMismatch(k)))) if k == leaf
+        ));
+    }
+
+    #[test]
+    fn visits_each_block_once() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        //      root
+        //     /    \
+        //    a      b
+        //     \    / \
+        //      shared c
+        let shared = Store::store(&"shared", None).unwrap();
+        let c = Store::store(&"c", None).unwrap();
+        let a = Store::store(&("a", [shared]), None).unwrap();
+        let b = Store::store(&("b", [shared, c]), None).unwrap();
+        let root = Store::store(&("root", [a, b]), None).unwrap();
+
+        let mut seen = Vec::new();
+        let visited = walk::<Store, Infallible>(&root, |k, block| {
+            assert_eq!(Store::load_bytes(k).unwrap(), block);
+            seen.push(*k);
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(visited, 5);
+        assert_eq!(seen, [root, a, b, shared, c]);
+
+        assert!(matches!(
+            walk_limited::<Store, Infallible>(&root, 4, |_, _| Ok(())),
+            Err(WalkError::TooManyBlocks(4))
+        ));
+        assert!(matches!(
+            walk::<Store, _>(&root, |k, _| if *k == b { Err(*k) } else { Ok(()) }),
+            Err(WalkError::Visit(k)) if k == b
+        ));
+
+        let missing = MemoryStore::<()>::store(&"missing", None).unwrap();
+        let broken = Store::store(&[missing], None).unwrap();
+        assert!(matches!(
+            walk::<Store, Infallible>(&broken, |_, _| Ok(())),
+            Err(WalkError::Store(MemoryError::NotFound(k))) if k == missing
+        ));
+    }
+
+    #[test]
+    fn gc_keeps_reachable_blocks() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let shared = Store::store(&"shared", None).unwrap();
+        let a = Store::store(&("a", [shared]), None).unwrap();
+        let b = Store::store(&("b", [shared]), None).unwrap();
+        let only_b = Store::store(&("only b", [b]), None).unwrap();
+        let garbage = Store::store(&"garbage", None).unwrap();
+        let missing = MemoryStore::<()>::store(&"missing", None).unwrap();
+
+        assert!(matches!(
+            gc::<Store>(&[a, missing], MissingRoots::Fail),
+            Err(MemoryError::Store(StoreError::NotFound(k))) if k == missing
+        ));
+        assert_eq!(Store::len(), 5);
+
+        let mut deleted = gc::<Store>(&[a, b, missing], MissingRoots::Skip).unwrap();
+        deleted.sort_by_key(|k| k.to_bytes());
+        let mut expected = [only_b, garbage];
+        expected.sort_by_key(|k| k.to_bytes());
+        assert_eq!(deleted, expected);
+        assert_eq!(Store::keys().unwrap().len(), 3);
+
+        assert_eq!(gc::<Store>(&[a], MissingRoots::Fail).unwrap(), [b]);
+        assert!(Store::contains(&shared));
+        assert!(gc::<Store>(&[a], MissingRoots::Fail).unwrap().is_empty());
+    }
+
+    /// Collect a DAG with one live and one garbage subtree through the wrapper `S`.
+    fn collect_through<S: StaticStore>()
+    where
+        S::Error: std::fmt::Debug,
+    {
+        let shared = S::store(&"shared", None).unwrap();
+        let root = S::store(&("root", [shared]), None).unwrap();
+        let garbage = S::store(&("garbage", [shared]), None).unwrap();
+        assert_eq!(gc::<S>(&[root], MissingRoots::Fail).unwrap(), [garbage]);
+        assert!(!S::has(&garbage).unwrap());
+        assert!(S::has(&root).unwrap() && S::has(&shared).unwrap());
+        assert!(gc::<S>(&[root], MissingRoots::Fail).unwrap().is_empty());
+    }
+
+    #[test]
+    fn gc_through_wrappers() {
+        struct A;
+        struct B;
+        struct C;
+        struct D;
+
+        collect_through::<SimStore<MemoryStore<A>>>();
+        ShardedStore::<MemoryStore<B>>::configure(
+            vec![Shard::of::<MemoryStore<B>>(), Shard::of::<MemoryStore<C>>()],
+            shard_by_digest,
+        );
+        collect_through::<ShardedStore<MemoryStore<B>>>();
+
+        // Blocks are collected from the batch's buffer and from the backend alike.
+        type Batched = Batch<MemoryStore<D>>;
+        collect_through::<Batched>();
+        let root =
+            Batched::store(&("root", [Batched::store(&"shared", None).unwrap()]), None).unwrap();
+        let committed = Batched::store(&"committed", None).unwrap();
+        Batched::commit().unwrap();
+        let buffered = Batched::store(&"buffered", None).unwrap();
+        let mut deleted = gc::<Batched>(&[root], MissingRoots::Fail).unwrap();
+        deleted.sort_by_key(|k| k.to_bytes());
+        let mut expected = [committed, buffered];
+        expected.sort_by_key(|k| k.to_bytes());
+        assert_eq!(deleted, expected);
+        assert!(Batched::is_empty());
+        assert_eq!(MemoryStore::<D>::len(), 2);
+    }
+
+    #[test]
+    fn gc_keeps_pinned_subtrees() {
+        struct Tag;
+        type Store = MemoryStore<Tag>;
+
+        let leaf = Store::store(&"leaf", None).unwrap();
+        let pinned = Store::store(&("pinned", [leaf]), None).unwrap();
+        let unpinned = Store::store(&"unpinned", None).unwrap();
+
+        assert!(Store::pin(&pinned).unwrap());
+        assert!(!Store::pin(&pinned).unwrap());
+        assert_eq!(Store::pins().unwrap(), [pinned]);
+        assert_eq!(gc_pinned::<Store>(MissingRoots::Fail).unwrap(), [unpinned]);
+        assert!(Store::contains(&pinned) && Store::contains(&leaf));
+
+        assert!(Store::unpin(&pinned).unwrap());
+        assert!(!Store::unpin(&pinned).unwrap());
+        let mut deleted = gc_pinned::<Store>(MissingRoots::Fail).unwrap();
+        deleted.sort_by_key(|k| k.to_bytes());
+        let mut expected = [pinned, leaf];
+        expected.sort_by_key(|k| k.to_bytes());
+        assert_eq!(deleted, expected);
+        assert!(Store::is_empty());
+    }
+
+    #[test]
+    fn diff_reports_new_blocks() {
+        struct Tag;
+        type Store = StatsStore<MemoryStore<Tag>>;
+
+        //    old      new
+        //   /   \    /   \
+        //  a     shared   b
+        //        /    \    \
+        //       x      y    c
+        let x = Store::store(&"x", None).unwrap();
+        let y = Store::store(&"y", None).unwrap();
+        let shared = Store::store(&("shared", [x, y]), None).unwrap();
+        let a = Store::store(&"a", None).unwrap();
+        let old = Store::store(&("old", [a, shared]), None).unwrap();
+        let c = Store::store(&"c", None).unwrap();
+        let b = Store::store(&("b", [c]), None).unwrap();
+        let new = Store::store(&("new", [shared, b]), None).unwrap();
+
+        let loads = Store::snapshot().loads;
+        assert_eq!(diff::<Store>(&old, &new).unwrap(), [new, b, c]);
+        // The old DAG's five blocks, then only the new root and b's subtree.
+        assert_eq!(Store::snapshot().loads - loads, 8);
+
+        assert!(diff::<Store>(&new, &shared).unwrap().is_empty());
+        assert!(diff::<Store>(&new, &new).unwrap().is_empty());
+    }
+}